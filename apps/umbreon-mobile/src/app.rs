@@ -1,13 +1,28 @@
-use crate::components::{ExplorePane, MemoryPane, NavigationBar};
+use crate::components::{DialoguePane, ExplorePane, MemoryPane, NavigationBar};
+use crate::config;
+use crate::danmaku_file::AttachmentState;
+use crate::feed_marks;
+use crate::live;
+use crate::live_probe;
+use crate::memory_sync;
+use crate::secrets;
 use crate::settings::SettingsPane;
 use crate::state::{self, AppContext, NavSection, ThemeMode};
 use crate::storage;
 use crate::style::BASE_STYLES;
 use crate::timeline;
 use dioxus::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use umbreon_core::danmaku_layout::OverlaySettings;
+use umbreon_core::danmaku_live::ConnectionState;
+use umbreon_core::player::{DEFAULT_TARGET_LATENCY, LowLatency, PlayerState};
+
+const OUTBOX_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
 
 #[allow(non_snake_case)]
 pub fn AppRoot() -> Element {
+    use_hook(secrets::migrate_plaintext);
     let stored_settings = storage::load_settings();
     let initial_theme = stored_settings.theme.unwrap_or(ThemeMode::Light);
     let initial_memory_server_url = stored_settings
@@ -20,11 +35,6 @@ pub fn AppRoot() -> Element {
         .as_deref()
         .unwrap_or("https://api.openai.com/v1")
         .to_string();
-    let initial_llm_api_key = stored_settings
-        .llm_api_key
-        .as_ref()
-        .cloned()
-        .unwrap_or_default();
     let initial_llm_model = stored_settings
         .llm_model
         .as_ref()
@@ -36,6 +46,27 @@ pub fn AppRoot() -> Element {
         .cloned()
         .unwrap_or_default();
 
+    let initial_llm_embedding_model = stored_settings
+        .llm_embedding_model
+        .clone()
+        .unwrap_or_default();
+    let initial_llm_prices = stored_settings.llm_prices.clone().unwrap_or_default();
+    let initial_llm_monthly_budget = stored_settings.llm_monthly_budget;
+    let initial_timeline_order = stored_settings
+        .timeline_order
+        .unwrap_or(state::TimelineOrder::Latest);
+    let initial_config_url = stored_settings.config_url.clone().unwrap_or_default();
+    let initial_low_latency = LowLatency {
+        enabled: stored_settings.live_low_latency.unwrap_or(false),
+        target_latency: stored_settings
+            .live_target_latency
+            .unwrap_or(DEFAULT_TARGET_LATENCY),
+    };
+    let initial_danmaku_overlay = stored_settings
+        .danmaku_overlay
+        .map(OverlaySettings::clamped)
+        .unwrap_or_default();
+
     let feed_bootstrap = timeline::init_feed_bootstrap(&stored_settings);
     let should_auto_sync_stale_cache = feed_bootstrap.should_auto_sync_stale_cache;
     let initial_feed_items = feed_bootstrap.feed_items;
@@ -44,16 +75,31 @@ pub fn AppRoot() -> Element {
     let nav = use_signal(|| NavSection::Explore);
     let theme = use_signal(|| initial_theme);
     let feed_items = use_signal(|| initial_feed_items);
-    let live_streams = use_signal(state::mock_live_streams);
-    let now_playing = use_signal(state::mock_initial_session);
-    let memory_panel = use_signal(state::mock_memory_panel);
+    let feed_marks = use_signal(feed_marks::load_marks);
+    let timeline_order = use_signal(|| initial_timeline_order);
+    let live_streams = use_signal(storage::load_live_channels);
+    let live_health = use_signal(HashMap::new);
+    let now_playing = use_signal(|| None::<state::MediaSession>);
+    let player = use_signal(PlayerState::default);
+    let low_latency = use_signal(|| initial_low_latency);
+    let danmaku = use_signal(Vec::new);
+    let danmaku_overlay = use_signal(|| initial_danmaku_overlay);
+    let danmaku_connection = use_signal(ConnectionState::default);
+    let danmaku_file = use_signal(AttachmentState::default);
+    let outbox = use_signal(memory_sync::summary);
+    let config_url = use_signal(|| initial_config_url);
+    let memory_policies = use_signal(|| config::cached_config().memory_policies);
     let feed_server_url = use_signal(|| initial_feed_server_url);
     let memory_server_url = use_signal(|| initial_memory_server_url);
     let feed_syncing = use_signal(|| false);
     let llm_endpoint = use_signal(|| initial_llm_endpoint);
-    let llm_api_key = use_signal(|| initial_llm_api_key);
+    let llm_api_key = use_signal(|| secrets::load(secrets::LLM_API_KEY).unwrap_or_default());
     let llm_model = use_signal(|| initial_llm_model);
     let llm_models = use_signal(|| initial_llm_models);
+    let llm_embedding_model = use_signal(|| initial_llm_embedding_model);
+    let llm_prices = use_signal(|| initial_llm_prices);
+    let llm_monthly_budget = use_signal(|| initial_llm_monthly_budget);
+    let active_conversation = use_signal(|| None::<String>);
     let settings_status = use_signal(|| None::<String>);
     let toast = use_signal(|| None::<state::ToastMessage>);
 
@@ -61,9 +107,20 @@ pub fn AppRoot() -> Element {
         nav,
         theme,
         feed_items,
+        feed_marks,
+        timeline_order,
         live_streams,
+        live_health,
         now_playing,
-        memory_panel,
+        player,
+        low_latency,
+        danmaku,
+        danmaku_overlay,
+        danmaku_connection,
+        danmaku_file,
+        outbox,
+        config_url,
+        memory_policies,
         feed_server_url,
         memory_server_url,
         feed_syncing,
@@ -71,6 +128,10 @@ pub fn AppRoot() -> Element {
         llm_api_key,
         llm_model,
         llm_models,
+        llm_embedding_model,
+        llm_prices,
+        llm_monthly_budget,
+        active_conversation,
         settings_status,
         toast,
     };
@@ -93,13 +154,65 @@ pub fn AppRoot() -> Element {
     };
 
     let feed_server_url = ctx.feed_server_url;
-    let settings_status = ctx.settings_status;
-    let feed_items = ctx.feed_items;
-    let feed_syncing = ctx.feed_syncing;
-    let llm_endpoint = ctx.llm_endpoint;
-    let llm_api_key = ctx.llm_api_key;
-    let llm_model = ctx.llm_model;
+    let sync_ctx = ctx.clone();
+    let toast_ctx = ctx.clone();
     let mut auto_sync_once = use_signal(|| false);
+    let memory_server_url = ctx.memory_server_url;
+    let outbox = ctx.outbox;
+    let feed_marks = ctx.feed_marks;
+
+    // Refreshes the remote config once per launch, reloads live channels and applies its
+    // purge policies.
+    let mut memory_policies = ctx.memory_policies;
+    let config_url = ctx.config_url;
+    let live_streams = ctx.live_streams;
+    use_future(move || async move {
+        let url = config_url.read().trim().to_string();
+        let mut live_config = None;
+        if !url.is_empty() {
+            match config::fetch_config(&url).await {
+                Ok(config) => {
+                    *memory_policies.write() = config.memory_policies;
+                    live_config = Some(config.live_streams);
+                }
+                Err(err) => tracing::warn!("remote config refresh failed: {err}"),
+            }
+        }
+        let live_config = live_config.unwrap_or_else(|| config::cached_config().live_streams);
+        live::trigger_refresh(live_config, live_streams);
+        let policies = memory_policies.read().clone();
+        if !policies.purge_on_launch || policies.rules.is_empty() {
+            return;
+        }
+        let purged = memory_sync::apply_purge_policies(&policies);
+        let memory_url = memory_server_url.read().trim().to_string();
+        if !memory_url.is_empty()
+            && let Err(err) = memory_sync::purge_remote(memory_url, &policies).await
+        {
+            tracing::warn!("remote memory purge failed: {err}");
+        }
+        feed_marks::forget_memories(&purged, feed_marks);
+    });
+
+    // Keeps the online/offline badges of the live list fresh: new channels are probed as
+    // soon as they appear, and everything again every `PROBE_INTERVAL`.
+    let live_health = ctx.live_health;
+    use_effect(move || live_probe::sync_channels(&live_streams.read(), live_health));
+    use_future(move || live_probe::run_background_probes(live_streams, live_health));
+
+    // Replays queued memory writes on launch and then periodically, so anything recorded
+    // offline reaches the server once it is reachable again.
+    use_future(move || async move {
+        loop {
+            let url = memory_server_url.read().trim().to_string();
+            if memory_sync::summary().queued > 0 {
+                memory_sync::trigger_flush(url, outbox, move || {
+                    feed_marks::refresh_marks(feed_marks)
+                });
+            }
+            futures_timer::Delay::new(OUTBOX_FLUSH_INTERVAL).await;
+        }
+    });
 
     use_effect(move || {
         if *auto_sync_once.read() || !should_auto_sync_stale_cache {
@@ -107,15 +220,7 @@ pub fn AppRoot() -> Element {
         }
         *auto_sync_once.write() = true;
         let url = feed_server_url.read().trim().to_string();
-        timeline::trigger_feed_sync(
-            url,
-            feed_items.clone(),
-            settings_status.clone(),
-            feed_syncing.clone(),
-            llm_endpoint.clone(),
-            llm_api_key.clone(),
-            llm_model.clone(),
-        );
+        timeline::trigger_feed_sync(url, &sync_ctx);
     });
 
     let toast_class = ctx
@@ -128,6 +233,23 @@ pub fn AppRoot() -> Element {
         })
         .unwrap_or("toast");
     let mut toast_signal = ctx.toast;
+    let mut nav_signal = ctx.nav;
+
+    // Runs the follow-up a toast offers, then dismisses it.
+    let mut run_toast_action = move |action: state::ToastAction| {
+        *toast_signal.write() = None;
+        match action {
+            state::ToastAction::OpenSettings => *nav_signal.write() = NavSection::Settings,
+            state::ToastAction::RetryFeedSync => {
+                timeline::trigger_feed_sync(feed_server_url.read().trim().to_string(), &toast_ctx)
+            }
+            state::ToastAction::RetryMemorySync => memory_sync::trigger_flush(
+                memory_server_url.read().trim().to_string(),
+                outbox,
+                move || feed_marks::refresh_marks(feed_marks),
+            ),
+        }
+    };
 
     rsx! {
         link {
@@ -139,12 +261,7 @@ pub fn AppRoot() -> Element {
             header { class: "umbreon-header", "{header_title}" }
             section { class: "umbreon-content",
                 match active_nav {
-                    NavSection::Dialogue => rsx!(
-                        div { class: "settings-pane",
-                            h2 { "对话" }
-                            p { "对话列表还在路上。" }
-                        }
-                    ),
+                    NavSection::Dialogue => rsx!(DialoguePane {}),
                     NavSection::Explore => rsx!(ExplorePane {}),
                     NavSection::Memory => rsx!(MemoryPane {}),
                     NavSection::Settings => rsx!(SettingsPane {}),
@@ -154,6 +271,13 @@ pub fn AppRoot() -> Element {
             if let Some(toast) = ctx.toast.read().clone() {
                 div { class: "{toast_class}",
                     span { "{toast.text}" }
+                    if let Some(action) = toast.action {
+                        button {
+                            class: "toast-action",
+                            onclick: move |_| run_toast_action(action),
+                            "{action.label()}"
+                        }
+                    }
                     button {
                         class: "toast-close",
                         onclick: move |_| {
@@ -0,0 +1,553 @@
+use crate::config;
+use crate::feed_marks;
+use crate::memory_sync;
+use crate::state::{
+    AppContext, ChatMessage, Conversation, FeedItem, FeedMark, LiveStream, LocalMemory, ThemeMode,
+    TimelineOrder,
+};
+use crate::storage::{self, StoredSettings};
+use crate::usage::PriceTable;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use umbreon_core::UmbreonError;
+
+const BUNDLE_FORMAT: &str = "umbreon-backup";
+/// Bumped whenever a field changes meaning; additive fields only need `#[serde(default)]`.
+const BUNDLE_VERSION: u32 = 1;
+
+/// Settings that travel with a backup. Credentials and the secrets key setup are left out
+/// on purpose: they are sealed to this device and must be entered again after a restore.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BundleSettings {
+    pub feed_server_url: Option<String>,
+    pub memory_server_url: Option<String>,
+    pub llm_endpoint: Option<String>,
+    pub llm_model: Option<String>,
+    pub llm_models: Option<Vec<String>>,
+    pub llm_embedding_model: Option<String>,
+    pub llm_prices: Option<PriceTable>,
+    pub llm_monthly_budget: Option<f64>,
+    pub config_url: Option<String>,
+    pub theme: Option<ThemeMode>,
+    pub timeline_order: Option<String>,
+    /// Playlist URLs imported in settings; the remote config's own travel with it.
+    pub live_playlists: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConversationBackup {
+    pub conversation: Conversation,
+    pub messages: Vec<ChatMessage>,
+}
+
+/// Everything needed to move Umbreon to another device, as one JSON document.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupBundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_ts: i64,
+    #[serde(default)]
+    pub settings: BundleSettings,
+    /// Raw text of the last fetched remote config.
+    #[serde(default)]
+    pub remote_config: Option<String>,
+    #[serde(default)]
+    pub feed_items: Vec<FeedItem>,
+    #[serde(default)]
+    pub feed_marks: Vec<FeedMark>,
+    #[serde(default)]
+    pub memories: Vec<LocalMemory>,
+    #[serde(default)]
+    pub conversations: Vec<ConversationBackup>,
+    /// Live channels with their favorite flag and last watch time.
+    #[serde(default)]
+    pub live_channels: Vec<LiveStream>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Keeps local data and adds what is missing; on conflicts the newer copy wins.
+    Merge,
+    /// Drops local feeds, bookmarks, memories, conversations and live channels first, so
+    /// those end up exactly like the bundle. Settings in the bundle overwrite local ones; settings it
+    /// does not carry are kept.
+    Replace,
+}
+
+impl ImportMode {
+    pub const ALL: [ImportMode; 2] = [ImportMode::Merge, ImportMode::Replace];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportMode::Merge => "merge",
+            ImportMode::Replace => "replace",
+        }
+    }
+
+    pub fn from_value(value: &str) -> ImportMode {
+        match value {
+            "replace" => ImportMode::Replace,
+            _ => ImportMode::Merge,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ImportMode::Merge => "合并",
+            ImportMode::Replace => "覆盖",
+        }
+    }
+}
+
+/// How many records an import added or overwrote, per kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub feed_items: usize,
+    pub feed_marks: usize,
+    pub memories: usize,
+    pub conversations: usize,
+    pub live_channels: usize,
+}
+
+impl ImportStats {
+    pub fn describe(&self) -> String {
+        format!(
+            "Feed {} 条、铸魂 {} 条、记忆 {} 条、对话 {} 个、直播频道 {} 个",
+            self.feed_items, self.feed_marks, self.memories, self.conversations, self.live_channels
+        )
+    }
+}
+
+fn bundle_settings(settings: StoredSettings) -> BundleSettings {
+    BundleSettings {
+        feed_server_url: settings.feed_server_url,
+        memory_server_url: settings.memory_server_url,
+        llm_endpoint: settings.llm_endpoint,
+        llm_model: settings.llm_model,
+        llm_models: settings.llm_models,
+        llm_embedding_model: settings.llm_embedding_model,
+        llm_prices: settings.llm_prices,
+        llm_monthly_budget: settings.llm_monthly_budget,
+        config_url: settings.config_url,
+        theme: settings.theme,
+        timeline_order: settings
+            .timeline_order
+            .map(|order| order.as_str().to_string()),
+        live_playlists: settings.live_playlists,
+    }
+}
+
+/// Snapshot of the local database.
+pub fn collect() -> BackupBundle {
+    let settings = storage::load_settings();
+    let remote_config = settings.remote_config.clone();
+    let conversations = storage::load_conversations()
+        .into_iter()
+        .map(|conversation| ConversationBackup {
+            messages: storage::load_chat_messages(&conversation.id),
+            conversation,
+        })
+        .collect();
+    BackupBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_ts: chrono::Utc::now().timestamp(),
+        settings: bundle_settings(settings),
+        remote_config,
+        feed_items: storage::load_feed_items(),
+        feed_marks: storage::load_feed_marks(),
+        memories: storage::load_local_memories(),
+        conversations,
+        live_channels: storage::load_live_channels(),
+    }
+}
+
+pub fn parse_bundle(text: &str) -> Result<BackupBundle, UmbreonError> {
+    let bundle = serde_json::from_str::<BackupBundle>(text)
+        .map_err(|err| UmbreonError::parse("备份文件", err))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(UmbreonError::parse("备份文件", "not an Umbreon backup"));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(UmbreonError::parse(
+            "备份文件",
+            format!("bundle version {} is newer than this app", bundle.version),
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Writes a bundle of the current state and returns where it went.
+pub fn export() -> Result<String, UmbreonError> {
+    let bundle = collect();
+    let text = serde_json::to_string_pretty(&bundle)
+        .map_err(|err| UmbreonError::storage(format!("encode backup failed: {err}")))?;
+    let name = format!(
+        "umbreon-backup-{}.json",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    storage::store_backup_file(&name, &text)
+}
+
+fn apply_settings(settings: BundleSettings, remote_config: Option<String>, mode: ImportMode) {
+    let local = storage::load_settings();
+    // Merging never overwrites a setting the user already has.
+    let take = |incoming: Option<String>, current: &Option<String>| match mode {
+        ImportMode::Merge if current.is_some() => None,
+        _ => incoming,
+    };
+    if let Some(url) = take(settings.feed_server_url, &local.feed_server_url) {
+        storage::store_feed_server_url(&url);
+    }
+    if let Some(url) = take(settings.memory_server_url, &local.memory_server_url) {
+        storage::store_memory_server_url(&url);
+    }
+    if let Some(endpoint) = take(settings.llm_endpoint, &local.llm_endpoint) {
+        storage::store_llm_endpoint(&endpoint);
+    }
+    if let Some(model) = take(settings.llm_model, &local.llm_model) {
+        storage::store_llm_model(&model);
+    }
+    if let Some(model) = take(settings.llm_embedding_model, &local.llm_embedding_model) {
+        storage::store_llm_embedding_model(&model);
+    }
+    if let Some(url) = take(settings.config_url, &local.config_url) {
+        storage::store_config_url(&url);
+    }
+    if let Some(text) = take(remote_config, &local.remote_config) {
+        storage::store_remote_config(&text);
+    }
+    let local_order = local.timeline_order.map(|order| order.as_str().to_string());
+    if let Some(order) = take(settings.timeline_order, &local_order) {
+        storage::store_timeline_order(TimelineOrder::from_value(&order));
+    }
+    let replace = mode == ImportMode::Replace;
+    if let Some(models) = settings.llm_models
+        && (replace || local.llm_models.is_none())
+    {
+        storage::store_llm_models(&models);
+    }
+    if let Some(prices) = settings.llm_prices
+        && (replace || local.llm_prices.is_none())
+    {
+        storage::store_llm_prices(&prices);
+    }
+    if settings.llm_monthly_budget.is_some() && (replace || local.llm_monthly_budget.is_none()) {
+        storage::store_llm_monthly_budget(settings.llm_monthly_budget);
+    }
+    if let Some(theme) = settings.theme
+        && (replace || local.theme.is_none())
+    {
+        storage::store_theme(theme);
+    }
+    // Merging keeps every playlist from both sides, or a refresh would drop the channels
+    // restored from the bundle's playlists.
+    if let Some(mut playlists) = settings.live_playlists {
+        if !replace {
+            let local = local.live_playlists.unwrap_or_default();
+            playlists.retain(|url| !local.contains(url));
+            playlists.splice(0..0, local);
+        }
+        storage::store_live_playlists(&playlists);
+    }
+}
+
+fn import_feed_items(items: Vec<FeedItem>, mode: ImportMode) -> Result<usize, UmbreonError> {
+    let mut merged = match mode {
+        ImportMode::Replace => Vec::new(),
+        ImportMode::Merge => storage::load_feed_items(),
+    };
+    let known = merged
+        .iter()
+        .map(|item| item.id.clone())
+        .collect::<HashSet<_>>();
+    let added = items
+        .into_iter()
+        .filter(|item| !known.contains(&item.id))
+        .collect::<Vec<_>>();
+    let count = added.len();
+    merged.extend(added);
+    merged.sort_by_key(|item| std::cmp::Reverse(item.published_ts));
+    storage::store_feed_items(&merged)?;
+    Ok(count)
+}
+
+fn import_feed_marks(marks: Vec<FeedMark>, mode: ImportMode) -> usize {
+    let local = storage::load_feed_marks();
+    if mode == ImportMode::Replace {
+        for mark in &local {
+            storage::delete_feed_mark(&mark.feed_id);
+        }
+    }
+    let local = match mode {
+        ImportMode::Replace => HashMap::new(),
+        ImportMode::Merge => local
+            .into_iter()
+            .map(|mark| (mark.feed_id.clone(), mark.updated_ts))
+            .collect::<HashMap<_, _>>(),
+    };
+    let mut count = 0;
+    for mark in marks {
+        if local
+            .get(&mark.feed_id)
+            .is_some_and(|updated_ts| *updated_ts >= mark.updated_ts)
+        {
+            continue;
+        }
+        storage::store_feed_mark(&mark);
+        count += 1;
+    }
+    count
+}
+
+/// Restored memories go through the outbox like new ones, so unsynced copies reach the
+/// memory server. Replacing deletes local memories missing from the bundle on the server
+/// too.
+fn import_memories(memories: Vec<LocalMemory>, mode: ImportMode) -> usize {
+    let local = storage::load_local_memories()
+        .into_iter()
+        .map(|memory| (memory.id.clone(), memory))
+        .collect::<HashMap<_, _>>();
+    let replace = mode == ImportMode::Replace;
+    if replace {
+        let incoming = memories
+            .iter()
+            .map(|memory| memory.id.as_str())
+            .collect::<HashSet<_>>();
+        for id in local.keys() {
+            if !incoming.contains(id.as_str()) {
+                memory_sync::delete_memory(id);
+            }
+        }
+    }
+    let mut count = 0;
+    for memory in memories {
+        let existing = local.get(&memory.id);
+        if !replace && existing.is_some_and(|existing| existing.updated_ts >= memory.updated_ts) {
+            continue;
+        }
+        memory_sync::restore_memory(memory, existing, replace);
+        count += 1;
+    }
+    count
+}
+
+/// Messages are kept in order of creation; when merging, ones already present locally
+/// (same role, text and time) are not duplicated.
+fn import_conversations(conversations: Vec<ConversationBackup>, mode: ImportMode) -> usize {
+    let local = storage::load_conversations();
+    if mode == ImportMode::Replace {
+        for conversation in &local {
+            storage::delete_conversation(&conversation.id);
+        }
+    }
+    let local = match mode {
+        ImportMode::Replace => HashMap::new(),
+        ImportMode::Merge => local
+            .into_iter()
+            .map(|conversation| (conversation.id.clone(), conversation))
+            .collect::<HashMap<_, _>>(),
+    };
+    let mut count = 0;
+    for backup in conversations {
+        let id = backup.conversation.id.clone();
+        let (conversation, mut messages) = match local.get(&id) {
+            Some(existing) => {
+                let mut messages = storage::load_chat_messages(&id);
+                let seen = messages
+                    .iter()
+                    .map(|message| (message.role, message.content.clone(), message.created_ts))
+                    .collect::<HashSet<_>>();
+                let before = messages.len();
+                messages.extend(backup.messages.into_iter().filter(|message| {
+                    !seen.contains(&(message.role, message.content.clone(), message.created_ts))
+                }));
+                let newer = backup.conversation.updated_ts > existing.updated_ts;
+                if messages.len() == before && !newer {
+                    continue;
+                }
+                let conversation = if newer {
+                    backup.conversation
+                } else {
+                    existing.clone()
+                };
+                (conversation, messages)
+            }
+            None => (backup.conversation, backup.messages),
+        };
+        messages.sort_by_key(|message| message.created_ts);
+        storage::delete_conversation(&id);
+        storage::store_conversation(&conversation);
+        for mut message in messages {
+            message.conversation_id = id.clone();
+            storage::append_chat_message(&message);
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Channels are stored per origin. When merging, origins missing locally are added whole
+/// (minus stream URLs another origin already lists) and channels present on both sides keep
+/// the favorite flag from either and the later watch time.
+fn import_live_channels(
+    channels: Vec<LiveStream>,
+    mode: ImportMode,
+) -> Result<usize, UmbreonError> {
+    let mut origins: Vec<(String, Vec<LiveStream>)> = Vec::new();
+    for channel in channels {
+        match origins
+            .iter_mut()
+            .find(|(origin, _)| *origin == channel.origin)
+        {
+            Some((_, group)) => group.push(channel),
+            None => origins.push((channel.origin.clone(), vec![channel])),
+        }
+    }
+    if mode == ImportMode::Replace {
+        let names = origins
+            .iter()
+            .map(|(origin, _)| origin.clone())
+            .collect::<Vec<_>>();
+        storage::retain_live_origins(&names);
+        let mut count = 0;
+        for (origin, group) in origins {
+            storage::replace_live_channels(&origin, &group)?;
+            for channel in &group {
+                storage::store_live_channel_state(
+                    &channel.id,
+                    channel.favorite,
+                    channel.last_watched_ts,
+                );
+            }
+            count += group.len();
+        }
+        return Ok(count);
+    }
+
+    let local = storage::load_live_channels();
+    let local_origins = local
+        .iter()
+        .map(|channel| channel.origin.clone())
+        .collect::<HashSet<_>>();
+    let mut known_urls = local
+        .iter()
+        .map(|channel| channel.stream_url.clone())
+        .collect::<HashSet<_>>();
+    let local = local
+        .into_iter()
+        .map(|channel| (channel.id.clone(), channel))
+        .collect::<HashMap<_, _>>();
+    let mut count = 0;
+    for (origin, group) in origins {
+        if !local_origins.contains(&origin) {
+            let group = group
+                .into_iter()
+                .filter(|channel| known_urls.insert(channel.stream_url.clone()))
+                .collect::<Vec<_>>();
+            storage::replace_live_channels(&origin, &group)?;
+            for channel in &group {
+                storage::store_live_channel_state(
+                    &channel.id,
+                    channel.favorite,
+                    channel.last_watched_ts,
+                );
+            }
+            count += group.len();
+            continue;
+        }
+        for channel in group {
+            let Some(existing) = local.get(&channel.id) else {
+                continue;
+            };
+            let favorite = existing.favorite || channel.favorite;
+            let last_watched_ts = existing.last_watched_ts.max(channel.last_watched_ts);
+            if favorite != existing.favorite || last_watched_ts != existing.last_watched_ts {
+                storage::store_live_channel_state(&channel.id, favorite, last_watched_ts);
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Applies `bundle` to the local database.
+pub fn import(bundle: BackupBundle, mode: ImportMode) -> Result<ImportStats, UmbreonError> {
+    apply_settings(bundle.settings, bundle.remote_config, mode);
+    Ok(ImportStats {
+        feed_items: import_feed_items(bundle.feed_items, mode)?,
+        feed_marks: import_feed_marks(bundle.feed_marks, mode),
+        memories: import_memories(bundle.memories, mode),
+        conversations: import_conversations(bundle.conversations, mode),
+        live_channels: import_live_channels(bundle.live_channels, mode)?,
+    })
+}
+
+pub fn import_file(path: &str, mode: ImportMode) -> Result<ImportStats, UmbreonError> {
+    let text = storage::read_local_file(path)?;
+    import(parse_bundle(&text)?, mode)
+}
+
+/// Re-reads everything an import may have changed into the app state.
+pub fn reload_context(ctx: &AppContext) {
+    let settings = storage::load_settings();
+    let mut feed_items = ctx.feed_items;
+    let mut feed_server_url = ctx.feed_server_url;
+    let mut memory_server_url = ctx.memory_server_url;
+    let mut llm_endpoint = ctx.llm_endpoint;
+    let mut llm_model = ctx.llm_model;
+    let mut llm_models = ctx.llm_models;
+    let mut llm_embedding_model = ctx.llm_embedding_model;
+    let mut llm_prices = ctx.llm_prices;
+    let mut llm_monthly_budget = ctx.llm_monthly_budget;
+    let mut config_url = ctx.config_url;
+    let mut memory_policies = ctx.memory_policies;
+    let mut theme = ctx.theme;
+    let mut timeline_order = ctx.timeline_order;
+    let mut outbox = ctx.outbox;
+    let mut live_streams = ctx.live_streams;
+    *feed_items.write() = storage::load_feed_items();
+    *live_streams.write() = storage::load_live_channels();
+    feed_marks::refresh_marks(ctx.feed_marks);
+    // Restored memories may have been queued for the memory server.
+    let summary = memory_sync::summary();
+    {
+        let mut outbox = outbox.write();
+        outbox.queued = summary.queued;
+        outbox.dead = summary.dead;
+    }
+    if let Some(url) = settings.feed_server_url {
+        *feed_server_url.write() = url;
+    }
+    if let Some(url) = settings.memory_server_url {
+        *memory_server_url.write() = url;
+    }
+    if let Some(endpoint) = settings.llm_endpoint {
+        *llm_endpoint.write() = endpoint;
+    }
+    if let Some(model) = settings.llm_model {
+        *llm_model.write() = model;
+    }
+    if let Some(models) = settings.llm_models {
+        *llm_models.write() = models;
+    }
+    if let Some(model) = settings.llm_embedding_model {
+        *llm_embedding_model.write() = model;
+    }
+    if let Some(prices) = settings.llm_prices {
+        *llm_prices.write() = prices;
+    }
+    *llm_monthly_budget.write() = settings.llm_monthly_budget;
+    if let Some(url) = settings.config_url {
+        *config_url.write() = url;
+    }
+    *memory_policies.write() = config::cached_config().memory_policies;
+    if let Some(mode) = settings.theme {
+        *theme.write() = mode;
+    }
+    if let Some(order) = settings.timeline_order {
+        *timeline_order.write() = order;
+    }
+}
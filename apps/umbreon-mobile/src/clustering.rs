@@ -0,0 +1,210 @@
+use crate::embeddings::DuplicatePair;
+use crate::helper::fnv1a;
+use crate::ranking::tokenize;
+use crate::state::FeedItem;
+use crate::timeline::plain_text_from_html;
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+/// Token-set Jaccard similarity above which two titles are treated as the same story.
+const TITLE_THRESHOLD: f64 = 0.8;
+/// Titles shorter than this many tokens are too generic to cluster on.
+const MIN_TITLE_TOKENS: usize = 4;
+const FINGERPRINT_CHARS: usize = 280;
+/// Bodies shorter than this are mostly boilerplate and would collide across stories.
+const MIN_FINGERPRINT_CHARS: usize = 80;
+
+const TRACKING_PARAMS: [&str; 14] = [
+    "fbclid",
+    "gclid",
+    "dclid",
+    "msclkid",
+    "mc_cid",
+    "mc_eid",
+    "igshid",
+    "ref",
+    "ref_src",
+    "ref_url",
+    "source",
+    "spm",
+    "share_source",
+    "share_medium",
+];
+
+/// One story: the copy shown on the card and the other copies folded into it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoryCluster {
+    pub primary: FeedItem,
+    pub duplicates: Vec<FeedItem>,
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// `link` without scheme, `www.`/`m.` host prefixes, fragment, trailing slash and tracking
+/// parameters, so the same article reached through different feeds compares equal.
+pub fn canonical_link(link: &str) -> Option<String> {
+    let url = Url::parse(link.trim()).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let host = host
+        .strip_prefix("www.")
+        .or_else(|| host.strip_prefix("m."))
+        .unwrap_or(&host)
+        .to_string();
+    let mut params = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>();
+    params.sort();
+    let path = url.path().trim_end_matches('/');
+    if params.is_empty() {
+        Some(format!("{host}{path}"))
+    } else {
+        Some(format!("{host}{path}?{}", params.join("&")))
+    }
+}
+
+fn title_tokens(title: &str) -> HashSet<String> {
+    tokenize(title).into_iter().collect()
+}
+
+fn title_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.len() < MIN_TITLE_TOKENS || b.len() < MIN_TITLE_TOKENS {
+        return 0.0;
+    }
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+/// Hash of the first few hundred letters of the original body, ignoring markup, case,
+/// punctuation and whitespace.
+fn content_fingerprint(item: &FeedItem) -> Option<u64> {
+    let normalized = plain_text_from_html(&item.full_content)
+        .chars()
+        .filter(|ch| ch.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .take(FINGERPRINT_CHARS)
+        .collect::<String>();
+    if normalized.chars().count() < MIN_FINGERPRINT_CHARS {
+        return None;
+    }
+    Some(fnv1a(normalized.bytes()))
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents[b.max(a)] = a.min(b);
+    }
+}
+
+/// Which copy represents the cluster: a preferred one (e.g. bookmarked) if any, then a
+/// summarized one, then the fullest body, then the earliest to be published.
+fn primary_rank(
+    item: &FeedItem,
+    preferred: &HashSet<&str>,
+) -> (bool, bool, usize, std::cmp::Reverse<i64>) {
+    (
+        preferred.contains(item.id.as_str()),
+        item.summarized,
+        item.full_content.len(),
+        std::cmp::Reverse(item.published_ts),
+    )
+}
+
+/// Groups items that are the same story: equal canonical links, near-identical titles,
+/// equal body fingerprints, or embedding pairs from the semantic index. Clusters keep the
+/// order of their first item in `items`; `preferred` ids win the primary slot.
+pub fn cluster_items(
+    items: Vec<FeedItem>,
+    embedding_pairs: &[DuplicatePair],
+    preferred: &HashSet<&str>,
+) -> Vec<StoryCluster> {
+    let mut parents = (0..items.len()).collect::<Vec<_>>();
+    let positions = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (item.id.as_str(), index))
+        .collect::<HashMap<_, _>>();
+
+    let mut by_link = HashMap::new();
+    let mut by_fingerprint = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        if let Some(link) = canonical_link(&item.link)
+            && let Some(first) = by_link.insert(link, index)
+        {
+            union(&mut parents, first, index);
+        }
+        if let Some(fingerprint) = content_fingerprint(item)
+            && let Some(first) = by_fingerprint.insert(fingerprint, index)
+        {
+            union(&mut parents, first, index);
+        }
+    }
+
+    let titles = items
+        .iter()
+        .map(|item| title_tokens(&item.title))
+        .collect::<Vec<_>>();
+    for index in 0..items.len() {
+        for other in index + 1..items.len() {
+            if title_similarity(&titles[index], &titles[other]) >= TITLE_THRESHOLD {
+                union(&mut parents, index, other);
+            }
+        }
+    }
+
+    for pair in embedding_pairs {
+        if let (Some(first), Some(second)) = (
+            positions.get(pair.first.as_str()),
+            positions.get(pair.second.as_str()),
+        ) {
+            union(&mut parents, *first, *second);
+        }
+    }
+
+    let mut groups: Vec<Vec<FeedItem>> = Vec::new();
+    let mut group_of_root = HashMap::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let root = find(&mut parents, index);
+        let group = *group_of_root.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(item);
+    }
+
+    groups
+        .into_iter()
+        .map(|mut group| {
+            let best = group
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, item)| primary_rank(item, preferred))
+                .map(|(index, _)| index)
+                .unwrap_or_default();
+            let primary = group.remove(best);
+            StoryCluster {
+                primary,
+                duplicates: group,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,191 @@
+use crate::danmaku_file::{AttachmentState, DanmakuAttachment, DanmakuFileSink, OFFSET_STEP};
+use crate::state::use_app_context;
+use chrono::Utc;
+use dioxus::prelude::*;
+use std::time::Duration;
+use umbreon_core::danmaku_layout::{DanmakuLayout, PlaybackClock, VIEWPORT_HEIGHT, VIEWPORT_WIDTH};
+
+/// Redraw interval while the player runs.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Comments drawn over the player video. Tracks come from `danmaku_layout`; positions follow
+/// the player clock, re-anchored on every report from the shell so seeks and rate changes
+/// take effect on the next frame.
+#[allow(non_snake_case)]
+#[component]
+pub fn DanmakuOverlay() -> Element {
+    let ctx = use_app_context();
+    let entries = ctx.danmaku;
+    let overlay = ctx.danmaku_overlay;
+    let file = ctx.danmaku_file;
+    let player = ctx.player;
+    let layout = use_memo(move || DanmakuLayout::build(&entries.read(), *overlay.read()));
+    let mut clock = use_signal(PlaybackClock::default);
+    let mut frame_ms = use_signal(now_ms);
+    use_effect(move || {
+        let state = player.read();
+        let now = now_ms();
+        clock
+            .write()
+            .sync(state.position, state.rate, state.is_playing(), now);
+        frame_ms.set(now);
+    });
+    use_future(move || async move {
+        loop {
+            futures_timer::Delay::new(FRAME_INTERVAL).await;
+            let idle = !clock.peek().is_playing()
+                || !overlay.peek().enabled
+                || layout.peek().placed().is_empty();
+            if !idle {
+                frame_ms.set(now_ms());
+            }
+        }
+    });
+
+    let settings = *overlay.read();
+    if !settings.enabled {
+        return rsx! {};
+    }
+    // An attached file's offset delays its comments; the layout stays on file time.
+    let time = clock.read().position_at(*frame_ms.read()) - file.read().offset();
+    let entries = entries.read();
+    let layout = layout.read();
+    let items = layout
+        .visible_at(time)
+        .into_iter()
+        .filter_map(|placed| {
+            let entry = entries.get(placed.index)?;
+            let style = format!(
+                "left: {:.3}%; top: {:.3}%; font-size: {:.3}cqw; color: #{:06X};",
+                placed.x_at(time) / VIEWPORT_WIDTH * 100.0,
+                placed.y / VIEWPORT_HEIGHT * 100.0,
+                placed.font_px / VIEWPORT_WIDTH * 100.0,
+                entry.color,
+            );
+            Some((placed.index, style, entry.text.replace('\n', " ")))
+        })
+        .collect::<Vec<_>>();
+
+    rsx! {
+        div { class: "danmaku-overlay", style: "opacity: {settings.opacity};",
+            for (index, style, text) in items {
+                span { key: "{index}", class: "danmaku-item", style: "{style}", "{text}" }
+            }
+        }
+    }
+}
+
+/// Attaches a danmaku file (Bilibili XML, ASS or JSON, by path or URL) to a VOD and
+/// calibrates its delay. The attachment is remembered for `video` and restored by the
+/// player pane the next time it plays.
+#[allow(non_snake_case)]
+#[component]
+pub fn DanmakuFilePanel(video: String) -> Element {
+    let ctx = use_app_context();
+    let sink = DanmakuFileSink {
+        danmaku: ctx.danmaku,
+        state: ctx.danmaku_file,
+        now_playing: ctx.now_playing,
+    };
+    let state = ctx.danmaku_file.read().clone();
+    let mut source = use_signal(String::new);
+    // Follow the attachment the player pane restores for this video.
+    use_effect(move || {
+        if let Some(attachment) = sink.state.read().attachment() {
+            source.set(attachment.source.clone());
+        }
+    });
+    let status = match &state {
+        AttachmentState::None => None,
+        AttachmentState::Loading(_) => Some("加载中…".to_string()),
+        AttachmentState::Loaded { format, count, .. } => {
+            Some(format!("已加载 {count} 条 · {}", format.label()))
+        }
+        AttachmentState::Failed { error, .. } => Some(format!("加载失败：{error}")),
+    };
+    let loaded_offset = match &state {
+        AttachmentState::Loaded { attachment, .. } => Some(attachment.offset),
+        _ => None,
+    };
+    let busy = matches!(state, AttachmentState::Loading(_));
+    let attach = {
+        let video = video.clone();
+        move |_| {
+            let path = source.read().trim().to_string();
+            if path.is_empty() {
+                return;
+            }
+            let attachment = DanmakuAttachment {
+                source: path,
+                offset: loaded_offset.unwrap_or_default(),
+            };
+            spawn(sink.attach(video.clone(), attachment));
+        }
+    };
+
+    rsx! {
+        div { class: "danmaku-file",
+            div { class: "danmaku-file-row",
+                input {
+                    class: "settings-input",
+                    placeholder: "弹幕文件路径或 URL（XML / ASS / JSON）",
+                    value: "{source.read()}",
+                    oninput: move |evt| source.set(evt.value()),
+                }
+                button { class: "player-danmaku", disabled: busy, onclick: attach, "加载" }
+                if state != AttachmentState::None {
+                    button {
+                        class: "player-danmaku",
+                        onclick: {
+                            let video = video.clone();
+                            move |_| {
+                                sink.detach(&video);
+                                source.set(String::new());
+                            }
+                        },
+                        "移除"
+                    }
+                }
+            }
+            if let Some(status) = status {
+                p { class: "danmaku-file-status", "{status}" }
+            }
+            if let Some(offset) = loaded_offset {
+                div { class: "danmaku-file-row",
+                    span { class: "player-status", "延迟校准" }
+                    button {
+                        class: "player-danmaku",
+                        onclick: {
+                            let video = video.clone();
+                            move |_| sink.set_offset(&video, offset - OFFSET_STEP)
+                        },
+                        "-{OFFSET_STEP}s"
+                    }
+                    span { class: "player-time", "{offset:+.1} 秒" }
+                    button {
+                        class: "player-danmaku",
+                        onclick: {
+                            let video = video.clone();
+                            move |_| sink.set_offset(&video, offset + OFFSET_STEP)
+                        },
+                        "+{OFFSET_STEP}s"
+                    }
+                    if offset != 0.0 {
+                        button {
+                            class: "player-danmaku",
+                            onclick: {
+                                let video = video.clone();
+                                move |_| sink.set_offset(&video, 0.0)
+                            },
+                            "归零"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
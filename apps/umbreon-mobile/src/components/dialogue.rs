@@ -0,0 +1,286 @@
+use crate::dialogue::{
+    self, ChatSettings, conversation_title, format_chat_time, trigger_chat_reply,
+};
+use crate::state::{ChatBackend, ChatMessage, ChatMode, ChatRole, Conversation, use_app_context};
+use crate::storage;
+use dioxus::prelude::*;
+
+#[allow(non_snake_case)]
+pub fn DialoguePane() -> Element {
+    let ctx = use_app_context();
+    let mut active_conversation = ctx.active_conversation;
+    let memory_server_url = ctx.memory_server_url;
+    let llm_endpoint = ctx.llm_endpoint;
+    let llm_api_key = ctx.llm_api_key;
+    let llm_model = ctx.llm_model;
+    let toast = ctx.toast;
+    let feed_items = ctx.feed_items;
+
+    let mut conversations = use_signal(storage::load_conversations);
+    let mut conversation = use_signal(|| None::<Conversation>);
+    let mut messages = use_signal(Vec::<ChatMessage>::new);
+    let streaming = use_signal(|| None::<String>);
+    let mut draft = use_signal(String::new);
+    let mut save_to_memory = use_signal(|| false);
+
+    use_effect(move || {
+        let active = active_conversation.read().clone();
+        match active {
+            Some(id) => {
+                let loaded = storage::load_conversations()
+                    .into_iter()
+                    .find(|item| item.id == id);
+                *messages.write() = storage::load_chat_messages(&id);
+                *conversation.write() = loaded;
+            }
+            None => {
+                *conversation.write() = None;
+                *messages.write() = Vec::new();
+                *conversations.write() = storage::load_conversations();
+            }
+        }
+    });
+
+    let Some(current) = conversation.read().clone() else {
+        return rsx! {
+            div { class: "dialogue-pane",
+                div { class: "dialogue-toolbar",
+                    button {
+                        class: "settings-sync",
+                        onclick: move |_| {
+                            let created =
+                                dialogue::new_conversation(ChatMode::Profile, ChatBackend::Memory);
+                            storage::store_conversation(&created);
+                            *active_conversation.write() = Some(created.id);
+                        },
+                        span { class: "material-icons", "add_comment" }
+                        span { "新对话" }
+                    }
+                }
+                if conversations.read().is_empty() {
+                    p { class: "empty-state", "还没有对话，开始一段新的吧。" }
+                } else {
+                    ul { class: "conversation-list",
+                        for item in conversations.read().clone().into_iter() {
+                            ConversationRow {
+                                key: "{item.id}",
+                                conversation: item.clone(),
+                                on_open: move |id: String| {
+                                    *active_conversation.write() = Some(id);
+                                },
+                                on_delete: move |id: String| {
+                                    storage::delete_conversation(&id);
+                                    *conversations.write() = storage::load_conversations();
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        };
+    };
+
+    let is_streaming = streaming.read().is_some();
+    let can_send = !is_streaming && !draft.read().trim().is_empty();
+    let title = conversation_title(&current);
+    let current_mode = current.mode;
+    let current_backend = current.backend;
+    let linked_item = current.feed_item_id.as_ref().and_then(|id| {
+        feed_items
+            .read()
+            .iter()
+            .find(|item| &item.id == id)
+            .map(|item| (item.title.clone(), item.link.clone()))
+    });
+
+    let mut send = move || {
+        let text = draft.read().trim().to_string();
+        if text.is_empty() || streaming.read().is_some() {
+            return;
+        }
+        *draft.write() = String::new();
+        let settings = ChatSettings {
+            memory_server_url: memory_server_url.read().trim().to_string(),
+            llm_endpoint: llm_endpoint.read().trim().to_string(),
+            llm_api_key: llm_api_key.read().trim().to_string(),
+            llm_model: llm_model.read().trim().to_string(),
+        };
+        trigger_chat_reply(
+            settings,
+            conversation,
+            text,
+            *save_to_memory.read(),
+            messages,
+            streaming,
+            toast,
+        );
+    };
+
+    rsx! {
+        div { class: "dialogue-pane",
+            div { class: "dialogue-toolbar",
+                button {
+                    class: "explore-back",
+                    onclick: move |_| {
+                        *active_conversation.write() = None;
+                    },
+                    span { class: "material-icons", "chevron_left" }
+                }
+                span { class: "dialogue-title", "{title}" }
+                select {
+                    class: "settings-select dialogue-mode",
+                    value: "{current_backend.as_str()}",
+                    onchange: move |evt| {
+                        let backend = ChatBackend::from_value(&evt.value());
+                        let mut updated = conversation.read().clone();
+                        if let Some(updated) = updated.as_mut() {
+                            updated.backend = backend;
+                            storage::store_conversation(updated);
+                        }
+                        *conversation.write() = updated;
+                    },
+                    for backend in ChatBackend::ALL.into_iter() {
+                        option {
+                            value: "{backend.as_str()}",
+                            selected: backend == current_backend,
+                            "{backend.label()}"
+                        }
+                    }
+                }
+                if current_backend == ChatBackend::Memory {
+                    select {
+                        class: "settings-select dialogue-mode",
+                        value: "{current_mode.as_str()}",
+                        onchange: move |evt| {
+                            let mode = ChatMode::from_value(&evt.value());
+                            let mut updated = conversation.read().clone();
+                            if let Some(updated) = updated.as_mut() {
+                                updated.mode = mode;
+                                storage::store_conversation(updated);
+                            }
+                            *conversation.write() = updated;
+                        },
+                        for mode in ChatMode::ALL.into_iter() {
+                            option {
+                                value: "{mode.as_str()}",
+                                selected: mode == current_mode,
+                                "{mode.label()}"
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some((item_title, link)) = linked_item {
+                a {
+                    class: "dialogue-article",
+                    href: "{link}",
+                    target: "_blank",
+                    span { class: "material-icons", "article" }
+                    span { "{item_title}" }
+                }
+            }
+            div { class: "chat-messages",
+                if messages.read().is_empty() && !is_streaming {
+                    p { class: "empty-state", "向 Umbreon 提问，回答会结合你的记忆。" }
+                }
+                for (index, message) in messages.read().clone().into_iter().enumerate() {
+                    ChatBubble { key: "{index}", message }
+                }
+                if let Some(partial) = streaming.read().clone() {
+                    div { class: "chat-bubble chat-bubble--assistant is-streaming",
+                        if partial.is_empty() {
+                            span { class: "material-icons chat-typing", "more_horiz" }
+                        } else {
+                            p { "{partial}" }
+                        }
+                    }
+                }
+            }
+            div { class: "chat-composer",
+                label { class: "chat-memory-toggle",
+                    input {
+                        r#type: "checkbox",
+                        checked: *save_to_memory.read(),
+                        onchange: move |_| {
+                            let next = !*save_to_memory.read();
+                            *save_to_memory.write() = next;
+                        }
+                    }
+                    span { "写入记忆" }
+                }
+                div { class: "settings-row",
+                    textarea {
+                        class: "settings-input chat-input",
+                        rows: "2",
+                        placeholder: "输入消息…",
+                        value: "{draft.read()}",
+                        oninput: move |evt| {
+                            *draft.write() = evt.value();
+                        }
+                    }
+                    button {
+                        class: if is_streaming {
+                            "settings-sync settings-sync-icon is-loading"
+                        } else {
+                            "settings-sync settings-sync-icon"
+                        },
+                        disabled: !can_send,
+                        onclick: move |_| send(),
+                        span { class: "material-icons", if is_streaming { "autorenew" } else { "send" } }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ConversationRow(
+    conversation: Conversation,
+    on_open: EventHandler<String>,
+    on_delete: EventHandler<String>,
+) -> Element {
+    let title = conversation_title(&conversation);
+    let updated = format_chat_time(conversation.updated_ts);
+    let open_id = conversation.id.clone();
+    let delete_id = conversation.id.clone();
+
+    rsx! {
+        li { class: "conversation-row",
+            onclick: move |_| {
+                on_open.call(open_id.clone());
+            },
+            if conversation.feed_item_id.is_some() {
+                span { class: "material-icons conversation-icon", "article" }
+            }
+            div { class: "conversation-main",
+                span { class: "conversation-title", "{title}" }
+                small { class: "conversation-meta", "{conversation.backend.label()} · {updated}" }
+            }
+            button {
+                class: "conversation-delete",
+                onclick: move |evt| {
+                    evt.stop_propagation();
+                    on_delete.call(delete_id.clone());
+                },
+                span { class: "material-icons", "delete_outline" }
+            }
+        }
+    }
+}
+
+#[component]
+fn ChatBubble(message: ChatMessage) -> Element {
+    let class = match message.role {
+        ChatRole::User => "chat-bubble chat-bubble--user",
+        ChatRole::Assistant => "chat-bubble chat-bubble--assistant",
+    };
+    let time = format_chat_time(message.created_ts);
+
+    rsx! {
+        div { class: "{class}",
+            p { "{message.content}" }
+            small { class: "chat-time", "{time}" }
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::state::{MediaKind, use_app_context};
+use crate::state::{MediaKind, TimelineOrder, use_app_context};
+use crate::storage;
 use dioxus::prelude::*;
 
 use super::player::NowPlayingPane;
@@ -18,14 +19,12 @@ pub fn ExplorePane() -> Element {
     let mut view = use_signal(|| ExploreView::Menu);
     let current = *view.read();
     let ctx = use_app_context();
-    let feed_items = ctx.feed_items;
-    let llm_endpoint = ctx.llm_endpoint;
-    let llm_api_key = ctx.llm_api_key;
-    let llm_model = ctx.llm_model;
     let feed_server_url = ctx.feed_server_url;
-    let settings_status = ctx.settings_status;
     let feed_syncing = ctx.feed_syncing;
     let is_syncing = *feed_syncing.read();
+    let mut timeline_order = ctx.timeline_order;
+    let order = *timeline_order.read();
+    let sync_ctx = ctx.clone();
 
     rsx! {
         section { class: "explore-pane",
@@ -40,6 +39,22 @@ pub fn ExplorePane() -> Element {
                         span { "返回" }
                     }
                     if current == ExploreView::Timeline {
+                        button {
+                            class: if order == TimelineOrder::Personalized {
+                                "explore-sync explore-order is-active"
+                            } else {
+                                "explore-sync explore-order"
+                            },
+                            title: if order == TimelineOrder::Personalized { "个性化排序" } else { "按时间排序" },
+                            onclick: move |_| {
+                                let next = timeline_order.read().toggle();
+                                *timeline_order.write() = next;
+                                storage::store_timeline_order(next);
+                            },
+                            span { class: "material-icons",
+                                if order == TimelineOrder::Personalized { "auto_awesome" } else { "schedule" }
+                            }
+                        }
                         button {
                             class: if is_syncing { "explore-sync is-loading" } else { "explore-sync" },
                             disabled: is_syncing,
@@ -48,15 +63,7 @@ pub fn ExplorePane() -> Element {
                                     return;
                                 }
                                 let url = feed_server_url.read().trim().to_string();
-                                trigger_feed_sync(
-                                    url,
-                                    feed_items.clone(),
-                                    settings_status.clone(),
-                                    feed_syncing.clone(),
-                                    llm_endpoint.clone(),
-                                    llm_api_key.clone(),
-                                    llm_model.clone(),
-                                );
+                                trigger_feed_sync(url, &sync_ctx);
                             },
                             span { class: "material-icons", "refresh" }
                         }
@@ -1,28 +1,472 @@
-use crate::state::use_app_context;
+use crate::dialogue::format_chat_time;
+use crate::embeddings::{self, EmbeddingSettings};
+use crate::feed_marks;
+use crate::memory_client::{DEFAULT_USER_ID, MemoryClient, MemorySearchResult};
+use crate::memory_sync;
+use crate::state::{
+    EmbeddingKind, LocalMemory, MemoryCategory, MemorySyncState, OutboxEntry, ToastAction,
+    ToastKind, ToastMessage, use_app_context,
+};
+use crate::storage;
 use dioxus::prelude::*;
 
+const SEARCH_LIMIT: u32 = 20;
+
+fn parse_tags(value: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for tag in value.split([',', '，']) {
+        let tag = tag.trim();
+        if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
 #[allow(non_snake_case)]
 pub fn MemoryPane() -> Element {
     let ctx = use_app_context();
-    let memory = ctx.memory_panel.read().clone();
+    let memory_server_url = ctx.memory_server_url;
+    let outbox = ctx.outbox;
+    let feed_marks = ctx.feed_marks;
+    let mut toast = ctx.toast;
+
+    let mut memories = use_signal(storage::load_local_memories);
+    let mut category = use_signal(|| None::<MemoryCategory>);
+    let mut query = use_signal(String::new);
+    let mut results = use_signal(|| None::<Vec<MemorySearchResult>>);
+    let mut searching = use_signal(|| false);
+    let mut local_search = use_signal(|| false);
+
+    // Sync results land in the outbox summary; reload so badges follow them.
+    use_effect(move || {
+        let _ = outbox.read();
+        *memories.write() = storage::load_local_memories();
+    });
+
+    let selected = *category.read();
+    let visible = memories
+        .read()
+        .iter()
+        .filter(|memory| selected.is_none_or(|category| memory.category == category))
+        .cloned()
+        .collect::<Vec<_>>();
+    let is_searching = *searching.read();
+    let is_local = *local_search.read();
+
+    let mut run_search = move || {
+        let text = query.read().trim().to_string();
+        if text.is_empty() || *searching.read() {
+            return;
+        }
+        if *local_search.read() {
+            let Some(settings) = EmbeddingSettings::load() else {
+                *toast.write() = Some(ToastMessage {
+                    kind: ToastKind::Error,
+                    text: "请先在设置中配置通用模型。".to_string(),
+                    action: Some(ToastAction::OpenSettings),
+                });
+                return;
+            };
+            *searching.write() = true;
+            spawn(async move {
+                match embeddings::search(
+                    &settings,
+                    &text,
+                    EmbeddingKind::Memory,
+                    SEARCH_LIMIT as usize,
+                )
+                .await
+                {
+                    Ok(hits) => {
+                        let local = memories.read();
+                        let found = hits
+                            .into_iter()
+                            .filter_map(|(id, score)| {
+                                let memory = local.iter().find(|memory| memory.id == id)?;
+                                Some(MemorySearchResult {
+                                    id,
+                                    content: memory.content.clone(),
+                                    score: Some(score),
+                                    category: Some(memory.category.as_str().to_string()),
+                                })
+                            })
+                            .collect();
+                        drop(local);
+                        *results.write() = Some(found);
+                    }
+                    Err(err) => {
+                        *toast.write() =
+                            Some(ToastMessage::from_error("本地语义搜索失败", &err, None));
+                    }
+                }
+                *searching.write() = false;
+            });
+            return;
+        }
+        let url = memory_server_url.read().trim().to_string();
+        if url.is_empty() {
+            *toast.write() = Some(ToastMessage {
+                kind: ToastKind::Error,
+                text: "请先在设置中填写 Memory Server。".to_string(),
+                action: Some(ToastAction::OpenSettings),
+            });
+            return;
+        }
+        *searching.write() = true;
+        spawn(async move {
+            let client = MemoryClient::new(url, DEFAULT_USER_ID);
+            match client.search_memories(text, Some(SEARCH_LIMIT), None).await {
+                Ok(response) if response.success => {
+                    *results.write() = Some(response.results.unwrap_or_default());
+                }
+                Ok(response) => {
+                    *toast.write() = Some(ToastMessage {
+                        kind: ToastKind::Error,
+                        text: format!(
+                            "记忆搜索失败：{}",
+                            response
+                                .error
+                                .unwrap_or_else(|| "unknown error".to_string())
+                        ),
+                        action: None,
+                    });
+                }
+                Err(err) => {
+                    *toast.write() = Some(ToastMessage::from_error("记忆搜索失败", &err, None));
+                }
+            }
+            *searching.write() = false;
+        });
+    };
 
     rsx! {
         section { class: "memory-pane",
-            header { h2 { "In-app Memory" } }
-            p { class: "status", "Synced: {memory.synced}" }
-            if let Some(ts) = memory.last_synced {
-                p { class: "status", "Last sync: {ts}" }
-            }
-            h3 { "Highlights" }
-            if memory.highlights.is_empty() {
-                p { class: "empty-state", "No highlights captured yet." }
+            OutboxPanel {}
+            div { class: "settings-row",
+                input {
+                    class: "settings-input",
+                    r#type: "search",
+                    placeholder: "搜索记忆…",
+                    value: "{query.read()}",
+                    oninput: move |evt| {
+                        let value = evt.value();
+                        if value.trim().is_empty() {
+                            *results.write() = None;
+                        }
+                        *query.write() = value;
+                    },
+                    onkeydown: move |evt| {
+                        if evt.key() == Key::Enter {
+                            run_search();
+                        }
+                    }
+                }
+                button {
+                    class: if is_searching {
+                        "settings-sync settings-sync-icon is-loading"
+                    } else {
+                        "settings-sync settings-sync-icon"
+                    },
+                    disabled: is_searching,
+                    onclick: move |_| run_search(),
+                    span { class: "material-icons", if is_searching { "autorenew" } else { "search" } }
+                }
+            }
+            div { class: "memory-filters",
+                button {
+                    class: if is_local { "memory-filter" } else { "memory-filter is-active" },
+                    onclick: move |_| *local_search.write() = false,
+                    "记忆服务"
+                }
+                button {
+                    class: if is_local { "memory-filter is-active" } else { "memory-filter" },
+                    onclick: move |_| *local_search.write() = true,
+                    "本地语义"
+                }
+            }
+            if let Some(found) = results.read().clone() {
+                h3 { "搜索结果" }
+                if found.is_empty() {
+                    p { class: "empty-state", "没有匹配的记忆。" }
+                } else {
+                    ul { class: "conversation-list",
+                        for (index, result) in found.into_iter().enumerate() {
+                            SearchResultRow { key: "{index}", result }
+                        }
+                    }
+                }
+            }
+            div { class: "memory-filters",
+                button {
+                    class: if selected.is_none() { "memory-filter is-active" } else { "memory-filter" },
+                    onclick: move |_| *category.write() = None,
+                    "全部"
+                }
+                for option in MemoryCategory::ALL.into_iter() {
+                    button {
+                        key: "{option.as_str()}",
+                        class: if selected == Some(option) { "memory-filter is-active" } else { "memory-filter" },
+                        onclick: move |_| *category.write() = Some(option),
+                        "{option.label()}"
+                    }
+                }
+            }
+            if visible.is_empty() {
+                p { class: "empty-state", "还没有本地记忆。" }
             } else {
-                ul {
-                    for note in memory.highlights.into_iter() {
-                        li { "{note}" }
+                ul { class: "conversation-list",
+                    for memory in visible.into_iter() {
+                        MemoryRow {
+                            key: "{memory.id}",
+                            memory: memory.clone(),
+                            on_save: move |(id, content, tags): (String, String, Vec<String>)| {
+                                memory_sync::edit_memory(&id, &content, &tags);
+                                *memories.write() = storage::load_local_memories();
+                                memory_sync::trigger_flush(
+                                    memory_server_url.read().trim().to_string(),
+                                    outbox,
+                                    move || feed_marks::refresh_marks(feed_marks),
+                                );
+                            },
+                            on_delete: move |id: String| {
+                                memory_sync::delete_memory(&id);
+                                feed_marks::forget_memories(&[id], feed_marks);
+                                *memories.write() = storage::load_local_memories();
+                                memory_sync::trigger_flush(
+                                    memory_server_url.read().trim().to_string(),
+                                    outbox,
+                                    move || feed_marks::refresh_marks(feed_marks),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn MemoryRow(
+    memory: LocalMemory,
+    on_save: EventHandler<(String, String, Vec<String>)>,
+    on_delete: EventHandler<String>,
+) -> Element {
+    let mut editing = use_signal(|| false);
+    let mut draft_content = use_signal(String::new);
+    let mut draft_tags = use_signal(String::new);
+
+    let (state_icon, state_class) = match memory.sync_state {
+        MemorySyncState::Pending => ("cloud_upload", "memory-state memory-state--pending"),
+        MemorySyncState::Synced => ("cloud_done", "memory-state"),
+        MemorySyncState::Failed => ("sync_problem", "memory-state memory-state--failed"),
+    };
+    let updated = format_chat_time(memory.updated_ts);
+    let tags = memory.tags.join(", ");
+    let edit_memory = memory.clone();
+    let save_id = memory.id.clone();
+    let delete_id = memory.id.clone();
+
+    if *editing.read() {
+        return rsx! {
+            li { class: "conversation-row memory-row is-editing",
+                div { class: "conversation-main",
+                    textarea {
+                        class: "settings-input",
+                        rows: "4",
+                        value: "{draft_content.read()}",
+                        oninput: move |evt| *draft_content.write() = evt.value()
                     }
+                    input {
+                        class: "settings-input",
+                        placeholder: "标签，用逗号分隔",
+                        value: "{draft_tags.read()}",
+                        oninput: move |evt| *draft_tags.write() = evt.value()
+                    }
+                }
+                button {
+                    class: "conversation-delete",
+                    onclick: move |_| {
+                        on_save.call((
+                            save_id.clone(),
+                            draft_content.read().clone(),
+                            parse_tags(&draft_tags.read()),
+                        ));
+                        *editing.write() = false;
+                    },
+                    span { class: "material-icons", "check" }
+                }
+                button {
+                    class: "conversation-delete",
+                    onclick: move |_| *editing.write() = false,
+                    span { class: "material-icons", "close" }
+                }
+            }
+        };
+    }
+
+    rsx! {
+        li { class: "conversation-row memory-row",
+            span { class: "material-icons {state_class}", "{state_icon}" }
+            div { class: "conversation-main",
+                span { class: "memory-content", "{memory.content}" }
+                small { class: "conversation-meta", "{memory.category.label()} · {updated}" }
+                if !tags.is_empty() {
+                    small { class: "conversation-meta", "# {tags}" }
+                }
+            }
+            button {
+                class: "conversation-delete",
+                onclick: move |_| {
+                    *draft_content.write() = edit_memory.content.clone();
+                    *draft_tags.write() = edit_memory.tags.join(", ");
+                    *editing.write() = true;
+                },
+                span { class: "material-icons", "edit" }
+            }
+            button {
+                class: "conversation-delete",
+                onclick: move |_| on_delete.call(delete_id.clone()),
+                span { class: "material-icons", "delete_outline" }
+            }
+        }
+    }
+}
+
+#[component]
+fn SearchResultRow(result: MemorySearchResult) -> Element {
+    let score = result
+        .score
+        .map(|score| format!("{score:.2}"))
+        .unwrap_or_else(|| "–".to_string());
+    let category = result
+        .category
+        .as_deref()
+        .and_then(MemoryCategory::parse)
+        .map(|category| category.label())
+        .unwrap_or("未分类");
+
+    rsx! {
+        li { class: "conversation-row memory-row",
+            span { class: "memory-score", "{score}" }
+            div { class: "conversation-main",
+                span { class: "memory-content", "{result.content}" }
+                small { class: "conversation-meta", "{category}" }
+            }
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+fn OutboxPanel() -> Element {
+    let ctx = use_app_context();
+    let mut outbox = ctx.outbox;
+    let memory_server_url = ctx.memory_server_url;
+    let feed_marks = ctx.feed_marks;
+    let mut toast = ctx.toast;
+    let mut dead_letters = use_signal(memory_sync::dead_letters);
+
+    let summary = outbox.read().clone();
+    let last_synced = summary
+        .last_synced
+        .map(format_chat_time)
+        .unwrap_or_else(|| "尚未同步".to_string());
+
+    let mut refresh = move || {
+        let syncing = outbox.read().syncing;
+        let next = memory_sync::summary();
+        let mut current = outbox.write();
+        current.queued = next.queued;
+        current.dead = next.dead;
+        current.syncing = syncing;
+        drop(current);
+        *dead_letters.write() = memory_sync::dead_letters();
+        feed_marks::refresh_marks(feed_marks);
+    };
+
+    rsx! {
+        div { class: "outbox-panel",
+            div { class: "settings-row settings-row-spread",
+                h3 { "同步队列" }
+                button {
+                    class: if summary.syncing {
+                        "settings-sync settings-sync-icon is-loading"
+                    } else {
+                        "settings-sync settings-sync-icon"
+                    },
+                    disabled: summary.syncing,
+                    onclick: move |_| {
+                        let url = memory_server_url.read().trim().to_string();
+                        memory_sync::trigger_flush(url, outbox, move || {
+                            *dead_letters.write() = memory_sync::dead_letters();
+                            feed_marks::refresh_marks(feed_marks);
+                            if let Some(err) = outbox.read().last_error.clone() {
+                                *toast.write() = Some(ToastMessage {
+                                    kind: ToastKind::Error,
+                                    text: format!("记忆同步失败：{err}"),
+                                    action: Some(ToastAction::RetryMemorySync),
+                                });
+                            }
+                        });
+                    },
+                    span { class: "material-icons", "sync" }
                 }
             }
+            p { class: "status", "待同步 {summary.queued} · 失败 {summary.dead} · 上次同步 {last_synced}" }
+            if let Some(err) = summary.last_error.as_ref() {
+                p { class: "settings-hint outbox-error", "{err}" }
+            }
+            if !dead_letters.read().is_empty() {
+                ul { class: "conversation-list",
+                    for entry in dead_letters.read().clone().into_iter() {
+                        DeadLetterRow {
+                            key: "{entry.seq}",
+                            entry: entry.clone(),
+                            on_retry: move |seq: i64| {
+                                memory_sync::retry_dead(seq);
+                                refresh();
+                            },
+                            on_discard: move |seq: i64| {
+                                memory_sync::discard_dead(seq);
+                                refresh();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn DeadLetterRow(
+    entry: OutboxEntry,
+    on_retry: EventHandler<i64>,
+    on_discard: EventHandler<i64>,
+) -> Element {
+    let preview = entry.payload.lines().next().unwrap_or_default().to_string();
+    let error = entry.last_error.clone().unwrap_or_default();
+    let created = format_chat_time(entry.created_ts);
+    let seq = entry.seq;
+
+    rsx! {
+        li { class: "conversation-row",
+            div { class: "conversation-main",
+                span { class: "conversation-title", "{entry.op.label()} · {preview}" }
+                small { class: "conversation-meta", "{created} · 尝试 {entry.attempts} 次" }
+                small { class: "outbox-error", "{error}" }
+            }
+            button {
+                class: "conversation-delete",
+                onclick: move |_| on_retry.call(seq),
+                span { class: "material-icons", "replay" }
+            }
+            button {
+                class: "conversation-delete",
+                onclick: move |_| on_discard.call(seq),
+                span { class: "material-icons", "delete_outline" }
+            }
         }
     }
 }
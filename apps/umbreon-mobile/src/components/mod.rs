@@ -1,9 +1,14 @@
+pub mod danmaku;
+pub mod dialogue;
 pub mod navigation;
 pub mod timeline;
 pub mod player;
 pub mod memory;
 pub mod explore;
+pub mod usage;
 
+pub use dialogue::DialoguePane;
 pub use navigation::NavigationBar;
 pub use memory::MemoryPane;
 pub use explore::ExplorePane;
+pub use usage::UsagePanel;
@@ -1,5 +1,16 @@
-use crate::state::{use_app_context, LiveStream, MediaKind, MediaSession};
+use crate::components::danmaku::{DanmakuFilePanel, DanmakuOverlay};
+use crate::danmaku_file::{self, AttachmentState, DanmakuFileSink};
+use crate::live;
+use crate::live_danmaku::{self, LiveDanmakuSink};
+use crate::player_shell::{self, SharedShell, PLAYER_ELEMENT_ID};
+use crate::state::{
+    use_app_context, LiveStream, MediaKind, MediaSession, ToastMessage, UI_LOCALE,
+};
+use crate::storage;
 use dioxus::prelude::*;
+use umbreon_core::danmaku_live::{ConnectionState, DanmakuBuffer};
+use umbreon_core::player::{PlaybackStatus, PlayerShell, PlayerState, RATES};
+use umbreon_core::UmbreonError;
 
 #[allow(non_snake_case)]
 #[component]
@@ -11,6 +22,79 @@ pub fn NowPlayingPane(mode: MediaKind) -> Element {
         MediaKind::Live => "Live",
         MediaKind::Vod => "VOD",
     };
+    let shell = player_shell::use_player_shell(ctx.player);
+    use_context_provider({
+        let shell = shell.clone();
+        move || shell
+    });
+    let player = ctx.player;
+    let mut toast = ctx.toast;
+    let low_latency = ctx.low_latency;
+    let mut danmaku = ctx.danmaku;
+    use_effect({
+        let shell = shell.clone();
+        move || {
+            let low_latency = *low_latency.read();
+            let result = player_shell::with_shell(&shell, player, |shell| {
+                shell.set_low_latency(low_latency.enabled, low_latency.target_latency)
+            });
+            if let Err(err) = result {
+                *toast.write() = Some(ToastMessage::from_error("低延迟模式", &err, None));
+            }
+        }
+    });
+    let file_sink = DanmakuFileSink {
+        danmaku,
+        state: ctx.danmaku_file,
+        now_playing: ctx.now_playing,
+    };
+    let stream_url = now_playing.as_ref().map(|session| session.stream_url.clone());
+    use_effect(use_reactive!(|stream_url| {
+        danmaku.write().clear();
+        let mut file_state = file_sink.state;
+        file_state.set(AttachmentState::None);
+        let Some(url) = stream_url else {
+            return;
+        };
+        let is_vod = file_sink
+            .now_playing
+            .peek()
+            .as_ref()
+            .is_some_and(|session| session.kind == MediaKind::Vod);
+        if let Some(attachment) = is_vod.then(|| danmaku_file::attachment_for(&url)).flatten() {
+            spawn(file_sink.attach(url.clone(), attachment));
+        }
+        let result = player_shell::with_shell(&shell, player, |shell| {
+            shell.load(&url)?;
+            shell.play()
+        });
+        if let Err(err) = result {
+            *toast.write() = Some(ToastMessage::from_error("播放失败", &err, None));
+        }
+    }));
+    let buffer = use_signal(DanmakuBuffer::default);
+    let sink = LiveDanmakuSink {
+        danmaku,
+        buffer,
+        connection: ctx.danmaku_connection,
+        player,
+    };
+    use_effect(move || sink.set_paused(!player.read().is_playing()));
+    let mut relay = use_signal(|| None::<Task>);
+    let relay_url = now_playing
+        .as_ref()
+        .and_then(|session| session.danmaku_endpoint.clone())
+        .filter(|endpoint| live_danmaku::is_followable(endpoint));
+    use_effect(use_reactive!(|relay_url| {
+        if let Some(task) = relay.write().take() {
+            task.cancel();
+        }
+        let mut connection = sink.connection;
+        connection.set(ConnectionState::Disconnected);
+        if let Some(url) = relay_url {
+            relay.set(Some(spawn(live_danmaku::follow(url, sink))));
+        }
+    }));
 
     rsx! {
         section { class: "player-pane",
@@ -31,6 +115,12 @@ pub fn NowPlayingPane(mode: MediaKind) -> Element {
 
 #[component]
 fn NowPlayingCard(session: MediaSession) -> Element {
+    let ctx = use_app_context();
+    let connection = ctx.danmaku_connection.read().clone();
+    let connection_error = match &connection {
+        ConnectionState::Failed(err) => Some(err.user_message(UI_LOCALE)),
+        _ => None,
+    };
     let kind_label = match session.kind {
         MediaKind::Live => "LIVE",
         MediaKind::Vod => "VOD",
@@ -41,30 +131,253 @@ fn NowPlayingCard(session: MediaSession) -> Element {
             span { class: "badge", "{kind_label}" }
             h3 { "{session.title}" }
             p { class: "source", "Source: {session.source}" }
-            p { class: "stream", "Stream: {session.stream_url}" }
-            if let Some(endpoint) = session.danmaku_endpoint {
-                p { class: "danmaku", "Danmaku: {endpoint}" }
+            div { class: "player-stage",
+                video { id: PLAYER_ELEMENT_ID, class: "player-video", playsinline: true }
+                DanmakuOverlay {}
+            }
+            PlayerControls {}
+            if session.kind == MediaKind::Vod {
+                DanmakuFilePanel { key: "{session.stream_url}", video: session.stream_url.clone() }
+            }
+            if session.danmaku_endpoint.is_some() && connection != ConnectionState::Disconnected {
+                p {
+                    class: if connection.is_connected() { "danmaku-connection is-connected" } else { "danmaku-connection" },
+                    title: connection_error.unwrap_or_default(),
+                    span { class: "material-icons", "forum" }
+                    "弹幕 · {connection.label()}"
+                }
             }
         }
     }
 }
 
+fn format_position(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Runs a control action on the shell and reports a failure as a toast.
+fn control(
+    shell: &SharedShell,
+    player: Signal<PlayerState>,
+    mut toast: Signal<Option<ToastMessage>>,
+    action: impl FnOnce(&mut PlayerShell) -> Result<(), UmbreonError>,
+) {
+    if let Err(err) = player_shell::with_shell(shell, player, action) {
+        *toast.write() = Some(ToastMessage::from_error("播放器", &err, None));
+    }
+}
+
+#[component]
+fn PlayerControls() -> Element {
+    let ctx = use_app_context();
+    let shell = use_context::<SharedShell>();
+    let player = ctx.player;
+    let toast = ctx.toast;
+    let mut low_latency = ctx.low_latency;
+    let mut danmaku_overlay = ctx.danmaku_overlay;
+    let danmaku_enabled = danmaku_overlay.read().enabled;
+    let state = player.read().clone();
+    let busy = matches!(state.status, PlaybackStatus::Loading | PlaybackStatus::Idle);
+    let toggle_icon = if state.is_playing() { "pause" } else { "play_arrow" };
+    let duration = state.duration.unwrap_or_default();
+    let time_label = if state.live {
+        match state.latency {
+            Some(latency) => format!("LIVE · 延迟 {latency:.1}s"),
+            None => "LIVE".to_string(),
+        }
+    } else {
+        format!(
+            "{} / {}",
+            format_position(state.position),
+            format_position(duration)
+        )
+    };
+    let quality_value = state
+        .quality
+        .map(|level| level.to_string())
+        .unwrap_or_default();
+
+    rsx! {
+        div { class: "player-controls",
+            button {
+                class: "player-toggle",
+                disabled: busy,
+                onclick: {
+                    let shell = shell.clone();
+                    move |_| control(&shell, player, toast, |shell| shell.toggle())
+                },
+                span { class: "material-icons", "{toggle_icon}" }
+            }
+            if state.can_seek() {
+                input {
+                    class: "player-seek",
+                    r#type: "range",
+                    min: "0",
+                    max: "{duration}",
+                    step: "any",
+                    value: "{state.position}",
+                    onchange: {
+                        let shell = shell.clone();
+                        move |evt: FormEvent| {
+                            if let Ok(position) = evt.value().parse::<f64>() {
+                                control(&shell, player, toast, |shell| shell.seek(position));
+                            }
+                        }
+                    }
+                }
+            }
+            span { class: "player-time", "{time_label}" }
+            select {
+                class: "player-rate",
+                value: "{state.rate}",
+                onchange: {
+                    let shell = shell.clone();
+                    move |evt: FormEvent| {
+                        if let Ok(rate) = evt.value().parse::<f64>() {
+                            control(&shell, player, toast, |shell| shell.set_rate(rate));
+                        }
+                    }
+                },
+                for rate in RATES {
+                    option { value: "{rate}", selected: rate == state.rate, "{rate}x" }
+                }
+            }
+            if !state.levels.is_empty() {
+                select {
+                    class: "player-quality",
+                    value: "{quality_value}",
+                    onchange: {
+                        let shell = shell.clone();
+                        move |evt: FormEvent| {
+                            let level = evt.value().parse::<usize>().ok();
+                            control(&shell, player, toast, |shell| shell.set_quality(level));
+                        }
+                    },
+                    option { value: "", selected: state.quality.is_none(), "自动" }
+                    for (index, level) in state.levels.iter().enumerate() {
+                        option {
+                            value: "{index}",
+                            selected: state.quality == Some(index),
+                            if state.quality.is_none() && state.active_level == Some(index) {
+                                "{level.label} (当前)"
+                            } else {
+                                "{level.label}"
+                            }
+                        }
+                    }
+                }
+            }
+            if state.live {
+                button {
+                    class: if state.low_latency.enabled { "player-low-latency is-active" } else { "player-low-latency" },
+                    title: "低延迟模式",
+                    onclick: move |_| {
+                        let mut current = *low_latency.read();
+                        current.enabled = !current.enabled;
+                        storage::store_live_low_latency(current.enabled, current.target_latency);
+                        *low_latency.write() = current;
+                    },
+                    span { class: "material-icons", "bolt" }
+                }
+            }
+            button {
+                class: if danmaku_enabled { "player-danmaku is-active" } else { "player-danmaku" },
+                title: "弹幕",
+                onclick: move |_| {
+                    let mut current = *danmaku_overlay.read();
+                    current.enabled = !current.enabled;
+                    storage::store_danmaku_overlay(&current);
+                    *danmaku_overlay.write() = current;
+                },
+                span { class: "material-icons",
+                    if danmaku_enabled {
+                        "subtitles"
+                    } else {
+                        "subtitles_off"
+                    }
+                }
+            }
+            span { class: "player-status", "{state.status.label()}" }
+        }
+        if let Some(error) = state.error.clone() {
+            p { class: "player-error", "{error}" }
+        }
+    }
+}
+
 #[component]
 fn LiveStreamList(streams: Vec<LiveStream>) -> Element {
+    let ctx = use_app_context();
+    let live_streams = ctx.live_streams;
+    let now_playing = ctx.now_playing;
+    let health = ctx.live_health.read().clone();
+    let mut query = use_signal(String::new);
+
     if streams.is_empty() {
-        return rsx! { p { class: "empty-state", "No live entries" } };
+        return rsx! {
+            p { class: "empty-state", "No live entries. Add `[live_streams]` to the remote config or import an M3U playlist in settings." }
+        };
     }
+    let sections = live::sections(&streams, &query.read());
 
     rsx! {
         div { class: "live-stream-list",
-            h3 { "Live entries" }
-            ul {
-                for stream in streams.into_iter() {
-                    li { key: "{stream.id}",
-                        span { class: "title", "{stream.title}" }
-                        small { class: "url", "{stream.stream_url}" }
-                        if let Some(danmaku) = stream.danmaku_endpoint {
-                            small { class: "danmaku", "Danmaku: {danmaku}" }
+            input {
+                class: "settings-input live-search",
+                placeholder: "搜索频道或分组",
+                value: "{query.read()}",
+                oninput: move |evt| *query.write() = evt.value()
+            }
+            if sections.is_empty() {
+                p { class: "empty-state", "没有匹配的频道" }
+            }
+            for section in sections {
+                div { key: "{section.title}", class: "live-section",
+                    h3 { "{section.title}" }
+                    ul {
+                        for stream in section.channels {
+                            li { key: "{stream.id}", class: "live-channel",
+                                onclick: {
+                                    let stream = stream.clone();
+                                    move |_| live::play(live_streams, now_playing, &stream)
+                                },
+                                if let Some(logo) = stream.logo.clone() {
+                                    img { class: "live-logo", src: "{logo}", alt: "" }
+                                }
+                                div { class: "live-channel-text",
+                                    span { class: "title", "{stream.title}" }
+                                    small { class: "url", "{stream.stream_url}" }
+                                    if let Some(danmaku) = stream.danmaku_endpoint.clone() {
+                                        small { class: "danmaku", "Danmaku: {danmaku}" }
+                                    }
+                                }
+                                if let Some(status) = health.get(&stream.id) {
+                                    span {
+                                        class: if status.online { "live-health is-online" } else { "live-health is-offline" },
+                                        title: "{status.summary()}",
+                                        if status.online { "在线" } else { "离线" }
+                                    }
+                                }
+                                button {
+                                    class: "live-favorite",
+                                    onclick: {
+                                        let id = stream.id.clone();
+                                        move |evt: MouseEvent| {
+                                            evt.stop_propagation();
+                                            live::toggle_favorite(live_streams, &id);
+                                        }
+                                    },
+                                    span { class: "material-icons",
+                                        if stream.favorite { "star" } else { "star_border" }
+                                    }
+                                }
+                            }
                         }
                     }
                 }
@@ -1,11 +1,26 @@
-use crate::state::{FeedItem, FeedSourceKind, use_app_context};
+use crate::clustering::cluster_items;
+use crate::dialogue;
+use crate::embeddings::{self, DUPLICATE_THRESHOLD};
+use crate::feed_marks::toggle_feed_mark;
+use crate::ranking::{InterestProfile, RankedItem, rank_items};
+use crate::state::{
+    ChatBackend, FeedItem, FeedSourceKind, MarkStatus, NavSection, TimelineOrder, use_app_context,
+};
+use crate::storage;
 use dioxus::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::info;
 use url::Url;
 
 const TIMELINE_ID: &str = "timeline-pane";
 
+/// One card: the ranked primary copy of a story plus the copies folded into it.
+#[derive(Clone, Debug, PartialEq)]
+struct TimelineEntry {
+    ranked: RankedItem,
+    duplicates: Vec<FeedItem>,
+}
+
 struct StackLangStamp {
     label: String,
     icon_url: Option<String>,
@@ -64,17 +79,81 @@ fn measure_card_height() -> Option<f64> {
     Some(card.get_bounding_client_rect().height())
 }
 
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 #[allow(non_snake_case)]
 pub fn TimelinePane() -> Element {
     let ctx = use_app_context();
-    let mut items = ctx.feed_items.read().clone();
-    let now_ts = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|duration| duration.as_secs() as i64)
-        .unwrap_or(0);
-    let cutoff_ts = now_ts.saturating_sub(60 * 24 * 60 * 60);
-    items.retain(|item| item.published_ts >= cutoff_ts);
-    items.sort_by(|a, b| b.published_ts.cmp(&a.published_ts));
+    let feed_items = ctx.feed_items;
+    let feed_marks = ctx.feed_marks;
+    let timeline_order = ctx.timeline_order;
+    let outbox = ctx.outbox;
+    // Bookmark status changes during sync leave the bookmarked set as it is, so clustering
+    // below does not rerun for them.
+    let bookmarked = use_memo(move || {
+        feed_marks
+            .read()
+            .values()
+            .filter(|mark| mark.marked)
+            .map(|mark| mark.feed_id.clone())
+            .collect::<HashSet<_>>()
+    });
+    // Clustering is quadratic in the item count, so it is cached on the items and the
+    // bookmarked ids instead of rerunning with every re-rank. Copies of the same story from
+    // different feeds share one card.
+    let clusters = use_memo(move || {
+        let mut items = feed_items.read().clone();
+        let cutoff_ts = unix_now().saturating_sub(60 * 24 * 60 * 60);
+        items.retain(|item| item.published_ts >= cutoff_ts);
+        let pairs = embeddings::near_duplicates(&items, DUPLICATE_THRESHOLD);
+        let bookmarked = bookmarked.read();
+        let preferred = bookmarked
+            .iter()
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+        cluster_items(items, &pairs, &preferred)
+    });
+    // Re-ranks when the stories, bookmarks or the order change; the outbox read picks up
+    // memory edits made elsewhere.
+    let entries = use_memo(move || {
+        let _ = outbox.read();
+        let now_ts = unix_now();
+        let clusters = clusters.read();
+        let items = clusters
+            .iter()
+            .flat_map(|cluster| std::iter::once(&cluster.primary).chain(&cluster.duplicates))
+            .cloned()
+            .collect::<Vec<_>>();
+        let asked = storage::load_conversations()
+            .into_iter()
+            .filter_map(|conversation| conversation.feed_item_id)
+            .collect::<Vec<_>>();
+        let marks = feed_marks.read();
+        let profile =
+            InterestProfile::build(&items, &marks, &asked, &storage::load_local_memories());
+        let mut duplicates = HashMap::new();
+        let primaries = clusters
+            .iter()
+            .map(|cluster| {
+                duplicates.insert(cluster.primary.id.clone(), cluster.duplicates.clone());
+                cluster.primary.clone()
+            })
+            .collect::<Vec<_>>();
+        let personalized = *timeline_order.read() == TimelineOrder::Personalized;
+        rank_items(primaries, &profile, personalized, now_ts)
+            .into_iter()
+            .map(|ranked| TimelineEntry {
+                duplicates: duplicates.remove(&ranked.item.id).unwrap_or_default(),
+                ranked,
+            })
+            .collect::<Vec<_>>()
+    });
+    let items = entries.read().clone();
     let total = items.len();
     let mut selected = use_signal(|| None::<FeedItem>);
 
@@ -98,7 +177,7 @@ pub fn TimelinePane() -> Element {
 
 #[cfg(target_arch = "wasm32")]
 fn render_virtual_timeline(
-    items: Vec<FeedItem>,
+    items: Vec<TimelineEntry>,
     total: usize,
     mut selected: Signal<Option<FeedItem>>,
 ) -> Element {
@@ -146,7 +225,10 @@ fn render_virtual_timeline(
                 div { class: "timeline-spacer", style: "height: {top_spacer}px" }
                 for item in slice.into_iter() {
                     FeedCard {
-                        item: item.clone(),
+                        key: "{item.ranked.item.id}",
+                        item: item.ranked.item.clone(),
+                        reasons: item.ranked.reasons.iter().map(|reason| reason.label()).collect::<Vec<_>>(),
+                        duplicates: item.duplicates.clone(),
                         on_open: move |card| {
                             *selected.write() = Some(card);
                         }
@@ -160,7 +242,7 @@ fn render_virtual_timeline(
 
 #[cfg(not(target_arch = "wasm32"))]
 fn render_paginated_timeline(
-    items: Vec<FeedItem>,
+    items: Vec<TimelineEntry>,
     total: usize,
     mut selected: Signal<Option<FeedItem>>,
 ) -> Element {
@@ -186,7 +268,10 @@ fn render_paginated_timeline(
             } else {
                 for item in slice.into_iter() {
                     FeedCard {
-                        item: item.clone(),
+                        key: "{item.ranked.item.id}",
+                        item: item.ranked.item.clone(),
+                        reasons: item.ranked.reasons.iter().map(|reason| reason.label()).collect::<Vec<_>>(),
+                        duplicates: item.duplicates.clone(),
                         on_open: move |card| {
                             *selected.write() = Some(card);
                         }
@@ -198,19 +283,38 @@ fn render_paginated_timeline(
 }
 
 #[component]
-fn FeedCard(item: FeedItem, on_open: EventHandler<FeedItem>) -> Element {
+fn FeedCard(
+    item: FeedItem,
+    reasons: Vec<String>,
+    duplicates: Vec<FeedItem>,
+    on_open: EventHandler<FeedItem>,
+) -> Element {
     let source_label = match item.source {
         FeedSourceKind::Atom => "ATOM",
         FeedSourceKind::RssHub => "RSSHub",
         FeedSourceKind::Custom => "Crawler",
     };
 
-    let mut forged = use_signal(|| false);
-    let mut card_class = if *forged.read() {
+    let ctx = use_app_context();
+    let feed_marks = ctx.feed_marks;
+    let memory_server_url = ctx.memory_server_url;
+    let outbox = ctx.outbox;
+    let mark = feed_marks.read().get(&item.id).cloned();
+    let marked = mark.as_ref().is_some_and(|mark| mark.marked);
+    let mark_status = mark.as_ref().map(|mark| mark.status);
+    let mark_error = mark.and_then(|mark| mark.error).unwrap_or_default();
+    let mut card_class = if marked {
         "feed-card feed-card--marked".to_string()
     } else {
         "feed-card".to_string()
     };
+    let (mark_class, mark_icon) = match mark_status {
+        Some(MarkStatus::Pending) => ("post-action post-action--pending", "autorenew"),
+        Some(MarkStatus::Failed) => ("post-action post-action--failed", "sync_problem"),
+        Some(MarkStatus::Synced) if marked => ("post-action post-action--marked", "bookmark_added"),
+        _ => ("post-action", "bookmark_add"),
+    };
+    let mark_item = item.clone();
 
     let card_item = item.clone();
     let fallback = item.author.chars().next().unwrap_or('?');
@@ -224,6 +328,13 @@ fn FeedCard(item: FeedItem, on_open: EventHandler<FeedItem>) -> Element {
     if is_summarized {
         card_class.push_str(" feed-card--summarized");
     }
+    if !reasons.is_empty() {
+        card_class.push_str(" feed-card--boosted");
+    }
+    let boost_text = reasons.join(" · ");
+    let mut show_duplicates = use_signal(|| false);
+    let duplicates_open = *show_duplicates.read();
+    let duplicate_count = duplicates.len();
     let summary_class = "feed-summary-indicator material-icons";
     rsx! {
         article {
@@ -253,12 +364,48 @@ fn FeedCard(item: FeedItem, on_open: EventHandler<FeedItem>) -> Element {
                         }
                     }
                 }
+                if !boost_text.is_empty() {
+                    p { class: "post-boost",
+                        span { class: "material-icons", "auto_awesome" }
+                        span { "{boost_text}" }
+                    }
+                }
                 div { class: "post-text", dangerous_inner_html: "{item.summary}" }
                 if !item.title.is_empty() {
                     p { class: "post-title", "{item.title}" }
                 }
                 div { class: "post-meta",
                     span { class: "post-source", "{source_label}" }
+                    if duplicate_count > 0 {
+                        button {
+                            class: "post-cluster",
+                            onclick: move |evt| {
+                                evt.stop_propagation();
+                                let open = *show_duplicates.read();
+                                *show_duplicates.write() = !open;
+                            },
+                            span { "另见 {duplicate_count} 个来源" }
+                            span { class: "material-icons",
+                                if duplicates_open { "expand_less" } else { "expand_more" }
+                            }
+                        }
+                    }
+                }
+                if duplicates_open {
+                    ul { class: "post-cluster-list",
+                        for duplicate in duplicates.iter() {
+                            li { key: "{duplicate.id}",
+                                a {
+                                    href: duplicate.link.clone(),
+                                    target: "_blank",
+                                    onclick: move |evt| {
+                                        evt.stop_propagation();
+                                    },
+                                    "{duplicate.author} · {duplicate.title}"
+                                }
+                            }
+                        }
+                    }
                 }
                 div { class: "post-actions",
                     a {
@@ -272,14 +419,18 @@ fn FeedCard(item: FeedItem, on_open: EventHandler<FeedItem>) -> Element {
                         span { "评论" }
                     }
                     button {
-                        class: if *forged.read() { "post-action post-action--marked" } else { "post-action" },
+                        class: mark_class,
+                        title: "{mark_error}",
                         onclick: move |evt| {
                             evt.stop_propagation();
-                            let next = !*forged.read();
-                            forged.set(next);
-                            info!("add to memory: {}", item.id);
+                            toggle_feed_mark(
+                                mark_item.clone(),
+                                memory_server_url.read().trim().to_string(),
+                                feed_marks,
+                                outbox,
+                            );
                         },
-                        span { class: "material-icons", "bookmark_add" }
+                        span { class: "material-icons", "{mark_icon}" }
                         span { "铸魂" }
                     }
                 }
@@ -290,6 +441,14 @@ fn FeedCard(item: FeedItem, on_open: EventHandler<FeedItem>) -> Element {
 
 #[component]
 fn FeedModal(item: FeedItem, on_close: EventHandler<()>) -> Element {
+    let ctx = use_app_context();
+    let mut nav = ctx.nav;
+    let mut active_conversation = ctx.active_conversation;
+    let llm_configured = !ctx.llm_endpoint.read().trim().is_empty()
+        && !ctx.llm_api_key.read().trim().is_empty()
+        && !ctx.llm_model.read().trim().is_empty();
+    let ask_item = item.clone();
+
     rsx! {
         div {
             class: "feed-modal-backdrop",
@@ -316,11 +475,29 @@ fn FeedModal(item: FeedItem, on_close: EventHandler<()>) -> Element {
                     span { "Published: {item.published_at}" }
                     span { "Source: {item.source:?}" }
                 }
-                a {
-                    class: "feed-modal-link",
-                    href: item.link,
-                    target: "_blank",
-                    "Open original"
+                div { class: "feed-modal-actions",
+                    a {
+                        class: "feed-modal-link",
+                        href: item.link,
+                        target: "_blank",
+                        "Open original"
+                    }
+                    button {
+                        class: "settings-action",
+                        onclick: move |_| {
+                            let backend = if llm_configured {
+                                ChatBackend::Llm
+                            } else {
+                                ChatBackend::Memory
+                            };
+                            let conversation = dialogue::open_article_conversation(&ask_item, backend);
+                            *active_conversation.write() = Some(conversation.id);
+                            *nav.write() = NavSection::Dialogue;
+                            on_close.call(());
+                        },
+                        span { class: "material-icons", "forum" }
+                        span { "问问这篇" }
+                    }
                 }
             }
         }
@@ -0,0 +1,179 @@
+use crate::state::use_app_context;
+use crate::storage;
+use crate::usage::{self, ModelPrice, UsageTotals};
+use dioxus::prelude::*;
+
+fn parse_price(value: &str) -> f64 {
+    value.trim().parse::<f64>().unwrap_or(0.0).max(0.0)
+}
+
+#[allow(non_snake_case)]
+pub fn UsagePanel() -> Element {
+    let ctx = use_app_context();
+    let llm_models = ctx.llm_models;
+    let mut llm_prices = ctx.llm_prices;
+    let mut llm_monthly_budget = ctx.llm_monthly_budget;
+    let mut report = use_signal(|| usage::load_month_report(&llm_prices.read()));
+
+    let mut priced_models = llm_models.read().clone();
+    for model in llm_prices.read().keys() {
+        if !priced_models.contains(model) {
+            priced_models.push(model.clone());
+        }
+    }
+    priced_models.sort();
+
+    let current = report.read().clone();
+    let budget = *llm_monthly_budget.read();
+    let budget_text = budget.map(|value| value.to_string()).unwrap_or_default();
+    let exhausted = budget.is_some_and(|value| current.total.cost >= value);
+    let spend_line = match budget {
+        Some(value) => format!("本月花费 {:.4} / {:.2}", current.total.cost, value),
+        None => format!("本月花费 {:.4}（未设置预算）", current.total.cost),
+    };
+
+    rsx! {
+        div { class: "settings-field",
+            div { class: "settings-row settings-row-spread",
+                label { class: "settings-label", "用量与成本" }
+                button {
+                    class: "settings-action settings-action-icon",
+                    onclick: move |_| {
+                        let next = usage::load_month_report(&llm_prices.read());
+                        *report.write() = next;
+                    },
+                    span { class: "material-icons", "refresh" }
+                }
+            }
+            p { class: "usage-spend", "{spend_line}" }
+            if exhausted {
+                p { class: "settings-hint usage-warning", "已超出月度预算，自动摘要已暂停。" }
+            }
+            label { class: "settings-label", "月度预算" }
+            input {
+                class: "settings-input",
+                r#type: "number",
+                min: "0",
+                step: "0.01",
+                placeholder: "不限",
+                value: "{budget_text}",
+                oninput: move |evt| {
+                    let value = evt.value();
+                    let parsed = value.trim().parse::<f64>().ok().filter(|value| *value >= 0.0);
+                    *llm_monthly_budget.write() = parsed;
+                    storage::store_llm_monthly_budget(parsed);
+                }
+            }
+            label { class: "settings-label", "价格表（每百万 tokens：输入 / 输出）" }
+            if priced_models.is_empty() {
+                p { class: "settings-hint", "拉取模型列表后即可设置价格。" }
+            }
+            for model in priced_models.into_iter() {
+                PriceRow {
+                    key: "{model}",
+                    model: model.clone(),
+                    price: llm_prices.read().get(&model).copied().unwrap_or_default(),
+                    on_change: move |(model, price): (String, ModelPrice)| {
+                        let mut prices = llm_prices.read().clone();
+                        if price == ModelPrice::default() {
+                            prices.remove(&model);
+                        } else {
+                            prices.insert(model, price);
+                        }
+                        storage::store_llm_prices(&prices);
+                        let next = usage::load_month_report(&prices);
+                        *llm_prices.write() = prices;
+                        *report.write() = next;
+                    }
+                }
+            }
+            UsageTable { title: "按模型", rows: current.by_model.clone() }
+            UsageTable { title: "按日期", rows: current.by_day.clone() }
+        }
+    }
+}
+
+#[component]
+fn PriceRow(
+    model: String,
+    price: ModelPrice,
+    on_change: EventHandler<(String, ModelPrice)>,
+) -> Element {
+    let prompt_model = model.clone();
+    let completion_model = model.clone();
+
+    rsx! {
+        div { class: "settings-row usage-price-row",
+            span { class: "usage-model", "{model}" }
+            input {
+                class: "settings-input usage-price-input",
+                r#type: "number",
+                min: "0",
+                step: "0.01",
+                value: "{price.prompt_per_million}",
+                oninput: move |evt| {
+                    on_change.call((
+                        prompt_model.clone(),
+                        ModelPrice {
+                            prompt_per_million: parse_price(&evt.value()),
+                            ..price
+                        },
+                    ));
+                }
+            }
+            input {
+                class: "settings-input usage-price-input",
+                r#type: "number",
+                min: "0",
+                step: "0.01",
+                value: "{price.completion_per_million}",
+                oninput: move |evt| {
+                    on_change.call((
+                        completion_model.clone(),
+                        ModelPrice {
+                            completion_per_million: parse_price(&evt.value()),
+                            ..price
+                        },
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn UsageTable(title: &'static str, rows: Vec<UsageTotals>) -> Element {
+    if rows.is_empty() {
+        return rsx! {
+            p { class: "settings-hint", "{title}：本月暂无调用记录。" }
+        };
+    }
+
+    rsx! {
+        table { class: "usage-table",
+            caption { "{title}" }
+            thead {
+                tr {
+                    th { "" }
+                    th { "调用" }
+                    th { "失败" }
+                    th { "输入" }
+                    th { "输出" }
+                    th { "花费" }
+                }
+            }
+            tbody {
+                for row in rows.into_iter() {
+                    tr { key: "{row.label}",
+                        td { "{row.label}" }
+                        td { "{row.calls}" }
+                        td { "{row.failures}" }
+                        td { "{row.prompt_tokens}" }
+                        td { "{row.completion_tokens}" }
+                        td { {format!("{:.4}", row.cost)} }
+                    }
+                }
+            }
+        }
+    }
+}
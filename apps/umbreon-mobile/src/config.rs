@@ -0,0 +1,144 @@
+use crate::state::MemoryCategory;
+use crate::storage;
+use serde::Deserialize;
+use umbreon_core::UmbreonError;
+use umbreon_core::opml::{self, FeedSource};
+
+/// The remote TOML config shared with the feed aggregator (the `[feeds]` gist). Sections the
+/// app does not read yet are ignored.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct RemoteConfig {
+    #[serde(default)]
+    pub memory_policies: MemoryPolicies,
+    #[serde(default)]
+    pub live_streams: LiveStreamsConfig,
+}
+
+/// `[live_streams]`: channels listed directly plus M3U playlists to pull channels from.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct LiveStreamsConfig {
+    #[serde(default)]
+    pub playlists: Vec<String>,
+    #[serde(default)]
+    pub channels: Vec<LiveChannelConfig>,
+}
+
+/// One `[[live_streams.channels]]` entry.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct LiveChannelConfig {
+    pub id: Option<String>,
+    pub name: String,
+    pub url: String,
+    pub group: Option<String>,
+    pub logo: Option<String>,
+    pub danmaku: Option<String>,
+}
+
+/// `[memory_policies]`: when and what to purge from memory.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct MemoryPolicies {
+    #[serde(default)]
+    pub purge_on_launch: bool,
+    #[serde(default)]
+    pub rules: Vec<PurgeRule>,
+}
+
+/// One `[[memory_policies.rules]]` entry. A rule without `category` covers every category.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct PurgeRule {
+    pub category: Option<String>,
+    pub max_age_days: Option<u32>,
+    pub max_items: Option<usize>,
+}
+
+impl PurgeRule {
+    pub fn applies_to(&self, category: MemoryCategory) -> bool {
+        match self.category.as_deref() {
+            None => true,
+            Some(value) => MemoryCategory::parse(value) == Some(category),
+        }
+    }
+
+    /// Short description for settings, e.g. "观看历史 · 30 天 · 最多 200 条".
+    pub fn describe(&self) -> String {
+        let mut parts = vec![match self.category.as_deref() {
+            None => "全部".to_string(),
+            Some(value) => MemoryCategory::parse(value)
+                .map(|category| category.label().to_string())
+                .unwrap_or_else(|| value.to_string()),
+        }];
+        if let Some(days) = self.max_age_days {
+            parts.push(format!("{days} 天"));
+        }
+        if let Some(limit) = self.max_items {
+            parts.push(format!("最多 {limit} 条"));
+        }
+        parts.join(" · ")
+    }
+}
+
+pub fn parse_config(text: &str) -> Result<RemoteConfig, UmbreonError> {
+    toml::from_str::<RemoteConfig>(text).map_err(|err| UmbreonError::parse("远程配置", err))
+}
+
+/// The last config fetched successfully, so policies still apply offline.
+pub fn cached_config() -> RemoteConfig {
+    storage::load_settings()
+        .remote_config
+        .and_then(|text| parse_config(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Downloads and parses the config, caching the raw text on success.
+pub async fn fetch_config(url: &str) -> Result<RemoteConfig, UmbreonError> {
+    let response = reqwest::get(url)
+        .await
+        .map_err(|err| UmbreonError::network("远程配置", err))?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(UmbreonError::status("远程配置", status, body));
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|err| UmbreonError::network("远程配置", err))?;
+    let config = parse_config(&text)?;
+    storage::store_remote_config(&text);
+    Ok(config)
+}
+
+/// The `[feeds.*]` sources of the cached config. No cached config means no sources; a
+/// config that does not parse is an error rather than an empty list.
+pub fn cached_feed_sources() -> Result<Vec<FeedSource>, UmbreonError> {
+    match storage::load_settings().remote_config {
+        Some(text) => opml::parse_feeds_toml(&text),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Writes the cached feed sources as OPML into `exports/` and returns the file path.
+pub fn export_opml() -> Result<String, UmbreonError> {
+    let sources = cached_feed_sources()?;
+    if sources.is_empty() {
+        return Err(UmbreonError::config(
+            "OPML",
+            "远程配置中没有 [feeds] 订阅源",
+        ));
+    }
+    storage::store_export_file(
+        "subscriptions.opml",
+        &opml::to_opml("Umbreon subscriptions", &sources),
+    )
+}
+
+/// Merges the feeds of an OPML file into the cached sources and writes the resulting
+/// `[feeds.*]` tables into `exports/` for pasting into the gist. Returns the TOML and how
+/// many feeds were new.
+pub fn import_opml(path: &str) -> Result<(String, usize), UmbreonError> {
+    let incoming = opml::parse_opml(&storage::read_local_file(path)?)?;
+    let (sources, added) = opml::merge_sources(cached_feed_sources()?, incoming);
+    let text = opml::to_feeds_toml(&sources)?;
+    storage::store_export_file("feeds.toml", &text)?;
+    Ok((text, added))
+}
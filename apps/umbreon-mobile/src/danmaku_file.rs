@@ -0,0 +1,156 @@
+use crate::state::{MediaSession, UI_LOCALE};
+use crate::storage;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use umbreon_core::UmbreonError;
+use umbreon_core::danmaku::{self, DanmakuEntry, DanmakuFormat};
+
+/// Offset step of the calibration buttons, in seconds.
+pub const OFFSET_STEP: f64 = 0.5;
+
+/// A danmaku file attached to a video. Attachments are remembered per video, keyed by the
+/// session's stream URL.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DanmakuAttachment {
+    /// Local path or http(s) URL.
+    pub source: String,
+    /// Seconds added to every comment time (延迟校准); positive shows comments later.
+    #[serde(default)]
+    pub offset: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum AttachmentState {
+    #[default]
+    None,
+    Loading(DanmakuAttachment),
+    Loaded {
+        attachment: DanmakuAttachment,
+        format: DanmakuFormat,
+        count: usize,
+    },
+    Failed {
+        attachment: DanmakuAttachment,
+        error: String,
+    },
+}
+
+impl AttachmentState {
+    pub fn attachment(&self) -> Option<&DanmakuAttachment> {
+        match self {
+            AttachmentState::None => None,
+            AttachmentState::Loading(attachment)
+            | AttachmentState::Loaded { attachment, .. }
+            | AttachmentState::Failed { attachment, .. } => Some(attachment),
+        }
+    }
+
+    /// Offset the overlay applies to the player clock.
+    pub fn offset(&self) -> f64 {
+        match self {
+            AttachmentState::Loaded { attachment, .. } => attachment.offset,
+            _ => 0.0,
+        }
+    }
+}
+
+pub fn attachment_for(video: &str) -> Option<DanmakuAttachment> {
+    storage::load_settings().danmaku_attachments?.remove(video)
+}
+
+fn remember(video: &str, attachment: &DanmakuAttachment) {
+    let mut attachments = storage::load_settings()
+        .danmaku_attachments
+        .unwrap_or_default();
+    attachments.insert(video.to_string(), attachment.clone());
+    storage::store_danmaku_attachments(&attachments);
+}
+
+fn forget(video: &str) {
+    let mut attachments = storage::load_settings()
+        .danmaku_attachments
+        .unwrap_or_default();
+    if attachments.remove(video).is_some() {
+        storage::store_danmaku_attachments(&attachments);
+    }
+}
+
+async fn read_source(source: &str) -> Result<String, UmbreonError> {
+    if !(source.starts_with("http://") || source.starts_with("https://")) {
+        return storage::read_local_file(source);
+    }
+    let response = reqwest::get(source)
+        .await
+        .map_err(|err| UmbreonError::network("弹幕文件", err))?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        return Err(UmbreonError::status("弹幕文件", status, String::new()));
+    }
+    response
+        .text()
+        .await
+        .map_err(|err| UmbreonError::network("弹幕文件", err))
+}
+
+/// Reads and parses the file at `source`, detecting its format.
+pub async fn load(source: &str) -> Result<(DanmakuFormat, Vec<DanmakuEntry>), UmbreonError> {
+    let source = source.trim();
+    let text = read_source(source).await?;
+    danmaku::parse_danmaku(&text, Some(source))
+}
+
+/// Where an attached file's comments go.
+#[derive(Clone, Copy)]
+pub struct DanmakuFileSink {
+    pub danmaku: Signal<Vec<DanmakuEntry>>,
+    pub state: Signal<AttachmentState>,
+    pub now_playing: Signal<Option<MediaSession>>,
+}
+
+impl DanmakuFileSink {
+    fn is_playing(&self, video: &str) -> bool {
+        self.now_playing
+            .peek()
+            .as_ref()
+            .is_some_and(|session| session.stream_url == video)
+    }
+
+    /// Loads `attachment` for `video` and remembers it once it parses. A load that finishes
+    /// after the user switched videos is dropped.
+    pub async fn attach(mut self, video: String, attachment: DanmakuAttachment) {
+        self.state.set(AttachmentState::Loading(attachment.clone()));
+        let result = load(&attachment.source).await;
+        if !self.is_playing(&video) {
+            return;
+        }
+        match result {
+            Ok((format, entries)) => {
+                remember(&video, &attachment);
+                self.state.set(AttachmentState::Loaded {
+                    attachment,
+                    format,
+                    count: entries.len(),
+                });
+                self.danmaku.set(entries);
+            }
+            Err(err) => self.state.set(AttachmentState::Failed {
+                attachment,
+                error: err.user_message(UI_LOCALE),
+            }),
+        }
+    }
+
+    pub fn set_offset(mut self, video: &str, offset: f64) {
+        let mut state = self.state.write();
+        if let AttachmentState::Loaded { attachment, .. } = &mut *state {
+            attachment.offset = offset;
+            remember(video, attachment);
+        }
+    }
+
+    pub fn detach(mut self, video: &str) {
+        forget(video);
+        self.state.set(AttachmentState::None);
+        self.danmaku.write().clear();
+    }
+}
@@ -0,0 +1,246 @@
+use crate::general_ai_client;
+use crate::memory_client::{DEFAULT_USER_ID, MemoryClient};
+use crate::memory_sync;
+use crate::state::{
+    ChatBackend, ChatMessage, ChatMode, ChatRole, Conversation, FeedItem, MemoryCategory,
+    ToastAction, ToastKind, ToastMessage,
+};
+use crate::storage;
+use crate::timeline::plain_text_from_html;
+use chrono::{FixedOffset, TimeZone, Utc};
+use dioxus::prelude::*;
+
+const TITLE_MAX_CHARS: usize = 24;
+const CONTEXT_MAX_CHARS: usize = 6000;
+
+/// Endpoints a chat reply may be sent to, read from settings when the user hits send.
+#[derive(Clone, Debug, Default)]
+pub struct ChatSettings {
+    pub memory_server_url: String,
+    pub llm_endpoint: String,
+    pub llm_api_key: String,
+    pub llm_model: String,
+}
+
+impl ChatSettings {
+    pub fn llm_configured(&self) -> bool {
+        !self.llm_endpoint.is_empty() && !self.llm_api_key.is_empty() && !self.llm_model.is_empty()
+    }
+}
+
+fn now_ts() -> i64 {
+    Utc::now().timestamp()
+}
+
+pub fn new_conversation(mode: ChatMode, backend: ChatBackend) -> Conversation {
+    let now = now_ts();
+    Conversation {
+        id: format!("conv-{}", Utc::now().timestamp_millis()),
+        title: String::new(),
+        mode,
+        backend,
+        feed_item_id: None,
+        context: None,
+        created_ts: now,
+        updated_ts: now,
+    }
+}
+
+fn article_context(item: &FeedItem) -> String {
+    let body = plain_text_from_html(&item.full_content);
+    let body = if body.is_empty() {
+        plain_text_from_html(&item.summary)
+    } else {
+        body
+    };
+    let body = body.chars().take(CONTEXT_MAX_CHARS).collect::<String>();
+    format!(
+        "以下是用户正在阅读的文章，请结合它回答问题。\n标题：{title}\n链接：{link}\n正文：{body}",
+        title = item.title.trim(),
+        link = item.link.trim(),
+        body = body
+    )
+}
+
+/// Reuses the conversation already linked to `item`, or creates and stores a new one
+/// carrying the article as context.
+pub fn open_article_conversation(item: &FeedItem, backend: ChatBackend) -> Conversation {
+    if let Some(existing) = storage::load_conversations()
+        .into_iter()
+        .find(|conversation| conversation.feed_item_id.as_deref() == Some(item.id.as_str()))
+    {
+        return existing;
+    }
+    let mut conversation = new_conversation(ChatMode::Query, backend);
+    conversation.title = title_from_message(&format!("关于：{}", item.title));
+    conversation.feed_item_id = Some(item.id.clone());
+    conversation.context = Some(article_context(item));
+    storage::store_conversation(&conversation);
+    conversation
+}
+
+pub fn conversation_title(conversation: &Conversation) -> String {
+    if conversation.title.trim().is_empty() {
+        "新对话".to_string()
+    } else {
+        conversation.title.clone()
+    }
+}
+
+pub fn format_chat_time(ts: i64) -> String {
+    let offset = FixedOffset::east_opt(8 * 3600)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("valid UTC offset"));
+    offset
+        .timestamp_opt(ts, 0)
+        .single()
+        .map(|dt| dt.format("%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn title_from_message(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    let mut title = line.chars().take(TITLE_MAX_CHARS).collect::<String>();
+    if line.chars().count() > TITLE_MAX_CHARS {
+        title.push('…');
+    }
+    title
+}
+
+/// Persists the user message, then streams the reply from the conversation's backend into
+/// `streaming` and appends it to `messages` once complete.
+pub fn trigger_chat_reply(
+    settings: ChatSettings,
+    mut conversation: Signal<Option<Conversation>>,
+    text: String,
+    save_to_memory: bool,
+    mut messages: Signal<Vec<ChatMessage>>,
+    mut streaming: Signal<Option<String>>,
+    mut toast: Signal<Option<ToastMessage>>,
+) {
+    let Some(mut current) = conversation.read().clone() else {
+        return;
+    };
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return;
+    }
+    let missing = match current.backend {
+        ChatBackend::Memory if settings.memory_server_url.is_empty() => {
+            Some("请先在设置中填写 Memory Server。")
+        }
+        ChatBackend::Llm if !settings.llm_configured() => {
+            Some("请先在设置中填写 Endpoint、API Key 并选择模型。")
+        }
+        _ => None,
+    };
+    if let Some(missing) = missing {
+        *toast.write() = Some(ToastMessage {
+            kind: ToastKind::Error,
+            text: missing.to_string(),
+            action: Some(ToastAction::OpenSettings),
+        });
+        return;
+    }
+
+    let user_message = ChatMessage {
+        conversation_id: current.id.clone(),
+        role: ChatRole::User,
+        content: text.clone(),
+        created_ts: now_ts(),
+    };
+    storage::append_chat_message(&user_message);
+    messages.write().push(user_message);
+
+    if current.title.trim().is_empty() {
+        current.title = title_from_message(&text);
+    }
+    current.updated_ts = now_ts();
+    storage::store_conversation(&current);
+    *conversation.write() = Some(current.clone());
+
+    let history = messages.read().clone();
+    *streaming.write() = Some(String::new());
+    spawn(async move {
+        let on_delta = |delta: &str| {
+            if let Some(buffer) = streaming.write().as_mut() {
+                buffer.push_str(delta);
+            }
+        };
+        let result = match current.backend {
+            ChatBackend::Memory => {
+                let client = MemoryClient::new(settings.memory_server_url.clone(), DEFAULT_USER_ID);
+                let add_memory = if save_to_memory { "always" } else { "never" };
+                let message = match current.context.as_deref() {
+                    Some(context) => format!("{context}\n\n问题：{text}"),
+                    None => text,
+                };
+                client
+                    .chat_stream(
+                        message,
+                        Some(current.mode.as_str().to_string()),
+                        Some(current.id.clone()),
+                        Some(add_memory.to_string()),
+                        on_delta,
+                    )
+                    .await
+            }
+            ChatBackend::Llm => {
+                general_ai_client::chat_stream(
+                    &settings.llm_endpoint,
+                    &settings.llm_api_key,
+                    &settings.llm_model,
+                    current.context.as_deref(),
+                    &history,
+                    on_delta,
+                )
+                .await
+            }
+        };
+
+        match result {
+            Ok(reply) => {
+                // The memory server stores the exchange itself via `addMemory`; LLM replies
+                // are queued through the outbox so they survive being offline.
+                if save_to_memory && current.backend == ChatBackend::Llm {
+                    let question = history
+                        .last()
+                        .map(|message| message.content.as_str())
+                        .unwrap_or_default();
+                    let exchange = format!("用户：{question}\n助手：{reply}");
+                    let category = if current.feed_item_id.is_some() {
+                        MemoryCategory::FeedInteraction
+                    } else {
+                        MemoryCategory::Preference
+                    };
+                    memory_sync::record_memory(
+                        exchange,
+                        category,
+                        Vec::new(),
+                        Some(current.id.clone()),
+                    );
+                }
+                let assistant_message = ChatMessage {
+                    conversation_id: current.id.clone(),
+                    role: ChatRole::Assistant,
+                    content: reply,
+                    created_ts: now_ts(),
+                };
+                storage::append_chat_message(&assistant_message);
+                messages.write().push(assistant_message);
+                current.updated_ts = now_ts();
+                storage::store_conversation(&current);
+                if save_to_memory {
+                    *toast.write() = Some(ToastMessage {
+                        kind: ToastKind::Success,
+                        text: "本轮对话已写入记忆。".to_string(),
+                        action: None,
+                    });
+                }
+            }
+            Err(err) => {
+                *toast.write() = Some(ToastMessage::from_error("对话失败", &err, None));
+            }
+        }
+        *streaming.write() = None;
+    });
+}
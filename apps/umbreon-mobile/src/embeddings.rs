@@ -0,0 +1,267 @@
+use crate::general_ai_client;
+use crate::helper::fnv1a;
+use crate::secrets;
+use crate::state::{EmbeddingKind, FeedItem, LocalMemory, StoredEmbedding};
+use crate::storage;
+use crate::timeline::plain_text_from_html;
+use crate::usage;
+use std::collections::{HashMap, HashSet};
+use umbreon_core::UmbreonError;
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+/// Cosine similarity above which two items from different sources count as the same story.
+pub const DUPLICATE_THRESHOLD: f32 = 0.92;
+const BATCH_SIZE: usize = 64;
+const MAX_INPUT_CHARS: usize = 2000;
+
+/// Where embeddings come from: the general LLM endpoint plus the embedding model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbeddingSettings {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+impl EmbeddingSettings {
+    /// Reads the stored LLM settings; `None` until an endpoint and key are configured.
+    pub fn load() -> Option<Self> {
+        let settings = storage::load_settings();
+        let endpoint = settings.llm_endpoint.unwrap_or_default().trim().to_string();
+        let api_key = secrets::load(secrets::LLM_API_KEY)
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if endpoint.is_empty() || api_key.is_empty() {
+            return None;
+        }
+        let model = settings
+            .llm_embedding_model
+            .map(|model| model.trim().to_string())
+            .filter(|model| !model.is_empty())
+            .unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string());
+        Some(EmbeddingSettings {
+            endpoint,
+            api_key,
+            model,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicatePair {
+    pub first: String,
+    pub second: String,
+    pub similarity: f32,
+}
+
+/// FNV-1a over the model and text, so switching models re-embeds everything.
+fn content_hash(model: &str, text: &str) -> String {
+    let hash = fnv1a(model.bytes().chain([0u8]).chain(text.bytes()));
+    format!("{hash:016x}")
+}
+
+fn truncate(text: &str) -> String {
+    text.chars().take(MAX_INPUT_CHARS).collect()
+}
+
+fn feed_item_text(item: &FeedItem) -> String {
+    let body = plain_text_from_html(&item.full_content);
+    let body = if body.is_empty() {
+        plain_text_from_html(&item.summary)
+    } else {
+        body
+    };
+    truncate(&format!("{}\n{}", item.title, body))
+}
+
+fn memory_text(memory: &LocalMemory) -> String {
+    if memory.tags.is_empty() {
+        truncate(&memory.content)
+    } else {
+        truncate(&format!("{}\n#{}", memory.content, memory.tags.join(" #")))
+    }
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let mut dot = 0.0f32;
+    let mut norm_a = 0.0f32;
+    let mut norm_b = 0.0f32;
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// The `k` candidates closest to `query`, best first.
+pub fn top_k(query: &[f32], candidates: &[StoredEmbedding], k: usize) -> Vec<(String, f32)> {
+    let mut scored = candidates
+        .iter()
+        .map(|candidate| (candidate.ref_id.clone(), cosine(query, &candidate.vector)))
+        .filter(|(_, score)| *score > 0.0)
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(k);
+    scored
+}
+
+/// Embeds new or changed documents and drops rows whose source is gone.
+async fn reindex_kind(
+    settings: &EmbeddingSettings,
+    kind: EmbeddingKind,
+    documents: Vec<(String, String)>,
+    stats: &mut IndexStats,
+) -> Result<(), UmbreonError> {
+    let existing = storage::load_embeddings(kind)
+        .into_iter()
+        .map(|embedding| (embedding.ref_id, embedding.content_hash))
+        .collect::<HashMap<_, _>>();
+    let live = documents
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .collect::<HashSet<_>>();
+    let stale = existing
+        .keys()
+        .filter(|id| !live.contains(id.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    stats.removed += stale.len();
+    storage::delete_embeddings(kind, &stale);
+
+    let mut pending = Vec::new();
+    for (id, text) in documents {
+        let hash = content_hash(&settings.model, &text);
+        if existing.get(&id) == Some(&hash) {
+            stats.unchanged += 1;
+        } else if !text.trim().is_empty() {
+            pending.push((id, hash, text));
+        }
+    }
+
+    for batch in pending.chunks(BATCH_SIZE) {
+        if usage::monthly_budget_exhausted() {
+            return Err(UmbreonError::BudgetExhausted);
+        }
+        let inputs = batch.iter().map(|(_, _, text)| text.clone()).collect();
+        let vectors = general_ai_client::embed_texts(
+            &settings.endpoint,
+            &settings.api_key,
+            &settings.model,
+            inputs,
+        )
+        .await?;
+        let now = chrono::Utc::now().timestamp();
+        let rows = batch
+            .iter()
+            .zip(vectors)
+            .map(|((id, hash, _), vector)| StoredEmbedding {
+                kind,
+                ref_id: id.clone(),
+                content_hash: hash.clone(),
+                vector,
+                updated_ts: now,
+            })
+            .collect::<Vec<_>>();
+        storage::store_embeddings(&rows)?;
+        stats.indexed += rows.len();
+    }
+    Ok(())
+}
+
+/// Brings the index in line with the current feed cache and local memories. Unchanged
+/// entries are skipped, so calling this after every sync only pays for what is new.
+pub async fn reindex(
+    settings: &EmbeddingSettings,
+    items: &[FeedItem],
+    memories: &[LocalMemory],
+) -> Result<IndexStats, UmbreonError> {
+    let mut stats = IndexStats::default();
+    let feed_documents = items
+        .iter()
+        .map(|item| (item.id.clone(), feed_item_text(item)))
+        .collect();
+    reindex_kind(
+        settings,
+        EmbeddingKind::FeedItem,
+        feed_documents,
+        &mut stats,
+    )
+    .await?;
+    let memory_documents = memories
+        .iter()
+        .map(|memory| (memory.id.clone(), memory_text(memory)))
+        .collect();
+    reindex_kind(
+        settings,
+        EmbeddingKind::Memory,
+        memory_documents,
+        &mut stats,
+    )
+    .await?;
+    Ok(stats)
+}
+
+/// Embeds `query` and returns the `k` most similar indexed entries of `kind`.
+pub async fn search(
+    settings: &EmbeddingSettings,
+    query: &str,
+    kind: EmbeddingKind,
+    k: usize,
+) -> Result<Vec<(String, f32)>, UmbreonError> {
+    let mut vectors = general_ai_client::embed_texts(
+        &settings.endpoint,
+        &settings.api_key,
+        &settings.model,
+        vec![truncate(query)],
+    )
+    .await?;
+    let Some(query_vector) = vectors.pop() else {
+        return Ok(Vec::new());
+    };
+    Ok(top_k(&query_vector, &storage::load_embeddings(kind), k))
+}
+
+/// Pairs of indexed feed items from different sources whose embeddings are at least
+/// `threshold` similar, most similar first.
+pub fn near_duplicates(items: &[FeedItem], threshold: f32) -> Vec<DuplicatePair> {
+    let vectors = storage::load_embeddings(EmbeddingKind::FeedItem)
+        .into_iter()
+        .map(|embedding| (embedding.ref_id, embedding.vector))
+        .collect::<HashMap<_, _>>();
+    let indexed = items
+        .iter()
+        .filter_map(|item| vectors.get(&item.id).map(|vector| (item, vector)))
+        .collect::<Vec<_>>();
+    let mut pairs = Vec::new();
+    for (index, (item, vector)) in indexed.iter().enumerate() {
+        for (other, other_vector) in &indexed[index + 1..] {
+            if item.author.trim() == other.author.trim() {
+                continue;
+            }
+            let similarity = cosine(vector, other_vector);
+            if similarity >= threshold {
+                pairs.push(DuplicatePair {
+                    first: item.id.clone(),
+                    second: other.id.clone(),
+                    similarity,
+                });
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    pairs
+}
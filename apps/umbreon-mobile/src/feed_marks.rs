@@ -0,0 +1,145 @@
+use crate::memory_sync;
+use crate::state::{
+    FeedItem, FeedMark, MarkStatus, MemoryCategory, MemorySyncState, OutboxSummary,
+};
+use crate::storage;
+use crate::timeline::plain_text_from_html;
+use chrono::Utc;
+use dioxus::prelude::*;
+use std::collections::HashMap;
+
+const MEMORY_SUMMARY_MAX_CHARS: usize = 600;
+
+const MARK_TAG: &str = "铸魂";
+
+pub fn load_marks() -> HashMap<String, FeedMark> {
+    storage::load_feed_marks()
+        .into_iter()
+        .map(|mut mark| {
+            sync_status(&mut mark);
+            (mark.feed_id.clone(), mark)
+        })
+        .collect()
+}
+
+/// Copies the sync state of the bookmark's local memory onto the mark. Marks written before
+/// the outbox existed point at a server id with no local row and keep their own status.
+fn sync_status(mark: &mut FeedMark) {
+    let Some(memory) = mark
+        .memory_id
+        .as_deref()
+        .and_then(storage::load_local_memory)
+    else {
+        return;
+    };
+    let (status, error) = match memory.sync_state {
+        MemorySyncState::Pending => (MarkStatus::Pending, None),
+        MemorySyncState::Synced => (MarkStatus::Synced, None),
+        MemorySyncState::Failed => {
+            let error = memory_sync::dead_letters()
+                .into_iter()
+                .find(|entry| entry.memory_id == memory.id)
+                .and_then(|entry| entry.last_error);
+            (MarkStatus::Failed, error)
+        }
+    };
+    if mark.status != status || mark.error != error {
+        mark.status = status;
+        mark.error = error;
+        storage::store_feed_mark(mark);
+    }
+}
+
+/// Re-reads mark states after the outbox has been flushed.
+pub fn refresh_marks(mut marks: Signal<HashMap<String, FeedMark>>) {
+    *marks.write() = load_marks();
+}
+
+/// Structured memory text for a bookmarked item.
+pub fn memory_text(item: &FeedItem) -> String {
+    let summary = plain_text_from_html(&item.summary)
+        .chars()
+        .take(MEMORY_SUMMARY_MAX_CHARS)
+        .collect::<String>();
+    let mut lines = vec![
+        "[铸魂] 收藏的文章".to_string(),
+        format!("标题：{}", item.title.trim()),
+        format!("链接：{}", item.link.trim()),
+        format!("作者：{}", item.author.trim()),
+    ];
+    if !summary.is_empty() {
+        lines.push(format!("摘要：{summary}"));
+    }
+    if !item.tags.is_empty() {
+        lines.push(format!("标签：{}", item.tags.join(", ")));
+    }
+    lines.join("\n")
+}
+
+/// Drops the bookmarks whose memories were deleted, e.g. by a purge policy.
+pub fn forget_memories(memory_ids: &[String], mut marks: Signal<HashMap<String, FeedMark>>) {
+    if memory_ids.is_empty() {
+        return;
+    }
+    let forgotten = marks
+        .read()
+        .values()
+        .filter(|mark| {
+            mark.memory_id
+                .as_ref()
+                .is_some_and(|id| memory_ids.contains(id))
+        })
+        .map(|mark| mark.feed_id.clone())
+        .collect::<Vec<_>>();
+    for feed_id in forgotten {
+        storage::delete_feed_mark(&feed_id);
+        marks.write().remove(&feed_id);
+    }
+}
+
+/// Flips the bookmark of `item`. The memory is written locally and delivered through the
+/// outbox, so marking works offline. Tapping a failed mark requeues its dead-lettered
+/// operations instead of flipping it.
+pub fn toggle_feed_mark(
+    item: FeedItem,
+    memory_server_url: String,
+    mut marks: Signal<HashMap<String, FeedMark>>,
+    mut outbox: Signal<OutboxSummary>,
+) {
+    let existing = marks.read().get(&item.id).cloned();
+    match existing {
+        Some(mark) if mark.status == MarkStatus::Failed && mark.marked => {
+            if let Some(memory_id) = mark.memory_id.as_deref() {
+                memory_sync::retry_memory(memory_id);
+            }
+            refresh_marks(marks);
+        }
+        Some(mark) if mark.marked => {
+            if let Some(memory_id) = mark.memory_id.as_deref() {
+                memory_sync::delete_memory(memory_id);
+            }
+            storage::delete_feed_mark(&item.id);
+            marks.write().remove(&item.id);
+        }
+        _ => {
+            let memory = memory_sync::record_memory(
+                memory_text(&item),
+                MemoryCategory::Mark,
+                vec![MARK_TAG.to_string()],
+                Some(item.id.clone()),
+            );
+            let mark = FeedMark {
+                feed_id: item.id.clone(),
+                marked: true,
+                memory_id: Some(memory.id),
+                status: MarkStatus::Pending,
+                error: None,
+                updated_ts: Utc::now().timestamp(),
+            };
+            storage::store_feed_mark(&mark);
+            marks.write().insert(mark.feed_id.clone(), mark);
+        }
+    }
+    *outbox.write() = memory_sync::summary();
+    memory_sync::trigger_flush(memory_server_url, outbox, move || refresh_marks(marks));
+}
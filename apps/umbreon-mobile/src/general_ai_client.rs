@@ -1,14 +1,60 @@
+use crate::secrets;
+use crate::state::{ChatMessage, ChatRole};
+use crate::usage;
 use async_openai::{
     Client,
     config::OpenAIConfig,
-    types::{ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs},
+    error::OpenAIError,
+    types::{
+        ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+        ChatCompletionStreamOptions, CreateChatCompletionRequestArgs, CreateEmbeddingRequestArgs,
+    },
 };
+use futures::StreamExt;
 use tracing::warn;
+use umbreon_core::UmbreonError;
 
-fn build_client(endpoint: &str, api_key: &str) -> Result<Client<OpenAIConfig>, String> {
+/// How the LLM endpoint is named in errors shown to the user.
+const SERVICE: &str = "LLM";
+
+/// Sorts async-openai failures into the shared error kinds, so auth and quota problems are
+/// told apart from flaky connections. Providers sometimes echo the API key back in the
+/// message, so it is scrubbed before the error is logged or shown.
+fn map_openai_error(err: OpenAIError) -> UmbreonError {
+    let detail = secrets::scrub(&err.to_string());
+    match err {
+        OpenAIError::Reqwest(err) => match err.status() {
+            Some(status) => UmbreonError::status(SERVICE, status.as_u16(), detail),
+            None => UmbreonError::network(SERVICE, detail),
+        },
+        OpenAIError::ApiError(api) => {
+            let code = api.code.as_deref().unwrap_or_default();
+            let kind = api.r#type.as_deref().unwrap_or_default();
+            let status = if code == "invalid_api_key" || kind == "authentication_error" {
+                401
+            } else if kind == "permission_error" {
+                403
+            } else if code == "rate_limit_exceeded" || kind == "rate_limit_error" {
+                429
+            } else if kind == "server_error" || kind == "overloaded_error" {
+                503
+            } else {
+                return UmbreonError::rejected(SERVICE, detail);
+            };
+            UmbreonError::status(SERVICE, status, detail)
+        }
+        OpenAIError::JSONDeserialize(_) => UmbreonError::parse(SERVICE, detail),
+        OpenAIError::StreamError(_) => UmbreonError::network(SERVICE, detail),
+        OpenAIError::InvalidArgument(_) => UmbreonError::config("模型", detail),
+        _ => UmbreonError::rejected(SERVICE, detail),
+    }
+}
+
+fn build_client(endpoint: &str, api_key: &str) -> Result<Client<OpenAIConfig>, UmbreonError> {
     let api_key = api_key.trim();
     if api_key.is_empty() {
-        return Err("API key is empty.".to_string());
+        return Err(UmbreonError::config("API Key", "empty"));
     }
     let endpoint = endpoint.trim().trim_end_matches('/').to_string();
     let mut config = OpenAIConfig::new().with_api_key(api_key);
@@ -18,11 +64,12 @@ fn build_client(endpoint: &str, api_key: &str) -> Result<Client<OpenAIConfig>, S
     Ok(Client::with_config(config))
 }
 
-pub async fn fetch_models(endpoint: &str, api_key: &str) -> Result<Vec<String>, String> {
+pub async fn fetch_models(endpoint: &str, api_key: &str) -> Result<Vec<String>, UmbreonError> {
     let client = build_client(endpoint, api_key)?;
     let response = client.models().list().await.map_err(|err| {
+        let err = map_openai_error(err);
         warn!(error = %err, endpoint = endpoint, "fetch models failed");
-        format!("fetch models failed (endpoint: {endpoint}): {err}")
+        err
     })?;
 
     let mut models = response
@@ -40,29 +87,50 @@ pub async fn test_chat(
     api_key: &str,
     model: &str,
     prompt: &str,
-) -> Result<String, String> {
+) -> Result<String, UmbreonError> {
+    chat_completion(endpoint, api_key, model, prompt, "test").await
+}
+
+async fn chat_completion(
+    endpoint: &str,
+    api_key: &str,
+    model: &str,
+    prompt: &str,
+    operation: &str,
+) -> Result<String, UmbreonError> {
     let model = model.trim();
     if model.is_empty() {
-        return Err("Model is empty.".to_string());
+        return Err(UmbreonError::config("模型", "empty"));
     }
     let client = build_client(endpoint, api_key)?;
 
     let message = ChatCompletionRequestUserMessageArgs::default()
         .content(prompt)
         .build()
-        .map_err(|err| format!("build chat message failed: {err}"))?;
+        .map_err(map_openai_error)?;
 
     let request = CreateChatCompletionRequestArgs::default()
         .model(model)
         .messages([message.into()])
         .build()
-        .map_err(|err| format!("build chat request failed: {err}"))?;
+        .map_err(map_openai_error)?;
 
-    let response = client.chat().create(request).await.map_err(|err| {
-        warn!(error = %err, endpoint = endpoint, model = model, "chat request failed");
-        format!("chat request failed (endpoint: {endpoint}, model: {model}): {err}")
-    })?;
+    let started_ms = usage::now_ms();
+    let response = match client.chat().create(request).await {
+        Ok(response) => response,
+        Err(err) => {
+            let err = map_openai_error(err);
+            warn!(error = %err, endpoint = endpoint, model = model, "chat request failed");
+            usage::record(operation, model, 0, 0, started_ms, Some(err.to_string()));
+            return Err(err);
+        }
+    };
 
+    let (prompt_tokens, completion_tokens) = response
+        .usage
+        .as_ref()
+        .map(|usage| (usage.prompt_tokens, usage.completion_tokens))
+        .unwrap_or_default();
     let text = response
         .choices
         .first()
@@ -70,9 +138,26 @@ pub async fn test_chat(
         .unwrap_or_default();
 
     if text.is_empty() {
-        return Err("LLM returned empty response.".to_string());
+        let err = UmbreonError::parse(SERVICE, "empty response");
+        usage::record(
+            operation,
+            model,
+            prompt_tokens,
+            completion_tokens,
+            started_ms,
+            Some(err.to_string()),
+        );
+        return Err(err);
     }
 
+    usage::record(
+        operation,
+        model,
+        prompt_tokens,
+        completion_tokens,
+        started_ms,
+        None,
+    );
     Ok(text)
 }
 
@@ -82,7 +167,7 @@ pub async fn summarize_text(
     model: &str,
     title: &str,
     body: &str,
-) -> Result<String, String> {
+) -> Result<String, UmbreonError> {
     let instruction = "请用1-2句中文总结以下内容，要求简洁、客观，不要列表，不要引用。";
     let prompt = format!(
         "{instruction}\n标题：{title}\n正文：{body}",
@@ -91,5 +176,176 @@ pub async fn summarize_text(
         body = body.trim()
     );
 
-    test_chat(endpoint, api_key, model, &prompt).await
+    chat_completion(endpoint, api_key, model, &prompt, "summarize").await
+}
+
+/// Streams a multi-turn chat reply, handing each text delta to `on_delta`.
+/// Returns the full reply once the stream ends.
+pub async fn chat_stream(
+    endpoint: &str,
+    api_key: &str,
+    model: &str,
+    system: Option<&str>,
+    history: &[ChatMessage],
+    mut on_delta: impl FnMut(&str),
+) -> Result<String, UmbreonError> {
+    let model = model.trim();
+    if model.is_empty() {
+        return Err(UmbreonError::config("模型", "empty"));
+    }
+    let client = build_client(endpoint, api_key)?;
+
+    let mut messages = Vec::<ChatCompletionRequestMessage>::with_capacity(history.len() + 1);
+    if let Some(system) = system.filter(|value| !value.trim().is_empty()) {
+        let message = ChatCompletionRequestSystemMessageArgs::default()
+            .content(system)
+            .build()
+            .map_err(map_openai_error)?;
+        messages.push(message.into());
+    }
+    for entry in history {
+        let message: ChatCompletionRequestMessage = match entry.role {
+            ChatRole::User => ChatCompletionRequestUserMessageArgs::default()
+                .content(entry.content.as_str())
+                .build()
+                .map_err(map_openai_error)?
+                .into(),
+            ChatRole::Assistant => ChatCompletionRequestAssistantMessageArgs::default()
+                .content(entry.content.as_str())
+                .build()
+                .map_err(map_openai_error)?
+                .into(),
+        };
+        messages.push(message);
+    }
+
+    let request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages(messages)
+        .stream_options(ChatCompletionStreamOptions {
+            include_usage: true,
+        })
+        .build()
+        .map_err(map_openai_error)?;
+
+    let started_ms = usage::now_ms();
+    let mut stream = match client.chat().create_stream(request).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            let err = map_openai_error(err);
+            warn!(error = %err, endpoint = endpoint, model = model, "chat stream failed");
+            usage::record("dialogue", model, 0, 0, started_ms, Some(err.to_string()));
+            return Err(err);
+        }
+    };
+
+    let mut text = String::new();
+    let mut tokens = (0u32, 0u32);
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let err = map_openai_error(err);
+                warn!(error = %err, endpoint = endpoint, model = model, "chat stream failed");
+                usage::record(
+                    "dialogue",
+                    model,
+                    tokens.0,
+                    tokens.1,
+                    started_ms,
+                    Some(err.to_string()),
+                );
+                return Err(err);
+            }
+        };
+        if let Some(usage) = chunk.usage.as_ref() {
+            tokens = (usage.prompt_tokens, usage.completion_tokens);
+        }
+        for choice in chunk.choices {
+            if let Some(delta) = choice.delta.content.filter(|value| !value.is_empty()) {
+                on_delta(&delta);
+                text.push_str(&delta);
+            }
+        }
+    }
+
+    if text.is_empty() {
+        let err = UmbreonError::parse(SERVICE, "empty response");
+        usage::record(
+            "dialogue",
+            model,
+            tokens.0,
+            tokens.1,
+            started_ms,
+            Some(err.to_string()),
+        );
+        return Err(err);
+    }
+
+    usage::record("dialogue", model, tokens.0, tokens.1, started_ms, None);
+    Ok(text)
+}
+
+/// Embeds `inputs` in one request, returning vectors in input order.
+pub async fn embed_texts(
+    endpoint: &str,
+    api_key: &str,
+    model: &str,
+    inputs: Vec<String>,
+) -> Result<Vec<Vec<f32>>, UmbreonError> {
+    let model = model.trim();
+    if model.is_empty() {
+        return Err(UmbreonError::config("Embedding 模型", "empty"));
+    }
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let client = build_client(endpoint, api_key)?;
+    let expected = inputs.len();
+    let request = CreateEmbeddingRequestArgs::default()
+        .model(model)
+        .input(inputs)
+        .build()
+        .map_err(map_openai_error)?;
+
+    let started_ms = usage::now_ms();
+    let response = match client.embeddings().create(request).await {
+        Ok(response) => response,
+        Err(err) => {
+            let err = map_openai_error(err);
+            warn!(error = %err, endpoint = endpoint, model = model, "embedding request failed");
+            usage::record("embedding", model, 0, 0, started_ms, Some(err.to_string()));
+            return Err(err);
+        }
+    };
+
+    let mut data = response.data;
+    data.sort_by_key(|embedding| embedding.index);
+    if data.len() != expected {
+        let err = UmbreonError::parse(
+            SERVICE,
+            format!("{} vectors for {expected} inputs", data.len()),
+        );
+        usage::record(
+            "embedding",
+            model,
+            response.usage.prompt_tokens,
+            0,
+            started_ms,
+            Some(err.to_string()),
+        );
+        return Err(err);
+    }
+    usage::record(
+        "embedding",
+        model,
+        response.usage.prompt_tokens,
+        0,
+        started_ms,
+        None,
+    );
+    Ok(data
+        .into_iter()
+        .map(|embedding| embedding.embedding)
+        .collect())
 }
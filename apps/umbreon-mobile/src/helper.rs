@@ -57,3 +57,14 @@ pub fn parse_timestamp_for_feed(feed_type: &FeedType, value: &str) -> Option<i64
         _ => parse_timestamp_atom(value),
     }
 }
+
+/// 64-bit FNV-1a. Stable across runs and platforms, so it is safe for ids and cache keys
+/// that end up on disk.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
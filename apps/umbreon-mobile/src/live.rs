@@ -0,0 +1,293 @@
+use crate::config::{LiveChannelConfig, LiveStreamsConfig};
+use crate::helper::fnv1a;
+use crate::state::{LiveStream, MediaKind, MediaSession};
+use crate::storage;
+use dioxus::prelude::*;
+use std::collections::HashSet;
+use umbreon_core::UmbreonError;
+use umbreon_core::playlist::{self, Channel, ChannelLibrary};
+
+/// Origin of the channels listed directly in the remote config.
+pub const CONFIG_ORIGIN: &str = "config";
+/// How many recently watched channels the live list shows above the groups.
+const RECENT_LIMIT: usize = 5;
+
+/// One titled block of the live list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelSection {
+    pub title: String,
+    pub channels: Vec<LiveStream>,
+}
+
+/// FNV-1a over the stream URL, so a channel keeps its favorite and watch time across
+/// refreshes even when the playlist reorders it.
+fn channel_id(url: &str) -> String {
+    format!("live-{:016x}", fnv1a(url.trim().bytes()))
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn from_config(entry: &LiveChannelConfig) -> LiveStream {
+    LiveStream {
+        id: non_empty(entry.id.as_deref()).unwrap_or_else(|| channel_id(&entry.url)),
+        title: entry.name.trim().to_string(),
+        stream_url: entry.url.trim().to_string(),
+        danmaku_endpoint: non_empty(entry.danmaku.as_deref()),
+        group: non_empty(entry.group.as_deref()),
+        logo: non_empty(entry.logo.as_deref()),
+        origin: CONFIG_ORIGIN.to_string(),
+        favorite: false,
+        last_watched_ts: None,
+    }
+}
+
+fn from_playlist(channel: &Channel, origin: &str) -> LiveStream {
+    LiveStream {
+        id: channel_id(&channel.url),
+        title: channel.name.clone(),
+        stream_url: channel.url.clone(),
+        danmaku_endpoint: None,
+        group: channel.group.clone(),
+        logo: channel.tvg_logo.clone(),
+        origin: origin.to_string(),
+        favorite: false,
+        last_watched_ts: None,
+    }
+}
+
+async fn fetch_playlist(url: &str) -> Result<Vec<Channel>, UmbreonError> {
+    let response = reqwest::get(url)
+        .await
+        .map_err(|err| UmbreonError::network("直播列表", err))?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(UmbreonError::status("直播列表", status, body));
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|err| UmbreonError::network("直播列表", err))?;
+    let mut library = ChannelLibrary::new();
+    library.add(playlist::parse_channel_list(&text)?);
+    Ok(library.channels().to_vec())
+}
+
+/// Stores `channels` under `origin`, skipping stream URLs another origin already lists.
+fn store_origin(
+    origin: &str,
+    channels: Vec<LiveStream>,
+    known_urls: &mut HashSet<String>,
+) -> Result<usize, UmbreonError> {
+    let channels = channels
+        .into_iter()
+        .filter(|channel| !channel.stream_url.is_empty())
+        .filter(|channel| known_urls.insert(channel.stream_url.clone()))
+        .collect::<Vec<_>>();
+    storage::replace_live_channels(origin, &channels)?;
+    Ok(channels.len())
+}
+
+/// Playlist URLs added in settings, on top of the remote config's.
+pub fn imported_playlists() -> Vec<String> {
+    storage::load_settings().live_playlists.unwrap_or_default()
+}
+
+/// Reloads the config channels and every playlist. A playlist that cannot be fetched keeps
+/// its stored channels; the first such failure is returned once the rest are done.
+pub async fn refresh(config: &LiveStreamsConfig) -> Result<(), UmbreonError> {
+    let mut known_urls = HashSet::new();
+    let config_channels = config.channels.iter().map(from_config).collect();
+    store_origin(CONFIG_ORIGIN, config_channels, &mut known_urls)?;
+
+    let mut origins = vec![CONFIG_ORIGIN.to_string()];
+    for url in config.playlists.iter().cloned().chain(imported_playlists()) {
+        let url = url.trim().to_string();
+        if !url.is_empty() && !origins.contains(&url) {
+            origins.push(url);
+        }
+    }
+    let mut failure = None;
+    for url in &origins[1..] {
+        match fetch_playlist(url).await {
+            Ok(channels) => {
+                let streams = channels
+                    .iter()
+                    .map(|channel| from_playlist(channel, url))
+                    .collect();
+                store_origin(url, streams, &mut known_urls)?;
+            }
+            Err(err) => {
+                tracing::warn!("live playlist {url} failed: {err}");
+                known_urls.extend(
+                    storage::load_live_channels()
+                        .into_iter()
+                        .filter(|channel| &channel.origin == url)
+                        .map(|channel| channel.stream_url),
+                );
+                failure.get_or_insert(err);
+            }
+        }
+    }
+    storage::retain_live_origins(&origins);
+    failure.map_or(Ok(()), Err)
+}
+
+/// Refreshes in the background and reloads `live_streams` when done.
+pub fn trigger_refresh(config: LiveStreamsConfig, mut live_streams: Signal<Vec<LiveStream>>) {
+    spawn(async move {
+        if let Err(err) = refresh(&config).await {
+            tracing::warn!("live channel refresh incomplete: {err}");
+        }
+        *live_streams.write() = storage::load_live_channels();
+    });
+}
+
+/// Fetches an M3U playlist, stores its channels and remembers the URL for later refreshes.
+/// Returns how many channels were added.
+pub async fn import_playlist(url: &str) -> Result<usize, UmbreonError> {
+    let url = url.trim().to_string();
+    if url.is_empty() {
+        return Err(UmbreonError::config("直播列表", "请填写播放列表地址"));
+    }
+    let channels = fetch_playlist(&url).await?;
+    let mut known_urls = storage::load_live_channels()
+        .into_iter()
+        .filter(|channel| channel.origin != url)
+        .map(|channel| channel.stream_url)
+        .collect::<HashSet<_>>();
+    let streams = channels
+        .iter()
+        .map(|channel| from_playlist(channel, &url))
+        .collect();
+    let added = store_origin(&url, streams, &mut known_urls)?;
+    let mut playlists = imported_playlists();
+    if !playlists.contains(&url) {
+        playlists.push(url);
+        storage::store_live_playlists(&playlists);
+    }
+    Ok(added)
+}
+
+/// Forgets an imported playlist and its channels.
+pub fn remove_playlist(url: &str) -> Result<(), UmbreonError> {
+    let mut playlists = imported_playlists();
+    playlists.retain(|playlist| playlist != url);
+    storage::store_live_playlists(&playlists);
+    storage::replace_live_channels(url, &[])
+}
+
+pub fn toggle_favorite(mut live_streams: Signal<Vec<LiveStream>>, id: &str) {
+    let mut streams = live_streams.write();
+    if let Some(channel) = streams.iter_mut().find(|channel| channel.id == id) {
+        channel.favorite = !channel.favorite;
+        storage::store_live_channel_state(&channel.id, channel.favorite, channel.last_watched_ts);
+    }
+}
+
+/// Starts `channel` in the player and records it as watched.
+pub fn play(
+    mut live_streams: Signal<Vec<LiveStream>>,
+    mut now_playing: Signal<Option<MediaSession>>,
+    channel: &LiveStream,
+) {
+    let now = chrono::Utc::now().timestamp();
+    if let Some(stored) = live_streams
+        .write()
+        .iter_mut()
+        .find(|stored| stored.id == channel.id)
+    {
+        stored.last_watched_ts = Some(now);
+        storage::store_live_channel_state(&stored.id, stored.favorite, stored.last_watched_ts);
+    }
+    let source = channel.group.clone().unwrap_or_else(|| {
+        if channel.origin == CONFIG_ORIGIN {
+            "远程配置".to_string()
+        } else {
+            channel.origin.clone()
+        }
+    });
+    *now_playing.write() = Some(MediaSession {
+        title: channel.title.clone(),
+        source,
+        kind: MediaKind::Live,
+        stream_url: channel.stream_url.clone(),
+        danmaku_endpoint: channel.danmaku_endpoint.clone(),
+    });
+}
+
+fn matches(channel: &LiveStream, query: &str) -> bool {
+    query.is_empty()
+        || channel.title.to_lowercase().contains(query)
+        || channel
+            .group
+            .as_deref()
+            .is_some_and(|group| group.to_lowercase().contains(query))
+}
+
+/// The live list: favorites, recently watched, then one section per group in order of first
+/// appearance. Only channels whose title or group contains `query` are included.
+pub fn sections(channels: &[LiveStream], query: &str) -> Vec<ChannelSection> {
+    let query = query.trim().to_lowercase();
+    let visible = channels
+        .iter()
+        .filter(|channel| matches(channel, &query))
+        .collect::<Vec<_>>();
+
+    let mut sections = Vec::new();
+    let favorites = visible
+        .iter()
+        .filter(|channel| channel.favorite)
+        .map(|channel| (*channel).clone())
+        .collect::<Vec<_>>();
+    if !favorites.is_empty() {
+        sections.push(ChannelSection {
+            title: "收藏".to_string(),
+            channels: favorites,
+        });
+    }
+    let mut recent = visible
+        .iter()
+        .filter(|channel| channel.last_watched_ts.is_some())
+        .map(|channel| (*channel).clone())
+        .collect::<Vec<_>>();
+    recent.sort_by_key(|channel| std::cmp::Reverse(channel.last_watched_ts));
+    recent.truncate(RECENT_LIMIT);
+    if !recent.is_empty() {
+        sections.push(ChannelSection {
+            title: "最近观看".to_string(),
+            channels: recent,
+        });
+    }
+
+    let mut groups: Vec<ChannelSection> = Vec::new();
+    let mut ungrouped = Vec::new();
+    for channel in visible {
+        let names = playlist::group_names(channel.group.as_deref()).collect::<Vec<_>>();
+        if names.is_empty() {
+            ungrouped.push(channel.clone());
+        }
+        for name in names {
+            match groups.iter_mut().find(|section| section.title == name) {
+                Some(section) => section.channels.push(channel.clone()),
+                None => groups.push(ChannelSection {
+                    title: name.to_string(),
+                    channels: vec![channel.clone()],
+                }),
+            }
+        }
+    }
+    sections.extend(groups);
+    if !ungrouped.is_empty() {
+        sections.push(ChannelSection {
+            title: "未分组".to_string(),
+            channels: ungrouped,
+        });
+    }
+    sections
+}
@@ -0,0 +1,274 @@
+use dioxus::prelude::*;
+use umbreon_core::UmbreonError;
+use umbreon_core::bilibili;
+use umbreon_core::danmaku::DanmakuEntry;
+use umbreon_core::danmaku_live::{
+    Backoff, ConnectionState, DanmakuBuffer, LiveEvent, RelayProtocol,
+};
+use umbreon_core::player::PlayerState;
+
+/// Live comments kept for the overlay, which rebuilds its layout from the whole list on every
+/// message; the cap bounds that work. Trimming drops many entries at once because it shifts
+/// every index, and with it which comments a reduced density keeps.
+const HISTORY_LIMIT: usize = 1500;
+const HISTORY_KEEP: usize = 1000;
+
+/// Room info API that hands out the danmaku host and the token for entering a room.
+const BILIBILI_DANMU_INFO: &str =
+    "https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo";
+/// Resolves short room numbers to the real room id.
+const BILIBILI_ROOM_INIT: &str = "https://api.live.bilibili.com/room/v1/Room/room_init";
+
+/// Whether a channel's `danmaku_endpoint` is something [`follow`] can connect to: a
+/// WebSocket relay, or a Bilibili room (`bilibili://live/<room>` or the room's page).
+pub fn is_followable(endpoint: &str) -> bool {
+    let endpoint = endpoint.trim();
+    endpoint.starts_with("ws://")
+        || endpoint.starts_with("wss://")
+        || bilibili::room_id(endpoint).is_some()
+}
+
+/// Where a live relay's comments go.
+#[derive(Clone, Copy)]
+pub struct LiveDanmakuSink {
+    pub danmaku: Signal<Vec<DanmakuEntry>>,
+    pub buffer: Signal<DanmakuBuffer>,
+    pub connection: Signal<ConnectionState>,
+    pub player: Signal<PlayerState>,
+}
+
+impl LiveDanmakuSink {
+    /// Shows `entries` at the current player position; relays send comments as they happen,
+    /// so their own timestamps are not on the player clock.
+    fn show(mut self, entries: Vec<DanmakuEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+        let position = self.player.peek().position;
+        let mut danmaku = self.danmaku.write();
+        danmaku.extend(entries.into_iter().map(|entry| DanmakuEntry {
+            time: position,
+            ..entry
+        }));
+        if danmaku.len() > HISTORY_LIMIT {
+            let excess = danmaku.len() - HISTORY_KEEP;
+            danmaku.drain(..excess);
+        }
+    }
+
+    fn receive(mut self, entries: Vec<DanmakuEntry>) {
+        let ready = self.buffer.write().push(entries);
+        self.show(ready);
+    }
+
+    /// Holds comments while the player is not running and releases them on resume.
+    pub fn set_paused(mut self, paused: bool) {
+        if self.buffer.peek().is_paused() == paused {
+            return;
+        }
+        let released = self.buffer.write().set_paused(paused);
+        self.show(released);
+    }
+}
+
+async fn bilibili_api(
+    url: &str,
+    query: &[(&str, String)],
+) -> Result<serde_json::Value, UmbreonError> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .query(query)
+        .send()
+        .await
+        .map_err(|err| UmbreonError::network("Bilibili", err))?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        return Err(UmbreonError::status("Bilibili", status, String::new()));
+    }
+    let body = response
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|err| UmbreonError::parse("Bilibili", err))?;
+    match body.get("code").and_then(serde_json::Value::as_i64) {
+        Some(0) => Ok(body["data"].clone()),
+        _ => Err(UmbreonError::rejected(
+            "Bilibili",
+            body.get("message")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or("unknown error"),
+        )),
+    }
+}
+
+/// The danmaku server and auth for a Bilibili room. When the room info API refuses (it
+/// wants a signed-in session for some rooms), the default host is tried without a token,
+/// which still delivers comments for most rooms. In the browser the room APIs usually fail
+/// on CORS, so the web build mostly takes that fallback.
+async fn bilibili_relay(room: u64) -> (String, RelayProtocol) {
+    let room_id = match bilibili_api(BILIBILI_ROOM_INIT, &[("id", room.to_string())]).await {
+        Ok(data) => data["room_id"].as_u64().unwrap_or(room),
+        Err(err) => {
+            tracing::warn!("bilibili room lookup failed: {err}");
+            room
+        }
+    };
+    let mut auth = bilibili::AuthParams {
+        room_id,
+        ..bilibili::AuthParams::default()
+    };
+    let mut host = bilibili::DEFAULT_HOST.to_string();
+    match bilibili_api(
+        BILIBILI_DANMU_INFO,
+        &[("id", room_id.to_string()), ("type", "0".to_string())],
+    )
+    .await
+    {
+        Ok(data) => {
+            auth.token = data["token"].as_str().map(str::to_string);
+            if let Some(listed) = data["host_list"][0]["host"].as_str() {
+                host = listed.to_string();
+            }
+        }
+        Err(err) => tracing::warn!("bilibili danmaku info failed: {err}"),
+    }
+    (format!("wss://{host}/sub"), RelayProtocol::Bilibili(auth))
+}
+
+/// Follows the relay behind `endpoint` until the task is cancelled or reconnecting gives
+/// up.
+pub async fn follow(endpoint: String, mut sink: LiveDanmakuSink) {
+    sink.connection.set(ConnectionState::Connecting);
+    let (url, protocol) = match bilibili::room_id(&endpoint) {
+        Some(room) => bilibili_relay(room).await,
+        None => (endpoint, RelayProtocol::JsonLines),
+    };
+    let on_event = move |event| match event {
+        LiveEvent::State(state) => sink.connection.set(state),
+        LiveEvent::Entries(entries) => sink.receive(entries),
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    umbreon_core::danmaku_live::run(&url, &protocol, Backoff::default(), on_event).await;
+    #[cfg(target_arch = "wasm32")]
+    browser::run(&url, &protocol, Backoff::default(), on_event).await;
+}
+
+/// The relay client on the browser's WebSocket, since the core one needs tokio. It keeps the
+/// same rules: decoding, backoff, Bilibili heartbeats and the idle timeout.
+#[cfg(target_arch = "wasm32")]
+mod browser {
+    use futures::future::{self, Either};
+    use futures::{SinkExt, StreamExt};
+    use futures_timer::Delay;
+    use gloo_net::websocket::futures::WebSocket;
+    use gloo_net::websocket::{Message, State, WebSocketError};
+    use std::time::Duration;
+    use umbreon_core::UmbreonError;
+    use umbreon_core::bilibili;
+    use umbreon_core::danmaku_live::{
+        Backoff, ConnectionState, IDLE_TIMEOUT, LiveEvent, RelayFrame, RelayProtocol, decode_frame,
+    };
+
+    fn relay_error(err: impl ToString) -> UmbreonError {
+        UmbreonError::network("danmaku relay", err)
+    }
+
+    /// Connects to `url` and reports events until `backoff` gives up or the room refuses the
+    /// client; cancel the task to stop earlier.
+    pub async fn run(
+        url: &str,
+        protocol: &RelayProtocol,
+        mut backoff: Backoff,
+        mut on_event: impl FnMut(LiveEvent),
+    ) {
+        loop {
+            on_event(LiveEvent::State(ConnectionState::Connecting));
+            let error = match session(url, protocol, &mut backoff, &mut on_event).await {
+                Ok(()) => relay_error("connection closed by the relay"),
+                Err(err) => err,
+            };
+            let delay = match error {
+                UmbreonError::Auth { .. } => None,
+                _ => backoff.next_delay(),
+            };
+            match delay {
+                Some(delay) => {
+                    on_event(LiveEvent::State(ConnectionState::Reconnecting {
+                        attempt: backoff.attempt(),
+                        delay,
+                    }));
+                    Delay::new(delay).await;
+                }
+                None => {
+                    on_event(LiveEvent::State(ConnectionState::Failed(error)));
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn session(
+        url: &str,
+        protocol: &RelayProtocol,
+        backoff: &mut Backoff,
+        on_event: &mut impl FnMut(LiveEvent),
+    ) -> Result<(), UmbreonError> {
+        let mut socket = WebSocket::open(url).map_err(relay_error)?;
+        // Ready once the handshake is over, whether it worked or not.
+        future::poll_fn(|cx| socket.poll_ready_unpin(cx))
+            .await
+            .map_err(relay_error)?;
+        if !matches!(socket.state(), State::Open) {
+            return Err(relay_error("could not connect to the relay"));
+        }
+        if let RelayProtocol::Bilibili(auth) = protocol {
+            let auth = bilibili::auth_packet(auth).encode();
+            socket
+                .send(Message::Bytes(auth))
+                .await
+                .map_err(relay_error)?;
+        }
+        on_event(LiveEvent::State(ConnectionState::Connected));
+        // Only Bilibili needs heartbeats; the JSON-lines relay never ticks in practice.
+        let heartbeat_every = match protocol {
+            RelayProtocol::Bilibili(_) => bilibili::HEARTBEAT_INTERVAL,
+            RelayProtocol::JsonLines => Duration::from_secs(24 * 60 * 60),
+        };
+        let mut heartbeat = Delay::new(heartbeat_every);
+        loop {
+            let idle = Delay::new(IDLE_TIMEOUT);
+            let message =
+                match future::select(socket.next(), future::select(idle, &mut heartbeat)).await {
+                    Either::Left((message, _)) => message,
+                    Either::Right((Either::Left(_), _)) => {
+                        return Err(relay_error("no messages from the relay"));
+                    }
+                    Either::Right((Either::Right(_), _)) => {
+                        heartbeat.reset(heartbeat_every);
+                        if let RelayProtocol::Bilibili(_) = protocol {
+                            let packet = bilibili::heartbeat_packet().encode();
+                            socket
+                                .send(Message::Bytes(packet))
+                                .await
+                                .map_err(relay_error)?;
+                        }
+                        continue;
+                    }
+                };
+            let decoded = match message {
+                None | Some(Err(WebSocketError::ConnectionClose(_))) => return Ok(()),
+                Some(Err(err)) => return Err(relay_error(err)),
+                Some(Ok(Message::Text(text))) => decode_frame(protocol, RelayFrame::Text(&text)),
+                Some(Ok(Message::Bytes(bytes))) => {
+                    decode_frame(protocol, RelayFrame::Binary(&bytes))
+                }
+            };
+            let Some(entries) = decoded? else {
+                continue;
+            };
+            backoff.reset();
+            if !entries.is_empty() {
+                on_event(LiveEvent::Entries(entries));
+            }
+        }
+    }
+}
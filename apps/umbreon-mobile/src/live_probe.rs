@@ -0,0 +1,297 @@
+use crate::state::LiveStream;
+use crate::storage;
+use chrono::Utc;
+use dioxus::prelude::*;
+use futures::StreamExt;
+use futures::future::{self, Either};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use umbreon_core::UmbreonError;
+use umbreon_core::player::QualityLevel;
+use umbreon_core::playlist::{self, MediaPlaylist, Playlist};
+use umbreon_core::probe::{self, QualityPreference};
+use url::Url;
+
+/// How often the live list re-checks its channels in the background.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Channels checked at the same time.
+const PROBE_CONCURRENCY: usize = 4;
+/// Budget for each request of a probe.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(8);
+/// Bytes requested from a media segment; enough to tell that it is really served.
+const SEGMENT_PROBE_BYTES: u64 = 64 * 1024;
+
+/// What the last probe of a channel found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamHealth {
+    pub online: bool,
+    pub checked_ts: i64,
+    /// Milliseconds from the first request until the first segment bytes arrived.
+    pub startup_ms: Option<i64>,
+    /// Seconds between where playback starts and the newest media.
+    pub live_edge: Option<f64>,
+    /// Label of the variant that was checked.
+    pub variant: Option<String>,
+    pub error: Option<String>,
+}
+
+impl StreamHealth {
+    pub fn summary(&self) -> String {
+        if !self.online {
+            return self.error.clone().unwrap_or_else(|| "无法播放".to_string());
+        }
+        let mut parts = Vec::new();
+        if let Some(variant) = &self.variant {
+            parts.push(variant.clone());
+        }
+        if let Some(startup) = self.startup_ms {
+            parts.push(format!("启动 {startup} ms"));
+        }
+        if let Some(edge) = self.live_edge {
+            parts.push(format!("距直播点 {edge:.1} 秒"));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// The user's variant choice for probing, from settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProbePreferences {
+    pub quality: QualityPreference,
+    /// Bits per second.
+    pub max_bandwidth: Option<u64>,
+}
+
+pub fn preferences() -> ProbePreferences {
+    let settings = storage::load_settings();
+    ProbePreferences {
+        quality: settings
+            .live_quality
+            .as_deref()
+            .and_then(QualityPreference::parse)
+            .unwrap_or_default(),
+        max_bandwidth: settings.live_max_bandwidth,
+    }
+}
+
+pub fn store_preferences(preferences: ProbePreferences) {
+    storage::store_live_quality(&preferences.quality.key());
+    storage::store_live_max_bandwidth(preferences.max_bandwidth);
+}
+
+async fn timed<T>(
+    request: impl std::future::Future<Output = Result<T, UmbreonError>>,
+) -> Result<T, UmbreonError> {
+    let request = std::pin::pin!(request);
+    match future::select(request, futures_timer::Delay::new(REQUEST_TIMEOUT)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(UmbreonError::network("直播探测", "请求超时")),
+    }
+}
+
+async fn fetch_text(url: &Url) -> Result<String, UmbreonError> {
+    timed(async {
+        let response = reqwest::get(url.clone())
+            .await
+            .map_err(|err| UmbreonError::network("直播探测", err))?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(UmbreonError::status("直播探测", status, String::new()));
+        }
+        response
+            .text()
+            .await
+            .map_err(|err| UmbreonError::network("直播探测", err))
+    })
+    .await
+}
+
+/// Requests the first bytes of a segment (or of the given byte range) and waits for data.
+async fn fetch_segment_start(
+    url: &Url,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<(), UmbreonError> {
+    let length = length
+        .unwrap_or(SEGMENT_PROBE_BYTES)
+        .min(SEGMENT_PROBE_BYTES);
+    let range = format!("bytes={offset}-{}", offset + length.max(1) - 1);
+    timed(async {
+        let response = reqwest::Client::new()
+            .get(url.clone())
+            .header("Range", range)
+            .send()
+            .await
+            .map_err(|err| UmbreonError::network("直播探测", err))?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(UmbreonError::status("直播探测", status, String::new()));
+        }
+        match response.bytes_stream().next().await {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(err)) => Err(UmbreonError::network("直播探测", err)),
+            None => Err(UmbreonError::network("直播探测", "分片为空")),
+        }
+    })
+    .await
+}
+
+fn resolve(base: &Url, reference: &str) -> Result<Url, UmbreonError> {
+    base.join(reference)
+        .map_err(|err| UmbreonError::parse("直播探测", err))
+}
+
+/// Wall-clock lag of the newest media, when the playlist carries program date times.
+fn program_date_lag(media: &MediaPlaylist) -> Option<f64> {
+    let (index, dated) = media
+        .segments
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, segment)| segment.program_date_time.is_some())?;
+    let start = chrono::DateTime::parse_from_rfc3339(dated.program_date_time.as_deref()?).ok()?;
+    let after: f64 = media.segments[index..]
+        .iter()
+        .map(|segment| segment.duration)
+        .sum();
+    let edge_ms = start.timestamp_millis() + (after * 1000.0) as i64;
+    let lag = (Utc::now().timestamp_millis() - edge_ms) as f64 / 1000.0;
+    // A negative lag is clock skew between us and the packager; ignore it.
+    (lag >= 0.0).then_some(lag)
+}
+
+struct Report {
+    startup_ms: i64,
+    live_edge: Option<f64>,
+    variant: Option<String>,
+}
+
+async fn probe_url(url: &str, preferences: ProbePreferences) -> Result<Report, UmbreonError> {
+    let started = Utc::now().timestamp_millis();
+    let mut playlist_url =
+        Url::parse(url.trim()).map_err(|err| UmbreonError::parse("直播探测", err))?;
+    let is_hls = playlist_url.path().to_ascii_lowercase().ends_with(".m3u8")
+        || playlist_url.path().to_ascii_lowercase().ends_with(".m3u");
+    if !is_hls {
+        fetch_segment_start(&playlist_url, 0, None).await?;
+        return Ok(Report {
+            startup_ms: Utc::now().timestamp_millis() - started,
+            live_edge: None,
+            variant: None,
+        });
+    }
+
+    let mut variant = None;
+    let media = match playlist::parse_playlist(&fetch_text(&playlist_url).await?)? {
+        Playlist::Media(media) => media,
+        Playlist::Master(master) => {
+            let chosen =
+                probe::select_variant(&master, preferences.quality, preferences.max_bandwidth)
+                    .ok_or_else(|| UmbreonError::parse("直播探测", "主播放列表没有可用的码率"))?;
+            variant = Some(QualityLevel::from_variant(chosen).label);
+            playlist_url = resolve(&playlist_url, &chosen.uri)?;
+            playlist::parse_media_playlist(&fetch_text(&playlist_url).await?)?
+        }
+        Playlist::Channels(_) => {
+            return Err(UmbreonError::parse("直播探测", "这是频道列表，不是流地址"));
+        }
+    };
+    let start = probe::start_point(&media)
+        .ok_or_else(|| UmbreonError::parse("直播探测", "播放列表没有分片"))?;
+    let segment_url = resolve(&playlist_url, &start.segment.uri)?;
+    let (offset, length) = start
+        .segment
+        .byte_range
+        .map_or((0, None), |range| (range.offset, Some(range.length)));
+    fetch_segment_start(&segment_url, offset, length).await?;
+    Ok(Report {
+        startup_ms: Utc::now().timestamp_millis() - started,
+        live_edge: program_date_lag(&media).or(start.edge_distance),
+        variant,
+    })
+}
+
+/// Whether `url` can be checked over HTTP; RTMP and friends are left unbadged.
+pub fn probeable(url: &str) -> bool {
+    let url = url.trim();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Fetches the channel's playlist, the variant matching `preferences` and the segment where
+/// playback would start.
+pub async fn probe_stream(url: &str, preferences: ProbePreferences) -> StreamHealth {
+    let checked_ts = Utc::now().timestamp();
+    match probe_url(url, preferences).await {
+        Ok(report) => StreamHealth {
+            online: true,
+            checked_ts,
+            startup_ms: Some(report.startup_ms),
+            live_edge: report.live_edge,
+            variant: report.variant,
+            error: None,
+        },
+        Err(err) => StreamHealth {
+            online: false,
+            checked_ts,
+            startup_ms: None,
+            live_edge: None,
+            variant: None,
+            error: Some(err.user_message(crate::state::UI_LOCALE)),
+        },
+    }
+}
+
+/// Probes every channel a few at a time and publishes each result as it arrives, keyed by
+/// channel id.
+pub async fn probe_all(
+    channels: Vec<LiveStream>,
+    mut health: Signal<HashMap<String, StreamHealth>>,
+) {
+    let preferences = preferences();
+    let mut results = futures::stream::iter(
+        channels
+            .into_iter()
+            .filter(|channel| probeable(&channel.stream_url)),
+    )
+    .map(|channel| async move {
+        let result = probe_stream(&channel.stream_url, preferences).await;
+        (channel.id, result)
+    })
+    .buffer_unordered(PROBE_CONCURRENCY);
+    while let Some((id, result)) = results.next().await {
+        health.write().insert(id, result);
+    }
+}
+
+/// Re-probes the current channel list every [`PROBE_INTERVAL`]. The first round is left to
+/// [`sync_channels`], which runs as soon as the list loads.
+pub async fn run_background_probes(
+    live_streams: Signal<Vec<LiveStream>>,
+    health: Signal<HashMap<String, StreamHealth>>,
+) {
+    loop {
+        futures_timer::Delay::new(PROBE_INTERVAL).await;
+        let channels = live_streams.peek().clone();
+        probe_all(channels, health).await;
+    }
+}
+
+/// Drops results of channels that are gone and probes the ones never checked, so the badges
+/// follow refreshes and playlist imports without waiting for the next round.
+pub fn sync_channels(channels: &[LiveStream], mut health: Signal<HashMap<String, StreamHealth>>) {
+    let ids = channels
+        .iter()
+        .map(|channel| channel.id.as_str())
+        .collect::<HashSet<_>>();
+    if health.peek().keys().any(|id| !ids.contains(id.as_str())) {
+        health.write().retain(|id, _| ids.contains(id.as_str()));
+    }
+    let unchecked = channels
+        .iter()
+        .filter(|channel| !health.peek().contains_key(&channel.id))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !unchecked.is_empty() {
+        spawn(probe_all(unchecked, health));
+    }
+}
@@ -1,13 +1,28 @@
 mod app;
+mod backup;
+mod clustering;
 mod components;
+mod config;
+mod danmaku_file;
+mod dialogue;
+mod embeddings;
+mod feed_marks;
 mod general_ai_client;
 mod helper;
+mod live;
+mod live_danmaku;
+mod live_probe;
 mod memory_client;
+mod memory_sync;
+mod player_shell;
+mod ranking;
+mod secrets;
 mod settings;
 mod state;
 mod storage;
 mod style;
 mod timeline;
+mod usage;
 
 use app::AppRoot;
 use dioxus_mobile::{Config, launch::launch};
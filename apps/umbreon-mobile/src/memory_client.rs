@@ -1,6 +1,12 @@
 #![allow(dead_code)]
 
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use umbreon_core::UmbreonError;
+
+pub const DEFAULT_USER_ID: &str = "umbreon";
+/// How the memory server is named in errors shown to the user.
+pub const SERVICE: &str = "Memory Server";
 
 #[derive(Clone, Debug)]
 pub struct MemoryClient {
@@ -23,11 +29,105 @@ impl MemoryClient {
         self
     }
 
-    pub async fn add_memory(&self, memory: impl Into<String>) -> Result<AddMemoryResponse, String> {
+    pub async fn add_memory(
+        &self,
+        memory: impl Into<String>,
+    ) -> Result<AddMemoryResponse, UmbreonError> {
         let body = AddMemoryRequest {
             user_id: self.user_id.clone(),
             action: "addMemory".into(),
             memory: memory.into(),
+            idempotency_key: None,
+            category: None,
+            tags: None,
+        };
+
+        self.post_json(body).await
+    }
+
+    /// Records a categorized memory under a client-chosen key so a replayed request updates
+    /// the same document instead of creating a duplicate.
+    pub async fn record_memory(
+        &self,
+        memory: impl Into<String>,
+        category: &str,
+        tags: Vec<String>,
+        idempotency_key: impl Into<String>,
+    ) -> Result<AddMemoryResponse, UmbreonError> {
+        let body = AddMemoryRequest {
+            user_id: self.user_id.clone(),
+            action: "addMemory".into(),
+            memory: memory.into(),
+            idempotency_key: Some(idempotency_key.into()),
+            category: Some(category.to_string()),
+            tags: Some(tags),
+        };
+
+        self.post_json(body).await
+    }
+
+    /// Searches memories, optionally restricted to one category.
+    pub async fn query_memory(
+        &self,
+        query: impl Into<String>,
+        category: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<SearchMemoriesResponse, UmbreonError> {
+        let body = QueryMemoryRequest {
+            user_id: self.user_id.clone(),
+            action: "queryMemory".into(),
+            query: query.into(),
+            category: category.map(str::to_string),
+            limit,
+        };
+
+        self.post_json(body).await
+    }
+
+    /// Deletes server-side memories older than `older_than_days`, optionally only those in
+    /// `category`. Covers memories that were never stored on this device.
+    pub async fn purge_memories(
+        &self,
+        category: Option<&str>,
+        older_than_days: u32,
+    ) -> Result<PurgeMemoriesResponse, UmbreonError> {
+        let body = PurgeMemoriesRequest {
+            user_id: self.user_id.clone(),
+            action: "purgeMemories".into(),
+            category: category.map(str::to_string),
+            older_than_days,
+        };
+
+        self.post_json(body).await
+    }
+
+    /// Replaces the tags stored on a memory. The category is sent along so the server
+    /// keeps it on the document's metadata.
+    pub async fn tag_memory(
+        &self,
+        id: impl Into<String>,
+        category: &str,
+        tags: Vec<String>,
+    ) -> Result<TagMemoryResponse, UmbreonError> {
+        let body = TagMemoryRequest {
+            user_id: self.user_id.clone(),
+            action: "tagMemory".into(),
+            id: id.into(),
+            category: Some(category.to_string()),
+            tags,
+        };
+
+        self.post_json(body).await
+    }
+
+    pub async fn delete_memory(
+        &self,
+        id: impl Into<String>,
+    ) -> Result<DeleteMemoryResponse, UmbreonError> {
+        let body = DeleteMemoryRequest {
+            user_id: self.user_id.clone(),
+            action: "deleteMemory".into(),
+            id: id.into(),
         };
 
         self.post_json(body).await
@@ -38,7 +138,7 @@ impl MemoryClient {
         query: impl Into<String>,
         limit: Option<u32>,
         include_full_docs: Option<bool>,
-    ) -> Result<SearchMemoriesResponse, String> {
+    ) -> Result<SearchMemoriesResponse, UmbreonError> {
         let body = SearchMemoriesRequest {
             user_id: self.user_id.clone(),
             action: "searchMemories".into(),
@@ -56,40 +156,120 @@ impl MemoryClient {
         mode: Option<String>,
         conversation_id: Option<String>,
         add_memory: Option<String>,
-    ) -> Result<ChatResponse, String> {
+    ) -> Result<ChatResponse, UmbreonError> {
         let body = ChatRequest {
             user_id: self.user_id.clone(),
             message: message.into(),
             mode,
             conversation_id,
             add_memory,
+            stream: None,
         };
 
         self.post_json(body).await
     }
 
+    /// Same as [`MemoryClient::chat`], but asks the server to stream the reply and hands each
+    /// text delta to `on_delta` as it arrives. Returns the full reply once the stream ends.
+    /// Servers that answer with a plain JSON body are handled as a single delta.
+    pub async fn chat_stream(
+        &self,
+        message: impl Into<String>,
+        mode: Option<String>,
+        conversation_id: Option<String>,
+        add_memory: Option<String>,
+        mut on_delta: impl FnMut(&str),
+    ) -> Result<String, UmbreonError> {
+        let body = ChatRequest {
+            user_id: self.user_id.clone(),
+            message: message.into(),
+            mode,
+            conversation_id,
+            add_memory,
+            stream: Some(true),
+        };
+
+        let response = self
+            .client
+            .post(&self.base_url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| UmbreonError::network(SERVICE, err))?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(UmbreonError::status(SERVICE, status, body));
+        }
+
+        let is_json = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+        if is_json {
+            let reply = response
+                .json::<ChatResponse>()
+                .await
+                .map_err(|err| UmbreonError::parse(SERVICE, err))?;
+            on_delta(&reply.text);
+            return Ok(reply.text);
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut pending = Vec::new();
+        let mut full = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|err| UmbreonError::network(SERVICE, err))?;
+            pending.extend_from_slice(&chunk);
+            // A chunk may end in the middle of a multi-byte character; keep the tail for later.
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(text) => text.len(),
+                Err(err) => err.valid_up_to(),
+            };
+            if valid == 0 {
+                continue;
+            }
+            let text = String::from_utf8_lossy(&pending[..valid]).into_owned();
+            pending.drain(..valid);
+            on_delta(&text);
+            full.push_str(&text);
+        }
+        if !pending.is_empty() {
+            let text = String::from_utf8_lossy(&pending).into_owned();
+            on_delta(&text);
+            full.push_str(&text);
+        }
+
+        if full.trim().is_empty() {
+            return Err(UmbreonError::parse(SERVICE, "empty reply"));
+        }
+        Ok(full)
+    }
+
     async fn post_json<T: Serialize, R: for<'de> Deserialize<'de>>(
         &self,
         payload: T,
-    ) -> Result<R, String> {
+    ) -> Result<R, UmbreonError> {
         let response = self
             .client
             .post(&self.base_url)
             .json(&payload)
             .send()
             .await
-            .map_err(|err| format!("memory server request failed: {err}"))?;
+            .map_err(|err| UmbreonError::network(SERVICE, err))?;
 
         if !response.status().is_success() {
-            let status = response.status();
+            let status = response.status().as_u16();
             let body = response.text().await.unwrap_or_default();
-            return Err(format!("memory server error {status}: {body}"));
+            return Err(UmbreonError::status(SERVICE, status, body));
         }
 
         response
             .json::<R>()
             .await
-            .map_err(|err| format!("failed to parse memory server response: {err}"))
+            .map_err(|err| UmbreonError::parse(SERVICE, err))
     }
 }
 
@@ -99,6 +279,49 @@ struct AddMemoryRequest {
     user_id: String,
     action: String,
     memory: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idempotency_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryMemoryRequest {
+    user_id: String,
+    action: String,
+    query: String,
+    category: Option<String>,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PurgeMemoriesRequest {
+    user_id: String,
+    action: String,
+    category: Option<String>,
+    older_than_days: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TagMemoryRequest {
+    user_id: String,
+    action: String,
+    id: String,
+    category: Option<String>,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteMemoryRequest {
+    user_id: String,
+    action: String,
+    id: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -119,6 +342,7 @@ struct ChatRequest {
     mode: Option<String>,
     conversation_id: Option<String>,
     add_memory: Option<String>,
+    stream: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -129,6 +353,28 @@ pub struct AddMemoryResponse {
     pub error: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteMemoryResponse {
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagMemoryResponse {
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeMemoriesResponse {
+    pub success: bool,
+    pub deleted: Option<usize>,
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchMemoriesResponse {
@@ -152,10 +398,11 @@ pub struct MemoryRecord {
     pub workflow_instance_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct MemorySearchResult {
     pub id: String,
     pub content: String,
     pub score: Option<f32>,
+    pub category: Option<String>,
 }
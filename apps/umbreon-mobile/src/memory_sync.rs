@@ -0,0 +1,524 @@
+use crate::config::MemoryPolicies;
+use crate::memory_client::{DEFAULT_USER_ID, MemoryClient, SERVICE};
+use crate::state::{
+    LocalMemory, MemoryCategory, MemorySyncState, OutboxEntry, OutboxOp, OutboxStatus,
+    OutboxSummary, UI_LOCALE,
+};
+use crate::storage;
+use chrono::Utc;
+use dioxus::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use umbreon_core::UmbreonError;
+
+/// Attempts before a retryable failure is moved to the dead-letter list anyway.
+pub const MAX_ATTEMPTS: u32 = 8;
+const BASE_BACKOFF_SECS: i64 = 5;
+const MAX_BACKOFF_SECS: i64 = 30 * 60;
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn now_ts() -> i64 {
+    Utc::now().timestamp()
+}
+
+fn unique_suffix() -> String {
+    let count = ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{count}", Utc::now().timestamp_millis())
+}
+
+fn enqueue(op: OutboxOp, memory_id: &str, idempotency_key: String, payload: String) {
+    storage::enqueue_outbox(&OutboxEntry {
+        seq: 0,
+        idempotency_key,
+        op,
+        memory_id: memory_id.to_string(),
+        payload,
+        attempts: 0,
+        next_attempt_ts: 0,
+        status: OutboxStatus::Queued,
+        last_error: None,
+        created_ts: now_ts(),
+    });
+}
+
+/// Stores a memory on device and queues it for the memory server.
+pub fn record_memory(
+    content: String,
+    category: MemoryCategory,
+    tags: Vec<String>,
+    source_ref: Option<String>,
+) -> LocalMemory {
+    let now = now_ts();
+    let memory = LocalMemory {
+        id: format!("mem-{}", unique_suffix()),
+        remote_id: None,
+        content,
+        category,
+        tags,
+        source_ref,
+        sync_state: MemorySyncState::Pending,
+        created_ts: now,
+        updated_ts: now,
+    };
+    storage::store_local_memory(&memory);
+    enqueue(
+        OutboxOp::Add,
+        &memory.id,
+        format!("add:{}", memory.id),
+        memory.content.clone(),
+    );
+    memory
+}
+
+/// Applies an edit from the Memory pane: new content is re-recorded under the memory's
+/// original key, and tag changes go out as tag/untag operations.
+pub fn edit_memory(id: &str, content: &str, tags: &[String]) {
+    let Some(mut memory) = storage::load_local_memory(id) else {
+        return;
+    };
+    let content = content.trim();
+    if !content.is_empty() && content != memory.content {
+        memory.content = content.to_string();
+        memory.sync_state = MemorySyncState::Pending;
+        memory.updated_ts = now_ts();
+        storage::store_local_memory(&memory);
+        enqueue(
+            OutboxOp::Update,
+            &memory.id,
+            format!("update:{}:{}", memory.id, unique_suffix()),
+            memory.content.clone(),
+        );
+    }
+    let added = tags
+        .iter()
+        .filter(|tag| !memory.tags.contains(tag))
+        .cloned()
+        .collect::<Vec<_>>();
+    let removed = memory
+        .tags
+        .iter()
+        .filter(|tag| !tags.contains(tag))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !removed.is_empty() {
+        untag_memory(id, &removed);
+    }
+    if !added.is_empty() {
+        tag_memory(id, &added);
+    }
+}
+
+/// Adds `tags` to a stored memory and queues the change.
+pub fn tag_memory(id: &str, tags: &[String]) {
+    let Some(memory) = storage::load_local_memory(id) else {
+        return;
+    };
+    let mut next = memory.tags.clone();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !next.iter().any(|existing| existing == tag) {
+            next.push(tag.to_string());
+        }
+    }
+    set_tags(memory, next);
+}
+
+/// Removes `tags` from a stored memory and queues the change.
+pub fn untag_memory(id: &str, tags: &[String]) {
+    let Some(memory) = storage::load_local_memory(id) else {
+        return;
+    };
+    let next = memory
+        .tags
+        .iter()
+        .filter(|existing| !tags.iter().any(|tag| tag.trim() == existing.as_str()))
+        .cloned()
+        .collect();
+    set_tags(memory, next);
+}
+
+/// The server replaces the whole tag set, so every change ships the full list.
+fn set_tags(mut memory: LocalMemory, tags: Vec<String>) {
+    if memory.tags == tags {
+        return;
+    }
+    memory.tags = tags;
+    memory.sync_state = MemorySyncState::Pending;
+    memory.updated_ts = now_ts();
+    storage::store_local_memory(&memory);
+    let payload = serde_json::to_string(&memory.tags).unwrap_or_else(|_| "[]".to_string());
+    enqueue(
+        OutboxOp::Tag,
+        &memory.id,
+        format!("tag:{}:{}", memory.id, unique_suffix()),
+        payload,
+    );
+}
+
+/// Deletes a memory locally and queues the remote delete. A memory that never reached the
+/// server is dropped together with its queued operations. Ids without a local row are
+/// treated as server ids.
+pub fn delete_memory(id: &str) {
+    let remote_id = match storage::load_local_memory(id) {
+        Some(memory) => memory.remote_id,
+        None => Some(id.to_string()),
+    };
+    drop_queued(id);
+    storage::delete_local_memory(id);
+    if let Some(remote_id) = remote_id {
+        enqueue(
+            OutboxOp::Delete,
+            id,
+            format!("delete:{id}:{}", unique_suffix()),
+            remote_id,
+        );
+    }
+}
+
+/// Removes every queued or dead-lettered operation for `memory_id`.
+fn drop_queued(memory_id: &str) {
+    for entry in storage::load_outbox() {
+        if entry.memory_id == memory_id {
+            storage::delete_outbox_entry(entry.seq);
+        }
+    }
+}
+
+/// Stores a memory restored from a backup over `existing` (the local copy, if any) and
+/// queues what the server may be missing: an add when it never got the memory, an update
+/// when the restored copy is unsynced or, with `overwrite`, differs from the local one.
+/// Operations queued for the local copy are dropped, since the restored copy wins.
+pub fn restore_memory(mut memory: LocalMemory, existing: Option<&LocalMemory>, overwrite: bool) {
+    if let Some(existing) = existing {
+        drop_queued(&existing.id);
+        memory.remote_id = memory.remote_id.or_else(|| existing.remote_id.clone());
+    }
+    let changed = existing.is_some_and(|existing| {
+        overwrite
+            && (existing.content != memory.content
+                || existing.category != memory.category
+                || existing.tags != memory.tags)
+    });
+    let needs_sync =
+        memory.remote_id.is_none() || memory.sync_state != MemorySyncState::Synced || changed;
+    if needs_sync {
+        memory.sync_state = MemorySyncState::Pending;
+    }
+    storage::store_local_memory(&memory);
+    if !needs_sync {
+        return;
+    }
+    match memory.remote_id {
+        None => enqueue(
+            OutboxOp::Add,
+            &memory.id,
+            format!("add:{}", memory.id),
+            memory.content.clone(),
+        ),
+        Some(_) => enqueue(
+            OutboxOp::Update,
+            &memory.id,
+            format!("update:{}:{}", memory.id, unique_suffix()),
+            memory.content.clone(),
+        ),
+    }
+}
+
+pub fn summary() -> OutboxSummary {
+    let entries = storage::load_outbox();
+    let dead = entries
+        .iter()
+        .filter(|entry| entry.status == OutboxStatus::Dead)
+        .count();
+    OutboxSummary {
+        queued: entries.len() - dead,
+        dead,
+        ..OutboxSummary::default()
+    }
+}
+
+pub fn dead_letters() -> Vec<OutboxEntry> {
+    storage::load_outbox()
+        .into_iter()
+        .filter(|entry| entry.status == OutboxStatus::Dead)
+        .collect()
+}
+
+/// Moves a dead-letter entry back into the queue with a fresh attempt budget.
+pub fn retry_dead(seq: i64) {
+    let Some(mut entry) = storage::load_outbox()
+        .into_iter()
+        .find(|entry| entry.seq == seq)
+    else {
+        return;
+    };
+    entry.status = OutboxStatus::Queued;
+    entry.attempts = 0;
+    entry.next_attempt_ts = 0;
+    entry.last_error = None;
+    storage::update_outbox_entry(&entry);
+    refresh_sync_state(&entry.memory_id);
+}
+
+/// Requeues every dead-letter entry belonging to `memory_id`.
+pub fn retry_memory(memory_id: &str) {
+    for entry in dead_letters() {
+        if entry.memory_id == memory_id {
+            retry_dead(entry.seq);
+        }
+    }
+}
+
+pub fn discard_dead(seq: i64) {
+    let Some(entry) = dead_letters().into_iter().find(|entry| entry.seq == seq) else {
+        return;
+    };
+    storage::delete_outbox_entry(seq);
+    refresh_sync_state(&entry.memory_id);
+}
+
+/// Derives a memory's sync state from the outbox: failed while anything is dead-lettered,
+/// pending while anything is queued, synced otherwise.
+fn refresh_sync_state(memory_id: &str) {
+    let Some(mut memory) = storage::load_local_memory(memory_id) else {
+        return;
+    };
+    let entries = storage::load_outbox()
+        .into_iter()
+        .filter(|entry| entry.memory_id == memory_id)
+        .collect::<Vec<_>>();
+    let state = if entries
+        .iter()
+        .any(|entry| entry.status == OutboxStatus::Dead)
+    {
+        MemorySyncState::Failed
+    } else if !entries.is_empty() || memory.remote_id.is_none() {
+        MemorySyncState::Pending
+    } else {
+        MemorySyncState::Synced
+    };
+    if memory.sync_state != state {
+        memory.sync_state = state;
+        storage::store_local_memory(&memory);
+    }
+}
+
+/// Ids of the local memories `policies` would purge: anything older than a matching rule's
+/// `max_age_days`, plus the oldest entries beyond its `max_items`.
+pub fn purge_candidates(
+    memories: &[LocalMemory],
+    policies: &MemoryPolicies,
+    now: i64,
+) -> Vec<String> {
+    let mut purged = Vec::new();
+    for rule in &policies.rules {
+        let mut matching = memories
+            .iter()
+            .filter(|memory| rule.applies_to(memory.category))
+            .filter(|memory| !purged.contains(&memory.id))
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|memory| std::cmp::Reverse(memory.updated_ts));
+        for (index, memory) in matching.into_iter().enumerate() {
+            let too_old = rule
+                .max_age_days
+                .is_some_and(|days| now - memory.updated_ts > i64::from(days) * DAY_SECS);
+            let over_limit = rule.max_items.is_some_and(|limit| index >= limit);
+            if too_old || over_limit {
+                purged.push(memory.id.clone());
+            }
+        }
+    }
+    purged
+}
+
+/// Deletes the local memories selected by `policies`, queueing their remote deletes.
+/// Returns the purged ids.
+pub fn apply_purge_policies(policies: &MemoryPolicies) -> Vec<String> {
+    let purged = purge_candidates(&storage::load_local_memories(), policies, now_ts());
+    for id in &purged {
+        delete_memory(id);
+    }
+    purged
+}
+
+/// Runs the age-based rules against the server as well, for memories that only exist there.
+pub async fn purge_remote(
+    base_url: String,
+    policies: &MemoryPolicies,
+) -> Result<usize, UmbreonError> {
+    let client = MemoryClient::new(base_url, DEFAULT_USER_ID);
+    let mut deleted = 0;
+    for rule in &policies.rules {
+        let Some(days) = rule.max_age_days else {
+            continue;
+        };
+        let response = client
+            .purge_memories(rule.category.as_deref(), days)
+            .await?;
+        if !response.success {
+            return Err(UmbreonError::rejected(
+                SERVICE,
+                response
+                    .error
+                    .unwrap_or_else(|| "purge refused".to_string()),
+            ));
+        }
+        deleted += response.deleted.unwrap_or_default();
+    }
+    Ok(deleted)
+}
+
+fn backoff_secs(attempts: u32) -> i64 {
+    let exponent = attempts.saturating_sub(1).min(16);
+    (BASE_BACKOFF_SECS << exponent).min(MAX_BACKOFF_SECS)
+}
+
+async fn deliver(client: &MemoryClient, entry: &OutboxEntry) -> Result<(), UmbreonError> {
+    match entry.op {
+        OutboxOp::Add | OutboxOp::Update => {
+            let (category, tags) = storage::load_local_memory(&entry.memory_id)
+                .map(|memory| (memory.category, memory.tags))
+                .unwrap_or((MemoryCategory::Preference, Vec::new()));
+            // Updates reuse the add key so the server overwrites the same document.
+            let key = match entry.op {
+                OutboxOp::Update => format!("add:{}", entry.memory_id),
+                _ => entry.idempotency_key.clone(),
+            };
+            let response = client
+                .record_memory(entry.payload.clone(), category.as_str(), tags, key)
+                .await?;
+            let record = match response.memory {
+                Some(record) if response.success => record,
+                _ => {
+                    return Err(UmbreonError::rejected(
+                        SERVICE,
+                        response
+                            .error
+                            .unwrap_or_else(|| "no record returned".to_string()),
+                    ));
+                }
+            };
+            match storage::load_local_memory(&entry.memory_id) {
+                Some(mut memory) => {
+                    memory.remote_id = Some(record.id);
+                    storage::store_local_memory(&memory);
+                }
+                // Deleted while the add was in flight: the delete found no remote id to
+                // queue, so clean up the document the add just created.
+                None => enqueue(
+                    OutboxOp::Delete,
+                    &entry.memory_id,
+                    format!("delete:{}:{}", entry.memory_id, unique_suffix()),
+                    record.id,
+                ),
+            }
+            Ok(())
+        }
+        OutboxOp::Tag => {
+            let Some((remote_id, category)) = storage::load_local_memory(&entry.memory_id)
+                .and_then(|memory| Some((memory.remote_id?, memory.category)))
+            else {
+                return Err(UmbreonError::rejected(
+                    SERVICE,
+                    "memory was never delivered",
+                ));
+            };
+            let tags = serde_json::from_str::<Vec<String>>(&entry.payload).unwrap_or_default();
+            let response = client
+                .tag_memory(remote_id, category.as_str(), tags)
+                .await?;
+            if response.success {
+                Ok(())
+            } else {
+                Err(UmbreonError::rejected(
+                    SERVICE,
+                    response.error.unwrap_or_else(|| "tags refused".to_string()),
+                ))
+            }
+        }
+        OutboxOp::Delete => {
+            let response = client.delete_memory(entry.payload.clone()).await?;
+            if response.success {
+                Ok(())
+            } else {
+                Err(UmbreonError::rejected(
+                    SERVICE,
+                    response
+                        .error
+                        .unwrap_or_else(|| "delete refused".to_string()),
+                ))
+            }
+        }
+    }
+}
+
+/// Replays queued operations in order. Stops at the first transient failure so later
+/// operations never overtake earlier ones; permanent failures are dead-lettered.
+/// `last_synced` is only set when something was delivered or the queue was drained.
+pub async fn flush_outbox(base_url: String) -> OutboxSummary {
+    let mut last_error = None;
+    let mut delivered = 0usize;
+    let has_server = !base_url.is_empty();
+    if has_server {
+        let client = MemoryClient::new(base_url, DEFAULT_USER_ID);
+        for mut entry in storage::load_outbox() {
+            if entry.status == OutboxStatus::Dead {
+                continue;
+            }
+            if entry.next_attempt_ts > now_ts() {
+                break;
+            }
+            match deliver(&client, &entry).await {
+                Ok(()) => {
+                    storage::delete_outbox_entry(entry.seq);
+                    refresh_sync_state(&entry.memory_id);
+                    delivered += 1;
+                }
+                Err(err) => {
+                    entry.attempts += 1;
+                    entry.last_error = Some(err.to_string());
+                    let retryable = err.is_retryable();
+                    if retryable && entry.attempts < MAX_ATTEMPTS {
+                        entry.next_attempt_ts = now_ts() + backoff_secs(entry.attempts);
+                        storage::update_outbox_entry(&entry);
+                        refresh_sync_state(&entry.memory_id);
+                        last_error = Some(err.user_message(UI_LOCALE));
+                        break;
+                    }
+                    entry.status = OutboxStatus::Dead;
+                    storage::update_outbox_entry(&entry);
+                    refresh_sync_state(&entry.memory_id);
+                    last_error = Some(err.user_message(UI_LOCALE));
+                }
+            }
+        }
+    }
+    let summary = summary();
+    let drained = has_server && last_error.is_none() && summary.queued == 0;
+    OutboxSummary {
+        last_synced: (delivered > 0 || drained).then(now_ts),
+        last_error,
+        ..summary
+    }
+}
+
+/// Runs [`flush_outbox`] unless a flush is already in flight, then calls `on_done` so
+/// callers can refresh whatever mirrors memory sync state.
+pub fn trigger_flush(
+    base_url: String,
+    mut outbox: Signal<OutboxSummary>,
+    on_done: impl FnOnce() + 'static,
+) {
+    if outbox.read().syncing {
+        return;
+    }
+    outbox.write().syncing = true;
+    spawn(async move {
+        let previous = outbox.read().last_synced;
+        let mut next = flush_outbox(base_url).await;
+        next.last_synced = next.last_synced.or(previous);
+        *outbox.write() = next;
+        on_done();
+    });
+}
@@ -0,0 +1,214 @@
+use dioxus::prelude::*;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use umbreon_core::UmbreonError;
+use umbreon_core::player::{PlayerBackend, PlayerCommand, PlayerEvent, PlayerShell, PlayerState};
+
+/// DOM id of the `<video>` element the HTML5 backend drives.
+pub const PLAYER_ELEMENT_ID: &str = "umbreon-player";
+const HLS_JS_URL: &str = "https://cdn.jsdelivr.net/npm/hls.js@1/dist/hls.min.js";
+
+/// Long-lived bridge script: waits for the `<video>` element, forwards its media events as
+/// `PlayerEvent` JSON and applies `PlayerCommand` JSON it receives. HLS goes through
+/// hls.js unless the WebView plays it natively.
+const HTML5_BRIDGE: &str = r#"
+const video = await (async () => {
+  let element;
+  while (!(element = document.getElementById("__ELEMENT_ID__"))) {
+    await new Promise((resolve) => requestAnimationFrame(resolve));
+  }
+  return element;
+})();
+const emit = (event) => dioxus.send(event);
+let hls = null;
+let currentUrl = null;
+let lowLatency = { enabled: false, target_latency: 3 };
+const finite = (value) => (Number.isFinite(value) ? value : null);
+const levels = () =>
+  hls
+    ? hls.levels.map((level) => ({
+        label: level.height ? `${level.height}p` : `${Math.round(level.bitrate / 1000)} kbps`,
+        bandwidth: level.bitrate,
+        width: level.width || null,
+        height: level.height || null,
+      }))
+    : [];
+video.addEventListener("loadedmetadata", () => {
+  if (!hls) {
+    emit({ type: "ready", duration: finite(video.duration), live: !Number.isFinite(video.duration), levels: [] });
+  }
+});
+video.addEventListener("playing", () => emit({ type: "playing" }));
+video.addEventListener("pause", () => emit({ type: "paused" }));
+video.addEventListener("waiting", () => emit({ type: "buffering" }));
+video.addEventListener("ended", () => emit({ type: "ended" }));
+const latency = () => {
+  if (hls) return hls.liveSyncPosition === null ? null : finite(hls.latency);
+  if (Number.isFinite(video.duration) || video.seekable.length === 0) return null;
+  return video.seekable.end(video.seekable.length - 1) - video.currentTime;
+};
+video.addEventListener("timeupdate", () => {
+  emit({ type: "time_update", position: video.currentTime, duration: hls ? null : finite(video.duration) });
+  const behind = latency();
+  if (behind !== null) emit({ type: "latency", latency: behind });
+});
+video.addEventListener("ratechange", () => emit({ type: "rate_changed", rate: video.playbackRate }));
+video.addEventListener("error", () => {
+  const error = video.error;
+  emit({ type: "error", message: error ? error.message || `media error ${error.code}` : "media error" });
+});
+const loadHlsJs = () =>
+  window.Hls
+    ? Promise.resolve(window.Hls)
+    : new Promise((resolve, reject) => {
+        const script = document.createElement("script");
+        script.src = "__HLS_JS_URL__";
+        script.onload = () => resolve(window.Hls);
+        script.onerror = () => reject(new Error("hls.js failed to load"));
+        document.head.appendChild(script);
+      });
+const load = async (url) => {
+  currentUrl = url;
+  emit({ type: "loading" });
+  if (hls) {
+    hls.destroy();
+    hls = null;
+  }
+  const isHls = /\.m3u8?($|\?)/i.test(url);
+  if (isHls && !video.canPlayType("application/vnd.apple.mpegurl")) {
+    const Hls = await loadHlsJs();
+    if (Hls.isSupported()) {
+      hls = new Hls(
+        lowLatency.enabled
+          ? { lowLatencyMode: true, liveSyncDuration: lowLatency.target_latency }
+          : { lowLatencyMode: false }
+      );
+      hls.once(Hls.Events.LEVEL_LOADED, (_, data) =>
+        emit({
+          type: "ready",
+          duration: data.details.live ? null : data.details.totalduration,
+          live: data.details.live,
+          levels: levels(),
+        })
+      );
+      hls.on(Hls.Events.LEVEL_SWITCHED, (_, data) =>
+        emit({ type: "quality_changed", quality: hls.autoLevelEnabled ? null : hls.currentLevel, active: data.level })
+      );
+      hls.on(Hls.Events.ERROR, (_, data) => {
+        if (data.fatal) emit({ type: "error", message: `${data.type}: ${data.details}` });
+      });
+      hls.loadSource(url);
+      hls.attachMedia(video);
+      return;
+    }
+  }
+  video.src = url;
+};
+const commands = {
+  load: (command) => load(command.url),
+  play: () => video.play().catch((error) => emit({ type: "error", message: String(error) })),
+  pause: () => video.pause(),
+  seek: (command) => {
+    video.currentTime = command.position;
+  },
+  set_rate: (command) => {
+    video.playbackRate = command.rate;
+  },
+  set_quality: (command) => {
+    if (hls) hls.currentLevel = command.level === null ? -1 : command.level;
+  },
+  set_low_latency: async (command) => {
+    const changed =
+      command.enabled !== lowLatency.enabled || command.target_latency !== lowLatency.target_latency;
+    lowLatency = { enabled: command.enabled, target_latency: command.target_latency };
+    // hls.js only reads its latency settings when created, so reload the live stream.
+    if (changed && hls && hls.liveSyncPosition !== null && currentUrl) {
+      const playing = !video.paused;
+      await load(currentUrl);
+      if (playing) video.play().catch(() => {});
+    }
+  },
+};
+while (true) {
+  const command = await dioxus.recv();
+  try {
+    await commands[command.type]?.(command);
+  } catch (error) {
+    emit({ type: "error", message: String(error) });
+  }
+}
+"#;
+
+pub type SharedShell = Rc<RefCell<PlayerShell>>;
+
+/// Plays through an HTML5 `<video>` element in the WebView renderer.
+pub struct Html5Backend {
+    bridge: UseEval,
+}
+
+impl Html5Backend {
+    /// Starts the bridge for the element with `element_id`; events go to `sink`.
+    pub fn attach(element_id: &str, mut sink: impl FnMut(PlayerEvent) + 'static) -> Self {
+        let bridge = eval(
+            &HTML5_BRIDGE
+                .replace("__ELEMENT_ID__", element_id)
+                .replace("__HLS_JS_URL__", HLS_JS_URL),
+        );
+        let mut receiver = bridge;
+        spawn(async move {
+            while let Ok(message) = receiver.recv().await {
+                match serde_json::from_value::<PlayerEvent>(message) {
+                    Ok(event) => sink(event),
+                    Err(err) => tracing::warn!("unexpected player message: {err}"),
+                }
+            }
+        });
+        Html5Backend { bridge }
+    }
+}
+
+impl PlayerBackend for Html5Backend {
+    fn name(&self) -> &'static str {
+        "html5"
+    }
+
+    fn send(&mut self, command: &PlayerCommand) -> Result<(), UmbreonError> {
+        let message =
+            serde_json::to_value(command).map_err(|err| UmbreonError::parse("播放器指令", err))?;
+        self.bridge
+            .send(message)
+            .map_err(|err| UmbreonError::config("播放器", format!("{err:?}")))
+    }
+}
+
+/// The player pane's shell, built once per mount. Every event it handles is mirrored into
+/// `state` so components can render from the signal.
+pub fn use_player_shell(mut state: Signal<PlayerState>) -> SharedShell {
+    use_hook(move || {
+        let shell = Rc::new_cyclic(|weak: &Weak<RefCell<PlayerShell>>| {
+            let weak = weak.clone();
+            let backend = Html5Backend::attach(PLAYER_ELEMENT_ID, move |event| {
+                if let Some(shell) = weak.upgrade() {
+                    shell.borrow_mut().handle(event);
+                }
+            });
+            RefCell::new(PlayerShell::new(Box::new(backend)))
+        });
+        shell
+            .borrow_mut()
+            .subscribe(move |_, current| *state.write() = current.clone());
+        shell
+    })
+}
+
+/// Runs `action` on the shell, then mirrors the resulting state.
+pub fn with_shell(
+    shell: &SharedShell,
+    mut state: Signal<PlayerState>,
+    action: impl FnOnce(&mut PlayerShell) -> Result<(), UmbreonError>,
+) -> Result<(), UmbreonError> {
+    let mut shell = shell.borrow_mut();
+    let result = action(&mut shell);
+    *state.write() = shell.state().clone();
+    result
+}
@@ -0,0 +1,289 @@
+use crate::state::{FeedItem, FeedMark, LocalMemory, MemoryCategory};
+use crate::timeline::plain_text_from_html;
+use std::collections::HashMap;
+
+const SIMILARITY_WEIGHT: f64 = 0.5;
+const RECENCY_WEIGHT: f64 = 0.3;
+const SOURCE_WEIGHT: f64 = 0.2;
+const RECENCY_HALF_LIFE_HOURS: f64 = 48.0;
+/// Similarity above which an item counts as matching a memory and gets highlighted.
+const MATCH_THRESHOLD: f64 = 0.18;
+const PROFILE_SUMMARY_CHARS: usize = 400;
+
+type TermVector = HashMap<String, f64>;
+
+/// Why an item was boosted, shown on its card.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoostReason {
+    SimilarToMarked(String),
+    MatchesPreference(String),
+    SharedTags(Vec<String>),
+    FavoriteSource(String),
+}
+
+impl BoostReason {
+    pub fn label(&self) -> String {
+        match self {
+            BoostReason::SimilarToMarked(title) => format!("与你铸魂的《{title}》相似"),
+            BoostReason::MatchesPreference(snippet) => format!("符合你的偏好：{snippet}"),
+            BoostReason::SharedTags(tags) => format!("你常看的标签：{}", tags.join("、")),
+            BoostReason::FavoriteSource(author) => format!("你常收藏 {author}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankedItem {
+    pub item: FeedItem,
+    pub score: f64,
+    /// Strongest reason first; empty when nothing in memory matched.
+    pub reasons: Vec<BoostReason>,
+}
+
+/// Lowercased terms: latin words as-is, CJK runs as character bigrams.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk = Vec::new();
+    let flush_cjk = |cjk: &mut Vec<char>, tokens: &mut Vec<String>| {
+        if cjk.len() == 1 {
+            tokens.push(cjk[0].to_string());
+        }
+        for pair in cjk.windows(2) {
+            tokens.push(pair.iter().collect());
+        }
+        cjk.clear();
+    };
+    for ch in text.chars() {
+        if is_cjk(ch) {
+            if word.chars().count() > 1 {
+                tokens.push(std::mem::take(&mut word));
+            }
+            word.clear();
+            cjk.push(ch);
+        } else if ch.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut tokens);
+            word.extend(ch.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk, &mut tokens);
+            if word.chars().count() > 1 {
+                tokens.push(std::mem::take(&mut word));
+            }
+            word.clear();
+        }
+    }
+    flush_cjk(&mut cjk, &mut tokens);
+    if word.chars().count() > 1 {
+        tokens.push(word);
+    }
+    tokens
+}
+
+fn is_cjk(ch: char) -> bool {
+    matches!(ch as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF)
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+fn term_vector(text: &str, tags: &[String]) -> TermVector {
+    let mut vector = TermVector::new();
+    for token in tokenize(text) {
+        *vector.entry(token).or_default() += 1.0;
+    }
+    for tag in tags {
+        let tag = normalize_tag(tag);
+        if !tag.is_empty() {
+            *vector.entry(format!("#{tag}")).or_default() += 2.0;
+        }
+    }
+    vector
+}
+
+fn item_vector(item: &FeedItem) -> TermVector {
+    let summary = plain_text_from_html(&item.summary)
+        .chars()
+        .take(PROFILE_SUMMARY_CHARS)
+        .collect::<String>();
+    term_vector(&format!("{} {}", item.title, summary), &item.tags)
+}
+
+fn cosine(a: &TermVector, b: &TermVector) -> f64 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let dot = small
+        .iter()
+        .filter_map(|(term, weight)| large.get(term).map(|other| weight * other))
+        .sum::<f64>();
+    if dot == 0.0 {
+        return 0.0;
+    }
+    let norm = |vector: &TermVector| vector.values().map(|w| w * w).sum::<f64>().sqrt();
+    dot / (norm(a) * norm(b))
+}
+
+fn recency(published_ts: i64, now: i64) -> f64 {
+    let age_hours = (now - published_ts).max(0) as f64 / 3600.0;
+    0.5f64.powf(age_hours / RECENCY_HALF_LIFE_HOURS)
+}
+
+fn snippet(text: &str) -> String {
+    let line = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    let mut snippet = line.trim().chars().take(16).collect::<String>();
+    if line.trim().chars().count() > 16 {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// What the user has shown interest in: bookmarked items, items they asked about, and
+/// stated preferences.
+pub struct InterestProfile {
+    liked: Vec<(String, TermVector)>,
+    preferences: Vec<(String, TermVector)>,
+    tag_counts: HashMap<String, usize>,
+    source_counts: HashMap<String, usize>,
+    liked_total: usize,
+}
+
+impl InterestProfile {
+    pub fn build(
+        items: &[FeedItem],
+        marks: &HashMap<String, FeedMark>,
+        asked_item_ids: &[String],
+        memories: &[LocalMemory],
+    ) -> Self {
+        let mut profile = InterestProfile {
+            liked: Vec::new(),
+            preferences: Vec::new(),
+            tag_counts: HashMap::new(),
+            source_counts: HashMap::new(),
+            liked_total: 0,
+        };
+        for item in items {
+            let marked = marks.get(&item.id).is_some_and(|mark| mark.marked);
+            if !marked && !asked_item_ids.contains(&item.id) {
+                continue;
+            }
+            profile.liked_total += 1;
+            for tag in &item.tags {
+                *profile.tag_counts.entry(normalize_tag(tag)).or_default() += 1;
+            }
+            *profile
+                .source_counts
+                .entry(item.author.trim().to_string())
+                .or_default() += 1;
+            profile.liked.push((item.id.clone(), item_vector(item)));
+        }
+        for memory in memories {
+            if memory.category == MemoryCategory::Preference {
+                profile.preferences.push((
+                    snippet(&memory.content),
+                    term_vector(&memory.content, &memory.tags),
+                ));
+            }
+        }
+        profile
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.liked.is_empty() && self.preferences.is_empty()
+    }
+
+    fn score(&self, item: &FeedItem, titles: &HashMap<&str, &str>, now: i64) -> RankedItem {
+        let vector = item_vector(item);
+        let mut reasons = Vec::new();
+
+        let best_liked = self
+            .liked
+            .iter()
+            .filter(|(id, _)| id != &item.id)
+            .map(|(id, liked)| (id, cosine(&vector, liked)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let best_preference = self
+            .preferences
+            .iter()
+            .map(|(snippet, preference)| (snippet, cosine(&vector, preference)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let liked_similarity = best_liked.map(|(_, score)| score).unwrap_or_default();
+        let preference_similarity = best_preference.map(|(_, score)| score).unwrap_or_default();
+        if let Some((id, score)) = best_liked
+            && score >= MATCH_THRESHOLD
+            && let Some(title) = titles.get(id.as_str())
+        {
+            reasons.push((score, BoostReason::SimilarToMarked(title.to_string())));
+        }
+        if let Some((snippet, score)) = best_preference
+            && score >= MATCH_THRESHOLD
+        {
+            reasons.push((score, BoostReason::MatchesPreference(snippet.clone())));
+        }
+
+        let shared_tags = item
+            .tags
+            .iter()
+            .filter(|tag| {
+                self.tag_counts
+                    .get(&normalize_tag(tag))
+                    .is_some_and(|count| *count >= 2)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let source_share = if self.liked_total == 0 {
+            0.0
+        } else {
+            self.source_counts
+                .get(item.author.trim())
+                .copied()
+                .unwrap_or_default() as f64
+                / self.liked_total as f64
+        };
+        if !shared_tags.is_empty() {
+            reasons.push((MATCH_THRESHOLD, BoostReason::SharedTags(shared_tags)));
+        }
+        if source_share >= 0.25 && self.liked_total >= 2 {
+            reasons.push((
+                source_share * MATCH_THRESHOLD,
+                BoostReason::FavoriteSource(item.author.trim().to_string()),
+            ));
+        }
+        reasons.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let similarity = liked_similarity.max(preference_similarity);
+        let score = SIMILARITY_WEIGHT * similarity
+            + RECENCY_WEIGHT * recency(item.published_ts, now)
+            + SOURCE_WEIGHT * source_share;
+        RankedItem {
+            item: item.clone(),
+            score,
+            reasons: reasons.into_iter().map(|(_, reason)| reason).collect(),
+        }
+    }
+}
+
+/// Scores `items` against `profile`. With `personalized` the result is ordered by score,
+/// otherwise it keeps newest first; highlighting and reasons are filled in either way.
+pub fn rank_items(
+    items: Vec<FeedItem>,
+    profile: &InterestProfile,
+    personalized: bool,
+    now: i64,
+) -> Vec<RankedItem> {
+    let titles = items
+        .iter()
+        .map(|item| (item.id.as_str(), item.title.as_str()))
+        .collect::<HashMap<_, _>>();
+    let mut ranked = items
+        .iter()
+        .map(|item| profile.score(item, &titles, now))
+        .collect::<Vec<_>>();
+    if personalized && !profile.is_empty() {
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    } else {
+        ranked.sort_by_key(|ranked| std::cmp::Reverse(ranked.item.published_ts));
+    }
+    ranked
+}
@@ -0,0 +1,196 @@
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use umbreon_core::UmbreonError;
+use umbreon_core::secrets::{self as sealing, MasterKey};
+
+pub const LLM_API_KEY: &str = "llm_api_key";
+
+/// Where the master key that seals stored credentials comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// A random key kept by the device, unlocked automatically.
+    #[default]
+    Device,
+    /// Derived from a passphrase the user enters once per launch.
+    Passphrase,
+}
+
+impl KeySource {
+    pub fn label(self) -> &'static str {
+        match self {
+            KeySource::Device => "设备密钥",
+            KeySource::Passphrase => "口令",
+        }
+    }
+}
+
+/// Stored in settings as JSON. `salt` and `check` are only set for passphrase keys.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct KeyParams {
+    source: KeySource,
+    salt: Option<String>,
+    check: Option<String>,
+}
+
+/// The master key once it is known for this launch.
+static SESSION_KEY: Mutex<Option<MasterKey>> = Mutex::new(None);
+
+fn session() -> MutexGuard<'static, Option<MasterKey>> {
+    SESSION_KEY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn key_params() -> KeyParams {
+    storage::load_settings()
+        .secret_key_params
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+/// The key held by the device keystore, created on first use. Also seals the database key,
+/// which has to be readable before any passphrase could be asked for.
+pub fn device_key() -> Result<MasterKey, UmbreonError> {
+    if let Some(encoded) = storage::load_device_key() {
+        return MasterKey::from_encoded(&encoded);
+    }
+    let key = MasterKey::generate();
+    storage::store_device_key(&key.encode())?;
+    Ok(key)
+}
+
+fn current_key() -> Result<MasterKey, UmbreonError> {
+    if let Some(key) = session().clone() {
+        return Ok(key);
+    }
+    let key = match key_params().source {
+        KeySource::Device => device_key()?,
+        KeySource::Passphrase => {
+            return Err(UmbreonError::Locked("passphrase not entered".to_string()));
+        }
+    };
+    *session() = Some(key.clone());
+    Ok(key)
+}
+
+pub fn key_source() -> KeySource {
+    key_params().source
+}
+
+/// True while a passphrase is set but has not been entered this launch.
+pub fn is_locked() -> bool {
+    session().is_none() && key_source() == KeySource::Passphrase
+}
+
+fn load_all(key: &MasterKey) -> Vec<(String, String)> {
+    storage::load_secrets()
+        .into_iter()
+        .filter_map(|(name, sealed)| match sealing::open(key, &name, &sealed) {
+            Ok(value) => Some((name, value)),
+            Err(err) => {
+                tracing::warn!("secret {name} unreadable: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// The decrypted secret, or `None` when it is unset or the store is locked.
+pub fn load(name: &str) -> Option<String> {
+    let key = current_key().ok()?;
+    let (_, sealed) = storage::load_secrets()
+        .into_iter()
+        .find(|(stored, _)| stored == name)?;
+    match sealing::open(&key, name, &sealed) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            tracing::warn!("secret {name} unreadable: {err}");
+            None
+        }
+    }
+}
+
+/// Seals and stores `value`; an empty value removes the secret.
+pub fn store(name: &str, value: &str) -> Result<(), UmbreonError> {
+    let value = value.trim();
+    if value.is_empty() {
+        storage::delete_secret(name);
+        return Ok(());
+    }
+    let key = current_key()?;
+    storage::store_secret(name, &sealing::seal(&key, name, value)?)
+}
+
+/// Moves credentials that older versions kept in plaintext into the sealed store. The
+/// plaintext copy is only removed once the sealed one is written.
+pub fn migrate_plaintext() {
+    let Some(api_key) = storage::load_legacy_llm_api_key() else {
+        return;
+    };
+    if api_key.trim().is_empty() {
+        storage::clear_legacy_llm_api_key();
+        return;
+    }
+    match store(LLM_API_KEY, &api_key) {
+        Ok(()) => storage::clear_legacy_llm_api_key(),
+        Err(err) => tracing::warn!("API key migration deferred: {err}"),
+    }
+}
+
+/// Derives the passphrase key and checks it against the stored marker.
+pub fn unlock(passphrase: &str) -> Result<(), UmbreonError> {
+    let params = key_params();
+    let (Some(salt), Some(check)) = (params.salt, params.check) else {
+        return Err(UmbreonError::config("口令", "no passphrase is set"));
+    };
+    let key = MasterKey::from_passphrase(passphrase, &salt)?;
+    if !key.verify(&check) {
+        return Err(UmbreonError::Locked("wrong passphrase".to_string()));
+    }
+    *session() = Some(key);
+    migrate_plaintext();
+    Ok(())
+}
+
+/// Re-seals every stored secret under a key derived from `passphrase`, or under the
+/// device key when `passphrase` is `None`. The store must be unlocked first. The secrets
+/// and the params of their new key are stored together, so an interrupted change leaves
+/// either the old key or the new one in place, never secrets the params cannot open.
+pub fn set_passphrase(passphrase: Option<&str>) -> Result<(), UmbreonError> {
+    let secrets = load_all(&current_key()?);
+    let (key, params) = match passphrase.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(passphrase) => {
+            let salt = sealing::new_salt();
+            let key = MasterKey::from_passphrase(passphrase, &salt)?;
+            let params = KeyParams {
+                source: KeySource::Passphrase,
+                check: Some(key.check_value()?),
+                salt: Some(salt),
+            };
+            (key, params)
+        }
+        None => (device_key()?, KeyParams::default()),
+    };
+    let sealed = secrets
+        .iter()
+        .map(|(name, value)| Ok((name.clone(), sealing::seal(&key, name, value)?)))
+        .collect::<Result<Vec<_>, UmbreonError>>()?;
+    let params = serde_json::to_string(&params)
+        .map_err(|err| UmbreonError::storage(format!("encode key params failed: {err}")))?;
+    storage::reseal_secrets(&sealed, &params)?;
+    *session() = Some(key);
+    Ok(())
+}
+
+/// `text` with every stored secret redacted, for anything that ends up in logs or on
+/// screen. Does nothing while the store is locked, when no secret can leak from it anyway.
+pub fn scrub(text: &str) -> String {
+    let Ok(key) = current_key() else {
+        return text.to_string();
+    };
+    load_all(&key)
+        .iter()
+        .fold(text.to_string(), |text, (_, value)| {
+            sealing::scrub(&text, value)
+        })
+}
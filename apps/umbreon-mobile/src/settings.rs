@@ -1,8 +1,30 @@
+use crate::backup::{self, ImportMode};
+use crate::components::UsagePanel;
+use crate::config;
+use crate::embeddings::{self, DUPLICATE_THRESHOLD, EmbeddingSettings};
+use crate::feed_marks;
 use crate::general_ai_client;
-use crate::state::{ThemeMode, ToastKind, ToastMessage, use_app_context};
+use crate::live;
+use crate::live_probe::{self, ProbePreferences};
+use crate::memory_sync;
+use crate::secrets::{self, KeySource};
+use crate::state::{ThemeMode, ToastKind, ToastMessage, UI_LOCALE, use_app_context};
 use crate::storage;
 use crate::timeline;
 use dioxus::prelude::*;
+use umbreon_core::UmbreonError;
+use umbreon_core::danmaku_layout::OverlaySettings;
+use umbreon_core::player::LowLatency;
+use umbreon_core::probe::QualityPreference;
+
+fn area_label(area: f64) -> &'static str {
+    match (area * 4.0).round() as u32 {
+        1 => "1/4 屏",
+        2 => "半屏",
+        3 => "3/4 屏",
+        _ => "全屏",
+    }
+}
 
 #[allow(non_snake_case)]
 pub fn SettingsPane() -> Element {
@@ -13,18 +35,82 @@ pub fn SettingsPane() -> Element {
     let mut llm_api_key = ctx.llm_api_key;
     let mut llm_model = ctx.llm_model;
     let llm_models = ctx.llm_models;
-    let settings_status = ctx.settings_status;
+    let mut llm_embedding_model = ctx.llm_embedding_model;
+    let mut settings_status = ctx.settings_status;
     let mut toast = ctx.toast;
     let mut theme = ctx.theme;
     let mode = *theme.read();
     let feed_items = ctx.feed_items;
     let feed_syncing = ctx.feed_syncing;
+    let sync_ctx = ctx.clone();
+    let mut config_url = ctx.config_url;
+    let mut memory_policies = ctx.memory_policies;
+    let feed_marks = ctx.feed_marks;
+    let mut is_fetching_config = use_signal(|| false);
+    let fetching_config = *is_fetching_config.read();
+    let policy_count = memory_policies.read().rules.len();
     let can_fetch_models =
         !llm_endpoint.read().trim().is_empty() && !llm_api_key.read().trim().is_empty();
     let can_test = can_fetch_models && !llm_model.read().trim().is_empty();
     let has_models = !llm_models.read().is_empty();
     let mut is_fetching_models = use_signal(|| false);
     let mut is_testing_model = use_signal(|| false);
+    let mut is_reindexing = use_signal(|| false);
+    let reindexing = *is_reindexing.read();
+    let mut passphrase = use_signal(String::new);
+    let mut key_source = use_signal(secrets::key_source);
+    let mut secrets_locked = use_signal(secrets::is_locked);
+    let source = *key_source.read();
+    let locked = *secrets_locked.read();
+    let encryption_supported = storage::database_encryption_supported();
+    let mut database_encrypted = use_signal(storage::database_encrypted);
+    let mut is_migrating_database = use_signal(|| false);
+    let db_encrypted = *database_encrypted.read();
+    let migrating_database = *is_migrating_database.read();
+    let mut backup_files = use_signal(storage::list_backup_files);
+    let mut import_path = use_signal(|| {
+        storage::list_backup_files()
+            .first()
+            .cloned()
+            .unwrap_or_default()
+    });
+    let mut import_mode = use_signal(|| ImportMode::Merge);
+    let backup_ctx = ctx.clone();
+    let mut opml_path = use_signal(String::new);
+    let mut imported_feeds = use_signal(|| None::<String>);
+    let mut live_streams = ctx.live_streams;
+    let mut playlist_url = use_signal(String::new);
+    let mut live_playlists = use_signal(live::imported_playlists);
+    let mut is_importing_playlist = use_signal(|| false);
+    let importing_playlist = *is_importing_playlist.read();
+    let live_health = ctx.live_health;
+    let mut probe_preferences = use_signal(live_probe::preferences);
+    let max_bandwidth_mbps = probe_preferences
+        .read()
+        .max_bandwidth
+        .map(|bits| format!("{}", bits as f64 / 1_000_000.0))
+        .unwrap_or_default();
+    let mut low_latency = ctx.low_latency;
+    let mut update_low_latency = move |enabled: bool, target_latency: f64| {
+        storage::store_live_low_latency(enabled, target_latency);
+        *low_latency.write() = LowLatency {
+            enabled,
+            target_latency,
+        };
+    };
+    let mut danmaku_overlay = ctx.danmaku_overlay;
+    let mut update_danmaku_overlay = move |overlay: OverlaySettings| {
+        let overlay = overlay.clamped();
+        storage::store_danmaku_overlay(&overlay);
+        *danmaku_overlay.write() = overlay;
+    };
+    let overlay = *danmaku_overlay.read();
+    let mut update_probe_preferences = move |preferences: ProbePreferences| {
+        *probe_preferences.write() = preferences;
+        live_probe::store_preferences(preferences);
+        let channels = live_streams.read().clone();
+        spawn(live_probe::probe_all(channels, live_health));
+    };
     let syncing_feeds = *feed_syncing.read();
     let fetching_models = *is_fetching_models.read();
     let testing_model = *is_testing_model.read();
@@ -60,15 +146,7 @@ pub fn SettingsPane() -> Element {
                             let memory_url = memory_server_url.read().trim().to_string();
                             storage::store_feed_server_url(&url);
                             storage::store_memory_server_url(&memory_url);
-                            timeline::trigger_feed_sync(
-                                url,
-                                feed_items.clone(),
-                                settings_status.clone(),
-                                feed_syncing.clone(),
-                                llm_endpoint.clone(),
-                                llm_api_key.clone(),
-                                llm_model.clone(),
-                            );
+                            timeline::trigger_feed_sync(url, &sync_ctx);
                         },
                         span { class: "material-icons", "sync" }
                     }
@@ -88,6 +166,104 @@ pub fn SettingsPane() -> Element {
                     }
                 }
             }
+            div { class: "settings-field",
+                label { class: "settings-label", "远程配置" }
+                div { class: "settings-row",
+                    input {
+                        class: "settings-input",
+                        r#type: "url",
+                        placeholder: "https://gist.githubusercontent.com/.../umbreon_app_settings.toml",
+                        value: "{config_url.read()}",
+                        oninput: move |evt| {
+                            let value = evt.value();
+                            *config_url.write() = value.clone();
+                            storage::store_config_url(&value);
+                        }
+                    }
+                    button {
+                        class: if fetching_config {
+                            "settings-sync settings-sync-icon is-loading"
+                        } else {
+                            "settings-sync settings-sync-icon"
+                        },
+                        disabled: fetching_config,
+                        onclick: move |_| {
+                            let url = config_url.read().trim().to_string();
+                            if url.is_empty() {
+                                *toast.write() = Some(ToastMessage {
+                                    kind: ToastKind::Error,
+                                    text: "请先填写远程配置地址。".to_string(),
+                                    action: None,
+                                });
+                                return;
+                            }
+                            *is_fetching_config.write() = true;
+                            spawn(async move {
+                                match config::fetch_config(&url).await {
+                                    Ok(config) => {
+                                        let rules = config.memory_policies.rules.len();
+                                        *memory_policies.write() = config.memory_policies;
+                                        let channels = config.live_streams.channels.len()
+                                            + config.live_streams.playlists.len();
+                                        live::trigger_refresh(config.live_streams, live_streams);
+                                        *toast.write() = Some(ToastMessage {
+                                            kind: ToastKind::Success,
+                                            text: format!("远程配置已更新，{rules} 条记忆清理规则，{channels} 个直播频道/列表。"),
+                                            action: None,
+                                        });
+                                    }
+                                    Err(err) => {
+                                        *toast.write() = Some(ToastMessage::from_error("远程配置拉取失败", &err, None));
+                                    }
+                                }
+                                *is_fetching_config.write() = false;
+                            });
+                        },
+                        span { class: "material-icons", "sync" }
+                    }
+                }
+                for (index, rule) in memory_policies.read().rules.iter().enumerate() {
+                    p { key: "{index}", class: "settings-hint", "{rule.describe()}" }
+                }
+                div { class: "settings-row settings-row-spread",
+                    p { class: "settings-hint", "记忆清理规则：{policy_count} 条" }
+                    button {
+                        class: "settings-action",
+                        disabled: policy_count == 0,
+                        onclick: move |_| {
+                            let policies = memory_policies.read().clone();
+                            let purged = memory_sync::apply_purge_policies(&policies);
+                            feed_marks::forget_memories(&purged, feed_marks);
+                            let local = purged.len();
+                            let memory_url = memory_server_url.read().trim().to_string();
+                            spawn(async move {
+                                let remote = if memory_url.is_empty() {
+                                    Ok(0)
+                                } else {
+                                    memory_sync::purge_remote(memory_url, &policies).await
+                                };
+                                *toast.write() = Some(match remote {
+                                    Ok(remote) => ToastMessage {
+                                        kind: ToastKind::Success,
+                                        text: format!("已清理本地 {local} 条、远端 {remote} 条记忆。"),
+                                        action: None,
+                                    },
+                                    Err(err) => ToastMessage {
+                                        kind: ToastKind::Error,
+                                        text: format!(
+                                            "已清理本地 {local} 条，远端清理失败：{}",
+                                            err.user_message(UI_LOCALE)
+                                        ),
+                                        action: None,
+                                    },
+                                });
+                            });
+                        },
+                        span { class: "material-icons", "cleaning_services" }
+                        span { "按策略清理" }
+                    }
+                }
+            }
             div { class: "settings-field",
                 label { class: "settings-label", "通用模型" }
                 label { class: "settings-label", "LLM Endpoint" }
@@ -106,12 +282,15 @@ pub fn SettingsPane() -> Element {
                 input {
                     class: "settings-input",
                     r#type: "password",
-                    placeholder: "API Key",
+                    placeholder: if locked { "已锁定，请先输入口令" } else { "API Key" },
+                    disabled: locked,
                     value: "{llm_api_key.read()}",
                     oninput: move |evt| {
                         let value = evt.value();
                         *llm_api_key.write() = value.clone();
-                        storage::store_llm_api_key(&value);
+                        if let Err(err) = secrets::store(secrets::LLM_API_KEY, &value) {
+                            *settings_status.write() = Some(err.user_message(UI_LOCALE));
+                        }
                     }
                 }
                 label { class: "settings-label", "模型" }
@@ -148,6 +327,7 @@ pub fn SettingsPane() -> Element {
                                 *toast.write() = Some(ToastMessage {
                                     kind: ToastKind::Error,
                                     text: "请先填写 Endpoint 和 API Key。".to_string(),
+                                    action: None,
                                 });
                                 return;
                             }
@@ -165,6 +345,7 @@ pub fn SettingsPane() -> Element {
                                             *toast.write() = Some(ToastMessage {
                                                 kind: ToastKind::Error,
                                                 text: "未获取到模型列表。".to_string(),
+                                                action: None,
                                             });
                                             *settings_status.write() = Some("模型列表为空。".to_string());
                                         } else {
@@ -180,15 +361,13 @@ pub fn SettingsPane() -> Element {
                                             *toast.write() = Some(ToastMessage {
                                                 kind: ToastKind::Success,
                                                 text: "已更新模型列表。".to_string(),
+                                                action: None,
                                             });
                                         }
                                     }
                                     Err(err) => {
                                         *settings_status.write() = Some("模型拉取失败。".to_string());
-                                        *toast.write() = Some(ToastMessage {
-                                            kind: ToastKind::Error,
-                                            text: format!("模型拉取失败：{err}"),
-                                        });
+                                        *toast.write() = Some(ToastMessage::from_error("模型拉取失败", &err, None));
                                     }
                                 }
                                 *is_fetching_models.write() = false;
@@ -217,6 +396,7 @@ pub fn SettingsPane() -> Element {
                                 *toast.write() = Some(ToastMessage {
                                     kind: ToastKind::Error,
                                     text: "请先填写 Endpoint、API Key 并选择模型。".to_string(),
+                                    action: None,
                                 });
                                 return;
                             }
@@ -239,14 +419,12 @@ pub fn SettingsPane() -> Element {
                                         *toast.write() = Some(ToastMessage {
                                             kind: ToastKind::Success,
                                             text: "测试成功。".to_string(),
+                                            action: None,
                                         });
                                     }
                                     Err(err) => {
                                         *settings_status.write() = Some("模型测试失败。".to_string());
-                                        *toast.write() = Some(ToastMessage {
-                                            kind: ToastKind::Error,
-                                            text: format!("测试失败：{err}"),
-                                        });
+                                        *toast.write() = Some(ToastMessage::from_error("测试失败", &err, None));
                                     }
                                 }
                                 *is_testing_model.write() = false;
@@ -256,7 +434,550 @@ pub fn SettingsPane() -> Element {
                         span { if testing_model { "测试中" } else { "测试" } }
                     }
                 }
+                label { class: "settings-label", "Embedding 模型" }
+                div { class: "settings-row",
+                    input {
+                        class: "settings-input",
+                        placeholder: embeddings::DEFAULT_EMBEDDING_MODEL,
+                        value: "{llm_embedding_model.read()}",
+                        oninput: move |evt| {
+                            let value = evt.value();
+                            *llm_embedding_model.write() = value.clone();
+                            storage::store_llm_embedding_model(&value);
+                        }
+                    }
+                    button {
+                        class: if reindexing {
+                            "settings-action settings-action-icon is-loading"
+                        } else {
+                            "settings-action settings-action-icon"
+                        },
+                        disabled: !can_fetch_models || reindexing,
+                        onclick: move |_| {
+                            let Some(settings) = EmbeddingSettings::load() else {
+                                *toast.write() = Some(ToastMessage {
+                                    kind: ToastKind::Error,
+                                    text: "请先填写 Endpoint 和 API Key。".to_string(),
+                                    action: None,
+                                });
+                                return;
+                            };
+                            *is_reindexing.write() = true;
+                            let mut settings_status = settings_status;
+                            spawn(async move {
+                                *settings_status.write() = Some("正在更新语义索引...".to_string());
+                                let items = feed_items.read().clone();
+                                let memories = storage::load_local_memories();
+                                match embeddings::reindex(&settings, &items, &memories).await {
+                                    Ok(stats) => {
+                                        let duplicates =
+                                            embeddings::near_duplicates(&items, DUPLICATE_THRESHOLD).len();
+                                        let text = format!(
+                                            "语义索引已更新：新增 {} 条，未变 {} 条，移除 {} 条，近似重复 {} 组。",
+                                            stats.indexed, stats.unchanged, stats.removed, duplicates
+                                        );
+                                        *settings_status.write() = Some(text.clone());
+                                        *toast.write() = Some(ToastMessage {
+                                            kind: ToastKind::Success,
+                                            text,
+                                            action: None,
+                                        });
+                                    }
+                                    Err(err) => {
+                                        *settings_status.write() = Some("语义索引更新失败。".to_string());
+                                        *toast.write() = Some(ToastMessage::from_error("索引失败", &err, None));
+                                    }
+                                }
+                                *is_reindexing.write() = false;
+                            });
+                        },
+                        span { class: "material-icons", "hub" }
+                    }
+                }
+                p { class: "settings-hint", "用于本地语义搜索与跨源去重，同步 Feed 后自动增量索引。" }
+            }
+            div { class: "settings-field",
+                label { class: "settings-label", "密钥保护" }
+                p { class: "settings-hint",
+                    if locked {
+                        "API Key 已用口令加密，输入口令解锁后才能使用。"
+                    } else {
+                        "API Key 加密保存，当前使用{source.label()}。"
+                    }
+                }
+                div { class: "settings-row",
+                    input {
+                        class: "settings-input",
+                        r#type: "password",
+                        placeholder: if locked { "口令" } else { "新口令" },
+                        value: "{passphrase.read()}",
+                        oninput: move |evt| *passphrase.write() = evt.value()
+                    }
+                    button {
+                        class: "settings-action",
+                        disabled: passphrase.read().trim().is_empty(),
+                        onclick: move |_| {
+                            let value = passphrase.read().clone();
+                            let result = if locked {
+                                secrets::unlock(&value)
+                            } else {
+                                secrets::set_passphrase(Some(&value))
+                            };
+                            *toast.write() = Some(match result {
+                                Ok(()) => {
+                                    passphrase.write().clear();
+                                    *secrets_locked.write() = false;
+                                    *key_source.write() = KeySource::Passphrase;
+                                    if locked {
+                                        *llm_api_key.write() = secrets::load(secrets::LLM_API_KEY)
+                                            .unwrap_or_default();
+                                    }
+                                    ToastMessage {
+                                        kind: ToastKind::Success,
+                                        text: if locked {
+                                            "已解锁。".to_string()
+                                        } else {
+                                            "口令已设置，之后每次启动需输入。".to_string()
+                                        },
+                                        action: None,
+                                    }
+                                }
+                                Err(UmbreonError::Locked(_)) if locked => ToastMessage {
+                                    kind: ToastKind::Error,
+                                    text: "口令不正确。".to_string(),
+                                    action: None,
+                                },
+                                Err(err) => ToastMessage::from_error(
+                                    if locked { "解锁失败" } else { "设置口令失败" },
+                                    &err,
+                                    None,
+                                ),
+                            });
+                        },
+                        span { class: "material-icons", if locked { "lock_open" } else { "lock" } }
+                        span { if locked { "解锁" } else { "设置口令" } }
+                    }
+                }
+                if source == KeySource::Passphrase && !locked {
+                    button {
+                        class: "settings-action",
+                        onclick: move |_| {
+                            *toast.write() = Some(match secrets::set_passphrase(None) {
+                                Ok(()) => {
+                                    *key_source.write() = KeySource::Device;
+                                    ToastMessage {
+                                        kind: ToastKind::Success,
+                                        text: "已改用设备密钥，启动时无需口令。".to_string(),
+                                        action: None,
+                                    }
+                                }
+                                Err(err) => ToastMessage::from_error("切换失败", &err, None),
+                            });
+                        },
+                        span { class: "material-icons", "phonelink_lock" }
+                        span { "改用设备密钥" }
+                    }
+                }
+            }
+            div { class: "settings-field",
+                label { class: "settings-label", "数据库加密" }
+                p { class: "settings-hint",
+                    if !encryption_supported {
+                        "当前版本未包含 SQLCipher，记忆与聊天记录以明文保存在本地。"
+                    } else if db_encrypted {
+                        "记忆、聊天与阅读记录已加密保存，密钥由设备密钥保护。"
+                    } else {
+                        "加密后记忆、聊天与阅读记录以 SQLCipher 加密文件保存。"
+                    }
+                }
+                if encryption_supported {
+                    div { class: "settings-actions",
+                        button {
+                            class: if migrating_database { "settings-action is-loading" } else { "settings-action" },
+                            disabled: migrating_database,
+                            onclick: move |_| {
+                                *is_migrating_database.write() = true;
+                                let encrypt = !*database_encrypted.read();
+                                // Dirty scopes render before tasks are polled, so the busy
+                                // state shows before the migration blocks.
+                                spawn(async move {
+                                    let result = if encrypt {
+                                        storage::encrypt_database()
+                                    } else {
+                                        storage::decrypt_database()
+                                    };
+                                    *toast.write() = Some(match result {
+                                        Ok(()) => {
+                                            *database_encrypted.write() = encrypt;
+                                            ToastMessage {
+                                                kind: ToastKind::Success,
+                                                text: if encrypt {
+                                                    "数据库已加密。".to_string()
+                                                } else {
+                                                    "数据库已解密。".to_string()
+                                                },
+                                                action: None,
+                                            }
+                                        }
+                                        Err(err) => ToastMessage::from_error("数据库迁移失败", &err, None),
+                                    });
+                                    *is_migrating_database.write() = false;
+                                });
+                            },
+                            span { class: "material-icons", if db_encrypted { "no_encryption" } else { "enhanced_encryption" } }
+                            span { if db_encrypted { "取消加密" } else { "加密数据库" } }
+                        }
+                        if db_encrypted {
+                            button {
+                                class: "settings-action",
+                                disabled: migrating_database,
+                                onclick: move |_| {
+                                    *is_migrating_database.write() = true;
+                                    spawn(async move {
+                                        *toast.write() = Some(match storage::rekey_database() {
+                                            Ok(()) => ToastMessage {
+                                                kind: ToastKind::Success,
+                                                text: "数据库密钥已更换。".to_string(),
+                                                action: None,
+                                            },
+                                            Err(err) => ToastMessage::from_error("更换密钥失败", &err, None),
+                                        });
+                                        *is_migrating_database.write() = false;
+                                    });
+                                },
+                                span { class: "material-icons", "key" }
+                                span { "更换密钥" }
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "settings-field",
+                label { class: "settings-label", "备份与恢复" }
+                p { class: "settings-hint", "备份包含设置、Feed 缓存、铸魂、记忆与聊天记录，不含 API Key。" }
+                div { class: "settings-actions",
+                    button {
+                        class: "settings-action",
+                        onclick: move |_| {
+                            *toast.write() = Some(match backup::export() {
+                                Ok(path) => {
+                                    *backup_files.write() = storage::list_backup_files();
+                                    *import_path.write() = path.clone();
+                                    ToastMessage {
+                                        kind: ToastKind::Success,
+                                        text: format!("已导出到 {path}"),
+                                        action: None,
+                                    }
+                                }
+                                Err(err) => ToastMessage::from_error("导出失败", &err, None),
+                            });
+                        },
+                        span { class: "material-icons", "file_download" }
+                        span { "导出备份" }
+                    }
+                }
+                if !backup_files.read().is_empty() {
+                    select {
+                        class: "settings-select",
+                        value: "{import_path.read()}",
+                        onchange: move |evt| *import_path.write() = evt.value(),
+                        for path in backup_files.read().iter() {
+                            option { value: "{path}", "{path}" }
+                        }
+                    }
+                }
+                input {
+                    class: "settings-input",
+                    placeholder: "备份文件路径",
+                    value: "{import_path.read()}",
+                    oninput: move |evt| *import_path.write() = evt.value()
+                }
+                div { class: "settings-row",
+                    select {
+                        class: "settings-select",
+                        value: "{import_mode.read().as_str()}",
+                        onchange: move |evt| *import_mode.write() = ImportMode::from_value(&evt.value()),
+                        for mode in ImportMode::ALL {
+                            option { value: "{mode.as_str()}", "{mode.label()}" }
+                        }
+                    }
+                    button {
+                        class: "settings-action",
+                        disabled: import_path.read().trim().is_empty(),
+                        onclick: move |_| {
+                            let path = import_path.read().trim().to_string();
+                            let mode = *import_mode.read();
+                            *toast.write() = Some(match backup::import_file(&path, mode) {
+                                Ok(stats) => {
+                                    backup::reload_context(&backup_ctx);
+                                    ToastMessage {
+                                        kind: ToastKind::Success,
+                                        text: format!("已导入：{}", stats.describe()),
+                                        action: None,
+                                    }
+                                }
+                                Err(err) => ToastMessage::from_error("导入失败", &err, None),
+                            });
+                        },
+                        span { class: "material-icons", "file_upload" }
+                        span { "导入" }
+                    }
+                }
+            }
+            div { class: "settings-field",
+                label { class: "settings-label", "直播播放列表" }
+                p { class: "settings-hint", "导入 M3U 播放列表，频道按 group-title 分组显示在直播页。" }
+                div { class: "settings-row",
+                    input {
+                        class: "settings-input",
+                        placeholder: "https://example.com/iptv.m3u",
+                        value: "{playlist_url.read()}",
+                        oninput: move |evt| *playlist_url.write() = evt.value()
+                    }
+                    button {
+                        class: "settings-action",
+                        disabled: importing_playlist || playlist_url.read().trim().is_empty(),
+                        onclick: move |_| {
+                            let url = playlist_url.read().trim().to_string();
+                            *is_importing_playlist.write() = true;
+                            spawn(async move {
+                                *toast.write() = Some(match live::import_playlist(&url).await {
+                                    Ok(added) => {
+                                        *live_streams.write() = storage::load_live_channels();
+                                        *live_playlists.write() = live::imported_playlists();
+                                        *playlist_url.write() = String::new();
+                                        ToastMessage {
+                                            kind: ToastKind::Success,
+                                            text: format!("已导入 {added} 个直播频道。"),
+                                            action: None,
+                                        }
+                                    }
+                                    Err(err) => ToastMessage::from_error("导入播放列表失败", &err, None),
+                                });
+                                *is_importing_playlist.write() = false;
+                            });
+                        },
+                        span { class: "material-icons", "playlist_add" }
+                        span { "导入" }
+                    }
+                }
+                for url in live_playlists.read().clone() {
+                    div { key: "{url}", class: "settings-row settings-row-spread",
+                        p { class: "settings-hint", "{url}" }
+                        button {
+                            class: "settings-action",
+                            onclick: move |_| {
+                                if let Err(err) = live::remove_playlist(&url) {
+                                    *toast.write() = Some(ToastMessage::from_error("移除播放列表失败", &err, None));
+                                }
+                                *live_streams.write() = storage::load_live_channels();
+                                *live_playlists.write() = live::imported_playlists();
+                            },
+                            span { class: "material-icons", "delete" }
+                        }
+                    }
+                }
+                label { class: "settings-label", "探测画质" }
+                p { class: "settings-hint", "后台检测直播频道时按此选择码率，并标记在线/离线。带宽上限留空表示不限制。" }
+                div { class: "settings-row",
+                    select {
+                        class: "settings-select",
+                        value: "{probe_preferences.read().quality.key()}",
+                        onchange: move |evt| {
+                            let mut preferences = *probe_preferences.read();
+                            preferences.quality = QualityPreference::parse(&evt.value()).unwrap_or_default();
+                            update_probe_preferences(preferences);
+                        },
+                        for preference in QualityPreference::CHOICES {
+                            option { value: "{preference.key()}", "{preference.label()}" }
+                        }
+                    }
+                    input {
+                        class: "settings-input",
+                        r#type: "number",
+                        min: "0",
+                        step: "0.5",
+                        placeholder: "带宽上限 (Mbps)",
+                        value: "{max_bandwidth_mbps}",
+                        onchange: move |evt| {
+                            let mut preferences = *probe_preferences.read();
+                            preferences.max_bandwidth = evt
+                                .value()
+                                .trim()
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|mbps| *mbps > 0.0)
+                                .map(|mbps| (mbps * 1_000_000.0) as u64);
+                            update_probe_preferences(preferences);
+                        }
+                    }
+                }
+                label { class: "settings-label", "低延迟模式" }
+                p { class: "settings-hint", "直播时贴近直播点播放，落后超过目标延迟时自动加速追赶。也可在播放器中切换。" }
+                div { class: "settings-row",
+                    label { class: "theme-switch",
+                        input {
+                            r#type: "checkbox",
+                            checked: low_latency.read().enabled,
+                            onchange: move |evt| {
+                                let target_latency = low_latency.read().target_latency;
+                                update_low_latency(evt.checked(), target_latency);
+                            }
+                        }
+                        span { class: "theme-slider" }
+                    }
+                    input {
+                        class: "settings-input",
+                        r#type: "number",
+                        min: "1",
+                        step: "0.5",
+                        placeholder: "目标延迟（秒）",
+                        value: "{low_latency.read().target_latency}",
+                        onchange: move |evt| {
+                            if let Ok(target_latency) = evt.value().trim().parse::<f64>()
+                                && target_latency.is_finite()
+                            {
+                                let enabled = low_latency.read().enabled;
+                                update_low_latency(enabled, target_latency.max(1.0));
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "settings-field",
+                label { class: "settings-label", "弹幕" }
+                p { class: "settings-hint", "播放器上方的弹幕层。密度低于 100% 时均匀抽掉一部分弹幕；轨道排满时新弹幕也会被跳过。" }
+                div { class: "settings-row",
+                    label { class: "theme-switch",
+                        input {
+                            r#type: "checkbox",
+                            checked: overlay.enabled,
+                            onchange: move |evt| {
+                                update_danmaku_overlay(OverlaySettings {
+                                    enabled: evt.checked(),
+                                    ..overlay
+                                });
+                            }
+                        }
+                        span { class: "theme-slider" }
+                    }
+                    select {
+                        class: "settings-select",
+                        value: "{overlay.area}",
+                        onchange: move |evt| {
+                            if let Ok(area) = evt.value().parse::<f64>() {
+                                update_danmaku_overlay(OverlaySettings { area, ..overlay });
+                            }
+                        },
+                        for area in OverlaySettings::AREAS {
+                            option { value: "{area}", selected: area == overlay.area, "显示区域 {area_label(area)}" }
+                        }
+                    }
+                }
+                div { class: "settings-row",
+                    span { class: "settings-hint", "不透明度 {(overlay.opacity * 100.0).round()}%" }
+                    input {
+                        r#type: "range",
+                        min: "0.1",
+                        max: "1",
+                        step: "0.05",
+                        value: "{overlay.opacity}",
+                        onchange: move |evt| {
+                            if let Ok(opacity) = evt.value().parse::<f64>() {
+                                update_danmaku_overlay(OverlaySettings { opacity, ..overlay });
+                            }
+                        }
+                    }
+                }
+                div { class: "settings-row",
+                    span { class: "settings-hint", "字号 {(overlay.font_scale * 100.0).round()}%" }
+                    input {
+                        r#type: "range",
+                        min: "0.5",
+                        max: "2",
+                        step: "0.1",
+                        value: "{overlay.font_scale}",
+                        onchange: move |evt| {
+                            if let Ok(font_scale) = evt.value().parse::<f64>() {
+                                update_danmaku_overlay(OverlaySettings { font_scale, ..overlay });
+                            }
+                        }
+                    }
+                }
+                div { class: "settings-row",
+                    span { class: "settings-hint", "密度 {(overlay.density * 100.0).round()}%" }
+                    input {
+                        r#type: "range",
+                        min: "0.1",
+                        max: "1",
+                        step: "0.1",
+                        value: "{overlay.density}",
+                        onchange: move |evt| {
+                            if let Ok(density) = evt.value().parse::<f64>() {
+                                update_danmaku_overlay(OverlaySettings { density, ..overlay });
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "settings-field",
+                label { class: "settings-label", "订阅源 OPML" }
+                p { class: "settings-hint", "导出远程配置中的 [feeds] 订阅源，或把 OPML 合并为可粘贴到 gist 的 [feeds] 配置。" }
+                div { class: "settings-actions",
+                    button {
+                        class: "settings-action",
+                        onclick: move |_| {
+                            *toast.write() = Some(match config::export_opml() {
+                                Ok(path) => ToastMessage {
+                                    kind: ToastKind::Success,
+                                    text: format!("已导出到 {path}"),
+                                    action: None,
+                                },
+                                Err(err) => ToastMessage::from_error("导出 OPML 失败", &err, None),
+                            });
+                        },
+                        span { class: "material-icons", "rss_feed" }
+                        span { "导出 OPML" }
+                    }
+                }
+                div { class: "settings-row",
+                    input {
+                        class: "settings-input",
+                        placeholder: "OPML 文件路径",
+                        value: "{opml_path.read()}",
+                        oninput: move |evt| *opml_path.write() = evt.value()
+                    }
+                    button {
+                        class: "settings-action",
+                        disabled: opml_path.read().trim().is_empty(),
+                        onclick: move |_| {
+                            let path = opml_path.read().trim().to_string();
+                            *toast.write() = Some(match config::import_opml(&path) {
+                                Ok((text, added)) => {
+                                    *imported_feeds.write() = Some(text);
+                                    ToastMessage {
+                                        kind: ToastKind::Success,
+                                        text: format!("新增 {added} 个订阅源，请将下方配置粘贴到 gist。"),
+                                        action: None,
+                                    }
+                                }
+                                Err(err) => ToastMessage::from_error("导入 OPML 失败", &err, None),
+                            });
+                        },
+                        span { class: "material-icons", "playlist_add" }
+                        span { "导入" }
+                    }
+                }
+                if let Some(text) = imported_feeds.read().clone() {
+                    textarea {
+                        class: "settings-input",
+                        readonly: true,
+                        rows: "8",
+                        value: "{text}",
+                    }
+                }
             }
+            UsagePanel {}
             div { class: "settings-field",
                 div { class: "settings-row settings-row-spread",
                     label { class: "settings-label", "Theme" }
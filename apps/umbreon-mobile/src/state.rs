@@ -1,6 +1,21 @@
+use crate::config::MemoryPolicies;
+use crate::danmaku_file::AttachmentState;
+use crate::live_probe::StreamHealth;
+use crate::usage::PriceTable;
 use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use umbreon_core::danmaku::DanmakuEntry;
+use umbreon_core::danmaku_layout::OverlaySettings;
+use umbreon_core::danmaku_live::ConnectionState;
+use umbreon_core::player::{LowLatency, PlayerState};
+use umbreon_core::{Locale, Recovery, UmbreonError};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Language for user-facing error messages.
+pub const UI_LOCALE: Locale = Locale::Zh;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ThemeMode {
     Light,
     Dark,
@@ -50,7 +65,8 @@ impl NavSection {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FeedSourceKind {
     Atom,
     RssHub,
@@ -63,13 +79,48 @@ pub enum ToastKind {
     Error,
 }
 
+/// A follow-up offered as a button next to the toast text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToastAction {
+    OpenSettings,
+    RetryFeedSync,
+    RetryMemorySync,
+}
+
+impl ToastAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ToastAction::OpenSettings => "去设置",
+            ToastAction::RetryFeedSync | ToastAction::RetryMemorySync => "重试",
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ToastMessage {
     pub kind: ToastKind,
     pub text: String,
+    pub action: Option<ToastAction>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl ToastMessage {
+    /// An error toast reading "`context`：<localized message>". Offers `retry` when the
+    /// failure is transient and a shortcut to settings when configuration is at fault.
+    pub fn from_error(context: &str, err: &UmbreonError, retry: Option<ToastAction>) -> Self {
+        let action = match err.recovery() {
+            Recovery::OpenSettings => Some(ToastAction::OpenSettings),
+            Recovery::Retry => retry,
+            Recovery::None => None,
+        };
+        ToastMessage {
+            kind: ToastKind::Error,
+            text: format!("{context}：{}", err.user_message(UI_LOCALE)),
+            action,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FeedItem {
     pub id: String,
     pub title: String,
@@ -85,6 +136,382 @@ pub struct FeedItem {
     pub tags: Vec<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemorySyncState {
+    Pending,
+    Synced,
+    Failed,
+}
+
+impl MemorySyncState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemorySyncState::Pending => "pending",
+            MemorySyncState::Synced => "synced",
+            MemorySyncState::Failed => "failed",
+        }
+    }
+
+    pub fn from_value(value: &str) -> MemorySyncState {
+        match value {
+            "synced" => MemorySyncState::Synced,
+            "failed" => MemorySyncState::Failed,
+            _ => MemorySyncState::Pending,
+        }
+    }
+}
+
+/// What a memory is about; drives purge policies and filtering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryCategory {
+    Preference,
+    WatchHistory,
+    FeedInteraction,
+    Mark,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 4] = [
+        MemoryCategory::Preference,
+        MemoryCategory::WatchHistory,
+        MemoryCategory::FeedInteraction,
+        MemoryCategory::Mark,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryCategory::Preference => "preference",
+            MemoryCategory::WatchHistory => "watch_history",
+            MemoryCategory::FeedInteraction => "feed_interaction",
+            MemoryCategory::Mark => "mark",
+        }
+    }
+
+    pub fn from_value(value: &str) -> MemoryCategory {
+        Self::parse(value).unwrap_or(MemoryCategory::Preference)
+    }
+
+    pub fn parse(value: &str) -> Option<MemoryCategory> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.as_str() == value.trim())
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MemoryCategory::Preference => "偏好",
+            MemoryCategory::WatchHistory => "观看历史",
+            MemoryCategory::FeedInteraction => "Feed 互动",
+            MemoryCategory::Mark => "铸魂",
+        }
+    }
+}
+
+/// A memory kept on device. `remote_id` is set once the outbox has delivered it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalMemory {
+    pub id: String,
+    pub remote_id: Option<String>,
+    pub content: String,
+    pub category: MemoryCategory,
+    pub tags: Vec<String>,
+    pub source_ref: Option<String>,
+    pub sync_state: MemorySyncState,
+    pub created_ts: i64,
+    pub updated_ts: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxOp {
+    Add,
+    Tag,
+    Update,
+    Delete,
+}
+
+impl OutboxOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxOp::Add => "add",
+            OutboxOp::Tag => "tag",
+            OutboxOp::Update => "update",
+            OutboxOp::Delete => "delete",
+        }
+    }
+
+    pub fn from_value(value: &str) -> OutboxOp {
+        match value {
+            "tag" => OutboxOp::Tag,
+            "update" => OutboxOp::Update,
+            "delete" => OutboxOp::Delete,
+            _ => OutboxOp::Add,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutboxOp::Add => "写入",
+            OutboxOp::Tag => "打标签",
+            OutboxOp::Update => "修改",
+            OutboxOp::Delete => "删除",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Queued,
+    Dead,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Queued => "queued",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+
+    pub fn from_value(value: &str) -> OutboxStatus {
+        match value {
+            "dead" => OutboxStatus::Dead,
+            _ => OutboxStatus::Queued,
+        }
+    }
+}
+
+/// A pending memory server write, replayed in `seq` order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub seq: i64,
+    pub idempotency_key: String,
+    pub op: OutboxOp,
+    pub memory_id: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub next_attempt_ts: i64,
+    pub status: OutboxStatus,
+    pub last_error: Option<String>,
+    pub created_ts: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutboxSummary {
+    pub queued: usize,
+    pub dead: usize,
+    pub syncing: bool,
+    pub last_synced: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EmbeddingKind {
+    FeedItem,
+    Memory,
+}
+
+impl EmbeddingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmbeddingKind::FeedItem => "feed_item",
+            EmbeddingKind::Memory => "memory",
+        }
+    }
+}
+
+/// A cached embedding row. `content_hash` covers the model and the embedded text, so a row
+/// is stale once either changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredEmbedding {
+    pub kind: EmbeddingKind,
+    pub ref_id: String,
+    pub content_hash: String,
+    pub vector: Vec<f32>,
+    pub updated_ts: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkStatus {
+    Pending,
+    Synced,
+    Failed,
+}
+
+impl MarkStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarkStatus::Pending => "pending",
+            MarkStatus::Synced => "synced",
+            MarkStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_value(value: &str) -> MarkStatus {
+        match value {
+            "synced" => MarkStatus::Synced,
+            "pending" => MarkStatus::Pending,
+            _ => MarkStatus::Failed,
+        }
+    }
+}
+
+/// Bookmark (铸魂) state of a feed item. `marked` is what the user asked for; `memory_id`
+/// is the memory server record backing it, if one has been created.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedMark {
+    pub feed_id: String,
+    pub marked: bool,
+    pub memory_id: Option<String>,
+    pub status: MarkStatus,
+    pub error: Option<String>,
+    pub updated_ts: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatMode {
+    Profile,
+    Query,
+    Full,
+}
+
+impl ChatMode {
+    pub const ALL: [ChatMode; 3] = [ChatMode::Profile, ChatMode::Query, ChatMode::Full];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatMode::Profile => "profile",
+            ChatMode::Query => "query",
+            ChatMode::Full => "full",
+        }
+    }
+
+    pub fn from_value(value: &str) -> ChatMode {
+        match value {
+            "query" => ChatMode::Query,
+            "full" => ChatMode::Full,
+            _ => ChatMode::Profile,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChatMode::Profile => "画像",
+            ChatMode::Query => "检索",
+            ChatMode::Full => "完整",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimelineOrder {
+    Latest,
+    Personalized,
+}
+
+impl TimelineOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelineOrder::Latest => "latest",
+            TimelineOrder::Personalized => "personalized",
+        }
+    }
+
+    pub fn from_value(value: &str) -> TimelineOrder {
+        match value {
+            "personalized" => TimelineOrder::Personalized,
+            _ => TimelineOrder::Latest,
+        }
+    }
+
+    pub fn toggle(&self) -> TimelineOrder {
+        match self {
+            TimelineOrder::Latest => TimelineOrder::Personalized,
+            TimelineOrder::Personalized => TimelineOrder::Latest,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatBackend {
+    Memory,
+    Llm,
+}
+
+impl ChatBackend {
+    pub const ALL: [ChatBackend; 2] = [ChatBackend::Memory, ChatBackend::Llm];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatBackend::Memory => "memory",
+            ChatBackend::Llm => "llm",
+        }
+    }
+
+    pub fn from_value(value: &str) -> ChatBackend {
+        match value {
+            "llm" => ChatBackend::Llm,
+            _ => ChatBackend::Memory,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChatBackend::Memory => "记忆",
+            ChatBackend::Llm => "通用模型",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+
+    pub fn from_value(value: &str) -> ChatRole {
+        match value {
+            "assistant" => ChatRole::Assistant,
+            _ => ChatRole::User,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub mode: ChatMode,
+    pub backend: ChatBackend,
+    pub feed_item_id: Option<String>,
+    pub context: Option<String>,
+    pub created_ts: i64,
+    pub updated_ts: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub conversation_id: String,
+    pub role: ChatRole,
+    pub content: String,
+    pub created_ts: i64,
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MediaKind {
@@ -92,12 +519,20 @@ pub enum MediaKind {
     Vod,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A live channel from the remote config or an imported M3U playlist.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LiveStream {
     pub id: String,
     pub title: String,
     pub stream_url: String,
     pub danmaku_endpoint: Option<String>,
+    /// `group-title`; several groups are separated by `;`.
+    pub group: Option<String>,
+    pub logo: Option<String>,
+    /// `config` for channels listed in the remote config, otherwise the playlist URL.
+    pub origin: String,
+    pub favorite: bool,
+    pub last_watched_ts: Option<i64>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -109,31 +544,29 @@ pub struct MediaSession {
     pub danmaku_endpoint: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct MemoryPanelState {
-    pub synced: bool,
-    pub last_synced: Option<String>,
-    pub highlights: Vec<String>,
-}
-
-impl Default for MemoryPanelState {
-    fn default() -> Self {
-        Self {
-            synced: false,
-            last_synced: None,
-            highlights: Vec::new(),
-        }
-    }
-}
-
 #[derive(Clone)]
 pub struct AppContext {
     pub nav: Signal<NavSection>,
     pub theme: Signal<ThemeMode>,
     pub feed_items: Signal<Vec<FeedItem>>,
+    pub feed_marks: Signal<HashMap<String, FeedMark>>,
+    pub timeline_order: Signal<TimelineOrder>,
     pub live_streams: Signal<Vec<LiveStream>>,
+    /// Latest probe result per live channel id.
+    pub live_health: Signal<HashMap<String, StreamHealth>>,
     pub now_playing: Signal<Option<MediaSession>>,
-    pub memory_panel: Signal<MemoryPanelState>,
+    pub player: Signal<PlayerState>,
+    pub low_latency: Signal<LowLatency>,
+    /// Comments for what is playing, drawn by the danmaku overlay.
+    pub danmaku: Signal<Vec<DanmakuEntry>>,
+    pub danmaku_overlay: Signal<OverlaySettings>,
+    /// State of the live danmaku relay for what is playing.
+    pub danmaku_connection: Signal<ConnectionState>,
+    /// Danmaku file attached to the playing video.
+    pub danmaku_file: Signal<AttachmentState>,
+    pub outbox: Signal<OutboxSummary>,
+    pub config_url: Signal<String>,
+    pub memory_policies: Signal<MemoryPolicies>,
     pub feed_server_url: Signal<String>,
     pub memory_server_url: Signal<String>,
     pub feed_syncing: Signal<bool>,
@@ -141,6 +574,10 @@ pub struct AppContext {
     pub llm_api_key: Signal<String>,
     pub llm_model: Signal<String>,
     pub llm_models: Signal<Vec<String>>,
+    pub llm_embedding_model: Signal<String>,
+    pub llm_prices: Signal<PriceTable>,
+    pub llm_monthly_budget: Signal<Option<f64>>,
+    pub active_conversation: Signal<Option<String>>,
     pub settings_status: Signal<Option<String>>,
     pub toast: Signal<Option<ToastMessage>>,
 }
@@ -197,42 +634,3 @@ pub(crate) fn mock_feed_items() -> Vec<FeedItem> {
         },
     ]
 }
-
-pub(crate) fn mock_live_streams() -> Vec<LiveStream> {
-    vec![
-        LiveStream {
-            id: "live-001".into(),
-            title: "伴生体策略例会".into(),
-            stream_url: "https://live.cdn.example.com/channel/umbreon/index.m3u8".into(),
-            danmaku_endpoint: Some("wss://danmaku.example.com/umbreon".into()),
-        },
-        LiveStream {
-            id: "live-002".into(),
-            title: "夜间低延迟测试".into(),
-            stream_url: "https://edge.example.net/night.m3u8".into(),
-            danmaku_endpoint: None,
-        },
-    ]
-}
-
-pub(crate) fn mock_initial_session() -> Option<MediaSession> {
-    Some(MediaSession {
-        title: "Umbreon intro stream".into(),
-        source: "Internal LIVE".into(),
-        kind: MediaKind::Live,
-        stream_url: "https://live.cdn.example.com/umbreon/intro.m3u8".into(),
-        danmaku_endpoint: Some("wss://danmaku.example.com/umbreon".into()),
-    })
-}
-
-pub(crate) fn mock_memory_panel() -> MemoryPanelState {
-    MemoryPanelState {
-        synced: true,
-        last_synced: Some("2026-02-06T08:15:00Z".into()),
-        highlights: vec![
-            "Feed preference: prioritize RSSHub anime tech".into(),
-            "Reminder: test custom danmaku overlay".into(),
-            "Memory: Live latency target < 2s".into(),
-        ],
-    }
-}
@@ -1,50 +1,266 @@
-use crate::state::{FeedItem, FeedSourceKind, ThemeMode};
+use crate::danmaku_file::DanmakuAttachment;
+use crate::state::{
+    ChatBackend, ChatMessage, ChatMode, ChatRole, Conversation, EmbeddingKind, FeedItem, FeedMark,
+    FeedSourceKind, LiveStream, LocalMemory, MarkStatus, MemoryCategory, MemorySyncState,
+    OutboxEntry, OutboxOp, OutboxStatus, StoredEmbedding, ThemeMode, TimelineOrder,
+};
+use crate::usage::{LlmUsageRecord, PriceTable};
+use std::collections::HashMap;
+use umbreon_core::UmbreonError;
+use umbreon_core::danmaku_layout::OverlaySettings;
 
 #[derive(Default)]
 pub struct StoredSettings {
     pub feed_server_url: Option<String>,
     pub memory_server_url: Option<String>,
     pub llm_endpoint: Option<String>,
-    pub llm_api_key: Option<String>,
     pub llm_model: Option<String>,
     pub llm_models: Option<Vec<String>>,
+    pub llm_embedding_model: Option<String>,
+    pub llm_prices: Option<PriceTable>,
+    pub llm_monthly_budget: Option<f64>,
+    pub config_url: Option<String>,
+    pub remote_config: Option<String>,
     pub theme: Option<ThemeMode>,
+    pub timeline_order: Option<TimelineOrder>,
+    /// How the secrets master key is obtained; see `secrets::KeyParams`.
+    pub secret_key_params: Option<String>,
+    /// M3U playlist URLs imported by hand, on top of the remote config's.
+    pub live_playlists: Option<Vec<String>>,
+    /// `QualityPreference` key used when probing live channels.
+    pub live_quality: Option<String>,
+    /// Bandwidth cap in bits per second for the probed variant.
+    pub live_max_bandwidth: Option<u64>,
+    pub live_low_latency: Option<bool>,
+    /// Seconds behind the live edge that low-latency playback aims for.
+    pub live_target_latency: Option<f64>,
+    pub danmaku_overlay: Option<OverlaySettings>,
+    /// Danmaku files attached to videos, by stream URL.
+    pub danmaku_attachments: Option<HashMap<String, DanmakuAttachment>>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 mod imp {
-    use super::{FeedItem, FeedSourceKind, StoredSettings, ThemeMode};
+    use super::{
+        ChatBackend, ChatMessage, ChatMode, ChatRole, Conversation, DanmakuAttachment,
+        EmbeddingKind, FeedItem, FeedMark, FeedSourceKind, HashMap, LiveStream, LlmUsageRecord,
+        LocalMemory, MarkStatus, MemoryCategory, MemorySyncState, OutboxEntry, OutboxOp,
+        OutboxStatus, OverlaySettings, PriceTable, StoredEmbedding, StoredSettings, ThemeMode,
+        TimelineOrder, UmbreonError,
+    };
     use chrono::{FixedOffset, TimeZone};
     use rusqlite::{Connection, params};
-    use std::path::PathBuf;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Mutex, Once, PoisonError};
+    use umbreon_core::secrets::{self as sealing, MasterKey};
 
     const SETTINGS_FEED_SERVER_URL: &str = "feed_server_url";
     const SETTINGS_GIST_URL_LEGACY: &str = "gist_url";
     const SETTINGS_MEMORY_SERVER_URL: &str = "memory_server_url";
     const SETTINGS_LLM_ENDPOINT: &str = "llm_endpoint";
-    const SETTINGS_LLM_API_KEY: &str = "llm_api_key";
+    /// The API key used to be stored here in plaintext; it now lives in `secrets`.
+    const SETTINGS_LLM_API_KEY_LEGACY: &str = "llm_api_key";
     const SETTINGS_LLM_MODEL: &str = "llm_model";
     const SETTINGS_LLM_MODELS: &str = "llm_models";
+    const SETTINGS_LLM_EMBEDDING_MODEL: &str = "llm_embedding_model";
+    const SETTINGS_LLM_PRICES: &str = "llm_prices";
+    const SETTINGS_LLM_MONTHLY_BUDGET: &str = "llm_monthly_budget";
+    const SETTINGS_CONFIG_URL: &str = "config_url";
+    const SETTINGS_REMOTE_CONFIG: &str = "remote_config";
     const SETTINGS_THEME: &str = "theme";
+    const SETTINGS_TIMELINE_ORDER: &str = "timeline_order";
+    const SETTINGS_SECRET_KEY_PARAMS: &str = "secret_key_params";
+    const SETTINGS_LIVE_PLAYLISTS: &str = "live_playlists";
+    const SETTINGS_LIVE_QUALITY: &str = "live_quality";
+    const SETTINGS_LIVE_MAX_BANDWIDTH: &str = "live_max_bandwidth";
+    const SETTINGS_LIVE_LOW_LATENCY: &str = "live_low_latency";
+    const SETTINGS_LIVE_TARGET_LATENCY: &str = "live_target_latency";
+    const SETTINGS_DANMAKU_OVERLAY: &str = "danmaku_overlay";
+    const SETTINGS_DANMAKU_ATTACHMENTS: &str = "danmaku_attachments";
 
-    fn db_path() -> PathBuf {
+    fn data_dir() -> PathBuf {
         let mut base = dirs::data_dir()
             .or_else(|| std::env::current_dir().ok())
             .unwrap_or_else(std::env::temp_dir);
         base.push("umbreon");
         let _ = std::fs::create_dir_all(&base);
-        base.push("umbreon.db");
         base
     }
 
+    fn db_path() -> PathBuf {
+        let mut path = data_dir();
+        path.push("umbreon.db");
+        path
+    }
+
+    /// Kept outside the database so a copied `umbreon.db` alone does not reveal secrets.
+    fn device_key_path() -> PathBuf {
+        let mut path = data_dir();
+        path.push("device.key");
+        path
+    }
+
+    const DATABASE_KEY_NAME: &str = "database_key";
+
+    /// The SQLCipher key sealed with the device key. Its presence is what marks the
+    /// database as encrypted.
+    fn database_key_path() -> PathBuf {
+        let mut path = data_dir();
+        path.push("umbreon.db.key");
+        path
+    }
+
+    /// A key change in progress: the new key is written here before the database is
+    /// re-encrypted and only replaces `umbreon.db.key` once it has opened the database.
+    fn pending_key_path() -> PathBuf {
+        database_key_path().with_extension("key.pending")
+    }
+
+    /// The current key moved aside while decryption swaps in the plaintext database, so a
+    /// key file never sits next to a database it does not open.
+    fn retired_key_path() -> PathBuf {
+        database_key_path().with_extension("key.retired")
+    }
+
+    /// Unsealed database key (hex), read once per launch.
+    static DATABASE_KEY: Mutex<Option<String>> = Mutex::new(None);
+    static RECOVER_DATABASE_KEY: Once = Once::new();
+
+    /// The key sealed in `path`, or `None` when there is no such file.
+    fn read_database_key(path: &Path) -> Result<Option<String>, UmbreonError> {
+        let Ok(sealed) = std::fs::read_to_string(path) else {
+            return Ok(None);
+        };
+        let device = crate::secrets::device_key()?;
+        sealing::open(&device, DATABASE_KEY_NAME, sealed.trim()).map(Some)
+    }
+
+    fn database_key() -> Option<String> {
+        RECOVER_DATABASE_KEY.call_once(recover_database_key);
+        let mut cached = DATABASE_KEY.lock().unwrap_or_else(PoisonError::into_inner);
+        if cached.is_none() {
+            match read_database_key(&database_key_path()) {
+                Ok(key) => *cached = Some(key?),
+                Err(err) => {
+                    tracing::warn!("database key unreadable: {err}");
+                    return None;
+                }
+            }
+        }
+        cached.clone()
+    }
+
+    fn write_database_key(path: &Path, key: &str) -> Result<(), UmbreonError> {
+        let device = crate::secrets::device_key()?;
+        let sealed = sealing::seal(&device, DATABASE_KEY_NAME, key)?;
+        write_private_file(path, &sealed)
+    }
+
+    fn apply_key(conn: &Connection, key: &str) -> Result<(), rusqlite::Error> {
+        conn.execute_batch(&format!("PRAGMA key = \"x'{key}'\";"))
+    }
+
+    /// Whether the database file opens with `key` (`None` for plaintext).
+    fn key_opens(key: Option<&str>) -> bool {
+        let Ok(conn) = Connection::open(db_path()) else {
+            return false;
+        };
+        if let Some(key) = key
+            && apply_key(&conn, key).is_err()
+        {
+            return false;
+        }
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+            .is_ok()
+    }
+
+    /// Finishes a key change that stopped halfway, going by which key opens the database: a
+    /// key retired by decryption comes back while the database is still encrypted, a key
+    /// file next to a plaintext database is removed, and a pending key from encryption or a
+    /// rekey is promoted when it, rather than the current key, opens the database.
+    fn recover_database_key() {
+        if !db_path().exists() {
+            return;
+        }
+        let retired = retired_key_path();
+        if retired.exists() {
+            if key_opens(None) {
+                if let Err(err) = std::fs::remove_file(&retired) {
+                    tracing::warn!("retired database key not removed: {err}");
+                }
+            } else {
+                match read_database_key(&retired) {
+                    Ok(Some(key)) if key_opens(Some(&key)) => {
+                        if let Err(err) = std::fs::rename(&retired, database_key_path()) {
+                            tracing::warn!("retired database key not restored: {err}");
+                        }
+                    }
+                    Ok(_) => tracing::warn!("retired database key does not open the database"),
+                    Err(err) => tracing::warn!("retired database key unreadable: {err}"),
+                }
+            }
+        }
+        if database_key_path().exists() && key_opens(None) {
+            tracing::warn!("removing a database key left next to a plaintext database");
+            if let Err(err) = std::fs::remove_file(database_key_path()) {
+                tracing::warn!("stale database key not removed: {err}");
+            }
+        }
+        let pending = pending_key_path();
+        if !pending.exists() {
+            return;
+        }
+        let current = match read_database_key(&database_key_path()) {
+            Ok(current) => current,
+            Err(err) => {
+                tracing::warn!("database key unreadable: {err}");
+                return;
+            }
+        };
+        if key_opens(current.as_deref()) {
+            let _ = std::fs::remove_file(&pending);
+            return;
+        }
+        match read_database_key(&pending) {
+            Ok(Some(key)) if key_opens(Some(&key)) => {
+                if let Err(err) = std::fs::rename(&pending, database_key_path()) {
+                    tracing::warn!("pending database key not promoted: {err}");
+                }
+            }
+            Ok(_) => tracing::warn!("pending database key does not open the database"),
+            Err(err) => tracing::warn!("pending database key unreadable: {err}"),
+        }
+    }
+
+    /// Replaces the current key with the pending one after checking it opens the database.
+    /// Until the rename, the old key file stays in place for [`recover_database_key`].
+    fn promote_pending_key(key: String) -> Result<(), UmbreonError> {
+        if !key_opens(Some(&key)) {
+            return Err(UmbreonError::storage(
+                "re-encrypted database does not open with the new key",
+            ));
+        }
+        std::fs::rename(pending_key_path(), database_key_path()).map_err(UmbreonError::storage)?;
+        *DATABASE_KEY.lock().unwrap_or_else(PoisonError::into_inner) = Some(key);
+        Ok(())
+    }
+
     fn open_db() -> Result<Connection, rusqlite::Error> {
         let conn = Connection::open(db_path())?;
+        if let Some(key) = database_key() {
+            apply_key(&conn, &key)?;
+        }
         conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS settings (\n                key TEXT PRIMARY KEY,\n                value TEXT NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS feeds (\n                id TEXT PRIMARY KEY,\n                title TEXT NOT NULL,\n                summary TEXT NOT NULL,\n                full_content TEXT NOT NULL,\n                summarized INTEGER NOT NULL DEFAULT 0,\n                source TEXT NOT NULL,\n                published_at TEXT NOT NULL,\n                published_ts INTEGER NOT NULL,\n                link TEXT NOT NULL,\n                author TEXT NOT NULL,\n                avatar_url TEXT,\n                tags TEXT\n            );",
+            "CREATE TABLE IF NOT EXISTS settings (\n                key TEXT PRIMARY KEY,\n                value TEXT NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS feeds (\n                id TEXT PRIMARY KEY,\n                title TEXT NOT NULL,\n                summary TEXT NOT NULL,\n                full_content TEXT NOT NULL,\n                summarized INTEGER NOT NULL DEFAULT 0,\n                source TEXT NOT NULL,\n                published_at TEXT NOT NULL,\n                published_ts INTEGER NOT NULL,\n                link TEXT NOT NULL,\n                author TEXT NOT NULL,\n                avatar_url TEXT,\n                tags TEXT\n            );\n            CREATE TABLE IF NOT EXISTS llm_usage (\n                id INTEGER PRIMARY KEY AUTOINCREMENT,\n                created_ts INTEGER NOT NULL,\n                operation TEXT NOT NULL,\n                model TEXT NOT NULL,\n                prompt_tokens INTEGER NOT NULL DEFAULT 0,\n                completion_tokens INTEGER NOT NULL DEFAULT 0,\n                latency_ms INTEGER NOT NULL DEFAULT 0,\n                success INTEGER NOT NULL DEFAULT 1,\n                error TEXT\n            );\n            CREATE INDEX IF NOT EXISTS llm_usage_created_ts ON llm_usage (created_ts);\n            CREATE TABLE IF NOT EXISTS conversations (\n                id TEXT PRIMARY KEY,\n                title TEXT NOT NULL,\n                mode TEXT NOT NULL,\n                backend TEXT,\n                feed_item_id TEXT,\n                context TEXT,\n                created_ts INTEGER NOT NULL,\n                updated_ts INTEGER NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS chat_messages (\n                id INTEGER PRIMARY KEY AUTOINCREMENT,\n                conversation_id TEXT NOT NULL,\n                role TEXT NOT NULL,\n                content TEXT NOT NULL,\n                created_ts INTEGER NOT NULL\n            );\n            CREATE INDEX IF NOT EXISTS chat_messages_conversation ON chat_messages (conversation_id, id);\n            CREATE TABLE IF NOT EXISTS feed_marks (\n                feed_id TEXT PRIMARY KEY,\n                marked INTEGER NOT NULL DEFAULT 1,\n                memory_id TEXT,\n                status TEXT NOT NULL,\n                error TEXT,\n                updated_ts INTEGER NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS memories (\n                id TEXT PRIMARY KEY,\n                remote_id TEXT,\n                content TEXT NOT NULL,\n                category TEXT,\n                tags TEXT,\n                source_ref TEXT,\n                sync_state TEXT NOT NULL,\n                created_ts INTEGER NOT NULL,\n                updated_ts INTEGER NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS memory_outbox (\n                seq INTEGER PRIMARY KEY AUTOINCREMENT,\n                idempotency_key TEXT NOT NULL UNIQUE,\n                op TEXT NOT NULL,\n                memory_id TEXT NOT NULL,\n                payload TEXT NOT NULL,\n                attempts INTEGER NOT NULL DEFAULT 0,\n                next_attempt_ts INTEGER NOT NULL DEFAULT 0,\n                status TEXT NOT NULL,\n                last_error TEXT,\n                created_ts INTEGER NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS embeddings (\n                kind TEXT NOT NULL,\n                ref_id TEXT NOT NULL,\n                content_hash TEXT NOT NULL,\n                dims INTEGER NOT NULL,\n                vector BLOB NOT NULL,\n                updated_ts INTEGER NOT NULL,\n                PRIMARY KEY (kind, ref_id)\n            );\n            CREATE TABLE IF NOT EXISTS secrets (\n                name TEXT PRIMARY KEY,\n                value TEXT NOT NULL,\n                updated_ts INTEGER NOT NULL\n            );\n            CREATE TABLE IF NOT EXISTS live_channels (\n                id TEXT PRIMARY KEY,\n                title TEXT NOT NULL,\n                stream_url TEXT NOT NULL,\n                danmaku_endpoint TEXT,\n                group_title TEXT,\n                logo TEXT,\n                origin TEXT NOT NULL,\n                position INTEGER NOT NULL,\n                favorite INTEGER NOT NULL DEFAULT 0,\n                last_watched_ts INTEGER\n            );",
         )?;
         let _ = conn.execute("ALTER TABLE feeds ADD COLUMN tags TEXT", []);
         let _ = conn.execute("ALTER TABLE feeds ADD COLUMN full_content TEXT", []);
         let _ = conn.execute("ALTER TABLE feeds ADD COLUMN summarized INTEGER", []);
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN backend TEXT", []);
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN feed_item_id TEXT", []);
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN context TEXT", []);
+        let _ = conn.execute("ALTER TABLE memories ADD COLUMN category TEXT", []);
         Ok(conn)
     }
 
@@ -79,6 +295,14 @@ mod imp {
         serde_json::from_str::<Vec<String>>(value).ok()
     }
 
+    fn prices_to_value(prices: &PriceTable) -> Option<String> {
+        serde_json::to_string(prices).ok()
+    }
+
+    fn prices_from_value(value: &str) -> Option<PriceTable> {
+        serde_json::from_str::<PriceTable>(value).ok()
+    }
+
     fn source_to_value(source: &FeedSourceKind) -> &'static str {
         match source {
             FeedSourceKind::Atom => "atom",
@@ -126,10 +350,35 @@ mod imp {
                 }
                 SETTINGS_MEMORY_SERVER_URL => settings.memory_server_url = Some(row.1),
                 SETTINGS_LLM_ENDPOINT => settings.llm_endpoint = Some(row.1),
-                SETTINGS_LLM_API_KEY => settings.llm_api_key = Some(row.1),
                 SETTINGS_LLM_MODEL => settings.llm_model = Some(row.1),
                 SETTINGS_LLM_MODELS => settings.llm_models = models_from_value(&row.1),
+                SETTINGS_LLM_EMBEDDING_MODEL => settings.llm_embedding_model = Some(row.1),
+                SETTINGS_LLM_PRICES => settings.llm_prices = prices_from_value(&row.1),
+                SETTINGS_LLM_MONTHLY_BUDGET => {
+                    settings.llm_monthly_budget = row.1.trim().parse::<f64>().ok()
+                }
+                SETTINGS_CONFIG_URL => settings.config_url = Some(row.1),
+                SETTINGS_REMOTE_CONFIG => settings.remote_config = Some(row.1),
                 SETTINGS_THEME => settings.theme = theme_from_value(&row.1),
+                SETTINGS_TIMELINE_ORDER => {
+                    settings.timeline_order = Some(TimelineOrder::from_value(&row.1))
+                }
+                SETTINGS_SECRET_KEY_PARAMS => settings.secret_key_params = Some(row.1),
+                SETTINGS_LIVE_PLAYLISTS => settings.live_playlists = models_from_value(&row.1),
+                SETTINGS_LIVE_QUALITY => settings.live_quality = Some(row.1),
+                SETTINGS_LIVE_MAX_BANDWIDTH => {
+                    settings.live_max_bandwidth = row.1.trim().parse::<u64>().ok()
+                }
+                SETTINGS_LIVE_LOW_LATENCY => settings.live_low_latency = Some(row.1 == "true"),
+                SETTINGS_LIVE_TARGET_LATENCY => {
+                    settings.live_target_latency = row.1.trim().parse::<f64>().ok()
+                }
+                SETTINGS_DANMAKU_OVERLAY => {
+                    settings.danmaku_overlay = serde_json::from_str(&row.1).ok()
+                }
+                SETTINGS_DANMAKU_ATTACHMENTS => {
+                    settings.danmaku_attachments = serde_json::from_str(&row.1).ok()
+                }
                 _ => {}
             }
         }
@@ -150,6 +399,20 @@ mod imp {
         let _ = upsert_setting(&conn, SETTINGS_MEMORY_SERVER_URL, url);
     }
 
+    pub fn store_config_url(url: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_CONFIG_URL, url);
+    }
+
+    pub fn store_remote_config(text: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_REMOTE_CONFIG, text);
+    }
+
     pub fn store_llm_endpoint(endpoint: &str) {
         let Ok(conn) = open_db() else {
             return;
@@ -157,11 +420,25 @@ mod imp {
         let _ = upsert_setting(&conn, SETTINGS_LLM_ENDPOINT, endpoint);
     }
 
-    pub fn store_llm_api_key(api_key: &str) {
+    /// The plaintext API key written by older versions, if it is still around.
+    pub fn load_legacy_llm_api_key() -> Option<String> {
+        let conn = open_db().ok()?;
+        conn.query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![SETTINGS_LLM_API_KEY_LEGACY],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+    }
+
+    pub fn clear_legacy_llm_api_key() {
         let Ok(conn) = open_db() else {
             return;
         };
-        let _ = upsert_setting(&conn, SETTINGS_LLM_API_KEY, api_key);
+        let _ = conn.execute(
+            "DELETE FROM settings WHERE key = ?1",
+            params![SETTINGS_LLM_API_KEY_LEGACY],
+        );
     }
 
     pub fn store_llm_model(model: &str) {
@@ -171,6 +448,13 @@ mod imp {
         let _ = upsert_setting(&conn, SETTINGS_LLM_MODEL, model);
     }
 
+    pub fn store_llm_embedding_model(model: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_LLM_EMBEDDING_MODEL, model);
+    }
+
     pub fn store_llm_models(models: &[String]) {
         let Some(value) = models_to_value(models) else {
             return;
@@ -181,6 +465,99 @@ mod imp {
         let _ = upsert_setting(&conn, SETTINGS_LLM_MODELS, &value);
     }
 
+    pub fn store_live_playlists(urls: &[String]) {
+        let Some(value) = models_to_value(urls) else {
+            return;
+        };
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_LIVE_PLAYLISTS, &value);
+    }
+
+    pub fn store_live_quality(quality: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_LIVE_QUALITY, quality);
+    }
+
+    pub fn store_danmaku_attachments(attachments: &HashMap<String, DanmakuAttachment>) {
+        let Ok(value) = serde_json::to_string(attachments) else {
+            return;
+        };
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_DANMAKU_ATTACHMENTS, &value);
+    }
+
+    pub fn store_danmaku_overlay(overlay: &OverlaySettings) {
+        let Ok(value) = serde_json::to_string(overlay) else {
+            return;
+        };
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_DANMAKU_OVERLAY, &value);
+    }
+
+    pub fn store_live_low_latency(enabled: bool, target_latency: f64) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_LIVE_LOW_LATENCY, &enabled.to_string());
+        let _ = upsert_setting(
+            &conn,
+            SETTINGS_LIVE_TARGET_LATENCY,
+            &target_latency.to_string(),
+        );
+    }
+
+    pub fn store_live_max_bandwidth(max_bandwidth: Option<u64>) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        match max_bandwidth {
+            Some(value) => {
+                let _ = upsert_setting(&conn, SETTINGS_LIVE_MAX_BANDWIDTH, &value.to_string());
+            }
+            None => {
+                let _ = conn.execute(
+                    "DELETE FROM settings WHERE key = ?1",
+                    params![SETTINGS_LIVE_MAX_BANDWIDTH],
+                );
+            }
+        }
+    }
+
+    pub fn store_llm_prices(prices: &PriceTable) {
+        let Some(value) = prices_to_value(prices) else {
+            return;
+        };
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_LLM_PRICES, &value);
+    }
+
+    pub fn store_llm_monthly_budget(budget: Option<f64>) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        match budget {
+            Some(value) => {
+                let _ = upsert_setting(&conn, SETTINGS_LLM_MONTHLY_BUDGET, &value.to_string());
+            }
+            None => {
+                let _ = conn.execute(
+                    "DELETE FROM settings WHERE key = ?1",
+                    params![SETTINGS_LLM_MONTHLY_BUDGET],
+                );
+            }
+        }
+    }
+
     pub fn store_theme(theme: ThemeMode) {
         let Ok(conn) = open_db() else {
             return;
@@ -188,6 +565,59 @@ mod imp {
         let _ = upsert_setting(&conn, SETTINGS_THEME, theme_to_value(theme));
     }
 
+    pub fn store_timeline_order(order: TimelineOrder) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = upsert_setting(&conn, SETTINGS_TIMELINE_ORDER, order.as_str());
+    }
+
+    pub fn store_llm_usage(record: &LlmUsageRecord) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "INSERT INTO llm_usage (created_ts, operation, model, prompt_tokens, completion_tokens, latency_ms, success, error)\n            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.created_ts,
+                record.operation,
+                record.model,
+                record.prompt_tokens,
+                record.completion_tokens,
+                record.latency_ms,
+                if record.success { 1 } else { 0 },
+                record.error,
+            ],
+        );
+    }
+
+    pub fn load_llm_usage_since(since_ts: i64) -> Vec<LlmUsageRecord> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT created_ts, operation, model, prompt_tokens, completion_tokens, latency_ms, success, error\n            FROM llm_usage\n            WHERE created_ts >= ?1\n            ORDER BY created_ts DESC",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map(params![since_ts], |row| {
+            let success: i64 = row.get(6)?;
+            Ok(LlmUsageRecord {
+                created_ts: row.get(0)?,
+                operation: row.get(1)?,
+                model: row.get(2)?,
+                prompt_tokens: row.get(3)?,
+                completion_tokens: row.get(4)?,
+                latency_ms: row.get(5)?,
+                success: success != 0,
+                error: row.get(7)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
+
     pub fn load_feed_items() -> Vec<FeedItem> {
         let Ok(conn) = open_db() else {
             return Vec::new();
@@ -231,19 +661,20 @@ mod imp {
         rows.flatten().collect()
     }
 
-    pub fn store_feed_items(items: &[FeedItem]) -> Result<(), String> {
-        let mut conn = open_db().map_err(|err| format!("open db failed: {err}"))?;
+    pub fn store_feed_items(items: &[FeedItem]) -> Result<(), UmbreonError> {
+        let mut conn =
+            open_db().map_err(|err| UmbreonError::storage(format!("open db failed: {err}")))?;
         let tx = conn
             .transaction()
-            .map_err(|err| format!("start transaction failed: {err}"))?;
+            .map_err(|err| UmbreonError::storage(format!("start transaction failed: {err}")))?;
         tx.execute("DELETE FROM feeds", [])
-            .map_err(|err| format!("clear feeds failed: {err}"))?;
+            .map_err(|err| UmbreonError::storage(format!("clear feeds failed: {err}")))?;
         {
             let mut stmt = tx
                 .prepare(
                     "INSERT INTO feeds (id, title, summary, full_content, summarized, source, published_at, published_ts, link, author, avatar_url, tags)\n                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
                 )
-                .map_err(|err| format!("prepare insert failed: {err}"))?;
+                .map_err(|err| UmbreonError::storage(format!("prepare insert failed: {err}")))?;
             for item in items {
                 let tags = item.tags.join(",");
                 stmt.execute(params![
@@ -260,82 +691,915 @@ mod imp {
                     item.avatar_url,
                     tags,
                 ])
-                .map_err(|err| format!("insert feed failed: {err}"))?;
+                .map_err(|err| UmbreonError::storage(format!("insert feed failed: {err}")))?;
             }
         }
         tx.commit()
-            .map_err(|err| format!("commit feeds failed: {err}"))?;
+            .map_err(|err| UmbreonError::storage(format!("commit feeds failed: {err}")))?;
         Ok(())
     }
-}
 
-#[cfg(target_arch = "wasm32")]
-mod imp {
-    use super::{FeedItem, StoredSettings, ThemeMode};
+    pub fn load_conversations() -> Vec<Conversation> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT id, title, mode, backend, feed_item_id, context, created_ts, updated_ts\n            FROM conversations\n            ORDER BY updated_ts DESC",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map([], |row| {
+            let mode: String = row.get(2)?;
+            let backend: Option<String> = row.get(3)?;
+            Ok(Conversation {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                mode: ChatMode::from_value(&mode),
+                backend: ChatBackend::from_value(backend.as_deref().unwrap_or_default()),
+                feed_item_id: row.get(4)?,
+                context: row.get(5)?,
+                created_ts: row.get(6)?,
+                updated_ts: row.get(7)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
 
-    const FEED_SERVER_STORAGE_KEY: &str = "umbreon.feed_server_url";
-    const GIST_STORAGE_KEY_LEGACY: &str = "umbreon.gist_url";
-    const MEMORY_SERVER_STORAGE_KEY: &str = "umbreon.memory_server_url";
-    const LLM_ENDPOINT_STORAGE_KEY: &str = "umbreon.llm_endpoint";
-    const LLM_API_KEY_STORAGE_KEY: &str = "umbreon.llm_api_key";
-    const LLM_MODEL_STORAGE_KEY: &str = "umbreon.llm_model";
-    const LLM_MODELS_STORAGE_KEY: &str = "umbreon.llm_models";
-    const THEME_STORAGE_KEY: &str = "umbreon.theme";
+    pub fn store_conversation(conversation: &Conversation) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "INSERT INTO conversations (id, title, mode, backend, feed_item_id, context, created_ts, updated_ts)\n            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)\n            ON CONFLICT(id) DO UPDATE SET title = excluded.title, mode = excluded.mode, backend = excluded.backend, updated_ts = excluded.updated_ts",
+            params![
+                conversation.id,
+                conversation.title,
+                conversation.mode.as_str(),
+                conversation.backend.as_str(),
+                conversation.feed_item_id,
+                conversation.context,
+                conversation.created_ts,
+                conversation.updated_ts,
+            ],
+        );
+    }
 
-    fn theme_from_value(value: &str) -> Option<ThemeMode> {
-        match value {
-            "light" => Some(ThemeMode::Light),
-            "dark" => Some(ThemeMode::Dark),
-            _ => None,
-        }
+    pub fn delete_conversation(id: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "DELETE FROM chat_messages WHERE conversation_id = ?1",
+            params![id],
+        );
+        let _ = conn.execute("DELETE FROM conversations WHERE id = ?1", params![id]);
     }
 
-    fn theme_to_value(theme: ThemeMode) -> &'static str {
-        match theme {
-            ThemeMode::Light => "light",
-            ThemeMode::Dark => "dark",
-        }
+    pub fn load_chat_messages(conversation_id: &str) -> Vec<ChatMessage> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT conversation_id, role, content, created_ts\n            FROM chat_messages\n            WHERE conversation_id = ?1\n            ORDER BY id ASC",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map(params![conversation_id], |row| {
+            let role: String = row.get(1)?;
+            Ok(ChatMessage {
+                conversation_id: row.get(0)?,
+                role: ChatRole::from_value(&role),
+                content: row.get(2)?,
+                created_ts: row.get(3)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
     }
 
-    fn models_to_value(models: &[String]) -> Option<String> {
-        serde_json::to_string(models).ok()
+    pub fn append_chat_message(message: &ChatMessage) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "INSERT INTO chat_messages (conversation_id, role, content, created_ts)\n            VALUES (?1, ?2, ?3, ?4)",
+            params![
+                message.conversation_id,
+                message.role.as_str(),
+                message.content,
+                message.created_ts,
+            ],
+        );
     }
 
-    fn models_from_value(value: &str) -> Option<Vec<String>> {
-        serde_json::from_str::<Vec<String>>(value).ok()
+    pub fn load_feed_marks() -> Vec<FeedMark> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT feed_id, marked, memory_id, status, error, updated_ts FROM feed_marks",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map([], |row| {
+            let marked: i64 = row.get(1)?;
+            let status: String = row.get(3)?;
+            Ok(FeedMark {
+                feed_id: row.get(0)?,
+                marked: marked != 0,
+                memory_id: row.get(2)?,
+                status: MarkStatus::from_value(&status),
+                error: row.get(4)?,
+                updated_ts: row.get(5)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
     }
 
-    pub fn load_settings() -> StoredSettings {
-        let mut settings = StoredSettings::default();
-        let Some(window) = web_sys::window() else {
-            return settings;
+    pub fn store_feed_mark(mark: &FeedMark) {
+        let Ok(conn) = open_db() else {
+            return;
         };
-        let Ok(Some(storage)) = window.local_storage() else {
-            return settings;
+        let _ = conn.execute(
+            "INSERT INTO feed_marks (feed_id, marked, memory_id, status, error, updated_ts)\n            VALUES (?1, ?2, ?3, ?4, ?5, ?6)\n            ON CONFLICT(feed_id) DO UPDATE SET marked = excluded.marked, memory_id = excluded.memory_id, status = excluded.status, error = excluded.error, updated_ts = excluded.updated_ts",
+            params![
+                mark.feed_id,
+                if mark.marked { 1 } else { 0 },
+                mark.memory_id,
+                mark.status.as_str(),
+                mark.error,
+                mark.updated_ts,
+            ],
+        );
+    }
+
+    pub fn delete_feed_mark(feed_id: &str) {
+        let Ok(conn) = open_db() else {
+            return;
         };
-        if let Ok(Some(value)) = storage.get_item(FEED_SERVER_STORAGE_KEY) {
-            settings.feed_server_url = Some(value);
-        } else if let Ok(Some(value)) = storage.get_item(GIST_STORAGE_KEY_LEGACY) {
-            settings.feed_server_url = Some(value);
-        }
-        if let Ok(Some(value)) = storage.get_item(MEMORY_SERVER_STORAGE_KEY) {
+        let _ = conn.execute(
+            "DELETE FROM feed_marks WHERE feed_id = ?1",
+            params![feed_id],
+        );
+    }
+
+    fn tags_to_value(tags: &[String]) -> String {
+        serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    fn tags_from_value(value: Option<String>) -> Vec<String> {
+        value
+            .and_then(|value| serde_json::from_str::<Vec<String>>(&value).ok())
+            .unwrap_or_default()
+    }
+
+    fn memory_from_row(row: &rusqlite::Row<'_>) -> Result<LocalMemory, rusqlite::Error> {
+        let sync_state: String = row.get(5)?;
+        let category: Option<String> = row.get(8)?;
+        Ok(LocalMemory {
+            id: row.get(0)?,
+            remote_id: row.get(1)?,
+            content: row.get(2)?,
+            category: MemoryCategory::from_value(category.as_deref().unwrap_or_default()),
+            tags: tags_from_value(row.get(3)?),
+            source_ref: row.get(4)?,
+            sync_state: MemorySyncState::from_value(&sync_state),
+            created_ts: row.get(6)?,
+            updated_ts: row.get(7)?,
+        })
+    }
+
+    pub fn load_local_memories() -> Vec<LocalMemory> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT id, remote_id, content, tags, source_ref, sync_state, created_ts, updated_ts, category\n            FROM memories\n            ORDER BY updated_ts DESC",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map([], memory_from_row) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
+
+    pub fn load_local_memory(id: &str) -> Option<LocalMemory> {
+        let conn = open_db().ok()?;
+        conn.query_row(
+            "SELECT id, remote_id, content, tags, source_ref, sync_state, created_ts, updated_ts, category\n            FROM memories\n            WHERE id = ?1",
+            params![id],
+            memory_from_row,
+        )
+        .ok()
+    }
+
+    pub fn store_local_memory(memory: &LocalMemory) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "INSERT INTO memories (id, remote_id, content, tags, source_ref, sync_state, created_ts, updated_ts, category)\n            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)\n            ON CONFLICT(id) DO UPDATE SET remote_id = excluded.remote_id, content = excluded.content, category = excluded.category, tags = excluded.tags, source_ref = excluded.source_ref, sync_state = excluded.sync_state, updated_ts = excluded.updated_ts",
+            params![
+                memory.id,
+                memory.remote_id,
+                memory.content,
+                tags_to_value(&memory.tags),
+                memory.source_ref,
+                memory.sync_state.as_str(),
+                memory.created_ts,
+                memory.updated_ts,
+                memory.category.as_str(),
+            ],
+        );
+    }
+
+    pub fn delete_local_memory(id: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute("DELETE FROM memories WHERE id = ?1", params![id]);
+    }
+
+    pub fn enqueue_outbox(entry: &OutboxEntry) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "INSERT OR IGNORE INTO memory_outbox (idempotency_key, op, memory_id, payload, attempts, next_attempt_ts, status, last_error, created_ts)\n            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                entry.idempotency_key,
+                entry.op.as_str(),
+                entry.memory_id,
+                entry.payload,
+                entry.attempts,
+                entry.next_attempt_ts,
+                entry.status.as_str(),
+                entry.last_error,
+                entry.created_ts,
+            ],
+        );
+    }
+
+    pub fn load_outbox() -> Vec<OutboxEntry> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT seq, idempotency_key, op, memory_id, payload, attempts, next_attempt_ts, status, last_error, created_ts\n            FROM memory_outbox\n            ORDER BY seq ASC",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map([], |row| {
+            let op: String = row.get(2)?;
+            let status: String = row.get(7)?;
+            Ok(OutboxEntry {
+                seq: row.get(0)?,
+                idempotency_key: row.get(1)?,
+                op: OutboxOp::from_value(&op),
+                memory_id: row.get(3)?,
+                payload: row.get(4)?,
+                attempts: row.get(5)?,
+                next_attempt_ts: row.get(6)?,
+                status: OutboxStatus::from_value(&status),
+                last_error: row.get(8)?,
+                created_ts: row.get(9)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
+
+    pub fn update_outbox_entry(entry: &OutboxEntry) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "UPDATE memory_outbox SET attempts = ?2, next_attempt_ts = ?3, status = ?4, last_error = ?5 WHERE seq = ?1",
+            params![
+                entry.seq,
+                entry.attempts,
+                entry.next_attempt_ts,
+                entry.status.as_str(),
+                entry.last_error,
+            ],
+        );
+    }
+
+    pub fn delete_outbox_entry(seq: i64) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute("DELETE FROM memory_outbox WHERE seq = ?1", params![seq]);
+    }
+
+    fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+        vector
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn vector_from_blob(blob: &[u8]) -> Vec<f32> {
+        blob.chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    }
+
+    pub fn load_embeddings(kind: EmbeddingKind) -> Vec<StoredEmbedding> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT ref_id, content_hash, vector, updated_ts\n            FROM embeddings\n            WHERE kind = ?1",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map(params![kind.as_str()], |row| {
+            let blob: Vec<u8> = row.get(2)?;
+            Ok(StoredEmbedding {
+                kind,
+                ref_id: row.get(0)?,
+                content_hash: row.get(1)?,
+                vector: vector_from_blob(&blob),
+                updated_ts: row.get(3)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
+
+    pub fn store_embeddings(embeddings: &[StoredEmbedding]) -> Result<(), UmbreonError> {
+        let mut conn = open_db().map_err(UmbreonError::storage)?;
+        let tx = conn.transaction().map_err(UmbreonError::storage)?;
+        for embedding in embeddings {
+            tx.execute(
+                "INSERT INTO embeddings (kind, ref_id, content_hash, dims, vector, updated_ts)\n                VALUES (?1, ?2, ?3, ?4, ?5, ?6)\n                ON CONFLICT(kind, ref_id) DO UPDATE SET content_hash = excluded.content_hash, dims = excluded.dims, vector = excluded.vector, updated_ts = excluded.updated_ts",
+                params![
+                    embedding.kind.as_str(),
+                    embedding.ref_id,
+                    embedding.content_hash,
+                    embedding.vector.len() as i64,
+                    vector_to_blob(&embedding.vector),
+                    embedding.updated_ts,
+                ],
+            )
+            .map_err(UmbreonError::storage)?;
+        }
+        tx.commit().map_err(UmbreonError::storage)
+    }
+
+    pub fn delete_embeddings(kind: EmbeddingKind, ref_ids: &[String]) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        for ref_id in ref_ids {
+            let _ = conn.execute(
+                "DELETE FROM embeddings WHERE kind = ?1 AND ref_id = ?2",
+                params![kind.as_str(), ref_id],
+            );
+        }
+    }
+
+    /// Live channels in the order they were listed by their config or playlist.
+    pub fn load_live_channels() -> Vec<LiveStream> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT id, title, stream_url, danmaku_endpoint, group_title, logo, origin, favorite, last_watched_ts\n            FROM live_channels ORDER BY origin <> 'config', origin, position",
+        ) else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map([], |row| {
+            let favorite: i64 = row.get(7)?;
+            Ok(LiveStream {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                stream_url: row.get(2)?,
+                danmaku_endpoint: row.get(3)?,
+                group: row.get(4)?,
+                logo: row.get(5)?,
+                origin: row.get(6)?,
+                favorite: favorite != 0,
+                last_watched_ts: row.get(8)?,
+            })
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
+
+    /// Replaces the channels of `origin`. Favorites and watch times of channels that are
+    /// still listed are kept.
+    pub fn replace_live_channels(
+        origin: &str,
+        channels: &[LiveStream],
+    ) -> Result<(), UmbreonError> {
+        let mut conn = open_db().map_err(UmbreonError::storage)?;
+        let tx = conn.transaction().map_err(UmbreonError::storage)?;
+        let listed = channels
+            .iter()
+            .map(|channel| channel.id.as_str())
+            .collect::<std::collections::HashSet<_>>();
+        let stale = {
+            let mut stmt = tx
+                .prepare("SELECT id FROM live_channels WHERE origin = ?1")
+                .map_err(UmbreonError::storage)?;
+            let ids = stmt
+                .query_map(params![origin], |row| row.get::<_, String>(0))
+                .map_err(UmbreonError::storage)?;
+            ids.flatten()
+                .filter(|id| !listed.contains(id.as_str()))
+                .collect::<Vec<_>>()
+        };
+        for id in stale {
+            tx.execute("DELETE FROM live_channels WHERE id = ?1", params![id])
+                .map_err(UmbreonError::storage)?;
+        }
+        for (position, channel) in channels.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO live_channels (id, title, stream_url, danmaku_endpoint, group_title, logo, origin, position)\n                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)\n                ON CONFLICT(id) DO UPDATE SET title = excluded.title, stream_url = excluded.stream_url, danmaku_endpoint = excluded.danmaku_endpoint, group_title = excluded.group_title, logo = excluded.logo, origin = excluded.origin, position = excluded.position",
+                params![
+                    channel.id,
+                    channel.title,
+                    channel.stream_url,
+                    channel.danmaku_endpoint,
+                    channel.group,
+                    channel.logo,
+                    origin,
+                    position as i64,
+                ],
+            )
+            .map_err(UmbreonError::storage)?;
+        }
+        tx.commit().map_err(UmbreonError::storage)
+    }
+
+    /// Drops the channels of every origin not in `origins`.
+    pub fn retain_live_origins(origins: &[String]) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let Ok(mut stmt) = conn.prepare("SELECT DISTINCT origin FROM live_channels") else {
+            return;
+        };
+        let Ok(rows) = stmt.query_map([], |row| row.get::<_, String>(0)) else {
+            return;
+        };
+        for origin in rows.flatten() {
+            if !origins.contains(&origin) {
+                let _ = conn.execute(
+                    "DELETE FROM live_channels WHERE origin = ?1",
+                    params![origin],
+                );
+            }
+        }
+    }
+
+    pub fn store_live_channel_state(id: &str, favorite: bool, last_watched_ts: Option<i64>) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute(
+            "UPDATE live_channels SET favorite = ?2, last_watched_ts = ?3 WHERE id = ?1",
+            params![id, if favorite { 1 } else { 0 }, last_watched_ts],
+        );
+    }
+
+    /// Sealed secret values by name; decrypting them is up to `secrets`.
+    pub fn load_secrets() -> Vec<(String, String)> {
+        let Ok(conn) = open_db() else {
+            return Vec::new();
+        };
+        let Ok(mut stmt) = conn.prepare("SELECT name, value FROM secrets ORDER BY name") else {
+            return Vec::new();
+        };
+        let Ok(rows) = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        }) else {
+            return Vec::new();
+        };
+        rows.flatten().collect()
+    }
+
+    pub fn store_secret(name: &str, sealed: &str) -> Result<(), UmbreonError> {
+        let conn = open_db().map_err(UmbreonError::storage)?;
+        conn.execute(
+            "INSERT INTO secrets (name, value, updated_ts) VALUES (?1, ?2, ?3)\n            ON CONFLICT(name) DO UPDATE SET value = excluded.value, updated_ts = excluded.updated_ts",
+            params![name, sealed, chrono::Utc::now().timestamp()],
+        )
+        .map_err(UmbreonError::storage)?;
+        Ok(())
+    }
+
+    pub fn delete_secret(name: &str) {
+        let Ok(conn) = open_db() else {
+            return;
+        };
+        let _ = conn.execute("DELETE FROM secrets WHERE name = ?1", params![name]);
+    }
+
+    /// Stores secrets sealed under a new master key together with the key params that
+    /// describe it, in one transaction, so the two can never disagree.
+    pub fn reseal_secrets(
+        sealed: &[(String, String)],
+        key_params: &str,
+    ) -> Result<(), UmbreonError> {
+        let mut conn = open_db().map_err(UmbreonError::storage)?;
+        let tx = conn.transaction().map_err(UmbreonError::storage)?;
+        let now = chrono::Utc::now().timestamp();
+        for (name, value) in sealed {
+            tx.execute(
+                "INSERT INTO secrets (name, value, updated_ts) VALUES (?1, ?2, ?3)\n                ON CONFLICT(name) DO UPDATE SET value = excluded.value, updated_ts = excluded.updated_ts",
+                params![name, value, now],
+            )
+            .map_err(UmbreonError::storage)?;
+        }
+        upsert_setting(&tx, SETTINGS_SECRET_KEY_PARAMS, key_params)
+            .map_err(UmbreonError::storage)?;
+        tx.commit().map_err(UmbreonError::storage)
+    }
+
+    pub fn load_device_key() -> Option<String> {
+        std::fs::read_to_string(device_key_path()).ok()
+    }
+
+    fn backup_dir() -> PathBuf {
+        let mut path = data_dir();
+        path.push("backups");
+        let _ = std::fs::create_dir_all(&path);
+        path
+    }
+
+    /// Writes a backup bundle into `backups/` and returns its full path.
+    pub fn store_backup_file(name: &str, contents: &str) -> Result<String, UmbreonError> {
+        let mut path = backup_dir();
+        path.push(name);
+        std::fs::write(&path, contents).map_err(UmbreonError::storage)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// Bundles in `backups/`, newest first.
+    pub fn list_backup_files() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(backup_dir()) else {
+            return Vec::new();
+        };
+        let mut paths = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        paths.sort_by(|a, b| b.cmp(a));
+        paths
+    }
+
+    /// Writes an export (e.g. an OPML subscription list) into `exports/` and returns its path.
+    pub fn store_export_file(name: &str, contents: &str) -> Result<String, UmbreonError> {
+        let mut path = data_dir();
+        path.push("exports");
+        std::fs::create_dir_all(&path).map_err(UmbreonError::storage)?;
+        path.push(name);
+        std::fs::write(&path, contents).map_err(UmbreonError::storage)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// Reads a user-picked file such as a backup bundle or an OPML document.
+    pub fn read_local_file(path: &str) -> Result<String, UmbreonError> {
+        std::fs::read_to_string(path.trim()).map_err(UmbreonError::storage)
+    }
+
+    pub fn database_encryption_supported() -> bool {
+        cfg!(feature = "sqlcipher")
+    }
+
+    pub fn database_encrypted() -> bool {
+        RECOVER_DATABASE_KEY.call_once(recover_database_key);
+        database_key_path().exists()
+    }
+
+    fn require_sqlcipher() -> Result<(), UmbreonError> {
+        if database_encryption_supported() {
+            Ok(())
+        } else {
+            Err(UmbreonError::config(
+                "数据库加密",
+                "this build does not include SQLCipher",
+            ))
+        }
+    }
+
+    /// Copies the open database into a new file keyed with `target_key` (hex, or `None`
+    /// for plaintext) and swaps it in place of `umbreon.db`. The old file is kept until
+    /// the new one is in place.
+    fn export_database(target_key: Option<&str>) -> Result<(), UmbreonError> {
+        let path = db_path();
+        let staging = path.with_extension("db.migrating");
+        let backup = path.with_extension("db.backup");
+        let _ = std::fs::remove_file(&staging);
+        {
+            let conn = open_db().map_err(UmbreonError::storage)?;
+            let key = target_key
+                .map(|key| format!("x'{key}'"))
+                .unwrap_or_default();
+            conn.execute(
+                "ATTACH DATABASE ?1 AS migrated KEY ?2",
+                params![staging.to_string_lossy(), key],
+            )
+            .map_err(UmbreonError::storage)?;
+            conn.query_row("SELECT sqlcipher_export('migrated')", [], |_| Ok(()))
+                .map_err(UmbreonError::storage)?;
+            conn.execute_batch("DETACH DATABASE migrated")
+                .map_err(UmbreonError::storage)?;
+        }
+        std::fs::rename(&path, &backup).map_err(UmbreonError::storage)?;
+        if let Err(err) = std::fs::rename(&staging, &path) {
+            let _ = std::fs::rename(&backup, &path);
+            return Err(UmbreonError::storage(err));
+        }
+        Ok(())
+    }
+
+    /// Moves the plaintext database into an encrypted copy under a fresh random key.
+    pub fn encrypt_database() -> Result<(), UmbreonError> {
+        require_sqlcipher()?;
+        if database_encrypted() {
+            return Ok(());
+        }
+        let key = MasterKey::generate().to_hex();
+        let pending = pending_key_path();
+        write_database_key(&pending, &key)?;
+        if let Err(err) = export_database(Some(&key)) {
+            let _ = std::fs::remove_file(&pending);
+            return Err(err);
+        }
+        promote_pending_key(key)?;
+        let _ = std::fs::remove_file(db_path().with_extension("db.backup"));
+        Ok(())
+    }
+
+    /// Turns encryption off again by exporting to a plaintext database. The key file is
+    /// retired before the swap and removed after it; if that stops halfway,
+    /// [`recover_database_key`] restores or removes it depending on which database is in
+    /// place.
+    pub fn decrypt_database() -> Result<(), UmbreonError> {
+        require_sqlcipher()?;
+        if !database_encrypted() {
+            return Ok(());
+        }
+        // Cached, so the export can still open the encrypted database once the file is moved.
+        if database_key().is_none() {
+            return Err(UmbreonError::storage("database key unreadable"));
+        }
+        let retired = retired_key_path();
+        std::fs::rename(database_key_path(), &retired).map_err(UmbreonError::storage)?;
+        if let Err(err) = export_database(None) {
+            let _ = std::fs::rename(&retired, database_key_path());
+            return Err(err);
+        }
+        *DATABASE_KEY.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        std::fs::remove_file(&retired).map_err(UmbreonError::storage)?;
+        let _ = std::fs::remove_file(db_path().with_extension("db.backup"));
+        Ok(())
+    }
+
+    /// Re-encrypts the database under a new random key. The new key is written beside the
+    /// current one first; if the rekey is interrupted, [`recover_database_key`] picks
+    /// whichever key opens the database on the next launch.
+    pub fn rekey_database() -> Result<(), UmbreonError> {
+        require_sqlcipher()?;
+        if !database_encrypted() {
+            return Err(UmbreonError::config(
+                "数据库加密",
+                "database is not encrypted",
+            ));
+        }
+        let key = MasterKey::generate().to_hex();
+        let pending = pending_key_path();
+        write_database_key(&pending, &key)?;
+        let conn = open_db().map_err(UmbreonError::storage)?;
+        if let Err(err) = conn.execute_batch(&format!("PRAGMA rekey = \"x'{key}'\";")) {
+            let _ = std::fs::remove_file(&pending);
+            return Err(UmbreonError::storage(err));
+        }
+        drop(conn);
+        promote_pending_key(key)
+    }
+
+    /// Writes `contents` through a temp file that is created readable by the current user
+    /// only and then renamed over `path`, so the key is never on disk with wider
+    /// permissions, nor half-written.
+    fn write_private_file(path: &Path, contents: &str) -> Result<(), UmbreonError> {
+        let mut staging = path.as_os_str().to_owned();
+        staging.push(".tmp");
+        let staging = PathBuf::from(staging);
+        let _ = std::fs::remove_file(&staging);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let result = options
+            .open(&staging)
+            .and_then(|mut file| {
+                file.write_all(contents.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&staging, path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&staging);
+        }
+        result.map_err(UmbreonError::storage)
+    }
+
+    /// Writes the device key readable by the current user only. Platform keystores
+    /// (Android Keystore, iOS Keychain) can replace this file behind the same calls.
+    pub fn store_device_key(encoded: &str) -> Result<(), UmbreonError> {
+        write_private_file(&device_key_path(), encoded)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod imp {
+    use super::{
+        ChatMessage, Conversation, DanmakuAttachment, EmbeddingKind, FeedItem, FeedMark, HashMap,
+        LiveStream, LlmUsageRecord, LocalMemory, OutboxEntry, OverlaySettings, PriceTable,
+        StoredEmbedding, StoredSettings, ThemeMode, TimelineOrder, UmbreonError,
+    };
+
+    const FEED_SERVER_STORAGE_KEY: &str = "umbreon.feed_server_url";
+    const GIST_STORAGE_KEY_LEGACY: &str = "umbreon.gist_url";
+    const MEMORY_SERVER_STORAGE_KEY: &str = "umbreon.memory_server_url";
+    const LLM_ENDPOINT_STORAGE_KEY: &str = "umbreon.llm_endpoint";
+    const LLM_API_KEY_STORAGE_KEY_LEGACY: &str = "umbreon.llm_api_key";
+    const LLM_MODEL_STORAGE_KEY: &str = "umbreon.llm_model";
+    const LLM_MODELS_STORAGE_KEY: &str = "umbreon.llm_models";
+    const LLM_EMBEDDING_MODEL_STORAGE_KEY: &str = "umbreon.llm_embedding_model";
+    const LLM_PRICES_STORAGE_KEY: &str = "umbreon.llm_prices";
+    const LLM_MONTHLY_BUDGET_STORAGE_KEY: &str = "umbreon.llm_monthly_budget";
+    const CONFIG_URL_STORAGE_KEY: &str = "umbreon.config_url";
+    const REMOTE_CONFIG_STORAGE_KEY: &str = "umbreon.remote_config";
+    const THEME_STORAGE_KEY: &str = "umbreon.theme";
+    const TIMELINE_ORDER_STORAGE_KEY: &str = "umbreon.timeline_order";
+    const SECRET_KEY_PARAMS_STORAGE_KEY: &str = "umbreon.secret_key_params";
+    const LIVE_PLAYLISTS_STORAGE_KEY: &str = "umbreon.live_playlists";
+    const LIVE_QUALITY_STORAGE_KEY: &str = "umbreon.live_quality";
+    const LIVE_MAX_BANDWIDTH_STORAGE_KEY: &str = "umbreon.live_max_bandwidth";
+    const LIVE_LOW_LATENCY_STORAGE_KEY: &str = "umbreon.live_low_latency";
+    const LIVE_TARGET_LATENCY_STORAGE_KEY: &str = "umbreon.live_target_latency";
+    const DANMAKU_OVERLAY_STORAGE_KEY: &str = "umbreon.danmaku_overlay";
+    const DANMAKU_ATTACHMENTS_STORAGE_KEY: &str = "umbreon.danmaku_attachments";
+    const SECRET_STORAGE_PREFIX: &str = "umbreon.secret.";
+    /// A re-seal in progress: the new key params and every secret sealed under them, written
+    /// in one item before they are applied and removed after.
+    const SECRET_RESEAL_STORAGE_KEY: &str = "umbreon.secret_reseal";
+    const DEVICE_KEY_STORAGE_KEY: &str = "umbreon.device_key";
+    const LLM_USAGE_STORAGE_KEY: &str = "umbreon.llm_usage";
+    /// Every origin's channels in one list, in load order within each origin.
+    const LIVE_CHANNELS_STORAGE_KEY: &str = "umbreon.live_channels";
+    const CONVERSATIONS_STORAGE_KEY: &str = "umbreon.conversations";
+    /// Followed by the conversation id; each conversation's messages are one list.
+    const CHAT_MESSAGES_STORAGE_PREFIX: &str = "umbreon.chat_messages.";
+    const FEED_MARKS_STORAGE_KEY: &str = "umbreon.feed_marks";
+    const MEMORIES_STORAGE_KEY: &str = "umbreon.memories";
+    const OUTBOX_STORAGE_KEY: &str = "umbreon.memory_outbox";
+    /// Last `seq` handed out; like the native AUTOINCREMENT it never goes back, so a drained
+    /// outbox does not reuse the numbers of entries a running flush still refers to.
+    const OUTBOX_SEQ_STORAGE_KEY: &str = "umbreon.memory_outbox_seq";
+    /// Usage older than this is dropped on write; the dashboard and budget only look at the
+    /// current month.
+    const LLM_USAGE_RETENTION_SECS: i64 = 62 * 24 * 3600;
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok().flatten()
+    }
+
+    fn load_json<T: serde::de::DeserializeOwned>(key: &str) -> Option<T> {
+        let value = local_storage()?.get_item(key).ok()??;
+        serde_json::from_str(&value).ok()
+    }
+
+    fn store_json<T: serde::Serialize + ?Sized>(key: &str, value: &T) {
+        let Ok(value) = serde_json::to_string(value) else {
+            return;
+        };
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(key, &value);
+        }
+    }
+
+    fn theme_from_value(value: &str) -> Option<ThemeMode> {
+        match value {
+            "light" => Some(ThemeMode::Light),
+            "dark" => Some(ThemeMode::Dark),
+            _ => None,
+        }
+    }
+
+    fn theme_to_value(theme: ThemeMode) -> &'static str {
+        match theme {
+            ThemeMode::Light => "light",
+            ThemeMode::Dark => "dark",
+        }
+    }
+
+    fn models_to_value(models: &[String]) -> Option<String> {
+        serde_json::to_string(models).ok()
+    }
+
+    fn models_from_value(value: &str) -> Option<Vec<String>> {
+        serde_json::from_str::<Vec<String>>(value).ok()
+    }
+
+    fn prices_to_value(prices: &PriceTable) -> Option<String> {
+        serde_json::to_string(prices).ok()
+    }
+
+    fn prices_from_value(value: &str) -> Option<PriceTable> {
+        serde_json::from_str::<PriceTable>(value).ok()
+    }
+
+    pub fn load_settings() -> StoredSettings {
+        finish_reseal();
+        let mut settings = StoredSettings::default();
+        let Some(window) = web_sys::window() else {
+            return settings;
+        };
+        let Ok(Some(storage)) = window.local_storage() else {
+            return settings;
+        };
+        if let Ok(Some(value)) = storage.get_item(FEED_SERVER_STORAGE_KEY) {
+            settings.feed_server_url = Some(value);
+        } else if let Ok(Some(value)) = storage.get_item(GIST_STORAGE_KEY_LEGACY) {
+            settings.feed_server_url = Some(value);
+        }
+        if let Ok(Some(value)) = storage.get_item(MEMORY_SERVER_STORAGE_KEY) {
             settings.memory_server_url = Some(value);
         }
         if let Ok(Some(value)) = storage.get_item(LLM_ENDPOINT_STORAGE_KEY) {
             settings.llm_endpoint = Some(value);
         }
-        if let Ok(Some(value)) = storage.get_item(LLM_API_KEY_STORAGE_KEY) {
-            settings.llm_api_key = Some(value);
-        }
         if let Ok(Some(value)) = storage.get_item(LLM_MODEL_STORAGE_KEY) {
             settings.llm_model = Some(value);
         }
         if let Ok(Some(value)) = storage.get_item(LLM_MODELS_STORAGE_KEY) {
             settings.llm_models = models_from_value(&value);
         }
+        if let Ok(Some(value)) = storage.get_item(LLM_EMBEDDING_MODEL_STORAGE_KEY) {
+            settings.llm_embedding_model = Some(value);
+        }
+        if let Ok(Some(value)) = storage.get_item(LLM_PRICES_STORAGE_KEY) {
+            settings.llm_prices = prices_from_value(&value);
+        }
+        if let Ok(Some(value)) = storage.get_item(LLM_MONTHLY_BUDGET_STORAGE_KEY) {
+            settings.llm_monthly_budget = value.trim().parse::<f64>().ok();
+        }
+        if let Ok(Some(value)) = storage.get_item(CONFIG_URL_STORAGE_KEY) {
+            settings.config_url = Some(value);
+        }
+        if let Ok(Some(value)) = storage.get_item(REMOTE_CONFIG_STORAGE_KEY) {
+            settings.remote_config = Some(value);
+        }
         if let Ok(Some(value)) = storage.get_item(THEME_STORAGE_KEY) {
             settings.theme = theme_from_value(&value);
         }
+        if let Ok(Some(value)) = storage.get_item(TIMELINE_ORDER_STORAGE_KEY) {
+            settings.timeline_order = Some(TimelineOrder::from_value(&value));
+        }
+        if let Ok(Some(value)) = storage.get_item(SECRET_KEY_PARAMS_STORAGE_KEY) {
+            settings.secret_key_params = Some(value);
+        }
+        if let Ok(Some(value)) = storage.get_item(LIVE_PLAYLISTS_STORAGE_KEY) {
+            settings.live_playlists = models_from_value(&value);
+        }
+        if let Ok(Some(value)) = storage.get_item(LIVE_QUALITY_STORAGE_KEY) {
+            settings.live_quality = Some(value);
+        }
+        if let Ok(Some(value)) = storage.get_item(LIVE_MAX_BANDWIDTH_STORAGE_KEY) {
+            settings.live_max_bandwidth = value.trim().parse::<u64>().ok();
+        }
+        if let Ok(Some(value)) = storage.get_item(LIVE_LOW_LATENCY_STORAGE_KEY) {
+            settings.live_low_latency = Some(value == "true");
+        }
+        if let Ok(Some(value)) = storage.get_item(LIVE_TARGET_LATENCY_STORAGE_KEY) {
+            settings.live_target_latency = value.trim().parse::<f64>().ok();
+        }
+        if let Ok(Some(value)) = storage.get_item(DANMAKU_OVERLAY_STORAGE_KEY) {
+            settings.danmaku_overlay = serde_json::from_str(&value).ok();
+        }
+        if let Ok(Some(value)) = storage.get_item(DANMAKU_ATTACHMENTS_STORAGE_KEY) {
+            settings.danmaku_attachments = serde_json::from_str(&value).ok();
+        }
         settings
     }
 
@@ -355,22 +1619,42 @@ mod imp {
         }
     }
 
-    pub fn store_llm_endpoint(endpoint: &str) {
+    pub fn store_config_url(url: &str) {
         if let Some(window) = web_sys::window() {
             if let Ok(Some(storage)) = window.local_storage() {
-                let _ = storage.set_item(LLM_ENDPOINT_STORAGE_KEY, endpoint);
+                let _ = storage.set_item(CONFIG_URL_STORAGE_KEY, url);
+            }
+        }
+    }
+
+    pub fn store_remote_config(text: &str) {
+        if let Some(window) = web_sys::window() {
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = storage.set_item(REMOTE_CONFIG_STORAGE_KEY, text);
             }
         }
     }
 
-    pub fn store_llm_api_key(api_key: &str) {
+    pub fn store_llm_endpoint(endpoint: &str) {
         if let Some(window) = web_sys::window() {
             if let Ok(Some(storage)) = window.local_storage() {
-                let _ = storage.set_item(LLM_API_KEY_STORAGE_KEY, api_key);
+                let _ = storage.set_item(LLM_ENDPOINT_STORAGE_KEY, endpoint);
             }
         }
     }
 
+    pub fn load_legacy_llm_api_key() -> Option<String> {
+        local_storage()?
+            .get_item(LLM_API_KEY_STORAGE_KEY_LEGACY)
+            .ok()?
+    }
+
+    pub fn clear_legacy_llm_api_key() {
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(LLM_API_KEY_STORAGE_KEY_LEGACY);
+        }
+    }
+
     pub fn store_llm_model(model: &str) {
         if let Some(window) = web_sys::window() {
             if let Ok(Some(storage)) = window.local_storage() {
@@ -379,6 +1663,14 @@ mod imp {
         }
     }
 
+    pub fn store_llm_embedding_model(model: &str) {
+        if let Some(window) = web_sys::window() {
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = storage.set_item(LLM_EMBEDDING_MODEL_STORAGE_KEY, model);
+            }
+        }
+    }
+
     pub fn store_llm_models(models: &[String]) {
         let Some(value) = models_to_value(models) else {
             return;
@@ -390,6 +1682,79 @@ mod imp {
         }
     }
 
+    pub fn store_live_playlists(urls: &[String]) {
+        let Some(value) = models_to_value(urls) else {
+            return;
+        };
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(LIVE_PLAYLISTS_STORAGE_KEY, &value);
+        }
+    }
+
+    pub fn store_live_quality(quality: &str) {
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(LIVE_QUALITY_STORAGE_KEY, quality);
+        }
+    }
+
+    pub fn store_danmaku_attachments(attachments: &HashMap<String, DanmakuAttachment>) {
+        let Ok(value) = serde_json::to_string(attachments) else {
+            return;
+        };
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(DANMAKU_ATTACHMENTS_STORAGE_KEY, &value);
+        }
+    }
+
+    pub fn store_danmaku_overlay(overlay: &OverlaySettings) {
+        let Ok(value) = serde_json::to_string(overlay) else {
+            return;
+        };
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(DANMAKU_OVERLAY_STORAGE_KEY, &value);
+        }
+    }
+
+    pub fn store_live_low_latency(enabled: bool, target_latency: f64) {
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(LIVE_LOW_LATENCY_STORAGE_KEY, &enabled.to_string());
+            let _ = storage.set_item(LIVE_TARGET_LATENCY_STORAGE_KEY, &target_latency.to_string());
+        }
+    }
+
+    pub fn store_live_max_bandwidth(max_bandwidth: Option<u64>) {
+        if let Some(storage) = local_storage() {
+            let _ = match max_bandwidth {
+                Some(value) => storage.set_item(LIVE_MAX_BANDWIDTH_STORAGE_KEY, &value.to_string()),
+                None => storage.remove_item(LIVE_MAX_BANDWIDTH_STORAGE_KEY),
+            };
+        }
+    }
+
+    pub fn store_llm_prices(prices: &PriceTable) {
+        let Some(value) = prices_to_value(prices) else {
+            return;
+        };
+        if let Some(window) = web_sys::window() {
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = storage.set_item(LLM_PRICES_STORAGE_KEY, &value);
+            }
+        }
+    }
+
+    pub fn store_llm_monthly_budget(budget: Option<f64>) {
+        if let Some(window) = web_sys::window() {
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = match budget {
+                    Some(value) => {
+                        storage.set_item(LLM_MONTHLY_BUDGET_STORAGE_KEY, &value.to_string())
+                    }
+                    None => storage.remove_item(LLM_MONTHLY_BUDGET_STORAGE_KEY),
+                };
+            }
+        }
+    }
+
     pub fn store_theme(theme: ThemeMode) {
         if let Some(window) = web_sys::window() {
             if let Ok(Some(storage)) = window.local_storage() {
@@ -398,16 +1763,443 @@ mod imp {
         }
     }
 
+    pub fn store_timeline_order(order: TimelineOrder) {
+        if let Some(window) = web_sys::window() {
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = storage.set_item(TIMELINE_ORDER_STORAGE_KEY, order.as_str());
+            }
+        }
+    }
+
     pub fn load_feed_items() -> Vec<FeedItem> {
         Vec::new()
     }
 
-    pub fn store_feed_items(_items: &[FeedItem]) -> Result<(), String> {
+    pub fn store_feed_items(_items: &[FeedItem]) -> Result<(), UmbreonError> {
+        Ok(())
+    }
+
+    fn load_llm_usage() -> Vec<LlmUsageRecord> {
+        local_storage()
+            .and_then(|storage| storage.get_item(LLM_USAGE_STORAGE_KEY).ok().flatten())
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn store_llm_usage(record: &LlmUsageRecord) {
+        let cutoff = record.created_ts - LLM_USAGE_RETENTION_SECS;
+        let mut records = load_llm_usage();
+        records.retain(|existing| existing.created_ts >= cutoff);
+        records.push(record.clone());
+        let Ok(value) = serde_json::to_string(&records) else {
+            return;
+        };
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(LLM_USAGE_STORAGE_KEY, &value);
+        }
+    }
+
+    pub fn load_llm_usage_since(since_ts: i64) -> Vec<LlmUsageRecord> {
+        let mut records = load_llm_usage();
+        records.retain(|record| record.created_ts >= since_ts);
+        records.sort_by_key(|record| std::cmp::Reverse(record.created_ts));
+        records
+    }
+
+    pub fn load_conversations() -> Vec<Conversation> {
+        let mut conversations =
+            load_json::<Vec<Conversation>>(CONVERSATIONS_STORAGE_KEY).unwrap_or_default();
+        conversations.sort_by_key(|conversation| std::cmp::Reverse(conversation.updated_ts));
+        conversations
+    }
+
+    /// Inserts or updates `conversation`; like the native upsert, the linked item, context and
+    /// creation time of an existing conversation are kept.
+    pub fn store_conversation(conversation: &Conversation) {
+        let mut conversations =
+            load_json::<Vec<Conversation>>(CONVERSATIONS_STORAGE_KEY).unwrap_or_default();
+        match conversations
+            .iter_mut()
+            .find(|existing| existing.id == conversation.id)
+        {
+            Some(existing) => {
+                existing.title = conversation.title.clone();
+                existing.mode = conversation.mode;
+                existing.backend = conversation.backend;
+                existing.updated_ts = conversation.updated_ts;
+            }
+            None => conversations.push(conversation.clone()),
+        }
+        store_json(CONVERSATIONS_STORAGE_KEY, &conversations);
+    }
+
+    pub fn delete_conversation(id: &str) {
+        let mut conversations =
+            load_json::<Vec<Conversation>>(CONVERSATIONS_STORAGE_KEY).unwrap_or_default();
+        conversations.retain(|conversation| conversation.id != id);
+        store_json(CONVERSATIONS_STORAGE_KEY, &conversations);
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(&format!("{CHAT_MESSAGES_STORAGE_PREFIX}{id}"));
+        }
+    }
+
+    pub fn load_chat_messages(conversation_id: &str) -> Vec<ChatMessage> {
+        load_json(&format!("{CHAT_MESSAGES_STORAGE_PREFIX}{conversation_id}")).unwrap_or_default()
+    }
+
+    pub fn append_chat_message(message: &ChatMessage) {
+        let mut messages = load_chat_messages(&message.conversation_id);
+        messages.push(message.clone());
+        store_json(
+            &format!("{CHAT_MESSAGES_STORAGE_PREFIX}{}", message.conversation_id),
+            &messages,
+        );
+    }
+
+    pub fn load_feed_marks() -> Vec<FeedMark> {
+        load_json(FEED_MARKS_STORAGE_KEY).unwrap_or_default()
+    }
+
+    pub fn store_feed_mark(mark: &FeedMark) {
+        let mut marks = load_feed_marks();
+        match marks
+            .iter_mut()
+            .find(|existing| existing.feed_id == mark.feed_id)
+        {
+            Some(existing) => *existing = mark.clone(),
+            None => marks.push(mark.clone()),
+        }
+        store_json(FEED_MARKS_STORAGE_KEY, &marks);
+    }
+
+    pub fn delete_feed_mark(feed_id: &str) {
+        let mut marks = load_feed_marks();
+        marks.retain(|mark| mark.feed_id != feed_id);
+        store_json(FEED_MARKS_STORAGE_KEY, &marks);
+    }
+
+    fn load_stored_memories() -> Vec<LocalMemory> {
+        load_json(MEMORIES_STORAGE_KEY).unwrap_or_default()
+    }
+
+    pub fn load_local_memories() -> Vec<LocalMemory> {
+        let mut memories = load_stored_memories();
+        memories.sort_by_key(|memory| std::cmp::Reverse(memory.updated_ts));
+        memories
+    }
+
+    pub fn load_local_memory(id: &str) -> Option<LocalMemory> {
+        load_stored_memories()
+            .into_iter()
+            .find(|memory| memory.id == id)
+    }
+
+    /// Inserts or updates `memory`, keeping the creation time of an existing one.
+    pub fn store_local_memory(memory: &LocalMemory) {
+        let mut memories = load_stored_memories();
+        match memories
+            .iter_mut()
+            .find(|existing| existing.id == memory.id)
+        {
+            Some(existing) => {
+                *existing = LocalMemory {
+                    created_ts: existing.created_ts,
+                    ..memory.clone()
+                }
+            }
+            None => memories.push(memory.clone()),
+        }
+        store_json(MEMORIES_STORAGE_KEY, &memories);
+    }
+
+    pub fn delete_local_memory(id: &str) {
+        let mut memories = load_stored_memories();
+        memories.retain(|memory| memory.id != id);
+        store_json(MEMORIES_STORAGE_KEY, &memories);
+    }
+
+    /// Appends `entry` with the next `seq`; an entry whose idempotency key is already queued
+    /// is ignored.
+    pub fn enqueue_outbox(entry: &OutboxEntry) {
+        let mut outbox = load_outbox();
+        if outbox
+            .iter()
+            .any(|queued| queued.idempotency_key == entry.idempotency_key)
+        {
+            return;
+        }
+        let last_seq = load_json::<i64>(OUTBOX_SEQ_STORAGE_KEY)
+            .unwrap_or_default()
+            .max(outbox.last().map_or(0, |queued| queued.seq));
+        let seq = last_seq + 1;
+        outbox.push(OutboxEntry {
+            seq,
+            ..entry.clone()
+        });
+        store_json(OUTBOX_SEQ_STORAGE_KEY, &seq);
+        store_json(OUTBOX_STORAGE_KEY, &outbox);
+    }
+
+    pub fn load_outbox() -> Vec<OutboxEntry> {
+        load_json(OUTBOX_STORAGE_KEY).unwrap_or_default()
+    }
+
+    pub fn update_outbox_entry(entry: &OutboxEntry) {
+        let mut outbox = load_outbox();
+        if let Some(queued) = outbox.iter_mut().find(|queued| queued.seq == entry.seq) {
+            queued.attempts = entry.attempts;
+            queued.next_attempt_ts = entry.next_attempt_ts;
+            queued.status = entry.status;
+            queued.last_error = entry.last_error.clone();
+            store_json(OUTBOX_STORAGE_KEY, &outbox);
+        }
+    }
+
+    pub fn delete_outbox_entry(seq: i64) {
+        let mut outbox = load_outbox();
+        outbox.retain(|queued| queued.seq != seq);
+        store_json(OUTBOX_STORAGE_KEY, &outbox);
+    }
+
+    pub fn load_embeddings(_kind: EmbeddingKind) -> Vec<StoredEmbedding> {
+        Vec::new()
+    }
+
+    pub fn store_embeddings(_embeddings: &[StoredEmbedding]) -> Result<(), UmbreonError> {
         Ok(())
     }
+
+    pub fn delete_embeddings(_kind: EmbeddingKind, _ref_ids: &[String]) {}
+
+    fn load_stored_channels() -> Vec<LiveStream> {
+        local_storage()
+            .and_then(|storage| storage.get_item(LIVE_CHANNELS_STORAGE_KEY).ok().flatten())
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default()
+    }
+
+    fn store_channels(channels: &[LiveStream]) -> Result<(), UmbreonError> {
+        let value = serde_json::to_string(channels)
+            .map_err(|err| UmbreonError::storage(format!("encode live channels failed: {err}")))?;
+        let storage = local_storage().ok_or_else(|| UmbreonError::storage("no localStorage"))?;
+        storage
+            .set_item(LIVE_CHANNELS_STORAGE_KEY, &value)
+            .map_err(|_| UmbreonError::storage("localStorage write failed"))
+    }
+
+    /// Config channels first, then each playlist's in playlist order, like the native query.
+    pub fn load_live_channels() -> Vec<LiveStream> {
+        let mut channels = load_stored_channels();
+        channels.sort_by(|a, b| {
+            (a.origin != "config", &a.origin).cmp(&(b.origin != "config", &b.origin))
+        });
+        channels
+    }
+
+    /// Replaces the channels of `origin`. Favorites and watch times of channels that are
+    /// still listed are kept.
+    pub fn replace_live_channels(
+        origin: &str,
+        channels: &[LiveStream],
+    ) -> Result<(), UmbreonError> {
+        let mut stored = load_stored_channels();
+        let state = stored
+            .iter()
+            .map(|channel| {
+                (
+                    channel.id.clone(),
+                    (channel.favorite, channel.last_watched_ts),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        stored.retain(|channel| {
+            channel.origin != origin && !channels.iter().any(|listed| listed.id == channel.id)
+        });
+        stored.extend(channels.iter().map(|channel| {
+            let (favorite, last_watched_ts) =
+                state.get(&channel.id).copied().unwrap_or((false, None));
+            LiveStream {
+                origin: origin.to_string(),
+                favorite,
+                last_watched_ts,
+                ..channel.clone()
+            }
+        }));
+        store_channels(&stored)
+    }
+
+    /// Drops the channels of every origin not in `origins`.
+    pub fn retain_live_origins(origins: &[String]) {
+        let mut channels = load_stored_channels();
+        let before = channels.len();
+        channels.retain(|channel| origins.contains(&channel.origin));
+        if channels.len() != before {
+            let _ = store_channels(&channels);
+        }
+    }
+
+    pub fn store_live_channel_state(id: &str, favorite: bool, last_watched_ts: Option<i64>) {
+        let mut channels = load_stored_channels();
+        if let Some(channel) = channels.iter_mut().find(|channel| channel.id == id) {
+            channel.favorite = favorite;
+            channel.last_watched_ts = last_watched_ts;
+            let _ = store_channels(&channels);
+        }
+    }
+
+    pub fn load_secrets() -> Vec<(String, String)> {
+        finish_reseal();
+        let Some(storage) = local_storage() else {
+            return Vec::new();
+        };
+        let count = storage.length().unwrap_or_default();
+        let mut secrets = (0..count)
+            .filter_map(|index| storage.key(index).ok().flatten())
+            .filter_map(|key| {
+                let name = key.strip_prefix(SECRET_STORAGE_PREFIX)?.to_string();
+                let value = storage.get_item(&key).ok().flatten()?;
+                Some((name, value))
+            })
+            .collect::<Vec<_>>();
+        secrets.sort();
+        secrets
+    }
+
+    pub fn store_secret(name: &str, sealed: &str) -> Result<(), UmbreonError> {
+        let storage = local_storage().ok_or_else(|| UmbreonError::storage("no localStorage"))?;
+        storage
+            .set_item(&format!("{SECRET_STORAGE_PREFIX}{name}"), sealed)
+            .map_err(|_| UmbreonError::storage("localStorage write failed"))
+    }
+
+    pub fn delete_secret(name: &str) {
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(&format!("{SECRET_STORAGE_PREFIX}{name}"));
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct PendingReseal {
+        key_params: String,
+        sealed: Vec<(String, String)>,
+    }
+
+    /// localStorage has no transactions, so the re-sealed secrets and their key params are
+    /// first written as one pending item; [`finish_reseal`] applies it, now or on the next
+    /// load if the page was closed halfway.
+    pub fn reseal_secrets(
+        sealed: &[(String, String)],
+        key_params: &str,
+    ) -> Result<(), UmbreonError> {
+        let storage = local_storage().ok_or_else(|| UmbreonError::storage("no localStorage"))?;
+        let pending = PendingReseal {
+            key_params: key_params.to_string(),
+            sealed: sealed.to_vec(),
+        };
+        let value = serde_json::to_string(&pending).map_err(UmbreonError::storage)?;
+        storage
+            .set_item(SECRET_RESEAL_STORAGE_KEY, &value)
+            .map_err(|_| UmbreonError::storage("localStorage write failed"))?;
+        finish_reseal();
+        Ok(())
+    }
+
+    fn finish_reseal() {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        let Some(pending) = load_json::<PendingReseal>(SECRET_RESEAL_STORAGE_KEY) else {
+            return;
+        };
+        for (name, value) in &pending.sealed {
+            if storage
+                .set_item(&format!("{SECRET_STORAGE_PREFIX}{name}"), value)
+                .is_err()
+            {
+                return;
+            }
+        }
+        if storage
+            .set_item(SECRET_KEY_PARAMS_STORAGE_KEY, &pending.key_params)
+            .is_ok()
+        {
+            let _ = storage.remove_item(SECRET_RESEAL_STORAGE_KEY);
+        }
+    }
+
+    pub fn load_device_key() -> Option<String> {
+        local_storage()?.get_item(DEVICE_KEY_STORAGE_KEY).ok()?
+    }
+
+    pub fn store_backup_file(_name: &str, _contents: &str) -> Result<String, UmbreonError> {
+        Err(UmbreonError::config("备份", "not available on the web"))
+    }
+
+    pub fn list_backup_files() -> Vec<String> {
+        Vec::new()
+    }
+
+    pub fn store_export_file(_name: &str, _contents: &str) -> Result<String, UmbreonError> {
+        Err(UmbreonError::config("导出", "not available on the web"))
+    }
+
+    pub fn read_local_file(_path: &str) -> Result<String, UmbreonError> {
+        Err(UmbreonError::config("文件", "not available on the web"))
+    }
+
+    pub fn database_encryption_supported() -> bool {
+        false
+    }
+
+    pub fn database_encrypted() -> bool {
+        false
+    }
+
+    pub fn encrypt_database() -> Result<(), UmbreonError> {
+        Err(UmbreonError::config(
+            "数据库加密",
+            "not available on the web",
+        ))
+    }
+
+    pub fn decrypt_database() -> Result<(), UmbreonError> {
+        Err(UmbreonError::config(
+            "数据库加密",
+            "not available on the web",
+        ))
+    }
+
+    pub fn rekey_database() -> Result<(), UmbreonError> {
+        Err(UmbreonError::config(
+            "数据库加密",
+            "not available on the web",
+        ))
+    }
+
+    /// The browser has no keystore reachable from here, so the key sits next to the data;
+    /// set a passphrase for real protection on the web build.
+    pub fn store_device_key(encoded: &str) -> Result<(), UmbreonError> {
+        let storage = local_storage().ok_or_else(|| UmbreonError::storage("no localStorage"))?;
+        storage
+            .set_item(DEVICE_KEY_STORAGE_KEY, encoded)
+            .map_err(|_| UmbreonError::storage("localStorage write failed"))
+    }
 }
 
 pub use imp::{
-    load_feed_items, load_settings, store_feed_items, store_feed_server_url, store_llm_api_key,
-    store_llm_endpoint, store_llm_model, store_llm_models, store_memory_server_url, store_theme,
+    append_chat_message, clear_legacy_llm_api_key, database_encrypted,
+    database_encryption_supported, decrypt_database, delete_conversation, delete_embeddings,
+    delete_feed_mark, delete_local_memory, delete_outbox_entry, delete_secret, encrypt_database,
+    enqueue_outbox, list_backup_files, load_chat_messages, load_conversations, load_device_key,
+    load_embeddings, load_feed_items, load_feed_marks, load_legacy_llm_api_key, load_live_channels,
+    load_llm_usage_since, load_local_memories, load_local_memory, load_outbox, load_secrets,
+    load_settings, read_local_file, rekey_database, replace_live_channels, reseal_secrets,
+    retain_live_origins, store_backup_file, store_config_url, store_conversation,
+    store_danmaku_attachments, store_danmaku_overlay, store_device_key, store_embeddings,
+    store_export_file, store_feed_items, store_feed_mark, store_feed_server_url,
+    store_live_channel_state, store_live_low_latency, store_live_max_bandwidth,
+    store_live_playlists, store_live_quality, store_llm_embedding_model, store_llm_endpoint,
+    store_llm_model, store_llm_models, store_llm_monthly_budget, store_llm_prices, store_llm_usage,
+    store_local_memory, store_memory_server_url, store_remote_config, store_secret, store_theme,
+    store_timeline_order, update_outbox_entry,
 };
@@ -108,7 +108,15 @@ pub const BASE_STYLES: &str = r#"
   justify-content: center;
 }
 
-.collapse-toggle .material-icons {
+.collapse-toggle .post-action--pending .material-icons {
+  animation: spin 1s linear infinite;
+}
+
+.post-action--failed {
+  color: var(--md-sys-color-error, #b3261e);
+}
+
+.material-icons {
   font-size: 20px;
   line-height: 1;
 }
@@ -442,6 +450,53 @@ pub const BASE_STYLES: &str = r#"
   justify-content: flex-start;
 }
 
+.usage-spend {
+  margin: 0;
+  font-size: 15px;
+  font-weight: 600;
+}
+
+.usage-warning {
+  color: var(--md-sys-color-error, #b3261e);
+}
+
+.usage-price-row .usage-model {
+  flex: 1.4;
+  font-size: 13px;
+  overflow: hidden;
+  text-overflow: ellipsis;
+  white-space: nowrap;
+}
+
+.usage-price-input {
+  min-width: 0;
+}
+
+.usage-table {
+  width: 100%;
+  border-collapse: collapse;
+  font-size: 12px;
+  color: var(--md-sys-color-on-surface);
+}
+
+.usage-table caption {
+  text-align: left;
+  padding-bottom: 4px;
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.usage-table th,
+.usage-table td {
+  padding: 4px 6px;
+  text-align: right;
+  border-bottom: 1px solid var(--md-sys-color-outline-variant);
+}
+
+.usage-table th:first-child,
+.usage-table td:first-child {
+  text-align: left;
+}
+
 .toast {
   position: absolute;
   left: 50%;
@@ -467,6 +522,16 @@ pub const BASE_STYLES: &str = r#"
   color: var(--md-sys-color-on-error-container);
 }
 
+.toast-action {
+  border: none;
+  background: transparent;
+  color: inherit;
+  font-weight: 600;
+  text-decoration: underline;
+  cursor: pointer;
+  white-space: nowrap;
+}
+
 .toast-close {
   border: none;
   background: transparent;
@@ -476,6 +541,226 @@ pub const BASE_STYLES: &str = r#"
   align-items: center;
 }
 
+.dialogue-pane {
+  flex: 1;
+  display: flex;
+  flex-direction: column;
+  gap: 12px;
+  padding: 16px 18px;
+  overflow: hidden;
+  background: var(--md-sys-color-background);
+}
+
+.dialogue-toolbar {
+  display: flex;
+  align-items: center;
+  gap: 10px;
+}
+
+.dialogue-title {
+  flex: 1;
+  font-weight: 600;
+  overflow: hidden;
+  text-overflow: ellipsis;
+  white-space: nowrap;
+}
+
+.dialogue-mode {
+  flex: 0 0 96px;
+}
+
+.conversation-list {
+  list-style: none;
+  margin: 0;
+  padding: 0;
+  display: flex;
+  flex-direction: column;
+  gap: 8px;
+  overflow: auto;
+}
+
+.conversation-row {
+  display: flex;
+  align-items: center;
+  gap: 10px;
+  padding: 12px 14px;
+  border-radius: 14px;
+  background: var(--md-sys-color-surface);
+  cursor: pointer;
+}
+
+.conversation-main {
+  flex: 1;
+  display: flex;
+  flex-direction: column;
+  gap: 4px;
+  min-width: 0;
+}
+
+.conversation-title {
+  font-weight: 600;
+  overflow: hidden;
+  text-overflow: ellipsis;
+  white-space: nowrap;
+}
+
+.conversation-meta {
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.conversation-delete {
+  border: none;
+  background: transparent;
+  color: var(--md-sys-color-on-surface-variant);
+  cursor: pointer;
+}
+
+.memory-pane {
+  padding: 18px;
+  display: flex;
+  flex-direction: column;
+  gap: 12px;
+  flex: 1;
+  overflow: auto;
+  background: var(--md-sys-color-background);
+}
+
+.memory-filters {
+  display: flex;
+  flex-wrap: wrap;
+  gap: 8px;
+}
+
+.memory-filter {
+  border: none;
+  border-radius: 999px;
+  padding: 6px 12px;
+  background: var(--md-sys-color-surface-container);
+  color: var(--md-sys-color-on-surface-variant);
+  cursor: pointer;
+}
+
+.memory-filter.is-active {
+  background: var(--md-sys-color-primary);
+  color: var(--md-sys-color-on-primary);
+}
+
+.memory-row {
+  align-items: flex-start;
+  cursor: default;
+}
+
+.memory-content {
+  white-space: pre-wrap;
+  overflow-wrap: anywhere;
+}
+
+.memory-state {
+  color: var(--md-sys-color-primary);
+}
+
+.memory-state--pending {
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.memory-state--failed {
+  color: var(--md-sys-color-error, #b3261e);
+}
+
+.memory-score {
+  min-width: 36px;
+  font-variant-numeric: tabular-nums;
+  color: var(--md-sys-color-primary);
+}
+
+.outbox-panel {
+  display: flex;
+  flex-direction: column;
+  gap: 8px;
+}
+
+.outbox-error {
+  color: var(--md-sys-color-error, #b3261e);
+  overflow-wrap: anywhere;
+}
+
+.conversation-icon {
+  color: var(--md-sys-color-primary);
+}
+
+.dialogue-article {
+  display: inline-flex;
+  align-items: center;
+  gap: 6px;
+  font-size: 12px;
+  color: var(--md-sys-color-primary);
+  overflow: hidden;
+  text-overflow: ellipsis;
+  white-space: nowrap;
+}
+
+.chat-messages {
+  flex: 1;
+  overflow: auto;
+  display: flex;
+  flex-direction: column;
+  gap: 10px;
+}
+
+.chat-bubble {
+  max-width: 85%;
+  padding: 10px 12px;
+  border-radius: 16px;
+  line-height: 1.5;
+}
+
+.chat-bubble p {
+  margin: 0;
+  white-space: pre-wrap;
+}
+
+.chat-bubble--user {
+  align-self: flex-end;
+  background: var(--md-sys-color-primary);
+  color: var(--md-sys-color-on-primary);
+}
+
+.chat-bubble--assistant {
+  align-self: flex-start;
+  background: var(--md-sys-color-surface);
+  color: var(--md-sys-color-on-surface);
+}
+
+.chat-time {
+  display: block;
+  margin-top: 4px;
+  font-size: 11px;
+  opacity: 0.7;
+}
+
+.chat-typing {
+  animation: spin 1s linear infinite;
+}
+
+.chat-composer {
+  display: flex;
+  flex-direction: column;
+  gap: 6px;
+}
+
+.chat-memory-toggle {
+  display: inline-flex;
+  align-items: center;
+  gap: 6px;
+  font-size: 13px;
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.chat-input {
+  resize: none;
+  font: inherit;
+}
+
 .explore-pane {
   padding: 18px;
   display: flex;
@@ -528,6 +813,14 @@ pub const BASE_STYLES: &str = r#"
   animation: spin 1s linear infinite;
 }
 
+.explore-order ~ .explore-sync {
+  margin-left: 0;
+}
+
+.explore-order.is-active {
+  color: var(--md-sys-color-primary);
+}
+
 .explore-card {
   background: var(--md-sys-color-surface);
   border-radius: 22px;
@@ -647,6 +940,50 @@ pub const BASE_STYLES: &str = r#"
   border-color: #f4a6c5;
 }
 
+.feed-card--boosted {
+  border-color: var(--md-sys-color-primary);
+}
+
+.post-boost {
+  margin: 0 0 6px;
+  display: flex;
+  align-items: center;
+  gap: 4px;
+  font-size: 12px;
+  color: var(--md-sys-color-primary);
+}
+
+.post-boost .material-icons {
+  font-size: 14px;
+}
+
+.post-cluster {
+  display: inline-flex;
+  align-items: center;
+  gap: 2px;
+  margin-left: 8px;
+  padding: 0;
+  border: none;
+  background: none;
+  font-size: 12px;
+  color: var(--md-sys-color-primary);
+  cursor: pointer;
+}
+
+.post-cluster .material-icons {
+  font-size: 16px;
+}
+
+.post-cluster-list {
+  margin: 4px 0 0;
+  padding-left: 18px;
+  font-size: 13px;
+}
+
+.post-cluster-list a {
+  color: var(--md-sys-color-on-surface-variant);
+}
+
 .post-avatar {
   width: 44px;
   height: 44px;
@@ -879,6 +1216,13 @@ pub const BASE_STYLES: &str = r#"
   color: var(--md-sys-color-primary);
 }
 
+.feed-modal-actions {
+  display: flex;
+  align-items: center;
+  justify-content: space-between;
+  gap: 10px;
+}
+
 .empty-state {
   color: var(--md-sys-color-on-surface-variant);
 }
@@ -893,4 +1237,212 @@ pub const BASE_STYLES: &str = r#"
 .timeline-spacer {
   height: 0;
 }
+
+.live-stream-list {
+  display: flex;
+  flex-direction: column;
+  gap: 12px;
+}
+
+.live-section h3 {
+  margin: 4px 0 8px;
+  font-size: 14px;
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.live-section ul {
+  list-style: none;
+  margin: 0;
+  padding: 0;
+  display: flex;
+  flex-direction: column;
+  gap: 8px;
+}
+
+.live-channel {
+  display: flex;
+  align-items: center;
+  gap: 12px;
+  padding: 10px 12px;
+  border-radius: 12px;
+  background: var(--md-sys-color-surface-container);
+  cursor: pointer;
+}
+
+.live-logo {
+  width: 36px;
+  height: 36px;
+  border-radius: 8px;
+  object-fit: contain;
+}
+
+.live-channel-text {
+  display: flex;
+  flex-direction: column;
+  gap: 2px;
+  flex: 1;
+  min-width: 0;
+}
+
+.live-channel-text small {
+  color: var(--md-sys-color-on-surface-variant);
+  overflow: hidden;
+  text-overflow: ellipsis;
+  white-space: nowrap;
+}
+
+.live-favorite {
+  border: none;
+  background: none;
+  color: var(--md-sys-color-primary);
+  cursor: pointer;
+}
+
+.live-health {
+  flex-shrink: 0;
+  padding: 2px 8px;
+  border-radius: 999px;
+  font-size: 11px;
+}
+
+.live-health.is-online {
+  background: rgba(46, 160, 67, 0.16);
+  color: #2ea043;
+}
+
+.live-health.is-offline {
+  background: rgba(248, 81, 73, 0.16);
+  color: #f85149;
+}
+
+.player-stage {
+  position: relative;
+}
+
+.player-video {
+  display: block;
+  width: 100%;
+  aspect-ratio: 16 / 9;
+  border-radius: 12px;
+  background: #000;
+}
+
+.player-controls {
+  display: flex;
+  flex-wrap: wrap;
+  align-items: center;
+  gap: 8px;
+  margin-top: 8px;
+}
+
+.player-toggle {
+  border: none;
+  border-radius: 50%;
+  width: 40px;
+  height: 40px;
+  background: var(--md-sys-color-primary);
+  color: var(--md-sys-color-on-primary);
+  cursor: pointer;
+}
+
+.player-toggle:disabled {
+  opacity: 0.5;
+  cursor: default;
+}
+
+.player-low-latency,
+.player-danmaku {
+  border: 1px solid var(--md-sys-color-outline);
+  border-radius: 999px;
+  background: none;
+  color: var(--md-sys-color-on-surface-variant);
+  cursor: pointer;
+}
+
+.player-low-latency.is-active,
+.player-danmaku.is-active {
+  border-color: var(--md-sys-color-primary);
+  color: var(--md-sys-color-primary);
+}
+
+.player-seek {
+  flex: 1;
+  min-width: 120px;
+}
+
+.player-time,
+.player-status {
+  font-size: 12px;
+  color: var(--md-sys-color-on-surface-variant);
+  font-variant-numeric: tabular-nums;
+}
+
+.player-error {
+  margin: 4px 0 0;
+  font-size: 12px;
+  color: var(--md-sys-color-error);
+}
+
+.danmaku-connection {
+  display: flex;
+  align-items: center;
+  gap: 4px;
+  margin: 4px 0 0;
+  font-size: 12px;
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.danmaku-connection .material-icons {
+  font-size: 16px;
+}
+
+.danmaku-connection.is-connected {
+  color: #2ea043;
+}
+
+.danmaku-file {
+  display: flex;
+  flex-direction: column;
+  gap: 6px;
+  margin-top: 8px;
+}
+
+.danmaku-file-row {
+  display: flex;
+  flex-wrap: wrap;
+  align-items: center;
+  gap: 8px;
+}
+
+.danmaku-file-row .player-danmaku {
+  padding: 6px 12px;
+}
+
+.danmaku-file-row .player-danmaku:disabled {
+  opacity: 0.5;
+  cursor: default;
+}
+
+.danmaku-file-status {
+  margin: 0;
+  font-size: 12px;
+  color: var(--md-sys-color-on-surface-variant);
+}
+
+.danmaku-overlay {
+  position: absolute;
+  inset: 0;
+  overflow: hidden;
+  border-radius: 12px;
+  pointer-events: none;
+  container-type: size;
+}
+
+.danmaku-item {
+  position: absolute;
+  white-space: pre;
+  line-height: 1.25;
+  font-weight: 600;
+  text-shadow: 0 0 2px rgba(0, 0, 0, 0.9), 1px 1px 1px rgba(0, 0, 0, 0.6);
+}
 "#;
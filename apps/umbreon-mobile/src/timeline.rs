@@ -1,10 +1,18 @@
+use crate::embeddings::{self, EmbeddingSettings};
 use crate::general_ai_client;
 use crate::helper;
-use crate::state::{self, FeedItem, FeedSourceKind};
+use crate::state::{
+    self, AppContext, FeedItem, FeedSourceKind, ToastAction, ToastMessage, UI_LOCALE,
+};
 use crate::storage;
+use crate::usage;
 use chrono::{FixedOffset, TimeZone};
 use dioxus::prelude::*;
 use feed_rs::model::FeedType;
+use umbreon_core::UmbreonError;
+
+/// How the feed server is named in errors shown to the user.
+const SERVICE: &str = "Feed Server";
 
 pub struct FeedBootstrap {
     pub feed_items: Vec<FeedItem>,
@@ -37,7 +45,7 @@ pub fn init_feed_bootstrap(stored_settings: &storage::StoredSettings) -> FeedBoo
     }
 }
 
-fn parse_feed_with_fallback(feed_bytes: &[u8]) -> Result<feed_rs::model::Feed, String> {
+fn parse_feed_with_fallback(feed_bytes: &[u8]) -> Result<feed_rs::model::Feed, UmbreonError> {
     if let Ok(feed) = feed_rs::parser::parse(feed_bytes) {
         return Ok(feed);
     }
@@ -60,13 +68,13 @@ fn parse_feed_with_fallback(feed_bytes: &[u8]) -> Result<feed_rs::model::Feed, S
             .replacen(r#"version=\"1.0\""#, r#"version=\"2.0\""#, 1)
             .replacen("version='1.0'", "version='2.0'", 1);
         return feed_rs::parser::parse(normalized.as_bytes())
-            .map_err(|err| format!("unable to parse feed: {err}"));
+            .map_err(|err| UmbreonError::parse("Feed", err));
     }
 
-    Err("unable to parse feed: no xml content".to_string())
+    Err(UmbreonError::parse("Feed", "no xml content"))
 }
 
-fn plain_text_from_html(input: &str) -> String {
+pub fn plain_text_from_html(input: &str) -> String {
     let mut output = String::new();
     let mut in_tag = false;
     for ch in input.chars() {
@@ -107,15 +115,20 @@ fn format_date_utc8(ts: i64) -> String {
         .unwrap_or_else(|| "1970-01-01".to_string())
 }
 
-pub async fn load_feeds_from_server(url: &str) -> Result<Vec<FeedItem>, String> {
-    let feed_bytes = reqwest::get(url)
+pub async fn load_feeds_from_server(url: &str) -> Result<Vec<FeedItem>, UmbreonError> {
+    let response = reqwest::get(url)
         .await
-        .map_err(|err| format!("failed to load feed server: {err}"))?
+        .map_err(|err| UmbreonError::network(SERVICE, err))?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(UmbreonError::status(SERVICE, status, body));
+    }
+    let feed_bytes = response
         .bytes()
         .await
-        .map_err(|err| format!("failed to read feed server: {err}"))?;
-    let parsed = parse_feed_with_fallback(feed_bytes.as_ref())
-        .map_err(|err| format!("failed to parse feed server: {err}"))?;
+        .map_err(|err| UmbreonError::network(SERVICE, err))?;
+    let parsed = parse_feed_with_fallback(feed_bytes.as_ref())?;
 
     let avatar_url = parsed
         .logo
@@ -168,25 +181,31 @@ pub async fn load_feeds_from_server(url: &str) -> Result<Vec<FeedItem>, String>
             .map(ToString::to_string)
             .or_else(|| entry.updated.as_ref().map(ToString::to_string))
             .ok_or_else(|| {
-                format!(
-                    "missing published/updated date in feed entry {}",
-                    if entry.id.is_empty() {
-                        title.clone()
-                    } else {
-                        entry.id.clone()
-                    }
+                UmbreonError::parse(
+                    "Feed",
+                    format!(
+                        "missing published/updated date in entry {}",
+                        if entry.id.is_empty() {
+                            title.clone()
+                        } else {
+                            entry.id.clone()
+                        }
+                    ),
                 )
             })?;
         let published_ts =
             helper::parse_timestamp_for_feed(&feed_type, &published_at).ok_or_else(|| {
-                format!(
-                    "invalid date '{}' in feed entry {}",
-                    published_at,
-                    if entry.id.is_empty() {
-                        title.clone()
-                    } else {
-                        entry.id.clone()
-                    }
+                UmbreonError::parse(
+                    "Feed",
+                    format!(
+                        "invalid date '{}' in entry {}",
+                        published_at,
+                        if entry.id.is_empty() {
+                            title.clone()
+                        } else {
+                            entry.id.clone()
+                        }
+                    ),
                 )
             })?;
         let published_at = format_date_utc8(published_ts);
@@ -235,7 +254,7 @@ pub async fn load_feeds_from_server(url: &str) -> Result<Vec<FeedItem>, String>
     }
 
     if items.is_empty() {
-        return Err("no feed entries found".to_string());
+        return Err(UmbreonError::parse("Feed", "no entries found"));
     }
 
     items.sort_by(|a, b| b.published_ts.cmp(&a.published_ts));
@@ -243,17 +262,18 @@ pub async fn load_feeds_from_server(url: &str) -> Result<Vec<FeedItem>, String>
     Ok(items)
 }
 
-pub fn trigger_feed_sync(
-    url: String,
-    mut feed_items: Signal<Vec<FeedItem>>,
-    mut settings_status: Signal<Option<String>>,
-    mut feed_syncing: Signal<bool>,
-    llm_endpoint: Signal<String>,
-    llm_api_key: Signal<String>,
-    llm_model: Signal<String>,
-) {
+pub fn trigger_feed_sync(url: String, ctx: &AppContext) {
+    let mut feed_items = ctx.feed_items;
+    let mut settings_status = ctx.settings_status;
+    let mut feed_syncing = ctx.feed_syncing;
+    let llm_endpoint = ctx.llm_endpoint;
+    let llm_api_key = ctx.llm_api_key;
+    let llm_model = ctx.llm_model;
+    let mut toast = ctx.toast;
     if url.is_empty() {
-        *settings_status.write() = Some("Please enter a Feed Server URL.".to_string());
+        let err = UmbreonError::config(SERVICE, "empty url");
+        *settings_status.write() = Some(err.user_message(UI_LOCALE));
+        *toast.write() = Some(ToastMessage::from_error("Feed 同步失败", &err, None));
         return;
     }
     *feed_syncing.write() = true;
@@ -265,7 +285,10 @@ pub fn trigger_feed_sync(
                 let api_key = llm_api_key.read().trim().to_string();
                 let model = llm_model.read().trim().to_string();
                 let mut items = items;
-                if !endpoint.is_empty() && !api_key.is_empty() && !model.is_empty() {
+                let llm_configured =
+                    !endpoint.is_empty() && !api_key.is_empty() && !model.is_empty();
+                let mut budget_exhausted = llm_configured && usage::monthly_budget_exhausted();
+                if llm_configured && !budget_exhausted {
                     let total = items.len();
                     let mut done = 0usize;
                     let mut pending = Vec::new();
@@ -283,11 +306,11 @@ pub fn trigger_feed_sync(
                     }
 
                     let mut offset = 0usize;
-                    while offset < pending.len() {
+                    while offset < pending.len() && !budget_exhausted {
                         let batch_end = (offset + 20).min(pending.len());
                         let chunk = &pending[offset..batch_end];
                         let mut chunk_offset = 0usize;
-                        while chunk_offset < chunk.len() {
+                        while chunk_offset < chunk.len() && !budget_exhausted {
                             let group_end = (chunk_offset + 5).min(chunk.len());
                             let group = &chunk[chunk_offset..group_end];
 
@@ -307,11 +330,16 @@ pub fn trigger_feed_sync(
                                 let api_key = api_key.clone();
                                 let model = model.clone();
                                 futures.push(async move {
+                                    // Checked per request so a sync stops as soon as the
+                                    // budget is spent, not after the whole batch.
+                                    if usage::monthly_budget_exhausted() {
+                                        return (None, fallback);
+                                    }
                                     let result = general_ai_client::summarize_text(
                                         &endpoint, &api_key, &model, &title, &content,
                                     )
                                     .await;
-                                    (result, fallback)
+                                    (Some(result), fallback)
                                 });
                             }
 
@@ -321,7 +349,12 @@ pub fn trigger_feed_sync(
                             {
                                 let item = &mut items[*index];
                                 match result {
-                                    Ok(summary) => {
+                                    None => {
+                                        budget_exhausted = true;
+                                        item.summary = fallback;
+                                        item.summarized = false;
+                                    }
+                                    Some(Ok(summary)) => {
                                         let cleaned = ammonia::Builder::default()
                                             .add_tags(["p", "br"])
                                             .clean(&summary)
@@ -334,7 +367,7 @@ pub fn trigger_feed_sync(
                                             item.summarized = true;
                                         }
                                     }
-                                    Err(_) => {
+                                    Some(Err(_)) => {
                                         item.summary = fallback;
                                         item.summarized = false;
                                     }
@@ -346,15 +379,41 @@ pub fn trigger_feed_sync(
                         offset = batch_end;
                     }
                 }
-                let mut status = "Feeds updated.".to_string();
+                let mut status = if budget_exhausted {
+                    "Feeds updated. Monthly LLM budget reached, summaries paused.".to_string()
+                } else {
+                    "Feeds updated.".to_string()
+                };
                 if let Err(err) = storage::store_feed_items(&items) {
+                    tracing::warn!("feed cache failed: {err}");
                     status = format!("Feeds updated, but cache failed: {err}");
+                    *toast.write() = Some(ToastMessage::from_error("Feed 缓存失败", &err, None));
+                }
+                if llm_configured
+                    && !budget_exhausted
+                    && let Some(settings) = EmbeddingSettings::load()
+                {
+                    let indexed_items = items.clone();
+                    spawn(async move {
+                        let memories = storage::load_local_memories();
+                        if let Err(err) =
+                            embeddings::reindex(&settings, &indexed_items, &memories).await
+                        {
+                            tracing::warn!("embedding reindex failed: {err}");
+                        }
+                    });
                 }
                 *feed_items.write() = items;
                 *settings_status.write() = Some(status);
             }
             Err(err) => {
-                *settings_status.write() = Some(err);
+                tracing::warn!("feed sync failed: {err}");
+                *settings_status.write() = Some(err.user_message(UI_LOCALE));
+                *toast.write() = Some(ToastMessage::from_error(
+                    "Feed 同步失败",
+                    &err,
+                    Some(ToastAction::RetryFeedSync),
+                ));
             }
         }
         *feed_syncing.write() = false;
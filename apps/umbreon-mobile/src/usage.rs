@@ -0,0 +1,163 @@
+use crate::storage;
+use chrono::{Datelike, FixedOffset, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LlmUsageRecord {
+    pub created_ts: i64,
+    pub operation: String,
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub latency_ms: i64,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Price per one million tokens, in the currency the budget is expressed in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+pub type PriceTable = BTreeMap<String, ModelPrice>;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UsageTotals {
+    pub label: String,
+    pub calls: u32,
+    pub failures: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UsageReport {
+    pub by_day: Vec<UsageTotals>,
+    pub by_model: Vec<UsageTotals>,
+    pub total: UsageTotals,
+}
+
+fn utc8() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("valid UTC offset"))
+}
+
+pub fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+fn day_label(ts: i64) -> String {
+    utc8()
+        .timestamp_opt(ts, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "1970-01-01".to_string())
+}
+
+/// Start of the current calendar month (UTC+8, same as the timeline dates) as a unix timestamp.
+pub fn month_start_ts() -> i64 {
+    let now = Utc::now().with_timezone(&utc8());
+    utc8()
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .map(|dt| dt.timestamp())
+        .unwrap_or(0)
+}
+
+pub fn record_cost(record: &LlmUsageRecord, prices: &PriceTable) -> f64 {
+    let Some(price) = prices.get(&record.model) else {
+        return 0.0;
+    };
+    (record.prompt_tokens as f64 * price.prompt_per_million
+        + record.completion_tokens as f64 * price.completion_per_million)
+        / 1_000_000.0
+}
+
+fn accumulate(totals: &mut UsageTotals, record: &LlmUsageRecord, cost: f64) {
+    totals.calls += 1;
+    if !record.success {
+        totals.failures += 1;
+    }
+    totals.prompt_tokens += record.prompt_tokens as u64;
+    totals.completion_tokens += record.completion_tokens as u64;
+    totals.cost += cost;
+}
+
+pub fn build_report(records: &[LlmUsageRecord], prices: &PriceTable) -> UsageReport {
+    let mut by_day = BTreeMap::<String, UsageTotals>::new();
+    let mut by_model = BTreeMap::<String, UsageTotals>::new();
+    let mut total = UsageTotals {
+        label: "total".to_string(),
+        ..UsageTotals::default()
+    };
+
+    for record in records {
+        let cost = record_cost(record, prices);
+        let day = day_label(record.created_ts);
+        accumulate(
+            by_day.entry(day.clone()).or_insert_with(|| UsageTotals {
+                label: day,
+                ..UsageTotals::default()
+            }),
+            record,
+            cost,
+        );
+        accumulate(
+            by_model
+                .entry(record.model.clone())
+                .or_insert_with(|| UsageTotals {
+                    label: record.model.clone(),
+                    ..UsageTotals::default()
+                }),
+            record,
+            cost,
+        );
+        accumulate(&mut total, record, cost);
+    }
+
+    UsageReport {
+        by_day: by_day.into_values().rev().collect(),
+        by_model: by_model.into_values().collect(),
+        total,
+    }
+}
+
+pub fn record(
+    operation: &str,
+    model: &str,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    started_ms: i64,
+    error: Option<String>,
+) {
+    let record = LlmUsageRecord {
+        created_ts: started_ms / 1000,
+        operation: operation.to_string(),
+        model: model.to_string(),
+        prompt_tokens,
+        completion_tokens,
+        latency_ms: now_ms().saturating_sub(started_ms),
+        success: error.is_none(),
+        error,
+    };
+    storage::store_llm_usage(&record);
+}
+
+pub fn load_month_report(prices: &PriceTable) -> UsageReport {
+    let records = storage::load_llm_usage_since(month_start_ts());
+    build_report(&records, prices)
+}
+
+/// Whether this month's spend has reached the configured budget. No budget means no limit.
+pub fn monthly_budget_exhausted() -> bool {
+    let settings = storage::load_settings();
+    let Some(budget) = settings.llm_monthly_budget else {
+        return false;
+    };
+    let prices = settings.llm_prices.unwrap_or_default();
+    load_month_report(&prices).total.cost >= budget
+}
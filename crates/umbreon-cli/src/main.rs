@@ -0,0 +1,119 @@
+//! Command line helpers for the Umbreon config files.
+//!
+//! ```text
+//! umbreon opml import <subscriptions.opml> [--merge <config.toml>] [-o <feeds.toml>]
+//! umbreon opml export <config.toml> [--title <title>] [-o <subscriptions.opml>]
+//! ```
+//!
+//! `-` reads stdin; without `-o` the result goes to stdout. With `--merge` the output is the
+//! whole config with its `[feeds]` updated, so `--merge config.toml -o config.toml` keeps
+//! the other sections (but not comments).
+
+use std::io::{Read, Write};
+use std::process::ExitCode;
+use umbreon_core::UmbreonError;
+use umbreon_core::opml;
+
+const USAGE: &str = "usage:
+  umbreon opml import <subscriptions.opml> [--merge <config.toml>] [-o <feeds.toml>]
+  umbreon opml export <config.toml> [--title <title>] [-o <subscriptions.opml>]";
+
+struct Options {
+    input: String,
+    output: Option<String>,
+    merge: Option<String>,
+    title: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        input: String::new(),
+        output: None,
+        merge: None,
+        title: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => options.output = Some(args.next()?.clone()),
+            "--merge" => options.merge = Some(args.next()?.clone()),
+            "--title" => options.title = Some(args.next()?.clone()),
+            _ if options.input.is_empty() => options.input = arg.clone(),
+            _ => return None,
+        }
+    }
+    (!options.input.is_empty()).then_some(options)
+}
+
+fn read_input(path: &str) -> Result<String, UmbreonError> {
+    if path == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(UmbreonError::storage)?;
+        return Ok(text);
+    }
+    std::fs::read_to_string(path).map_err(|err| UmbreonError::storage(format!("{path}: {err}")))
+}
+
+fn write_output(path: Option<&str>, text: &str) -> Result<(), UmbreonError> {
+    match path {
+        Some(path) if path != "-" => std::fs::write(path, text)
+            .map_err(|err| UmbreonError::storage(format!("{path}: {err}"))),
+        _ => std::io::stdout()
+            .write_all(text.as_bytes())
+            .map_err(UmbreonError::storage),
+    }
+}
+
+fn import(options: &Options) -> Result<(), UmbreonError> {
+    let incoming = opml::parse_opml(&read_input(&options.input)?)?;
+    let config = match &options.merge {
+        Some(path) => Some(read_input(path)?),
+        None => None,
+    };
+    let existing = match &config {
+        Some(text) => opml::parse_feeds_toml(text)?,
+        None => Vec::new(),
+    };
+    let (sources, added) = opml::merge_sources(existing, incoming);
+    let text = match &config {
+        Some(text) => opml::update_feeds_toml(text, &sources)?,
+        None => opml::to_feeds_toml(&sources)?,
+    };
+    write_output(options.output.as_deref(), &text)?;
+    eprintln!("{added} feeds imported, {} total", sources.len());
+    Ok(())
+}
+
+fn export(options: &Options) -> Result<(), UmbreonError> {
+    let sources = opml::parse_feeds_toml(&read_input(&options.input)?)?;
+    let title = options.title.as_deref().unwrap_or("Umbreon subscriptions");
+    write_output(options.output.as_deref(), &opml::to_opml(title, &sources))?;
+    eprintln!("{} feeds exported", sources.len());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let command = match args.first().map(String::as_str) {
+        Some("opml") => args.get(1).map(String::as_str),
+        _ => None,
+    };
+    let options = args.get(2..).and_then(parse_options);
+    let result = match (command, options) {
+        (Some("import"), Some(options)) => import(&options),
+        (Some("export"), Some(options)) => export(&options),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
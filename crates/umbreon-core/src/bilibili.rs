@@ -0,0 +1,456 @@
+//! Bilibili live danmaku protocol: the binary packets of the `wss://…/sub` endpoint and the
+//! mapping of its `DANMU_MSG` and `SUPER_CHAT_MESSAGE` commands to [`DanmakuEntry`].
+//!
+//! Every packet starts with a 16-byte big-endian header — total length, header length,
+//! protocol version, operation, sequence — followed by the body. Message bodies of version 2
+//! and 3 are zlib and brotli streams holding further packets; one WebSocket message may carry
+//! several packets back to back.
+
+use crate::danmaku::{DEFAULT_COLOR, DEFAULT_SIZE, DanmakuEntry, DanmakuMode, parse_color};
+use crate::error::{Result, UmbreonError};
+use serde_json::{Value, json};
+use std::io::Read;
+use std::time::Duration;
+
+pub const HEADER_LEN: usize = 16;
+/// The server drops clients that stay quiet for longer than about 70 seconds.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// Used when the room info request does not name a host.
+pub const DEFAULT_HOST: &str = "broadcastlv.chat.bilibili.com";
+
+/// Plain JSON body.
+pub const VERSION_JSON: u16 = 0;
+/// Heartbeat, auth and popularity packets.
+pub const VERSION_CONTROL: u16 = 1;
+pub const VERSION_ZLIB: u16 = 2;
+pub const VERSION_BROTLI: u16 = 3;
+
+/// Bodies decompress to at most this many bytes; real batches are a few kilobytes.
+const MAX_INFLATED: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Heartbeat,
+    /// Carries the room popularity as a big-endian `u32`.
+    HeartbeatReply,
+    /// One JSON command, or compressed packets.
+    Message,
+    Auth,
+    AuthReply,
+    Other(u32),
+}
+
+impl Operation {
+    pub fn code(self) -> u32 {
+        match self {
+            Operation::Heartbeat => 2,
+            Operation::HeartbeatReply => 3,
+            Operation::Message => 5,
+            Operation::Auth => 7,
+            Operation::AuthReply => 8,
+            Operation::Other(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            2 => Operation::Heartbeat,
+            3 => Operation::HeartbeatReply,
+            5 => Operation::Message,
+            7 => Operation::Auth,
+            8 => Operation::AuthReply,
+            other => Operation::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub version: u16,
+    pub operation: Operation,
+    pub sequence: u32,
+    pub body: Vec<u8>,
+}
+
+impl Packet {
+    /// A control packet, as the client sends them.
+    pub fn new(operation: Operation, body: impl Into<Vec<u8>>) -> Self {
+        Packet {
+            version: VERSION_CONTROL,
+            operation,
+            sequence: 1,
+            body: body.into(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_LEN + self.body.len());
+        data.extend(((HEADER_LEN + self.body.len()) as u32).to_be_bytes());
+        data.extend((HEADER_LEN as u16).to_be_bytes());
+        data.extend(self.version.to_be_bytes());
+        data.extend(self.operation.code().to_be_bytes());
+        data.extend(self.sequence.to_be_bytes());
+        data.extend(&self.body);
+        data
+    }
+}
+
+fn packet_error(message: impl ToString) -> UmbreonError {
+    UmbreonError::parse("Bilibili packet", message)
+}
+
+fn inflate(reader: impl Read, what: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader
+        .take(MAX_INFLATED)
+        .read_to_end(&mut data)
+        .map_err(|err| packet_error(format!("{what}: {err}")))?;
+    Ok(data)
+}
+
+/// Splits a WebSocket message into packets. Compressed message packets are replaced by the
+/// packets they contain.
+pub fn decode_packets(data: &[u8]) -> Result<Vec<Packet>> {
+    let mut packets = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < HEADER_LEN {
+            return Err(packet_error(format!("{} trailing bytes", rest.len())));
+        }
+        let be32 =
+            |at: usize| u32::from_be_bytes([rest[at], rest[at + 1], rest[at + 2], rest[at + 3]]);
+        let be16 = |at: usize| u16::from_be_bytes([rest[at], rest[at + 1]]);
+        let length = be32(0) as usize;
+        let header_len = be16(4) as usize;
+        if header_len < HEADER_LEN || length < header_len || length > rest.len() {
+            return Err(packet_error(format!(
+                "bad header: length {length}, header {header_len}, {} bytes left",
+                rest.len()
+            )));
+        }
+        let version = be16(6);
+        let operation = Operation::from_code(be32(8));
+        let sequence = be32(12);
+        let body = &rest[header_len..length];
+        match (operation, version) {
+            (Operation::Message, VERSION_ZLIB) => {
+                let inflated = inflate(flate2::read::ZlibDecoder::new(body), "zlib")?;
+                packets.extend(decode_packets(&inflated)?);
+            }
+            (Operation::Message, VERSION_BROTLI) => {
+                let inflated =
+                    inflate(brotli_decompressor::Decompressor::new(body, 4096), "brotli")?;
+                packets.extend(decode_packets(&inflated)?);
+            }
+            _ => packets.push(Packet {
+                version,
+                operation,
+                sequence,
+                body: body.to_vec(),
+            }),
+        }
+        rest = &rest[length..];
+    }
+    Ok(packets)
+}
+
+/// What the client needs to enter a room; `token` and `buvid` come from the room info API
+/// and may be left out for anonymous access, which some rooms restrict.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuthParams {
+    pub room_id: u64,
+    pub uid: u64,
+    pub token: Option<String>,
+    pub buvid: Option<String>,
+}
+
+/// First packet after connecting. It asks for brotli-compressed messages.
+pub fn auth_packet(auth: &AuthParams) -> Packet {
+    let mut body = json!({
+        "uid": auth.uid,
+        "roomid": auth.room_id,
+        "protover": VERSION_BROTLI,
+        "platform": "web",
+        "type": 2,
+    });
+    if let Some(token) = &auth.token {
+        body["key"] = json!(token);
+    }
+    if let Some(buvid) = &auth.buvid {
+        body["buvid"] = json!(buvid);
+    }
+    Packet::new(Operation::Auth, body.to_string())
+}
+
+/// Sent every [`HEARTBEAT_INTERVAL`]; the web client's body is kept as is.
+pub fn heartbeat_packet() -> Packet {
+    Packet::new(Operation::Heartbeat, "[object Object]")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LiveMessage {
+    /// `code` 0 means the room accepted the client.
+    AuthReply {
+        code: i64,
+    },
+    Popularity(u32),
+    Danmaku(DanmakuEntry),
+    /// Gifts, entries, rank changes and the many other commands without a comment.
+    Other {
+        cmd: String,
+    },
+}
+
+impl LiveMessage {
+    pub fn into_danmaku(self) -> Option<DanmakuEntry> {
+        match self {
+            LiveMessage::Danmaku(entry) => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+/// Reads one decoded packet. Packets that are not valid for their operation yield `None`.
+pub fn parse_packet(packet: &Packet) -> Option<LiveMessage> {
+    match packet.operation {
+        Operation::AuthReply => {
+            let body = serde_json::from_slice::<Value>(&packet.body).ok()?;
+            Some(LiveMessage::AuthReply {
+                code: body.get("code").and_then(Value::as_i64).unwrap_or(0),
+            })
+        }
+        Operation::HeartbeatReply => {
+            let bytes = packet.body.get(..4)?;
+            Some(LiveMessage::Popularity(u32::from_be_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3],
+            ])))
+        }
+        Operation::Message => {
+            let command = serde_json::from_slice::<Value>(&packet.body).ok()?;
+            parse_command(&command)
+        }
+        _ => None,
+    }
+}
+
+fn parse_command(command: &Value) -> Option<LiveMessage> {
+    let cmd = command.get("cmd")?.as_str()?;
+    // Newer servers append display options, as in `DANMU_MSG:4:0:2:2:2:0`.
+    let entry = match cmd.split(':').next().unwrap_or(cmd) {
+        "DANMU_MSG" => danmu_msg(command.get("info")?),
+        "SUPER_CHAT_MESSAGE" => super_chat(command.get("data")?),
+        _ => None,
+    };
+    Some(match entry {
+        Some(entry) => LiveMessage::Danmaku(entry),
+        None => LiveMessage::Other {
+            cmd: cmd.to_string(),
+        },
+    })
+}
+
+/// `info[0]` holds the display fields (mode, size, color, sent at in ms, …, sender hash at
+/// 7), `info[1]` the text and `info[2]` the user as `[uid, name, …]`. Colors may carry bits
+/// above the RGB ones.
+fn danmu_msg(info: &Value) -> Option<DanmakuEntry> {
+    let meta = info.get(0)?;
+    let text = info.get(1)?.as_str()?;
+    let number = |index: usize| meta.get(index).and_then(Value::as_u64);
+    let mode = DanmakuMode::from_bilibili(number(1).unwrap_or(1) as u32)?;
+    Some(DanmakuEntry {
+        mode,
+        color: number(3).map_or(DEFAULT_COLOR, |color| color as u32 & 0xFFFFFF),
+        size: number(2).map_or(DEFAULT_SIZE, |size| size as u32),
+        sender: meta
+            .get(7)
+            .and_then(Value::as_str)
+            .filter(|hash| !hash.is_empty())
+            .map(str::to_string),
+        sent_at: number(4).map(|ms| (ms / 1000) as i64),
+        ..DanmakuEntry::new(0.0, text)
+    })
+}
+
+/// The sender hash Bilibili puts in `DANMU_MSG` and danmaku files: the CRC32 of the uid in
+/// lowercase hex, so super chats and comments from one user share a sender.
+fn sender_hash(uid: &str) -> String {
+    let mut crc = flate2::Crc::new();
+    crc.update(uid.as_bytes());
+    format!("{:x}", crc.sum())
+}
+
+/// Super chats become top comments in the chat's color, labelled with price and sender.
+fn super_chat(data: &Value) -> Option<DanmakuEntry> {
+    let message = data.get("message")?.as_str()?;
+    let price = data.get("price").and_then(Value::as_u64).unwrap_or(0);
+    let name = data
+        .pointer("/user_info/uname")
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let color = data
+        .get("background_bottom_color")
+        .and_then(Value::as_str)
+        .and_then(parse_color)
+        .unwrap_or(DEFAULT_COLOR);
+    Some(DanmakuEntry {
+        mode: DanmakuMode::Top,
+        color,
+        sender: data.get("uid").map(|uid| match uid.as_str() {
+            Some(uid) => sender_hash(uid),
+            None => sender_hash(&uid.to_string()),
+        }),
+        sent_at: data.get("start_time").and_then(Value::as_i64),
+        id: data.get("id").map(|id| format!("sc-{id}")),
+        ..DanmakuEntry::new(0.0, format!("SC ¥{price} {name}：{message}"))
+    })
+}
+
+/// Room id of a `bilibili://live/<room>` endpoint or a `live.bilibili.com/<room>` page.
+pub fn room_id(endpoint: &str) -> Option<u64> {
+    let endpoint = endpoint.trim();
+    let path = endpoint.strip_prefix("bilibili://live/").or_else(|| {
+        let rest = endpoint
+            .strip_prefix("https://")
+            .or_else(|| endpoint.strip_prefix("http://"))?;
+        rest.strip_prefix("live.bilibili.com/")
+    })?;
+    let room = path.split(['/', '?', '#']).next()?;
+    room.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTH_REPLY: &[u8] = include_bytes!("../fixtures/bilibili/auth_reply.bin");
+    const HEARTBEAT_REPLY: &[u8] = include_bytes!("../fixtures/bilibili/heartbeat_reply.bin");
+    const DANMAKU_ZLIB: &[u8] = include_bytes!("../fixtures/bilibili/danmaku_zlib.bin");
+    const DANMAKU_BROTLI: &[u8] = include_bytes!("../fixtures/bilibili/danmaku_brotli.bin");
+
+    fn messages(data: &[u8]) -> Vec<LiveMessage> {
+        decode_packets(data)
+            .unwrap()
+            .iter()
+            .filter_map(parse_packet)
+            .collect()
+    }
+
+    #[test]
+    fn control_packets_decode_back_to_back() {
+        let mut data = AUTH_REPLY.to_vec();
+        data.extend(HEARTBEAT_REPLY);
+        assert_eq!(
+            messages(&data),
+            [
+                LiveMessage::AuthReply { code: 0 },
+                LiveMessage::Popularity(12345)
+            ]
+        );
+    }
+
+    #[test]
+    fn zlib_batches_expand_into_danmaku() {
+        let packets = decode_packets(DANMAKU_ZLIB).unwrap();
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|packet| packet.version == VERSION_JSON));
+
+        let messages = messages(DANMAKU_ZLIB);
+        assert_eq!(
+            messages[1],
+            LiveMessage::Other {
+                cmd: "INTERACT_WORD".to_string()
+            }
+        );
+        let entries = messages
+            .into_iter()
+            .filter_map(LiveMessage::into_danmaku)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].text, "主播晚上好");
+        assert_eq!(entries[0].mode, DanmakuMode::Scroll);
+        assert_eq!(entries[0].color, 0xFFFFFF);
+        assert_eq!(entries[0].sender.as_deref(), Some("a1b2c3d4"));
+        assert_eq!(entries[0].sent_at, Some(1_700_000_000));
+        assert_eq!(entries[1].text, "顶部弹幕");
+        assert_eq!(entries[1].mode, DanmakuMode::Top);
+        assert_eq!(entries[1].size, 18);
+        assert_eq!(entries[1].color, 0xFF6868);
+    }
+
+    #[test]
+    fn brotli_batches_carry_super_chats() {
+        let entries = messages(DANMAKU_BROTLI)
+            .into_iter()
+            .filter_map(LiveMessage::into_danmaku)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].text, "底部弹幕");
+        assert_eq!(entries[0].mode, DanmakuMode::Bottom);
+
+        let super_chat = &entries[1];
+        assert_eq!(super_chat.text, "SC ¥30 观众乙：主播加油！");
+        assert_eq!(super_chat.mode, DanmakuMode::Top);
+        assert_eq!(super_chat.color, 0x2A60B2);
+        assert_eq!(super_chat.sender.as_deref(), Some("14258326"));
+        assert_eq!(super_chat.sent_at, Some(1_700_000_100));
+        assert_eq!(super_chat.id.as_deref(), Some("sc-9876543"));
+    }
+
+    #[test]
+    fn danmu_colors_keep_only_the_rgb_bits() {
+        let command = serde_json::json!({
+            "cmd": "DANMU_MSG",
+            "info": [
+                [0, 1, 25, 0x1_00FF_6868u64, 1_700_000_000_000u64, 0, 0, "a1b2c3d4"],
+                "彩色",
+                [1, "观众"],
+            ],
+        });
+        let entry = parse_command(&command)
+            .and_then(LiveMessage::into_danmaku)
+            .unwrap();
+        assert_eq!(entry.color, 0xFF6868);
+    }
+
+    #[test]
+    fn client_packets_round_trip() {
+        let auth = AuthParams {
+            room_id: 21452505,
+            token: Some("token".to_string()),
+            ..AuthParams::default()
+        };
+        let encoded = auth_packet(&auth).encode();
+        assert_eq!(&encoded[..4], (encoded.len() as u32).to_be_bytes());
+        assert_eq!(&encoded[4..16], [0, 16, 0, 1, 0, 0, 0, 7, 0, 0, 0, 1]);
+        let decoded = decode_packets(&encoded).unwrap();
+        assert_eq!(decoded, [auth_packet(&auth)]);
+        let body = serde_json::from_slice::<Value>(&decoded[0].body).unwrap();
+        assert_eq!(body["roomid"], 21452505);
+        assert_eq!(body["protover"], 3);
+        assert_eq!(body["key"], "token");
+
+        let heartbeat = decode_packets(&heartbeat_packet().encode()).unwrap();
+        assert_eq!(heartbeat[0].operation, Operation::Heartbeat);
+    }
+
+    #[test]
+    fn truncated_packets_are_errors() {
+        assert!(decode_packets(&DANMAKU_ZLIB[..DANMAKU_ZLIB.len() - 1]).is_err());
+        assert!(decode_packets(&AUTH_REPLY[..10]).is_err());
+        let mut corrupt = DANMAKU_BROTLI.to_vec();
+        corrupt.truncate(40);
+        corrupt[3] = 40;
+        assert!(decode_packets(&corrupt).is_err());
+    }
+
+    #[test]
+    fn room_ids_come_from_endpoints_and_pages() {
+        assert_eq!(room_id("bilibili://live/21452505"), Some(21452505));
+        assert_eq!(
+            room_id("https://live.bilibili.com/545068?broadcast_type=0"),
+            Some(545068)
+        );
+        assert_eq!(room_id("wss://example.com/danmaku"), None);
+        assert_eq!(room_id("bilibili://live/abc"), None);
+    }
+}
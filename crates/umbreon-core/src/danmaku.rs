@@ -0,0 +1,837 @@
+//! Danmaku (bullet comments): one [`DanmakuEntry`] model for every source the player reads —
+//! Bilibili XML dumps, JSON-lines streams and ASS/SSA scripts converted from danmaku — with a
+//! writer for each format so entries can be saved back.
+//!
+//! Parsers skip what cannot be drawn as plain text (Bilibili's advanced and code modes, ASS
+//! comments) and return entries sorted by time. [`parse_danmaku`] picks the parser for files
+//! of unknown format.
+
+use crate::error::{Result, UmbreonError};
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Bilibili's default font size; the other sizes are relative to it.
+pub const DEFAULT_SIZE: u32 = 25;
+pub const DEFAULT_COLOR: u32 = 0xFFFFFF;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DanmakuMode {
+    /// Crosses the screen from right to left.
+    #[default]
+    Scroll,
+    /// Pinned to the top, centered.
+    Top,
+    /// Pinned to the bottom, centered.
+    Bottom,
+}
+
+impl DanmakuMode {
+    /// Bilibili mode codes: 1–3 scroll, 4 bottom, 5 top, 6 reverse scroll (drawn as a normal
+    /// scroll). The positioned, code and BAS modes (7–9) have no plain-text form.
+    pub fn from_bilibili(code: u32) -> Option<Self> {
+        match code {
+            1..=3 | 6 => Some(DanmakuMode::Scroll),
+            4 => Some(DanmakuMode::Bottom),
+            5 => Some(DanmakuMode::Top),
+            _ => None,
+        }
+    }
+
+    pub fn bilibili_code(self) -> u32 {
+        match self {
+            DanmakuMode::Scroll => 1,
+            DanmakuMode::Bottom => 4,
+            DanmakuMode::Top => 5,
+        }
+    }
+}
+
+/// One comment. JSON lines use these field names; `t`/`offset` and `content` are accepted
+/// for `time` and `text`, and `color` may be a number or a `#RRGGBB` string.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DanmakuEntry {
+    /// Seconds from the start of the video or stream.
+    #[serde(alias = "t", alias = "offset")]
+    pub time: f64,
+    #[serde(alias = "content")]
+    pub text: String,
+    #[serde(default)]
+    pub mode: DanmakuMode,
+    /// `0xRRGGBB`.
+    #[serde(default = "default_color", with = "hex_color")]
+    pub color: u32,
+    #[serde(default = "default_size")]
+    pub size: u32,
+    /// Anonymised sender id (Bilibili's user hash), for blocking and counting senders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Bilibili comment pool: 0 normal, 1 subtitles, 2 special.
+    #[serde(default)]
+    pub pool: u32,
+    /// Unix seconds at which the comment was sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+impl DanmakuEntry {
+    /// A white, default-size scrolling comment.
+    pub fn new(time: f64, text: impl Into<String>) -> Self {
+        DanmakuEntry {
+            time,
+            text: text.into(),
+            mode: DanmakuMode::Scroll,
+            color: DEFAULT_COLOR,
+            size: DEFAULT_SIZE,
+            sender: None,
+            pool: 0,
+            sent_at: None,
+            id: None,
+        }
+    }
+}
+
+fn default_color() -> u32 {
+    DEFAULT_COLOR
+}
+
+fn default_size() -> u32 {
+    DEFAULT_SIZE
+}
+
+/// Parses `#RRGGBB`, `0xRRGGBB` or a decimal color.
+pub fn parse_color(value: &str) -> Option<u32> {
+    let value = value.trim();
+    match value.strip_prefix('#').or_else(|| value.strip_prefix("0x")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+    .filter(|color| *color <= 0xFFFFFF)
+}
+
+mod hex_color {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    pub fn serialize<S: Serializer>(color: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("#{color:06X}"))
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Color {
+        Number(u32),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let color = match Color::deserialize(deserializer)? {
+            Color::Number(color) => Some(color).filter(|color| *color <= 0xFFFFFF),
+            Color::Text(text) => super::parse_color(&text),
+        };
+        color.ok_or_else(|| de::Error::custom("invalid color"))
+    }
+}
+
+fn sort_by_time(entries: &mut [DanmakuEntry]) {
+    entries.sort_by(|a, b| a.time.total_cmp(&b.time));
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Reads the `p` attribute of a Bilibili `<d>` element:
+/// `time,mode,size,color,sent_at,pool,sender,id`. Returns `None` for advanced modes.
+fn bilibili_entry(p: &str, text: String) -> Result<Option<DanmakuEntry>> {
+    let fields = p.split(',').map(str::trim).collect::<Vec<_>>();
+    if fields.len() < 4 {
+        return Err(UmbreonError::parse(
+            "Bilibili XML",
+            format!("too few fields in p={p:?}"),
+        ));
+    }
+    let number = |index: usize| -> Result<u64> {
+        fields.get(index).map_or(Ok(0), |value| {
+            value.parse().map_err(|_| {
+                UmbreonError::parse("Bilibili XML", format!("invalid field {index} in p={p:?}"))
+            })
+        })
+    };
+    let time = fields[0]
+        .parse::<f64>()
+        .ok()
+        .filter(|time| time.is_finite() && *time >= 0.0)
+        .ok_or_else(|| UmbreonError::parse("Bilibili XML", format!("invalid time in p={p:?}")))?;
+    let Some(mode) = DanmakuMode::from_bilibili(number(1)? as u32) else {
+        return Ok(None);
+    };
+    let sent_at = number(4)?;
+    Ok(Some(DanmakuEntry {
+        time,
+        text,
+        mode,
+        color: (number(3)? as u32) & 0xFFFFFF,
+        size: number(2)? as u32,
+        sender: fields.get(6).and_then(|value| non_empty(value)),
+        pool: number(5)? as u32,
+        sent_at: (sent_at > 0).then_some(sent_at as i64),
+        id: fields
+            .get(7)
+            .and_then(|value| non_empty(value))
+            .filter(|id| id != "0"),
+    }))
+}
+
+/// A Bilibili comment dump (`<i>` root with one `<d p="...">` per comment).
+pub fn parse_bilibili_xml(xml: &str) -> Result<Vec<DanmakuEntry>> {
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut saw_root = false;
+    // The `p` attribute and text of the `<d>` being read.
+    let mut current: Option<(String, String)> = None;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|err| UmbreonError::parse("Bilibili XML", err))?;
+        match event {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"i" => saw_root = true,
+                b"d" => {
+                    let p = element
+                        .attributes()
+                        .flatten()
+                        .find(|attr| attr.key.local_name().as_ref() == b"p")
+                        .and_then(|attr| attr.decode_and_unescape_value(&reader).ok())
+                        .ok_or_else(|| UmbreonError::parse("Bilibili XML", "<d> without p"))?;
+                    current = Some((p.into_owned(), String::new()));
+                }
+                _ => {}
+            },
+            Event::Text(text) => {
+                if let Some((_, body)) = current.as_mut() {
+                    let text = text
+                        .unescape()
+                        .map_err(|err| UmbreonError::parse("Bilibili XML", err))?;
+                    body.push_str(&text);
+                }
+            }
+            Event::CData(text) => {
+                if let Some((_, body)) = current.as_mut() {
+                    body.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Event::Empty(element) if element.local_name().as_ref() == b"i" => saw_root = true,
+            Event::End(element) if element.local_name().as_ref() == b"d" => {
+                if let Some((p, text)) = current.take()
+                    && let Some(entry) = bilibili_entry(&p, text)?
+                {
+                    entries.push(entry);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !saw_root {
+        return Err(UmbreonError::parse(
+            "Bilibili XML",
+            "missing <i> root element",
+        ));
+    }
+    sort_by_time(&mut entries);
+    Ok(entries)
+}
+
+/// A Bilibili-style dump of `entries`.
+pub fn to_bilibili_xml(entries: &[DanmakuEntry]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<i>\n");
+    xml.push_str("  <chatserver>chat.bilibili.com</chatserver>\n");
+    for entry in entries {
+        xml.push_str(&format!(
+            "  <d p=\"{:.5},{},{},{},{},{},{},{}\">{}</d>\n",
+            entry.time,
+            entry.mode.bilibili_code(),
+            entry.size,
+            entry.color,
+            entry.sent_at.unwrap_or(0),
+            entry.pool,
+            escape(entry.sender.as_deref().unwrap_or_default()),
+            escape(entry.id.as_deref().unwrap_or("0")),
+            escape(entry.text.as_str()),
+        ));
+    }
+    xml.push_str("</i>\n");
+    xml
+}
+
+/// One JSON object per line, as sent by live danmaku relays. Blank lines are skipped.
+pub fn parse_json_lines(text: &str) -> Result<Vec<DanmakuEntry>> {
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<DanmakuEntry>(line).map_err(|err| {
+            UmbreonError::parse("danmaku JSON", format!("line {}: {err}", index + 1))
+        })?;
+        entries.push(entry);
+    }
+    sort_by_time(&mut entries);
+    Ok(entries)
+}
+
+pub fn to_json_lines(entries: &[DanmakuEntry]) -> String {
+    let mut text = String::new();
+    for entry in entries {
+        // A struct of plain fields always serializes.
+        text.push_str(&serde_json::to_string(entry).unwrap_or_default());
+        text.push('\n');
+    }
+    text
+}
+
+/// Layout of an ASS script written by [`to_ass`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssOptions {
+    pub width: u32,
+    pub height: u32,
+    pub font: String,
+    /// Seconds a scrolling comment takes to cross the screen.
+    pub scroll_duration: f64,
+    /// Seconds a top or bottom comment stays.
+    pub fixed_duration: f64,
+}
+
+impl Default for AssOptions {
+    fn default() -> Self {
+        AssOptions {
+            width: 1920,
+            height: 1080,
+            font: "sans-serif".to_string(),
+            scroll_duration: 8.0,
+            fixed_duration: 4.0,
+        }
+    }
+}
+
+/// `H:MM:SS.cc` to seconds.
+fn ass_time(value: &str) -> Option<f64> {
+    let mut parts = value.trim().splitn(3, ':');
+    let hours = parts.next()?.parse::<f64>().ok()?;
+    let minutes = parts.next()?.parse::<f64>().ok()?;
+    let seconds = parts.next()?.parse::<f64>().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn format_ass_time(seconds: f64) -> String {
+    let centis = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// `&H[AA]BBGGRR&` to `0xRRGGBB`.
+fn ass_color(value: &str) -> Option<u32> {
+    let hex = value
+        .trim()
+        .trim_start_matches("&H")
+        .trim_start_matches("&h")
+        .trim_end_matches('&');
+    let bgr = u32::from_str_radix(hex, 16).ok()? & 0xFFFFFF;
+    Some(((bgr & 0xFF) << 16) | (bgr & 0xFF00) | (bgr >> 16))
+}
+
+/// The value of a `\c&H..&` or `\1c&H..&` tag; other tags starting with `c`, such as
+/// `\clip`, are not colors.
+fn color_override(tag: &str) -> Option<&str> {
+    tag.strip_prefix("1c")
+        .or_else(|| tag.strip_prefix('c'))
+        .filter(|value| value.starts_with("&H") || value.starts_with("&h"))
+}
+
+fn format_ass_color(rgb: u32) -> String {
+    let bgr = ((rgb & 0xFF) << 16) | (rgb & 0xFF00) | ((rgb >> 16) & 0xFF);
+    format!("&H{bgr:06X}&")
+}
+
+/// Override tags of a dialogue text, e.g. `["move(1920,0,-200,0)", "an8"]`.
+fn override_tags(text: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        tags.extend(
+            rest[start + 1..start + end]
+                .split('\\')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty()),
+        );
+        rest = &rest[start + end + 1..];
+    }
+    tags
+}
+
+/// Dialogue text without override blocks, with `\N` as a line break and `\h` as a space.
+fn plain_text(text: &str) -> String {
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        plain.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    plain.push_str(rest);
+    plain
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+}
+
+/// Scrolling when the line moves; otherwise pinned to the top or bottom by its alignment.
+fn ass_mode(tags: &[&str], style_alignment: Option<u32>) -> DanmakuMode {
+    if tags.iter().any(|tag| tag.starts_with("move(")) {
+        return DanmakuMode::Scroll;
+    }
+    // `\an` uses numpad positions; legacy `\a` puts 5–7 at the top.
+    let alignment = tags
+        .iter()
+        .rev()
+        .find_map(|tag| {
+            if let Some(value) = tag.strip_prefix("an") {
+                value.parse::<u32>().ok()
+            } else {
+                tag.strip_prefix('a')
+                    .and_then(|value| value.parse::<u32>().ok())
+                    .map(|legacy| if (5..=7).contains(&legacy) { 8 } else { 2 })
+            }
+        })
+        .or(style_alignment);
+    match alignment {
+        Some(7..=9) => DanmakuMode::Top,
+        _ => DanmakuMode::Bottom,
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct AssStyle {
+    size: Option<u32>,
+    color: Option<u32>,
+    alignment: Option<u32>,
+}
+
+/// The `Dialogue:` lines of an ASS (v4+) or SSA (v4) script. Entry size and color come
+/// from `\fs` and `\c`/`\1c` overrides, falling back to the line's style.
+pub fn parse_ass(text: &str) -> Result<Vec<DanmakuEntry>> {
+    let mut section = String::new();
+    let mut style_format: Vec<String> = Vec::new();
+    let mut event_format: Vec<String> = Vec::new();
+    let mut styles: HashMap<String, AssStyle> = HashMap::new();
+    let mut entries = Vec::new();
+    for (index, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].to_ascii_lowercase();
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let fields = |format: &[String]| {
+            value
+                .splitn(format.len().max(1), ',')
+                .map(str::trim)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        match (section.as_str(), key.trim()) {
+            ("v4+ styles" | "v4 styles", "Format") | ("events", "Format") => {
+                let names = value
+                    .split(',')
+                    .map(|name| name.trim().to_ascii_lowercase())
+                    .collect();
+                if section == "events" {
+                    event_format = names;
+                } else {
+                    style_format = names;
+                }
+            }
+            ("v4+ styles" | "v4 styles", "Style") => {
+                let values = fields(&style_format);
+                let get = |name: &str| {
+                    style_format
+                        .iter()
+                        .position(|field| field == name)
+                        .and_then(|index| values.get(index))
+                };
+                if let Some(name) = get("name") {
+                    styles.insert(
+                        name.clone(),
+                        AssStyle {
+                            size: get("fontsize")
+                                .and_then(|size| size.parse::<f64>().ok())
+                                .map(|size| size.round() as u32),
+                            color: get("primarycolour").and_then(|color| ass_color(color)),
+                            alignment: get("alignment").and_then(|value| value.parse().ok()),
+                        },
+                    );
+                }
+            }
+            ("events", "Dialogue") => {
+                if event_format.is_empty() {
+                    return Err(UmbreonError::parse(
+                        "ASS",
+                        format!("line {}: Dialogue before the events Format", index + 1),
+                    ));
+                }
+                let values = fields(&event_format);
+                let get = |name: &str| {
+                    event_format
+                        .iter()
+                        .position(|field| field == name)
+                        .and_then(|index| values.get(index))
+                        .map(String::as_str)
+                };
+                let time = get("start").and_then(ass_time).ok_or_else(|| {
+                    UmbreonError::parse("ASS", format!("line {}: invalid Start", index + 1))
+                })?;
+                let raw = get("text").unwrap_or_default();
+                let style = get("style")
+                    .and_then(|name| styles.get(name.trim_start_matches('*')))
+                    .copied()
+                    .unwrap_or_default();
+                let tags = override_tags(raw);
+                let color = tags
+                    .iter()
+                    .rev()
+                    .find_map(|tag| color_override(tag))
+                    .and_then(ass_color)
+                    .or(style.color)
+                    .unwrap_or(DEFAULT_COLOR);
+                let size = tags
+                    .iter()
+                    .rev()
+                    .find_map(|tag| {
+                        tag.strip_prefix("fs")
+                            .and_then(|size| size.parse::<f64>().ok())
+                    })
+                    .map(|size| size.round() as u32)
+                    .or(style.size)
+                    .unwrap_or(DEFAULT_SIZE);
+                entries.push(DanmakuEntry {
+                    mode: ass_mode(&tags, style.alignment),
+                    color,
+                    size,
+                    sender: get("name").and_then(non_empty),
+                    ..DanmakuEntry::new(time, plain_text(raw))
+                });
+            }
+            _ => {}
+        }
+    }
+    if event_format.is_empty() {
+        return Err(UmbreonError::parse("ASS", "missing [Events] section"));
+    }
+    sort_by_time(&mut entries);
+    Ok(entries)
+}
+
+/// An ASS script drawing `entries` the way danmaku2ass does: scrolling lines move across
+/// the screen, top and bottom lines are centered. Rows are assigned round-robin; the
+/// player's own overlay does proper collision avoidance.
+pub fn to_ass(entries: &[DanmakuEntry], options: &AssOptions) -> String {
+    let mut script = format!(
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: {}\nPlayResY: {}\n\n",
+        options.width, options.height
+    );
+    script.push_str("[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n");
+    script.push_str(&format!(
+        "Style: Danmaku,{},{DEFAULT_SIZE},&H00FFFFFF,&H00FFFFFF,&H00000000,&H00000000,0,0,0,0,100,100,0.00,0.00,1,1,0,7,0,0,0,0\n\n",
+        options.font
+    ));
+    script.push_str(
+        "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    let line_height = DEFAULT_SIZE + 4;
+    let rows = (options.height / line_height).max(1);
+    let mut next_row: HashMap<DanmakuMode, u32> = HashMap::new();
+    for entry in entries {
+        let row = next_row.entry(entry.mode).or_default();
+        let offset = (*row % rows) * line_height;
+        *row += 1;
+        let text_width = entry.text.chars().count() as u32 * entry.size;
+        let (position, duration) = match entry.mode {
+            DanmakuMode::Scroll => (
+                format!("\\move({},{offset},-{text_width},{offset})", options.width),
+                options.scroll_duration,
+            ),
+            DanmakuMode::Top => (
+                format!("\\an8\\pos({},{offset})", options.width / 2),
+                options.fixed_duration,
+            ),
+            DanmakuMode::Bottom => (
+                format!(
+                    "\\an2\\pos({},{})",
+                    options.width / 2,
+                    options.height - offset
+                ),
+                options.fixed_duration,
+            ),
+        };
+        let mut tags = position;
+        if entry.color != DEFAULT_COLOR {
+            tags.push_str(&format!("\\c{}", format_ass_color(entry.color)));
+        }
+        if entry.size != DEFAULT_SIZE {
+            tags.push_str(&format!("\\fs{}", entry.size));
+        }
+        // ASS cannot escape braces, so they are written full-width.
+        let text = entry
+            .text
+            .replace('{', "｛")
+            .replace('}', "｝")
+            .replace('\n', "\\N");
+        script.push_str(&format!(
+            "Dialogue: 2,{},{},Danmaku,{},0000,0000,0000,,{{{tags}}}{text}\n",
+            format_ass_time(entry.time),
+            format_ass_time(entry.time + duration),
+            entry
+                .sender
+                .as_deref()
+                .unwrap_or_default()
+                .replace(',', " "),
+        ));
+    }
+    script
+}
+
+/// The file formats [`parse_danmaku`] reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DanmakuFormat {
+    BilibiliXml,
+    /// JSON lines, or one JSON array of entries.
+    Json,
+    Ass,
+}
+
+impl DanmakuFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            DanmakuFormat::BilibiliXml => "Bilibili XML",
+            DanmakuFormat::Json => "JSON",
+            DanmakuFormat::Ass => "ASS",
+        }
+    }
+
+    fn from_extension(name: &str) -> Option<Self> {
+        let name = name.split(['?', '#']).next().unwrap_or(name);
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "xml" => Some(DanmakuFormat::BilibiliXml),
+            "json" | "jsonl" | "ndjson" => Some(DanmakuFormat::Json),
+            "ass" | "ssa" => Some(DanmakuFormat::Ass),
+            _ => None,
+        }
+    }
+}
+
+/// Tells the format from the content, falling back to the extension of `name` (a path or
+/// URL) when the content is not conclusive.
+pub fn detect_format(text: &str, name: Option<&str>) -> Option<DanmakuFormat> {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    // ASS sections are bracketed too, so they are ruled out before JSON arrays.
+    if text.starts_with("[Script Info]") || text.contains("[Events]") {
+        Some(DanmakuFormat::Ass)
+    } else if text.starts_with('<') {
+        Some(DanmakuFormat::BilibiliXml)
+    } else if text.starts_with('{') || text.starts_with('[') {
+        Some(DanmakuFormat::Json)
+    } else {
+        name.and_then(DanmakuFormat::from_extension)
+    }
+}
+
+/// Parses a danmaku file of any supported format, as detected by [`detect_format`].
+pub fn parse_danmaku(text: &str, name: Option<&str>) -> Result<(DanmakuFormat, Vec<DanmakuEntry>)> {
+    let format = detect_format(text, name)
+        .ok_or_else(|| UmbreonError::parse("danmaku", "unknown file format"))?;
+    let text = text.trim_start_matches('\u{feff}');
+    let entries = match format {
+        DanmakuFormat::BilibiliXml => parse_bilibili_xml(text)?,
+        DanmakuFormat::Ass => parse_ass(text)?,
+        DanmakuFormat::Json if text.trim_start().starts_with('[') => {
+            let mut entries = serde_json::from_str::<Vec<DanmakuEntry>>(text)
+                .map_err(|err| UmbreonError::parse("danmaku JSON", err))?;
+            sort_by_time(&mut entries);
+            entries
+        }
+        DanmakuFormat::Json => parse_json_lines(text)?,
+    };
+    Ok((format, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BILIBILI: &str = include_str!("../fixtures/danmaku/bilibili.xml");
+    const JSON_LINES: &str = include_str!("../fixtures/danmaku/live.jsonl");
+    const ASS: &str = include_str!("../fixtures/danmaku/danmaku.ass");
+
+    #[test]
+    fn bilibili_xml_reads_attributes_and_skips_advanced_modes() {
+        let entries = parse_bilibili_xml(BILIBILI).unwrap();
+        assert_eq!(entries.len(), 4);
+        let texts = entries
+            .iter()
+            .map(|entry| entry.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec!["顶部红字", "底部字幕 & <注释>", "逆向弹幕", "前方高能"]
+        );
+        let top = &entries[0];
+        assert_eq!(top.time, 3.2);
+        assert_eq!(top.mode, DanmakuMode::Top);
+        assert_eq!(top.color, 0xFF0000);
+        assert_eq!(top.sender.as_deref(), Some("9c0e77aa"));
+        assert_eq!(top.sent_at, Some(1770300012));
+        let bottom = &entries[1];
+        assert_eq!(
+            (bottom.mode, bottom.size, bottom.pool),
+            (DanmakuMode::Bottom, 18, 1)
+        );
+        assert_eq!(entries[2].mode, DanmakuMode::Scroll);
+        assert_eq!(entries[3].id.as_deref(), Some("41526832412534784"));
+    }
+
+    #[test]
+    fn bilibili_xml_round_trips() {
+        let entries = parse_bilibili_xml(BILIBILI).unwrap();
+        let written = to_bilibili_xml(&entries);
+        assert_eq!(parse_bilibili_xml(&written).unwrap(), entries);
+    }
+
+    #[test]
+    fn json_lines_accept_aliases_and_round_trip() {
+        let entries = parse_json_lines(JSON_LINES).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].text, "沙发");
+        assert_eq!(entries[0].color, DEFAULT_COLOR);
+        assert_eq!(entries[1].sender.as_deref(), Some("a1b2c3"));
+        let pinned = &entries[2];
+        assert_eq!(pinned.text, "置顶提醒");
+        assert_eq!(pinned.time, 2.25);
+        assert_eq!(pinned.mode, DanmakuMode::Top);
+        assert_eq!(pinned.color, 0xFFFF00);
+
+        let written = to_json_lines(&entries);
+        assert!(
+            written
+                .lines()
+                .next()
+                .unwrap()
+                .contains("\"color\":\"#FFFFFF\"")
+        );
+        assert_eq!(parse_json_lines(&written).unwrap(), entries);
+    }
+
+    #[test]
+    fn ass_dialogue_lines_map_to_modes_colors_and_sizes() {
+        let entries = parse_ass(ASS).unwrap();
+        assert_eq!(entries.len(), 3);
+        let top = &entries[0];
+        assert_eq!(
+            (top.time, top.mode, top.color),
+            (3.2, DanmakuMode::Top, 0xFF0000)
+        );
+        assert_eq!(top.size, 36);
+        let bottom = &entries[1];
+        assert_eq!(bottom.text, "底部\n字幕");
+        assert_eq!(
+            (bottom.mode, bottom.color, bottom.size),
+            (DanmakuMode::Bottom, 0x00FF00, 26)
+        );
+        assert_eq!(bottom.sender, None);
+        let scroll = &entries[2];
+        assert_eq!((scroll.mode, scroll.color), (DanmakuMode::Scroll, 0xFFFFFF));
+        assert_eq!(scroll.sender.as_deref(), Some("5f2d1a3b"));
+    }
+
+    #[test]
+    fn ass_round_trips_what_it_can_represent() {
+        let mut entries = parse_bilibili_xml(BILIBILI).unwrap();
+        entries.push(DanmakuEntry::new(61.5, "a, b {c}"));
+        let parsed = parse_ass(&to_ass(&entries, &AssOptions::default())).unwrap();
+        assert_eq!(parsed.len(), entries.len());
+        for (original, parsed) in entries.iter().zip(&parsed) {
+            assert!((original.time - parsed.time).abs() < 0.01);
+            assert_eq!(parsed.mode, original.mode);
+            assert_eq!(parsed.color, original.color);
+            assert_eq!(parsed.size, original.size);
+            assert_eq!(parsed.sender, original.sender);
+        }
+        assert_eq!(parsed[1].text, entries[1].text);
+        assert_eq!(parsed.last().unwrap().text, "a, b ｛c｝");
+    }
+
+    #[test]
+    fn formats_are_detected_from_content_or_name() {
+        for (text, format, count) in [
+            (BILIBILI, DanmakuFormat::BilibiliXml, 4),
+            (JSON_LINES, DanmakuFormat::Json, 3),
+            (ASS, DanmakuFormat::Ass, 3),
+        ] {
+            let (detected, entries) = parse_danmaku(text, Some("download.bin")).unwrap();
+            assert_eq!(detected, format);
+            assert_eq!(entries.len(), count, "{format:?}");
+        }
+
+        let array = "\u{feff}[{\"time\": 3, \"text\": \"后\"}, {\"time\": 1, \"text\": \"前\"}]";
+        let (format, entries) = parse_danmaku(array, None).unwrap();
+        assert_eq!(format, DanmakuFormat::Json);
+        assert_eq!(entries[0].text, "前");
+
+        assert_eq!(
+            detect_format("", Some("https://example.com/av1.XML?token=1")),
+            Some(DanmakuFormat::BilibiliXml)
+        );
+        assert_eq!(
+            detect_format("", Some("/videos/ep1.ssa")),
+            Some(DanmakuFormat::Ass)
+        );
+        assert_eq!(detect_format("hello", Some("notes.txt")), None);
+        assert!(parse_danmaku("hello", None).is_err());
+    }
+
+    #[test]
+    fn malformed_danmaku_is_rejected() {
+        assert!(parse_bilibili_xml("<root/>").is_err());
+        assert!(parse_bilibili_xml("<i><d p=\"x,1,25,0\">a</d></i>").is_err());
+        assert!(parse_json_lines("{\"time\": 1}").is_err());
+        assert!(
+            parse_json_lines("{\"time\": 1, \"text\": \"a\", \"color\": \"#GG0000\"}").is_err()
+        );
+        assert!(parse_ass("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,hi").is_err());
+        assert_eq!(parse_color("#00ff00"), Some(0x00FF00));
+        assert_eq!(parse_color("16777215"), Some(0xFFFFFF));
+    }
+}
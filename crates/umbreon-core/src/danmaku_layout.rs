@@ -0,0 +1,508 @@
+//! Danmaku overlay layout: which track each comment takes and where it is at a given moment
+//! of the player clock.
+//!
+//! Everything is computed in a fixed virtual viewport of [`VIEWPORT_WIDTH`] by
+//! [`VIEWPORT_HEIGHT`] pixels; the renderer scales it to the real player. Placement depends
+//! only on comment times, so seeking just asks for a different moment and rate changes only
+//! change how fast the clock runs.
+
+use crate::danmaku::{DEFAULT_SIZE, DanmakuEntry, DanmakuMode};
+use serde::{Deserialize, Serialize};
+
+pub const VIEWPORT_WIDTH: f64 = 1920.0;
+pub const VIEWPORT_HEIGHT: f64 = 1080.0;
+/// Font size, in viewport pixels, of a default-size comment at scale 1.
+pub const BASE_FONT_PX: f64 = 48.0;
+/// Track height as a multiple of the font size.
+const LINE_SPACING: f64 = 1.25;
+/// Seconds a scrolling comment takes to cross the screen.
+pub const SCROLL_DURATION: f64 = 8.0;
+/// Seconds a top or bottom comment stays up.
+pub const FIXED_DURATION: f64 = 4.0;
+/// Golden-ratio steps spread the comments kept at a reduced density evenly over time.
+const DENSITY_STEP: f64 = 0.618_033_988_749_895;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    pub enabled: bool,
+    /// 0.1–1.
+    pub opacity: f64,
+    /// Multiplies every comment's font size, 0.5–2.
+    pub font_scale: f64,
+    /// Fraction of the player height comments may use, measured from the top, 0.25–1.
+    pub area: f64,
+    /// Fraction of comments shown, 0.1–1.
+    pub density: f64,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        OverlaySettings {
+            enabled: true,
+            opacity: 0.85,
+            font_scale: 1.0,
+            area: 1.0,
+            density: 1.0,
+        }
+    }
+}
+
+impl OverlaySettings {
+    /// Display area choices offered in settings.
+    pub const AREAS: [f64; 4] = [0.25, 0.5, 0.75, 1.0];
+
+    /// The same settings with every value inside its range.
+    pub fn clamped(self) -> Self {
+        let clamp = |value: f64, min: f64, max: f64, fallback: f64| {
+            if value.is_finite() {
+                value.clamp(min, max)
+            } else {
+                fallback
+            }
+        };
+        let defaults = OverlaySettings::default();
+        OverlaySettings {
+            enabled: self.enabled,
+            opacity: clamp(self.opacity, 0.1, 1.0, defaults.opacity),
+            font_scale: clamp(self.font_scale, 0.5, 2.0, defaults.font_scale),
+            area: clamp(self.area, 0.25, 1.0, defaults.area),
+            density: clamp(self.density, 0.1, 1.0, defaults.density),
+        }
+    }
+
+    fn line_height(&self) -> f64 {
+        BASE_FONT_PX * self.font_scale * LINE_SPACING
+    }
+
+    /// Tracks that fit in the display area; there is always at least one.
+    pub fn track_count(&self) -> usize {
+        ((VIEWPORT_HEIGHT * self.area / self.line_height()).floor() as usize).max(1)
+    }
+}
+
+/// Rough rendered width of a single line of `text`: full-width characters take one em,
+/// everything else a little over half.
+pub fn estimate_width(text: &str, font_px: f64) -> f64 {
+    let ems: f64 = text
+        .chars()
+        .filter(|ch| !ch.is_control())
+        .map(|ch| if ch.is_ascii() { 0.55 } else { 1.0 })
+        .sum();
+    ems * font_px
+}
+
+/// A comment with its track and screen time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlacedDanmaku {
+    /// Index of the entry in the list given to [`DanmakuLayout::build`] or
+    /// [`DanmakuLayout::push`].
+    pub index: usize,
+    pub mode: DanmakuMode,
+    /// Counted from the top for scroll and top comments and from the bottom of the display
+    /// area for bottom comments. A comment larger than the default size also takes the tracks
+    /// after this one.
+    pub track: usize,
+    pub start: f64,
+    pub end: f64,
+    pub font_px: f64,
+    pub width: f64,
+    /// Top edge, in viewport pixels.
+    pub y: f64,
+}
+
+impl PlacedDanmaku {
+    fn speed(&self) -> f64 {
+        (VIEWPORT_WIDTH + self.width) / (self.end - self.start)
+    }
+
+    /// Left edge at `time`. Scrolling comments enter at the right edge and leave once their
+    /// tail passes the left edge; fixed ones are centered.
+    pub fn x_at(&self, time: f64) -> f64 {
+        match self.mode {
+            DanmakuMode::Scroll => {
+                let elapsed = (time - self.start).clamp(0.0, self.end - self.start);
+                VIEWPORT_WIDTH - elapsed * self.speed()
+            }
+            DanmakuMode::Top | DanmakuMode::Bottom => (VIEWPORT_WIDTH - self.width) / 2.0,
+        }
+    }
+
+    pub fn is_visible_at(&self, time: f64) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+/// Track assignment for a list of comments. Comments that find no free track, or that the
+/// density setting thins out, are left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DanmakuLayout {
+    settings: OverlaySettings,
+    /// The last comment placed in each scroll track.
+    scroll: Vec<Option<PlacedDanmaku>>,
+    /// When each top and bottom track frees up.
+    top: Vec<f64>,
+    bottom: Vec<f64>,
+    /// Sorted by start time.
+    placed: Vec<PlacedDanmaku>,
+}
+
+impl DanmakuLayout {
+    pub fn new(settings: OverlaySettings) -> Self {
+        let settings = settings.clamped();
+        let tracks = settings.track_count();
+        DanmakuLayout {
+            settings,
+            scroll: vec![None; tracks],
+            top: vec![f64::NEG_INFINITY; tracks],
+            bottom: vec![f64::NEG_INFINITY; tracks],
+            placed: Vec::new(),
+        }
+    }
+
+    /// Lays out `entries` in time order; indices in the result refer to `entries`.
+    pub fn build(entries: &[DanmakuEntry], settings: OverlaySettings) -> Self {
+        let mut layout = DanmakuLayout::new(settings);
+        let mut order = (0..entries.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| entries[*a].time.total_cmp(&entries[*b].time));
+        for index in order {
+            layout.push(index, &entries[index]);
+        }
+        layout
+    }
+
+    pub fn settings(&self) -> OverlaySettings {
+        self.settings
+    }
+
+    pub fn placed(&self) -> &[PlacedDanmaku] {
+        &self.placed
+    }
+
+    /// Places one more comment, for sources that arrive over time. Tracks are only checked
+    /// against earlier comments, so entries should come in time order.
+    pub fn push(&mut self, index: usize, entry: &DanmakuEntry) -> Option<&PlacedDanmaku> {
+        if !entry.time.is_finite() || entry.text.trim().is_empty() || !self.keeps(index) {
+            return None;
+        }
+        let font_px =
+            BASE_FONT_PX * self.settings.font_scale * entry.size as f64 / DEFAULT_SIZE as f64;
+        let width = estimate_width(&entry.text, font_px);
+        let line_height = self.settings.line_height();
+        let area_bottom = self.scroll.len() as f64 * line_height;
+        let span = ((font_px * LINE_SPACING / line_height).ceil() as usize).max(1);
+        let mut placed = PlacedDanmaku {
+            index,
+            mode: entry.mode,
+            track: 0,
+            start: entry.time,
+            end: entry.time
+                + match entry.mode {
+                    DanmakuMode::Scroll => SCROLL_DURATION,
+                    DanmakuMode::Top | DanmakuMode::Bottom => FIXED_DURATION,
+                },
+            font_px,
+            width,
+            y: 0.0,
+        };
+        match entry.mode {
+            DanmakuMode::Scroll => {
+                let track = free_tracks(&self.scroll, span, |last| {
+                    last.as_ref().is_none_or(|last| fits_after(last, &placed))
+                })?;
+                placed.track = track;
+                placed.y = track as f64 * line_height;
+                self.scroll[track..track + span].fill(Some(placed.clone()));
+            }
+            DanmakuMode::Top => {
+                let track = free_tracks(&self.top, span, |free| *free <= placed.start)?;
+                placed.track = track;
+                placed.y = track as f64 * line_height;
+                self.top[track..track + span].fill(placed.end);
+            }
+            DanmakuMode::Bottom => {
+                let track = free_tracks(&self.bottom, span, |free| *free <= placed.start)?;
+                placed.track = track;
+                placed.y = area_bottom - (track + span) as f64 * line_height;
+                self.bottom[track..track + span].fill(placed.end);
+            }
+        }
+        let at = self
+            .placed
+            .partition_point(|other| other.start <= placed.start);
+        self.placed.insert(at, placed);
+        self.placed.get(at)
+    }
+
+    fn keeps(&self, index: usize) -> bool {
+        self.settings.density >= 1.0
+            || ((index as f64 + 1.0) * DENSITY_STEP).fract() < self.settings.density
+    }
+
+    /// Comments on screen at `time`, in start order.
+    pub fn visible_at(&self, time: f64) -> Vec<&PlacedDanmaku> {
+        let longest = SCROLL_DURATION.max(FIXED_DURATION);
+        let first = self
+            .placed
+            .partition_point(|placed| placed.start <= time - longest);
+        let last = self.placed.partition_point(|placed| placed.start <= time);
+        self.placed[first..last]
+            .iter()
+            .filter(|placed| placed.is_visible_at(time))
+            .collect()
+    }
+}
+
+/// The first of `span` adjacent tracks that are all free, if any.
+fn free_tracks<T>(tracks: &[T], span: usize, free: impl Fn(&T) -> bool) -> Option<usize> {
+    tracks
+        .windows(span)
+        .position(|window| window.iter().all(&free))
+}
+
+/// Whether `next` can follow `last` in a scroll track: `last` must have fully entered the
+/// screen when `next` appears, and `next` (faster if longer) must not reach the left edge
+/// before `last` has left it.
+fn fits_after(last: &PlacedDanmaku, next: &PlacedDanmaku) -> bool {
+    let entered = last.start + last.width / last.speed();
+    let reaches_left = next.start + VIEWPORT_WIDTH / next.speed();
+    entered <= next.start && reaches_left >= last.end
+}
+
+/// The player position between the backend's reports, which come a few times a second.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlaybackClock {
+    position: f64,
+    rate: f64,
+    playing: bool,
+    /// Wall-clock milliseconds of the last report.
+    anchor_ms: i64,
+}
+
+impl PlaybackClock {
+    /// Re-anchors on a report from the player, including seeks and rate changes.
+    pub fn sync(&mut self, position: f64, rate: f64, playing: bool, now_ms: i64) {
+        *self = PlaybackClock {
+            position,
+            rate,
+            playing,
+            anchor_ms: now_ms,
+        };
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn position_at(&self, now_ms: i64) -> f64 {
+        if !self.playing {
+            return self.position;
+        }
+        let elapsed = (now_ms - self.anchor_ms).max(0) as f64 / 1000.0;
+        self.position + elapsed * self.rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time: f64, text: &str, mode: DanmakuMode) -> DanmakuEntry {
+        DanmakuEntry {
+            mode,
+            ..DanmakuEntry::new(time, text)
+        }
+    }
+
+    fn overlaps(a: &PlacedDanmaku, b: &PlacedDanmaku, time: f64) -> bool {
+        let (a_left, b_left) = (a.x_at(time), b.x_at(time));
+        let (a_height, b_height) = (a.font_px * LINE_SPACING, b.font_px * LINE_SPACING);
+        a.y < b.y + b_height
+            && b.y < a.y + a_height
+            && a_left < b_left + b.width
+            && b_left < a_left + a.width
+    }
+
+    #[test]
+    fn scrolling_comments_never_overlap() {
+        let entries = (0..200)
+            .map(|i| {
+                let text = "弹幕".repeat(1 + i % 7) + &"x".repeat(i % 5);
+                entry(i as f64 * 0.13, &text, DanmakuMode::Scroll)
+            })
+            .collect::<Vec<_>>();
+        let layout = DanmakuLayout::build(&entries, OverlaySettings::default());
+        assert!(layout.placed().len() > 100, "{}", layout.placed().len());
+        let mut time = 0.0;
+        while time < 35.0 {
+            let visible = layout.visible_at(time);
+            for (i, a) in visible.iter().enumerate() {
+                for b in &visible[i + 1..] {
+                    assert!(
+                        !overlaps(a, b, time),
+                        "{} and {} at {time}",
+                        a.index,
+                        b.index
+                    );
+                }
+            }
+            time += 0.05;
+        }
+    }
+
+    #[test]
+    fn fixed_comments_stack_in_their_own_tracks() {
+        let entries = vec![
+            entry(1.0, "顶部一", DanmakuMode::Top),
+            entry(1.5, "顶部二", DanmakuMode::Top),
+            entry(6.0, "顶部三", DanmakuMode::Top),
+            entry(1.0, "底部一", DanmakuMode::Bottom),
+            entry(2.0, "底部二", DanmakuMode::Bottom),
+        ];
+        let layout = DanmakuLayout::build(&entries, OverlaySettings::default());
+        let track = |index: usize| {
+            let placed = layout.placed().iter().find(|p| p.index == index).unwrap();
+            (placed.track, placed.y)
+        };
+        let line = BASE_FONT_PX * LINE_SPACING;
+        let bottom = OverlaySettings::default().track_count() as f64 * line;
+        assert_eq!(track(0), (0, 0.0));
+        assert_eq!(track(1), (1, line));
+        // The first top track is free again once comment 0 has expired at 5s.
+        assert_eq!(track(2), (0, 0.0));
+        assert_eq!(track(3), (0, bottom - line));
+        assert_eq!(track(4), (1, bottom - 2.0 * line));
+        assert_eq!(
+            layout.placed()[2].x_at(3.0),
+            (VIEWPORT_WIDTH - layout.placed()[2].width) / 2.0
+        );
+    }
+
+    #[test]
+    fn large_comments_take_as_many_tracks_as_they_need() {
+        let large = |time: f64, mode: DanmakuMode| DanmakuEntry {
+            size: DEFAULT_SIZE * 2,
+            ..entry(time, "大字", mode)
+        };
+        let entries = vec![
+            large(0.0, DanmakuMode::Scroll),
+            entry(0.0, "普通", DanmakuMode::Scroll),
+            large(0.0, DanmakuMode::Top),
+            entry(0.0, "普通", DanmakuMode::Top),
+            large(0.0, DanmakuMode::Bottom),
+            entry(0.0, "普通", DanmakuMode::Bottom),
+        ];
+        let layout = DanmakuLayout::build(&entries, OverlaySettings::default());
+        let tracks = layout
+            .placed()
+            .iter()
+            .map(|placed| (placed.index, placed.track))
+            .collect::<Vec<_>>();
+        assert_eq!(tracks, vec![(0, 0), (1, 2), (2, 0), (3, 2), (4, 0), (5, 2)]);
+        let line = BASE_FONT_PX * LINE_SPACING;
+        let bottom = OverlaySettings::default().track_count() as f64 * line;
+        assert_eq!(layout.placed()[4].y, bottom - 2.0 * line);
+        for (i, a) in layout.placed().iter().enumerate() {
+            for b in &layout.placed()[i + 1..] {
+                assert!(!overlaps(a, b, 0.5), "{} and {}", a.index, b.index);
+            }
+        }
+
+        // A comment taller than the whole display area finds no room.
+        let narrow = OverlaySettings {
+            area: 0.25,
+            font_scale: 2.0,
+            ..OverlaySettings::default()
+        };
+        assert_eq!(narrow.track_count(), 2);
+        let huge = DanmakuEntry {
+            size: DEFAULT_SIZE * 3,
+            ..entry(0.0, "超大", DanmakuMode::Scroll)
+        };
+        assert!(DanmakuLayout::build(&[huge], narrow).placed().is_empty());
+    }
+
+    #[test]
+    fn area_and_density_limit_what_is_shown() {
+        let entries = (0..40)
+            .map(|i| entry(0.0, &format!("同时 {i}"), DanmakuMode::Scroll))
+            .collect::<Vec<_>>();
+        let quarter = OverlaySettings {
+            area: 0.25,
+            ..OverlaySettings::default()
+        };
+        let layout = DanmakuLayout::build(&entries, quarter);
+        assert_eq!(quarter.track_count(), 4);
+        assert_eq!(layout.placed().len(), 4);
+        assert!(layout.placed().iter().all(|p| p.y < VIEWPORT_HEIGHT * 0.25));
+
+        let spread = (0..1000)
+            .map(|i| entry(i as f64 * 10.0, "稀疏", DanmakuMode::Scroll))
+            .collect::<Vec<_>>();
+        let half = OverlaySettings {
+            density: 0.5,
+            ..OverlaySettings::default()
+        };
+        let kept = DanmakuLayout::build(&spread, half).placed().len();
+        assert!((480..=520).contains(&kept), "{kept}");
+        // Thinning is stable, so rebuilding after a settings change keeps the same comments.
+        assert_eq!(
+            DanmakuLayout::build(&spread, half),
+            DanmakuLayout::build(&spread, half)
+        );
+    }
+
+    #[test]
+    fn seeking_asks_for_a_different_moment() {
+        let entries = vec![
+            entry(2.0, "开头", DanmakuMode::Scroll),
+            entry(60.0, "中间", DanmakuMode::Scroll),
+            entry(61.0, "中间顶部", DanmakuMode::Top),
+        ];
+        let layout = DanmakuLayout::build(&entries, OverlaySettings::default());
+        let indices = |time: f64| {
+            layout
+                .visible_at(time)
+                .iter()
+                .map(|p| p.index)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(indices(3.0), vec![0]);
+        assert_eq!(indices(62.0), vec![1, 2]);
+        assert_eq!(indices(66.0), vec![1]);
+        assert_eq!(indices(5.0), vec![0]);
+        assert!(indices(30.0).is_empty());
+
+        let scroll = &layout.placed()[0];
+        assert_eq!(scroll.x_at(2.0), VIEWPORT_WIDTH);
+        assert_eq!(scroll.x_at(2.0 + SCROLL_DURATION), -scroll.width);
+    }
+
+    #[test]
+    fn clock_runs_at_the_player_rate_between_reports() {
+        let mut clock = PlaybackClock::default();
+        clock.sync(10.0, 1.0, true, 1_000);
+        assert_eq!(clock.position_at(1_500), 10.5);
+        clock.sync(10.5, 2.0, true, 1_500);
+        assert_eq!(clock.position_at(2_000), 11.5);
+        clock.sync(120.0, 2.0, false, 2_000);
+        assert_eq!(clock.position_at(9_000), 120.0);
+    }
+
+    #[test]
+    fn settings_are_clamped() {
+        let settings = OverlaySettings {
+            enabled: false,
+            opacity: 3.0,
+            font_scale: 0.1,
+            area: f64::NAN,
+            density: 0.0,
+        }
+        .clamped();
+        assert_eq!(settings.opacity, 1.0);
+        assert_eq!(settings.font_scale, 0.5);
+        assert_eq!(settings.area, 1.0);
+        assert_eq!(settings.density, 0.1);
+        assert!(!settings.enabled);
+    }
+}
@@ -0,0 +1,659 @@
+//! Live danmaku from a WebSocket relay.
+//!
+//! [`run`] keeps one relay connection alive: it decodes the relay's messages — JSON lines, or
+//! Bilibili's binary packets — into [`DanmakuEntry`]s, reports every [`ConnectionState`]
+//! change and reconnects with a [`Backoff`] after errors, idle sockets and server closes.
+//! [`DanmakuBuffer`] holds what arrives while the player is paused.
+//!
+//! The connection itself needs the `live` feature and a tokio runtime. Decoding
+//! ([`decode_frame`]), backoff and buffering are always available, so a client on another
+//! WebSocket implementation can follow the same rules.
+
+use crate::bilibili::{self, AuthParams, LiveMessage};
+use crate::danmaku::DanmakuEntry;
+use crate::error::{Result, UmbreonError};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Entries held while paused; older ones are dropped first.
+pub const BUFFER_CAPACITY: usize = 500;
+/// A socket with no traffic for this long is treated as dead and reconnected.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    /// Waiting `delay` before the next try; `attempt` counts failures since the relay last
+    /// delivered a message.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    /// Retries are used up, or the room refused the client.
+    Failed(UmbreonError),
+}
+
+impl ConnectionState {
+    pub fn label(&self) -> String {
+        match self {
+            ConnectionState::Disconnected => "未连接".to_string(),
+            ConnectionState::Connecting => "连接中".to_string(),
+            ConnectionState::Connected => "已连接".to_string(),
+            ConnectionState::Reconnecting { attempt, delay } => {
+                format!(
+                    "{:.0} 秒后重连（第 {attempt} 次）",
+                    delay.as_secs_f64().ceil()
+                )
+            }
+            ConnectionState::Failed(_) => "连接失败".to_string(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected)
+    }
+}
+
+/// How a relay frames its messages.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RelayProtocol {
+    /// Text messages of [JSON lines](decode_message).
+    #[default]
+    JsonLines,
+    /// Bilibili's live server: binary packets, an auth packet to enter the room and
+    /// heartbeats to stay in it.
+    Bilibili(AuthParams),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LiveEvent {
+    State(ConnectionState),
+    Entries(Vec<DanmakuEntry>),
+}
+
+/// One message from the relay socket, whichever WebSocket client received it.
+#[derive(Clone, Copy, Debug)]
+pub enum RelayFrame<'a> {
+    Text(&'a str),
+    Binary(&'a [u8]),
+}
+
+/// Entries in one relay message, or `None` when it held nothing that shows the relay is
+/// working: no comments and, for Bilibili, no accepting auth reply. Any other auth reply is
+/// an [`UmbreonError::Auth`]: the room refused the client.
+pub fn decode_frame(
+    protocol: &RelayProtocol,
+    frame: RelayFrame<'_>,
+) -> Result<Option<Vec<DanmakuEntry>>> {
+    let entries = match (protocol, frame) {
+        (RelayProtocol::JsonLines, RelayFrame::Text(text)) => decode_message(text),
+        (RelayProtocol::JsonLines, RelayFrame::Binary(bytes)) => {
+            decode_message(&String::from_utf8_lossy(bytes))
+        }
+        (RelayProtocol::Bilibili(_), RelayFrame::Binary(bytes)) => {
+            let mut entries = Vec::new();
+            let mut accepted = false;
+            for packet in bilibili::decode_packets(bytes)? {
+                match bilibili::parse_packet(&packet) {
+                    Some(LiveMessage::AuthReply { code: 0 }) => accepted = true,
+                    Some(LiveMessage::AuthReply { code }) => {
+                        return Err(UmbreonError::Auth {
+                            service: "Bilibili live".to_string(),
+                            message: format!("auth code {code}"),
+                        });
+                    }
+                    Some(LiveMessage::Danmaku(entry)) => entries.push(entry),
+                    _ => {}
+                }
+            }
+            if accepted {
+                return Ok(Some(entries));
+            }
+            entries
+        }
+        (RelayProtocol::Bilibili(_), RelayFrame::Text(_)) => Vec::new(),
+    };
+    Ok((!entries.is_empty()).then_some(entries))
+}
+
+/// Exponential reconnect delays: `initial`, doubling up to `max`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// Failures in a row before giving up; `None` retries forever.
+    pub max_attempts: Option<u32>,
+    attempt: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_secs(1), Duration::from_secs(30))
+    }
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            max_attempts: None,
+            attempt: 0,
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Records a failure and returns how long to wait, or `None` when retries are used up.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| self.attempt >= max) {
+            return None;
+        }
+        let delay = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.attempt.min(16)))
+            .min(self.max);
+        self.attempt += 1;
+        Some(delay)
+    }
+
+    /// Called once a connection proves itself by delivering a message; a socket that opens
+    /// and drops straight away keeps backing off.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Decodes one relay message: JSON objects, one per line. Lines that are not comments are
+/// skipped rather than failing the message. Live relays often leave out `time`; those
+/// entries read as time 0 and the caller stamps them with the player position.
+pub fn decode_message(text: &str) -> Vec<DanmakuEntry> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let mut value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+            let object = value.as_object_mut()?;
+            if !["time", "t", "offset"]
+                .iter()
+                .any(|key| object.contains_key(*key))
+            {
+                object.insert("time".to_string(), 0.0.into());
+            }
+            serde_json::from_value::<DanmakuEntry>(value).ok()
+        })
+        .collect()
+}
+
+/// Passes entries through while playing and holds them while paused, so comments sent
+/// during a pause show up when playback resumes.
+#[derive(Clone, Debug, Default)]
+pub struct DanmakuBuffer {
+    paused: bool,
+    pending: VecDeque<DanmakuEntry>,
+    dropped: usize,
+}
+
+impl DanmakuBuffer {
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Entries held right now.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Entries dropped because the buffer was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Returns the held entries when resuming.
+    pub fn set_paused(&mut self, paused: bool) -> Vec<DanmakuEntry> {
+        self.paused = paused;
+        if paused {
+            return Vec::new();
+        }
+        self.pending.drain(..).collect()
+    }
+
+    /// Returns the entries to show now.
+    pub fn push(&mut self, entries: Vec<DanmakuEntry>) -> Vec<DanmakuEntry> {
+        if !self.paused {
+            return entries;
+        }
+        self.pending.extend(entries);
+        while self.pending.len() > BUFFER_CAPACITY {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "live")]
+pub use client::run;
+
+#[cfg(feature = "live")]
+mod client {
+    use super::{
+        Backoff, ConnectionState, IDLE_TIMEOUT, LiveEvent, RelayFrame, RelayProtocol, decode_frame,
+    };
+    use crate::bilibili;
+    use crate::danmaku::DanmakuEntry;
+    use crate::error::{Result, UmbreonError};
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn relay_error(err: impl ToString) -> UmbreonError {
+        UmbreonError::network("danmaku relay", err)
+    }
+
+    fn decode(protocol: &RelayProtocol, message: Message) -> Result<Option<Vec<DanmakuEntry>>> {
+        match message {
+            Message::Text(text) => decode_frame(protocol, RelayFrame::Text(&text)),
+            Message::Binary(bytes) => decode_frame(protocol, RelayFrame::Binary(&bytes)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Connects to `url` and reports events until `backoff` gives up or the room refuses the
+    /// client; cancel the task to stop earlier.
+    pub async fn run(
+        url: &str,
+        protocol: &RelayProtocol,
+        mut backoff: Backoff,
+        mut on_event: impl FnMut(LiveEvent),
+    ) {
+        loop {
+            on_event(LiveEvent::State(ConnectionState::Connecting));
+            let error = match session(url, protocol, &mut backoff, &mut on_event).await {
+                Ok(()) => relay_error("connection closed by the relay"),
+                Err(err) => err,
+            };
+            let delay = match error {
+                UmbreonError::Auth { .. } => None,
+                _ => backoff.next_delay(),
+            };
+            match delay {
+                Some(delay) => {
+                    on_event(LiveEvent::State(ConnectionState::Reconnecting {
+                        attempt: backoff.attempt(),
+                        delay,
+                    }));
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    on_event(LiveEvent::State(ConnectionState::Failed(error)));
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn session(
+        url: &str,
+        protocol: &RelayProtocol,
+        backoff: &mut Backoff,
+        on_event: &mut impl FnMut(LiveEvent),
+    ) -> Result<()> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(relay_error)?;
+        if let RelayProtocol::Bilibili(auth) = protocol {
+            let auth = bilibili::auth_packet(auth).encode();
+            socket
+                .send(Message::Binary(auth))
+                .await
+                .map_err(relay_error)?;
+        }
+        on_event(LiveEvent::State(ConnectionState::Connected));
+        // Only Bilibili needs heartbeats; the JSON-lines relay never ticks in practice.
+        let heartbeat_every = match protocol {
+            RelayProtocol::Bilibili(_) => bilibili::HEARTBEAT_INTERVAL,
+            RelayProtocol::JsonLines => Duration::from_secs(24 * 60 * 60),
+        };
+        let mut heartbeat = tokio::time::interval(heartbeat_every);
+        heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            let message = tokio::select! {
+                message = tokio::time::timeout(IDLE_TIMEOUT, socket.next()) => {
+                    message.map_err(|_| relay_error("no messages from the relay"))?
+                }
+                _ = heartbeat.tick() => {
+                    if let RelayProtocol::Bilibili(_) = protocol {
+                        let packet = bilibili::heartbeat_packet().encode();
+                        socket.send(Message::Binary(packet)).await.map_err(relay_error)?;
+                    }
+                    continue;
+                }
+            };
+            let message = match message {
+                None => return Ok(()),
+                Some(message) => message.map_err(relay_error)?,
+            };
+            if let Message::Close(_) = message {
+                return Ok(());
+            }
+            let Some(entries) = decode(protocol, message)? else {
+                continue;
+            };
+            backoff.reset();
+            if !entries.is_empty() {
+                on_event(LiveEvent::Entries(entries));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(entries: &[DanmakuEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.text.as_str()).collect()
+    }
+
+    #[test]
+    fn messages_decode_line_by_line() {
+        let entries = decode_message(
+            "{\"text\":\"第一条\",\"color\":\"#FF0000\"}\n\
+             not json\n\
+             \n\
+             {\"t\":12.5,\"content\":\"带时间\",\"mode\":\"top\"}\n\
+             [1, 2]\n",
+        );
+        assert_eq!(texts(&entries), ["第一条", "带时间"]);
+        assert_eq!(entries[0].time, 0.0);
+        assert_eq!(entries[0].color, 0xFF0000);
+        assert_eq!(entries[1].time, 12.5);
+    }
+
+    #[test]
+    fn frames_decode_by_protocol() {
+        let json = RelayFrame::Binary("{\"text\":\"二进制\"}".as_bytes());
+        let entries = decode_frame(&RelayProtocol::JsonLines, json)
+            .unwrap()
+            .unwrap();
+        assert_eq!(texts(&entries), ["二进制"]);
+
+        let bilibili = RelayProtocol::Bilibili(AuthParams::default());
+        assert_eq!(
+            decode_frame(&bilibili, RelayFrame::Text("{\"text\":\"x\"}")).unwrap(),
+            None
+        );
+        assert!(decode_frame(&bilibili, RelayFrame::Binary(&[0, 0, 0, 1])).is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut backoff =
+            Backoff::new(Duration::from_secs(1), Duration::from_secs(5)).with_max_attempts(5);
+        let delays = std::iter::from_fn(|| backoff.next_delay())
+            .map(|delay| delay.as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn buffer_holds_entries_while_paused() {
+        let mut buffer = DanmakuBuffer::default();
+        let live = buffer.push(vec![DanmakuEntry::new(0.0, "直通")]);
+        assert_eq!(texts(&live), ["直通"]);
+
+        assert!(buffer.set_paused(true).is_empty());
+        let held = (0..BUFFER_CAPACITY + 3)
+            .map(|i| DanmakuEntry::new(0.0, format!("暂停 {i}")))
+            .collect();
+        assert!(buffer.push(held).is_empty());
+        assert_eq!(buffer.len(), BUFFER_CAPACITY);
+        assert_eq!(buffer.dropped(), 3);
+
+        let released = buffer.set_paused(false);
+        assert_eq!(released.len(), BUFFER_CAPACITY);
+        assert_eq!(released[0].text, "暂停 3");
+        assert!(buffer.is_empty());
+    }
+
+    #[cfg(feature = "live")]
+    mod relay {
+        use super::super::*;
+        use futures_util::{SinkExt, StreamExt};
+        use tokio::net::TcpListener;
+        use tokio_tungstenite::tungstenite::Message;
+
+        /// Serves one connection per script, sending its messages and closing, then stops
+        /// listening so further connections are refused. Returns the first message each
+        /// client sent when `expect_hello` is set. Each socket is held until the client hangs
+        /// up: dropping it while a heartbeat is in flight would reset the connection and lose
+        /// the messages the client has not read yet.
+        async fn serve(
+            listener: TcpListener,
+            scripts: Vec<Vec<Message>>,
+            expect_hello: bool,
+        ) -> Vec<Message> {
+            let mut hellos = Vec::new();
+            for script in scripts {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                if expect_hello {
+                    hellos.push(socket.next().await.unwrap().unwrap());
+                }
+                for message in script {
+                    socket.send(message).await.unwrap();
+                }
+                socket.close(None).await.unwrap();
+                while let Some(Ok(_)) = socket.next().await {}
+            }
+            hellos
+        }
+
+        fn text(text: &str) -> Message {
+            Message::Text(text.to_string())
+        }
+
+        fn trace(event: LiveEvent) -> String {
+            match event {
+                LiveEvent::State(ConnectionState::Reconnecting { attempt, .. }) => {
+                    format!("reconnecting {attempt}")
+                }
+                LiveEvent::State(ConnectionState::Failed(_)) => "failed".to_string(),
+                LiveEvent::State(state) => format!("{state:?}").to_lowercase(),
+                LiveEvent::Entries(entries) => entries
+                    .iter()
+                    .map(|entry| entry.text.clone())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            }
+        }
+
+        #[tokio::test]
+        async fn reconnects_after_close_and_gives_up_when_refused() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}", listener.local_addr().unwrap());
+            let server = tokio::spawn(serve(
+                listener,
+                vec![
+                    vec![
+                        text("{\"text\":\"一\"}\n{\"text\":\"二\"}"),
+                        text("garbage"),
+                        text("{\"text\":\"三\"}"),
+                    ],
+                    vec![text("{\"text\":\"四\"}")],
+                ],
+                false,
+            ));
+
+            let backoff = Backoff::new(Duration::from_millis(20), Duration::from_millis(40))
+                .with_max_attempts(2);
+            let mut events = Vec::new();
+            run(&url, &RelayProtocol::JsonLines, backoff, |event| {
+                events.push(trace(event))
+            })
+            .await;
+            server.await.unwrap();
+
+            assert_eq!(
+                events,
+                [
+                    "connecting",
+                    "connected",
+                    "一,二",
+                    "三",
+                    "reconnecting 1",
+                    "connecting",
+                    "connected",
+                    "四",
+                    "reconnecting 1",
+                    "connecting",
+                    "reconnecting 2",
+                    "connecting",
+                    "failed",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn backoff_only_resets_once_the_relay_delivers() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}", listener.local_addr().unwrap());
+            let server = tokio::spawn(serve(
+                listener,
+                vec![
+                    vec![],
+                    vec![text("garbage")],
+                    vec![text("{\"text\":\"一\"}")],
+                ],
+                false,
+            ));
+
+            let backoff = Backoff::new(Duration::from_millis(20), Duration::from_millis(40))
+                .with_max_attempts(3);
+            let mut events = Vec::new();
+            run(&url, &RelayProtocol::JsonLines, backoff, |event| {
+                events.push(trace(event))
+            })
+            .await;
+            server.await.unwrap();
+
+            assert_eq!(
+                events,
+                [
+                    "connecting",
+                    "connected",
+                    "reconnecting 1",
+                    "connecting",
+                    "connected",
+                    "reconnecting 2",
+                    "connecting",
+                    "connected",
+                    "一",
+                    "reconnecting 1",
+                    "connecting",
+                    "reconnecting 2",
+                    "connecting",
+                    "reconnecting 3",
+                    "connecting",
+                    "failed",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn bilibili_rooms_authenticate_and_decode_packets() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}", listener.local_addr().unwrap());
+            let fixture = |bytes: &[u8]| Message::Binary(bytes.to_vec());
+            let server = tokio::spawn(serve(
+                listener,
+                vec![vec![
+                    fixture(include_bytes!("../fixtures/bilibili/auth_reply.bin")),
+                    fixture(include_bytes!("../fixtures/bilibili/heartbeat_reply.bin")),
+                    fixture(include_bytes!("../fixtures/bilibili/danmaku_zlib.bin")),
+                    fixture(include_bytes!("../fixtures/bilibili/danmaku_brotli.bin")),
+                ]],
+                true,
+            ));
+
+            let auth = AuthParams {
+                room_id: 21452505,
+                ..AuthParams::default()
+            };
+            let backoff = Backoff::default().with_max_attempts(0);
+            let mut events = Vec::new();
+            run(
+                &url,
+                &RelayProtocol::Bilibili(auth.clone()),
+                backoff,
+                |event| events.push(trace(event)),
+            )
+            .await;
+            let hellos = server.await.unwrap();
+
+            assert_eq!(
+                hellos,
+                [Message::Binary(
+                    crate::bilibili::auth_packet(&auth).encode()
+                )]
+            );
+            assert_eq!(
+                events,
+                [
+                    "connecting",
+                    "connected",
+                    "主播晚上好,顶部弹幕",
+                    "底部弹幕,SC ¥30 观众乙：主播加油！",
+                    "failed",
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn refused_auth_stops_retrying() {
+            use crate::bilibili::{Operation, Packet};
+
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}", listener.local_addr().unwrap());
+            let refused = Packet::new(Operation::AuthReply, "{\"code\":-101}").encode();
+            let server = tokio::spawn(serve(listener, vec![vec![Message::Binary(refused)]], true));
+
+            let backoff = Backoff::default().with_max_attempts(3);
+            let mut failure = None;
+            let mut events = Vec::new();
+            run(
+                &url,
+                &RelayProtocol::Bilibili(AuthParams::default()),
+                backoff,
+                |event| {
+                    if let LiveEvent::State(ConnectionState::Failed(err)) = &event {
+                        failure = Some(err.kind());
+                    }
+                    events.push(trace(event))
+                },
+            )
+            .await;
+            server.await.unwrap();
+
+            assert_eq!(events, ["connecting", "connected", "failed"]);
+            assert_eq!(failure, Some(crate::error::ErrorKind::Auth));
+        }
+    }
+}
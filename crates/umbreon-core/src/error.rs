@@ -0,0 +1,285 @@
+//! Error model shared by the app and its service clients.
+//!
+//! Every failure carries an [`ErrorKind`] so callers can decide how to react without
+//! parsing messages: whether to retry, send the user to settings, or just report it.
+
+use thiserror::Error;
+
+pub type Result<T, E = UmbreonError> = std::result::Result<T, E>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The request never got a response: DNS, TLS, timeouts, dropped connections.
+    Network,
+    /// The server answered with a non-success status that is not an auth failure.
+    Http,
+    /// Missing or rejected credentials.
+    Auth,
+    /// A response or document could not be decoded.
+    Parse,
+    /// Local persistence failed.
+    Storage,
+    /// A required setting is missing or invalid.
+    Config,
+    /// The monthly LLM budget has been spent.
+    Budget,
+}
+
+/// What the user can do about an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Trying again later may succeed.
+    Retry,
+    /// A setting needs fixing first.
+    OpenSettings,
+    /// Nothing actionable; report and move on.
+    None,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    Zh,
+    En,
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum UmbreonError {
+    #[error("{service} request failed: {message}")]
+    Network { service: String, message: String },
+    #[error("{service} returned {status}: {message}")]
+    Http {
+        service: String,
+        status: u16,
+        message: String,
+    },
+    /// The server answered but refused the operation, e.g. `success: false` in the body.
+    #[error("{service} refused the request: {message}")]
+    Rejected { service: String, message: String },
+    #[error("{service} rejected the credentials: {message}")]
+    Auth { service: String, message: String },
+    #[error("failed to parse {what}: {message}")]
+    Parse { what: String, message: String },
+    #[error("storage failed: {0}")]
+    Storage(String),
+    #[error("{setting} is not configured: {message}")]
+    Config { setting: String, message: String },
+    #[error("monthly LLM budget exhausted")]
+    BudgetExhausted,
+    /// Stored secrets cannot be read: the passphrase is missing or wrong, or the data
+    /// was sealed with another key.
+    #[error("secrets are locked: {0}")]
+    Locked(String),
+}
+
+impl UmbreonError {
+    pub fn network(service: impl Into<String>, message: impl ToString) -> Self {
+        UmbreonError::Network {
+            service: service.into(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Maps a non-success status: 401/403 become [`UmbreonError::Auth`], everything else
+    /// [`UmbreonError::Http`].
+    pub fn status(service: impl Into<String>, status: u16, message: impl ToString) -> Self {
+        let service = service.into();
+        let message = message.to_string();
+        if status == 401 || status == 403 {
+            UmbreonError::Auth { service, message }
+        } else {
+            UmbreonError::Http {
+                service,
+                status,
+                message,
+            }
+        }
+    }
+
+    pub fn rejected(service: impl Into<String>, message: impl ToString) -> Self {
+        UmbreonError::Rejected {
+            service: service.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn parse(what: impl Into<String>, message: impl ToString) -> Self {
+        UmbreonError::Parse {
+            what: what.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn storage(message: impl ToString) -> Self {
+        UmbreonError::Storage(message.to_string())
+    }
+
+    pub fn config(setting: impl Into<String>, message: impl ToString) -> Self {
+        UmbreonError::Config {
+            setting: setting.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            UmbreonError::Network { .. } => ErrorKind::Network,
+            UmbreonError::Http { .. } | UmbreonError::Rejected { .. } => ErrorKind::Http,
+            UmbreonError::Auth { .. } => ErrorKind::Auth,
+            UmbreonError::Parse { .. } => ErrorKind::Parse,
+            UmbreonError::Storage(_) => ErrorKind::Storage,
+            UmbreonError::Config { .. } => ErrorKind::Config,
+            UmbreonError::BudgetExhausted => ErrorKind::Budget,
+            UmbreonError::Locked(_) => ErrorKind::Auth,
+        }
+    }
+
+    /// Network failures, rate limits and 5xx responses are transient; everything else
+    /// fails the same way until something changes.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            UmbreonError::Network { .. } => true,
+            UmbreonError::Http { status, .. } => *status == 408 || *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    pub fn recovery(&self) -> Recovery {
+        match self.kind() {
+            ErrorKind::Auth | ErrorKind::Config | ErrorKind::Budget => Recovery::OpenSettings,
+            _ if self.is_retryable() => Recovery::Retry,
+            _ => Recovery::None,
+        }
+    }
+
+    /// A short message for toasts and status lines. Details stay in `Display` for logs.
+    pub fn user_message(&self, locale: Locale) -> String {
+        match (self, locale) {
+            (UmbreonError::Network { service, .. }, Locale::Zh) => {
+                format!("无法连接 {service}，请检查网络。")
+            }
+            (UmbreonError::Network { service, .. }, Locale::En) => {
+                format!("Could not reach {service}. Check your connection.")
+            }
+            (
+                UmbreonError::Http {
+                    service, status, ..
+                },
+                Locale::Zh,
+            ) if *status == 429 => {
+                format!("{service} 请求过于频繁，请稍后再试。")
+            }
+            (
+                UmbreonError::Http {
+                    service, status, ..
+                },
+                Locale::En,
+            ) if *status == 429 => {
+                format!("{service} is rate limiting requests. Try again later.")
+            }
+            (
+                UmbreonError::Http {
+                    service, status, ..
+                },
+                Locale::Zh,
+            ) if *status >= 500 => {
+                format!("{service} 暂时不可用（{status}）。")
+            }
+            (
+                UmbreonError::Http {
+                    service, status, ..
+                },
+                Locale::En,
+            ) if *status >= 500 => {
+                format!("{service} is temporarily unavailable ({status}).")
+            }
+            (
+                UmbreonError::Http {
+                    service, status, ..
+                },
+                Locale::Zh,
+            ) => {
+                format!("{service} 拒绝了请求（{status}）。")
+            }
+            (
+                UmbreonError::Http {
+                    service, status, ..
+                },
+                Locale::En,
+            ) => {
+                format!("{service} rejected the request ({status}).")
+            }
+            (UmbreonError::Rejected { service, message }, Locale::Zh) => {
+                format!("{service} 拒绝了请求：{message}")
+            }
+            (UmbreonError::Rejected { service, message }, Locale::En) => {
+                format!("{service} refused the request: {message}")
+            }
+            (UmbreonError::Auth { service, .. }, Locale::Zh) => {
+                format!("{service} 认证失败，请检查 API Key。")
+            }
+            (UmbreonError::Auth { service, .. }, Locale::En) => {
+                format!("{service} rejected the credentials. Check the API key.")
+            }
+            (UmbreonError::Parse { what, .. }, Locale::Zh) => format!("{what} 格式无法解析。"),
+            (UmbreonError::Parse { what, .. }, Locale::En) => format!("Could not read {what}."),
+            (UmbreonError::Storage(_), Locale::Zh) => "本地存储写入失败。".to_string(),
+            (UmbreonError::Storage(_), Locale::En) => "Saving to local storage failed.".to_string(),
+            (UmbreonError::Config { setting, .. }, Locale::Zh) => {
+                format!("请先在设置中填写 {setting}。")
+            }
+            (UmbreonError::Config { setting, .. }, Locale::En) => {
+                format!("Set {setting} in settings first.")
+            }
+            (UmbreonError::BudgetExhausted, Locale::Zh) => "本月 LLM 预算已用完。".to_string(),
+            (UmbreonError::BudgetExhausted, Locale::En) => {
+                "The monthly LLM budget is used up.".to_string()
+            }
+            (UmbreonError::Locked(_), Locale::Zh) => "密钥已锁定，请在设置中输入口令。".to_string(),
+            (UmbreonError::Locked(_), Locale::En) => {
+                "Secrets are locked. Enter the passphrase in settings.".to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_maps_auth_failures() {
+        let err = UmbreonError::status("Memory Server", 401, "unauthorized");
+        assert_eq!(err.kind(), ErrorKind::Auth);
+        assert_eq!(err.recovery(), Recovery::OpenSettings);
+
+        let err = UmbreonError::status("Memory Server", 404, "not found");
+        assert_eq!(err.kind(), ErrorKind::Http);
+        assert_eq!(err.recovery(), Recovery::None);
+    }
+
+    #[test]
+    fn transient_failures_are_retryable() {
+        assert!(UmbreonError::network("Feed Server", "timed out").is_retryable());
+        assert!(UmbreonError::status("LLM", 429, "slow down").is_retryable());
+        assert!(UmbreonError::status("LLM", 503, "unavailable").is_retryable());
+        assert!(!UmbreonError::status("LLM", 400, "bad request").is_retryable());
+        assert!(!UmbreonError::parse("feed", "unexpected eof").is_retryable());
+        assert!(!UmbreonError::rejected("Memory Server", "unknown id").is_retryable());
+        assert!(!UmbreonError::BudgetExhausted.is_retryable());
+    }
+
+    #[test]
+    fn user_messages_are_localized() {
+        let err = UmbreonError::config("Feed Server", "empty url");
+        assert_eq!(
+            err.user_message(Locale::Zh),
+            "请先在设置中填写 Feed Server。"
+        );
+        assert_eq!(
+            err.user_message(Locale::En),
+            "Set Feed Server in settings first."
+        );
+        assert_eq!(err.to_string(), "Feed Server is not configured: empty url");
+    }
+}
@@ -1,3 +1,16 @@
+pub mod bilibili;
+pub mod danmaku;
+pub mod danmaku_layout;
+pub mod danmaku_live;
+pub mod error;
+pub mod opml;
+pub mod player;
+pub mod playlist;
+pub mod probe;
+pub mod secrets;
+
+pub use error::{ErrorKind, Locale, Recovery, Result, UmbreonError};
+
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
 }
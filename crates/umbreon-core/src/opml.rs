@@ -0,0 +1,391 @@
+//! Subscription lists: OPML 2.0 in and out, and the `[feeds.*]` tables of the gist TOML
+//! that the feed aggregator reads.
+//!
+//! OPML folders and `category` attributes become tags; on export every source is a flat
+//! `<outline type="rss">` with its tags in `category`, which readers treat as folders.
+
+use crate::error::{Result, UmbreonError};
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// One subscription. `key` is its table name under `[feeds]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeedSource {
+    pub key: String,
+    pub name: String,
+    pub url: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FeedsFile {
+    #[serde(default)]
+    feeds: BTreeMap<String, FeedEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FeedEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Optional so one half-written table does not fail the whole config; tables without
+    /// a URL are skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+fn attribute(reader: &Reader<&[u8]>, element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attr| attr.key.local_name().as_ref().eq_ignore_ascii_case(name))
+        .and_then(|attr| attr.decode_and_unescape_value(reader).ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Lowercase ASCII slug usable as a bare TOML key, e.g. "Hacker News" → "hacker-news".
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for ch in text.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Comparable form of a feed URL, so the same feed listed twice is only kept once.
+fn url_identity(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    url.to_ascii_lowercase()
+}
+
+/// A key for `name` (or the URL when the name has no ASCII) that is not in `taken`.
+fn unique_key(name: &str, url: &str, taken: &mut HashSet<String>) -> String {
+    let mut base = slug(name);
+    if base.is_empty() {
+        base = slug(url_identity(url).as_str());
+    }
+    if base.is_empty() {
+        base = "feed".to_string();
+    }
+    let mut key = base.clone();
+    let mut suffix = 2;
+    while taken.contains(&key) {
+        key = format!("{base}-{suffix}");
+        suffix += 1;
+    }
+    taken.insert(key.clone());
+    key
+}
+
+fn push_tags(tags: &mut Vec<String>, candidates: impl IntoIterator<Item = String>) {
+    for tag in candidates {
+        let tag = tag.trim().trim_matches('/').trim().to_string();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+}
+
+/// Reads every `<outline>` with an `xmlUrl`. Enclosing folder outlines and the `category`
+/// attribute (comma separated, `/` paths allowed) become tags. Duplicate URLs are dropped.
+pub fn parse_opml(xml: &str) -> Result<Vec<FeedSource>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    // One entry per open <outline>: the folder name, or None for a feed outline.
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut sources = Vec::new();
+    let mut seen_urls = HashSet::new();
+    let mut keys = HashSet::new();
+    let mut saw_opml = false;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|err| UmbreonError::parse("OPML", err))?;
+        let (element, has_children) = match &event {
+            Event::Start(element) => (element, true),
+            Event::Empty(element) => (element, false),
+            Event::End(element) => {
+                if element
+                    .local_name()
+                    .as_ref()
+                    .eq_ignore_ascii_case(b"outline")
+                {
+                    folders.pop();
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let name = element.local_name();
+        if name.as_ref().eq_ignore_ascii_case(b"opml") {
+            saw_opml = true;
+            continue;
+        }
+        if !name.as_ref().eq_ignore_ascii_case(b"outline") {
+            continue;
+        }
+        let title =
+            attribute(&reader, element, b"title").or_else(|| attribute(&reader, element, b"text"));
+        let Some(url) = attribute(&reader, element, b"xmlUrl") else {
+            if has_children {
+                folders.push(title);
+            }
+            continue;
+        };
+        if has_children {
+            folders.push(None);
+        }
+        if !seen_urls.insert(url_identity(&url)) {
+            continue;
+        }
+        let mut tags = Vec::new();
+        push_tags(&mut tags, folders.iter().flatten().cloned());
+        if let Some(category) = attribute(&reader, element, b"category") {
+            push_tags(&mut tags, category.split(',').map(str::to_string));
+        }
+        let name = title.unwrap_or_else(|| url.clone());
+        sources.push(FeedSource {
+            key: unique_key(&name, &url, &mut keys),
+            name,
+            url,
+            tags,
+        });
+    }
+    if !saw_opml {
+        return Err(UmbreonError::parse("OPML", "missing <opml> root element"));
+    }
+    Ok(sources)
+}
+
+/// An OPML 2.0 document listing `sources`.
+pub fn to_opml(title: &str, sources: &[FeedSource]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<opml version=\"2.0\">\n  <head>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", escape(title)));
+    xml.push_str("  </head>\n  <body>\n");
+    for source in sources {
+        let name = escape(source.name.as_str());
+        xml.push_str(&format!(
+            "    <outline type=\"rss\" text=\"{name}\" title=\"{name}\" xmlUrl=\"{}\"",
+            escape(source.url.as_str())
+        ));
+        if !source.tags.is_empty() {
+            xml.push_str(&format!(
+                " category=\"{}\"",
+                escape(source.tags.join(",").as_str())
+            ));
+        }
+        xml.push_str("/>\n");
+    }
+    xml.push_str("  </body>\n</opml>\n");
+    xml
+}
+
+/// The `[feeds.*]` tables of a gist config; other sections and tables without a `url` are
+/// ignored.
+pub fn parse_feeds_toml(text: &str) -> Result<Vec<FeedSource>> {
+    let file =
+        toml::from_str::<FeedsFile>(text).map_err(|err| UmbreonError::parse("[feeds]", err))?;
+    Ok(file
+        .feeds
+        .into_iter()
+        .filter_map(|(key, entry)| {
+            let url = entry.url?.trim().to_string();
+            if url.is_empty() {
+                return None;
+            }
+            Some(FeedSource {
+                name: entry.name.unwrap_or_else(|| key.clone()),
+                key,
+                url,
+                tags: entry.tags,
+            })
+        })
+        .collect())
+}
+
+/// `sources` as `[feeds.*]` tables, ready to paste into the gist.
+pub fn to_feeds_toml(sources: &[FeedSource]) -> Result<String> {
+    let file = FeedsFile {
+        feeds: sources
+            .iter()
+            .map(|source| {
+                (
+                    source.key.clone(),
+                    FeedEntry {
+                        name: Some(source.name.clone()),
+                        url: Some(source.url.clone()),
+                        tags: source.tags.clone(),
+                    },
+                )
+            })
+            .collect(),
+    };
+    toml::to_string(&file).map_err(|err| UmbreonError::parse("[feeds]", err))
+}
+
+/// `config` with `sources` written into its `[feeds]` table. Every other section, and feed
+/// tables without a `url`, are kept; comments and formatting are not.
+pub fn update_feeds_toml(config: &str, sources: &[FeedSource]) -> Result<String> {
+    let mut table =
+        toml::from_str::<toml::Table>(config).map_err(|err| UmbreonError::parse("[feeds]", err))?;
+    let mut feeds = match table.remove("feeds") {
+        Some(toml::Value::Table(feeds)) => feeds,
+        _ => toml::Table::new(),
+    };
+    for source in sources {
+        let entry = FeedEntry {
+            name: Some(source.name.clone()),
+            url: Some(source.url.clone()),
+            tags: source.tags.clone(),
+        };
+        let value =
+            toml::Value::try_from(entry).map_err(|err| UmbreonError::parse("[feeds]", err))?;
+        feeds.insert(source.key.clone(), value);
+    }
+    table.insert("feeds".to_string(), toml::Value::Table(feeds));
+    toml::to_string(&table).map_err(|err| UmbreonError::parse("[feeds]", err))
+}
+
+/// `existing` followed by the sources of `incoming` whose URL is new. Incoming keys are
+/// renamed when they clash. Returns the merged list and how many were added.
+pub fn merge_sources(
+    existing: Vec<FeedSource>,
+    incoming: Vec<FeedSource>,
+) -> (Vec<FeedSource>, usize) {
+    let mut urls = existing
+        .iter()
+        .map(|source| url_identity(&source.url))
+        .collect::<HashSet<_>>();
+    let mut keys = existing
+        .iter()
+        .map(|source| source.key.clone())
+        .collect::<HashSet<_>>();
+    let mut merged = existing;
+    let mut added = 0;
+    for mut source in incoming {
+        if !urls.insert(url_identity(&source.url)) {
+            continue;
+        }
+        if keys.contains(&source.key) {
+            source.key = unique_key(&source.name, &source.url, &mut keys);
+        } else {
+            keys.insert(source.key.clone());
+        }
+        merged.push(source);
+        added += 1;
+    }
+    (merged, added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Reader export</title></head>
+  <body>
+    <outline text="Tech" title="Tech">
+      <outline type="rss" text="Hacker News" xmlUrl="https://news.ycombinator.com/rss" htmlUrl="https://news.ycombinator.com/"/>
+      <outline type="rss" text="少数派" xmlUrl="https://rss.datuan.dev/sspai/index" category="/中文,#rss"/>
+    </outline>
+    <outline type="rss" text="Rust Blog" xmlUrl="https://blog.rust-lang.org/feed.xml" category="StackLang:rust"></outline>
+    <outline type="rss" text="HN again" xmlUrl="http://news.ycombinator.com/rss/"/>
+  </body>
+</opml>"#;
+
+    #[test]
+    fn opml_folders_and_categories_become_tags() {
+        let sources = parse_opml(FIXTURE).unwrap();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].key, "hacker-news");
+        assert_eq!(sources[0].tags, vec!["Tech"]);
+        assert_eq!(sources[1].name, "少数派");
+        assert_eq!(sources[1].key, "rss-datuan-dev-sspai-index");
+        assert_eq!(sources[1].tags, vec!["Tech", "中文", "#rss"]);
+        assert_eq!(sources[2].key, "rust-blog");
+        assert_eq!(sources[2].tags, vec!["StackLang:rust"]);
+    }
+
+    #[test]
+    fn export_round_trips_through_opml_and_toml() {
+        let sources = parse_opml(FIXTURE).unwrap();
+        let reparsed = parse_opml(&to_opml("Umbreon", &sources)).unwrap();
+        assert_eq!(reparsed, sources);
+
+        let toml = to_feeds_toml(&sources).unwrap();
+        assert!(toml.contains("[feeds.hacker-news]"));
+        let mut from_toml = parse_feeds_toml(&toml).unwrap();
+        from_toml.sort_by(|a, b| a.url.cmp(&b.url));
+        let mut expected = sources.clone();
+        expected.sort_by(|a, b| a.url.cmp(&b.url));
+        assert_eq!(from_toml, expected);
+    }
+
+    #[test]
+    fn merging_skips_known_urls_and_renames_clashing_keys() {
+        let existing = parse_feeds_toml(
+            "[feeds.hacker-news]\nname = \"HN\"\nurl = \"https://example.com/hn.xml\"\n\n[memory_policies]\npurge_on_launch = true\n",
+        )
+        .unwrap();
+        let (merged, added) = merge_sources(existing, parse_opml(FIXTURE).unwrap());
+        assert_eq!(added, 3);
+        assert_eq!(merged[1].key, "hacker-news-2");
+
+        let (again, added) = merge_sources(merged.clone(), parse_opml(FIXTURE).unwrap());
+        assert_eq!(added, 0);
+        assert_eq!(again, merged);
+    }
+
+    #[test]
+    fn updating_a_config_keeps_its_other_sections() {
+        let config = "[feeds.draft]\nname = \"Draft\"\n\n[memory_policies]\npurge_on_launch = true\n\n[[memory_policies.rules]]\ncategory = \"mark\"\nmax_items = 500\n\n[live_streams]\nplaylists = [\"https://example.com/live.m3u\"]\n";
+        let sources = parse_opml(FIXTURE).unwrap();
+        let updated = update_feeds_toml(config, &sources).unwrap();
+
+        assert_eq!(parse_feeds_toml(&updated).unwrap().len(), 3);
+        let table = toml::from_str::<toml::Table>(&updated).unwrap();
+        assert!(table["feeds"].get("draft").is_some());
+        assert_eq!(
+            table["memory_policies"]["rules"][0]["max_items"].as_integer(),
+            Some(500)
+        );
+        assert_eq!(
+            table["live_streams"]["playlists"][0].as_str(),
+            Some("https://example.com/live.m3u")
+        );
+    }
+
+    #[test]
+    fn feed_tables_without_a_url_are_skipped() {
+        let sources = parse_feeds_toml(
+            "[feeds.draft]\nname = \"Draft\"\n\n[feeds.rust]\nurl = \" https://blog.rust-lang.org/feed.xml \"\n",
+        )
+        .unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].key, "rust");
+        assert_eq!(sources[0].name, "rust");
+        assert_eq!(sources[0].url, "https://blog.rust-lang.org/feed.xml");
+    }
+
+    #[test]
+    fn documents_without_an_opml_root_are_rejected() {
+        assert!(parse_opml("<rss><channel/></rss>").is_err());
+        assert!(parse_opml("<opml><body><outline").is_err());
+    }
+}
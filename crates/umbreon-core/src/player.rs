@@ -0,0 +1,674 @@
+//! PlayerShell: one control surface over interchangeable playback backends.
+//!
+//! A backend receives [`PlayerCommand`]s and reports what actually happened as
+//! [`PlayerEvent`]s. The shell validates commands, folds events into a [`PlayerState`] and
+//! hands every event to its subscribers, so the UI only ever renders state that the
+//! backend confirmed.
+
+use crate::error::{Result, UmbreonError};
+use crate::playlist::Variant;
+use serde::{Deserialize, Serialize};
+
+/// Playback rates offered in the UI.
+pub const RATES: [f64; 6] = [0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+const MIN_RATE: f64 = 0.25;
+const MAX_RATE: f64 = 4.0;
+/// Default distance from the live edge that low-latency playback aims for, in seconds.
+pub const DEFAULT_TARGET_LATENCY: f64 = 3.0;
+/// Latency drift, in seconds, tolerated before catch-up changes the rate.
+const CATCH_UP_TOLERANCE: f64 = 0.5;
+/// Drift, in seconds, at which the catch-up rate would reach 2× before clamping; the rate
+/// grows linearly with drift up to that point.
+const CATCH_UP_WINDOW: f64 = 10.0;
+const MIN_CATCH_UP_RATE: f64 = 0.9;
+const MAX_CATCH_UP_RATE: f64 = 1.5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackStatus {
+    #[default]
+    Idle,
+    Loading,
+    Playing,
+    Paused,
+    Buffering,
+    Ended,
+    Failed,
+}
+
+impl PlaybackStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            PlaybackStatus::Idle => "未播放",
+            PlaybackStatus::Loading => "加载中",
+            PlaybackStatus::Playing => "播放中",
+            PlaybackStatus::Paused => "已暂停",
+            PlaybackStatus::Buffering => "缓冲中",
+            PlaybackStatus::Ended => "已结束",
+            PlaybackStatus::Failed => "播放失败",
+        }
+    }
+}
+
+/// One selectable quality, in the backend's level order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QualityLevel {
+    pub label: String,
+    pub bandwidth: u64,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+impl QualityLevel {
+    pub fn from_variant(variant: &Variant) -> Self {
+        let label = match variant.resolution {
+            Some(resolution) => format!("{}p", resolution.height),
+            None => format!("{} kbps", variant.bandwidth / 1000),
+        };
+        QualityLevel {
+            label,
+            bandwidth: variant.bandwidth,
+            width: variant.resolution.map(|resolution| resolution.width),
+            height: variant.resolution.map(|resolution| resolution.height),
+        }
+    }
+}
+
+/// Low-latency live playback: the backend starts close to the edge and the shell keeps the
+/// latency near `target_latency` by nudging the playback rate.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LowLatency {
+    pub enabled: bool,
+    /// Seconds behind the live edge.
+    pub target_latency: f64,
+}
+
+impl Default for LowLatency {
+    fn default() -> Self {
+        LowLatency {
+            enabled: false,
+            target_latency: DEFAULT_TARGET_LATENCY,
+        }
+    }
+}
+
+/// The playback rate that brings `latency` back towards `target`: faster when behind,
+/// slightly slower when too close to the edge, 1× within the tolerance. Rates move in 0.05
+/// steps so small jitter does not cause a stream of rate changes.
+pub fn catch_up_rate(latency: f64, target: f64) -> f64 {
+    let drift = latency - target;
+    if !drift.is_finite() || drift.abs() <= CATCH_UP_TOLERANCE {
+        return 1.0;
+    }
+    let rate = (1.0 + drift / CATCH_UP_WINDOW).clamp(MIN_CATCH_UP_RATE, MAX_CATCH_UP_RATE);
+    (rate * 20.0).round() / 20.0
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlayerCommand {
+    Load {
+        url: String,
+    },
+    Play,
+    Pause,
+    Seek {
+        position: f64,
+    },
+    SetRate {
+        rate: f64,
+    },
+    /// `None` lets the backend pick (adaptive bitrate).
+    SetQuality {
+        level: Option<usize>,
+    },
+    /// Applies to the current and later loads.
+    SetLowLatency {
+        enabled: bool,
+        target_latency: f64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlayerEvent {
+    Loading,
+    /// Metadata is known. `duration` is `None` for live streams.
+    Ready {
+        #[serde(default)]
+        duration: Option<f64>,
+        #[serde(default)]
+        live: bool,
+        #[serde(default)]
+        levels: Vec<QualityLevel>,
+    },
+    Playing,
+    Paused,
+    Buffering,
+    TimeUpdate {
+        position: f64,
+        #[serde(default)]
+        duration: Option<f64>,
+    },
+    RateChanged {
+        rate: f64,
+    },
+    /// `quality` is the requested level (`None` for automatic), `active` the one playing.
+    QualityChanged {
+        #[serde(default)]
+        quality: Option<usize>,
+        #[serde(default)]
+        active: Option<usize>,
+    },
+    /// Seconds between the playhead and the live edge.
+    Latency {
+        latency: f64,
+    },
+    Ended,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerState {
+    pub source: Option<String>,
+    pub status: PlaybackStatus,
+    /// Seconds from the start of the media (or of the live window).
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub live: bool,
+    pub rate: f64,
+    pub levels: Vec<QualityLevel>,
+    /// The requested level; `None` is automatic.
+    pub quality: Option<usize>,
+    /// The level currently playing, when the backend reports it.
+    pub active_level: Option<usize>,
+    pub error: Option<String>,
+    /// Distance from the live edge, when the backend reports it.
+    pub latency: Option<f64>,
+    pub low_latency: LowLatency,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        PlayerState {
+            source: None,
+            status: PlaybackStatus::Idle,
+            position: 0.0,
+            duration: None,
+            live: false,
+            rate: 1.0,
+            levels: Vec::new(),
+            quality: None,
+            active_level: None,
+            error: None,
+            latency: None,
+            low_latency: LowLatency::default(),
+        }
+    }
+}
+
+impl PlayerState {
+    pub fn apply(&mut self, event: &PlayerEvent) {
+        match event {
+            PlayerEvent::Loading => {
+                self.status = PlaybackStatus::Loading;
+                self.error = None;
+            }
+            PlayerEvent::Ready {
+                duration,
+                live,
+                levels,
+            } => {
+                self.duration = *duration;
+                self.live = *live;
+                self.levels = levels.clone();
+                if self.quality.is_some_and(|level| level >= levels.len()) {
+                    self.quality = None;
+                }
+                if self.status == PlaybackStatus::Loading {
+                    self.status = PlaybackStatus::Paused;
+                }
+            }
+            PlayerEvent::Playing => self.status = PlaybackStatus::Playing,
+            PlayerEvent::Paused => self.status = PlaybackStatus::Paused,
+            PlayerEvent::Buffering => self.status = PlaybackStatus::Buffering,
+            PlayerEvent::TimeUpdate { position, duration } => {
+                self.position = *position;
+                if duration.is_some() {
+                    self.duration = *duration;
+                }
+            }
+            PlayerEvent::RateChanged { rate } => self.rate = *rate,
+            PlayerEvent::QualityChanged { quality, active } => {
+                self.quality = *quality;
+                self.active_level = *active;
+            }
+            PlayerEvent::Latency { latency } => self.latency = Some(*latency),
+            PlayerEvent::Ended => self.status = PlaybackStatus::Ended,
+            PlayerEvent::Error { message } => {
+                self.status = PlaybackStatus::Failed;
+                self.error = Some(message.clone());
+            }
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(
+            self.status,
+            PlaybackStatus::Playing | PlaybackStatus::Buffering
+        )
+    }
+
+    /// Whether the timeline can be scrubbed: a finite VOD duration is known.
+    pub fn can_seek(&self) -> bool {
+        !self.live && self.duration.is_some_and(|duration| duration > 0.0)
+    }
+}
+
+/// A playback implementation: the WebView's `<video>` element, ExoPlayer behind JNI, ...
+///
+/// Backends that learn about progress asynchronously report events through whatever sink
+/// they were built with and route them to [`PlayerShell::handle`]. Synchronous ones can
+/// return them from [`PlayerBackend::drain_events`] instead, which the shell calls after
+/// every command.
+pub trait PlayerBackend {
+    fn name(&self) -> &'static str;
+
+    fn send(&mut self, command: &PlayerCommand) -> Result<()>;
+
+    fn drain_events(&mut self) -> Vec<PlayerEvent> {
+        Vec::new()
+    }
+}
+
+/// A backend without a player: it acknowledges every command with the event a real player
+/// would send. Used where no native player is wired up yet and as the reference a native
+/// bridge implements against.
+#[derive(Debug, Default)]
+pub struct StubBackend {
+    /// Every command received, oldest first.
+    pub commands: Vec<PlayerCommand>,
+    /// Levels reported on load.
+    pub levels: Vec<QualityLevel>,
+    pending: Vec<PlayerEvent>,
+}
+
+impl StubBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_levels(levels: Vec<QualityLevel>) -> Self {
+        StubBackend {
+            levels,
+            ..Self::default()
+        }
+    }
+}
+
+impl PlayerBackend for StubBackend {
+    fn name(&self) -> &'static str {
+        "stub"
+    }
+
+    fn send(&mut self, command: &PlayerCommand) -> Result<()> {
+        self.commands.push(command.clone());
+        match command {
+            PlayerCommand::Load { .. } => {
+                self.pending.push(PlayerEvent::Loading);
+                self.pending.push(PlayerEvent::Ready {
+                    duration: None,
+                    live: true,
+                    levels: self.levels.clone(),
+                });
+            }
+            PlayerCommand::Play => self.pending.push(PlayerEvent::Playing),
+            PlayerCommand::Pause => self.pending.push(PlayerEvent::Paused),
+            PlayerCommand::Seek { position } => self.pending.push(PlayerEvent::TimeUpdate {
+                position: *position,
+                duration: None,
+            }),
+            PlayerCommand::SetRate { rate } => {
+                self.pending.push(PlayerEvent::RateChanged { rate: *rate })
+            }
+            PlayerCommand::SetQuality { level } => self.pending.push(PlayerEvent::QualityChanged {
+                quality: *level,
+                active: *level,
+            }),
+            PlayerCommand::SetLowLatency { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn drain_events(&mut self) -> Vec<PlayerEvent> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+type Subscriber = Box<dyn FnMut(&PlayerEvent, &PlayerState)>;
+
+pub struct PlayerShell {
+    backend: Box<dyn PlayerBackend>,
+    state: PlayerState,
+    subscribers: Vec<Subscriber>,
+    /// The current rate was set by live catch-up rather than by the user.
+    catching_up: bool,
+}
+
+impl PlayerShell {
+    pub fn new(backend: Box<dyn PlayerBackend>) -> Self {
+        PlayerShell {
+            backend,
+            state: PlayerState::default(),
+            subscribers: Vec::new(),
+            catching_up: false,
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    pub fn state(&self) -> &PlayerState {
+        &self.state
+    }
+
+    /// Calls `subscriber` with every event and the state after applying it.
+    pub fn subscribe(&mut self, subscriber: impl FnMut(&PlayerEvent, &PlayerState) + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    /// Folds a backend event into the state and notifies subscribers. Latency reports drive
+    /// live catch-up while low latency is on.
+    pub fn handle(&mut self, event: PlayerEvent) {
+        self.state.apply(&event);
+        for subscriber in &mut self.subscribers {
+            subscriber(&event, &self.state);
+        }
+        if let PlayerEvent::Latency { latency } = event {
+            self.catch_up(latency);
+        }
+    }
+
+    /// Adjusts the rate towards the target latency. A rate the user picked by hand is left
+    /// alone; so is anything that is not live and playing.
+    fn catch_up(&mut self, latency: f64) {
+        let low_latency = self.state.low_latency;
+        if !low_latency.enabled
+            || !self.state.live
+            || self.state.status != PlaybackStatus::Playing
+            || (!self.catching_up && self.state.rate != 1.0)
+        {
+            return;
+        }
+        let rate = catch_up_rate(latency, low_latency.target_latency);
+        if (rate - self.state.rate).abs() < f64::EPSILON {
+            return;
+        }
+        // A failed rate change is retried with the next latency report.
+        if self.send(PlayerCommand::SetRate { rate }).is_ok() {
+            self.catching_up = rate != 1.0;
+        }
+    }
+
+    fn send(&mut self, command: PlayerCommand) -> Result<()> {
+        self.backend.send(&command)?;
+        for event in self.backend.drain_events() {
+            self.handle(event);
+        }
+        Ok(())
+    }
+
+    fn require_source(&self) -> Result<()> {
+        if self.state.source.is_none() {
+            return Err(UmbreonError::config("播放器", "没有正在播放的流"));
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, url: &str) -> Result<()> {
+        let url = url.trim();
+        if url.is_empty() {
+            return Err(UmbreonError::config("播放器", "播放地址为空"));
+        }
+        if self.catching_up {
+            self.state.rate = 1.0;
+            self.catching_up = false;
+        }
+        self.state = PlayerState {
+            source: Some(url.to_string()),
+            rate: self.state.rate,
+            low_latency: self.state.low_latency,
+            ..PlayerState::default()
+        };
+        self.send(PlayerCommand::Load {
+            url: url.to_string(),
+        })
+    }
+
+    pub fn play(&mut self) -> Result<()> {
+        self.require_source()?;
+        self.send(PlayerCommand::Play)
+    }
+
+    pub fn pause(&mut self) -> Result<()> {
+        self.require_source()?;
+        self.send(PlayerCommand::Pause)
+    }
+
+    pub fn toggle(&mut self) -> Result<()> {
+        if self.state.is_playing() {
+            self.pause()
+        } else {
+            self.play()
+        }
+    }
+
+    /// Seeks to `position` seconds, clamped to the known duration.
+    pub fn seek(&mut self, position: f64) -> Result<()> {
+        self.require_source()?;
+        if !position.is_finite() {
+            return Err(UmbreonError::config("播放器", "无效的跳转位置"));
+        }
+        let mut position = position.max(0.0);
+        if let Some(duration) = self.state.duration {
+            position = position.min(duration);
+        }
+        self.send(PlayerCommand::Seek { position })
+    }
+
+    /// Sets the playback rate, clamped to 0.25–4×.
+    pub fn set_rate(&mut self, rate: f64) -> Result<()> {
+        if !rate.is_finite() {
+            return Err(UmbreonError::config("播放器", "无效的倍速"));
+        }
+        let rate = rate.clamp(MIN_RATE, MAX_RATE);
+        self.catching_up = false;
+        if self.state.source.is_none() {
+            self.state.rate = rate;
+            return Ok(());
+        }
+        self.send(PlayerCommand::SetRate { rate })
+    }
+
+    /// Turns low-latency playback on or off. `target_latency` is clamped to at least one
+    /// second. Turning it off undoes any catch-up rate.
+    pub fn set_low_latency(&mut self, enabled: bool, target_latency: f64) -> Result<()> {
+        if !target_latency.is_finite() {
+            return Err(UmbreonError::config("播放器", "无效的目标延迟"));
+        }
+        let low_latency = LowLatency {
+            enabled,
+            target_latency: target_latency.max(1.0),
+        };
+        self.backend.send(&PlayerCommand::SetLowLatency {
+            enabled: low_latency.enabled,
+            target_latency: low_latency.target_latency,
+        })?;
+        self.state.low_latency = low_latency;
+        if !enabled && self.catching_up {
+            self.catching_up = false;
+            if self.state.source.is_some() {
+                self.send(PlayerCommand::SetRate { rate: 1.0 })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Picks a quality level by index, or automatic selection with `None`.
+    pub fn set_quality(&mut self, level: Option<usize>) -> Result<()> {
+        self.require_source()?;
+        if let Some(index) = level
+            && index >= self.state.levels.len()
+        {
+            return Err(UmbreonError::config(
+                "播放器",
+                format!("没有第 {} 档清晰度", index + 1),
+            ));
+        }
+        self.send(PlayerCommand::SetQuality { level })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::{Resolution, Variant};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn levels() -> Vec<QualityLevel> {
+        [(1920, 1080, 6_000_000), (1280, 720, 3_000_000)]
+            .into_iter()
+            .map(|(width, height, bandwidth)| {
+                QualityLevel::from_variant(&Variant {
+                    bandwidth,
+                    resolution: Some(Resolution { width, height }),
+                    ..Variant::default()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn commands_flow_through_the_backend_into_state() {
+        let mut shell = PlayerShell::new(Box::new(StubBackend::with_levels(levels())));
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = seen.clone();
+        shell.subscribe(move |event, state| sink.borrow_mut().push((event.clone(), state.status)));
+
+        shell.load("https://live.example.com/index.m3u8").unwrap();
+        assert_eq!(shell.state().status, PlaybackStatus::Paused);
+        assert_eq!(shell.state().levels[0].label, "1080p");
+        shell.toggle().unwrap();
+        assert!(shell.state().is_playing());
+        shell.set_rate(9.0).unwrap();
+        assert_eq!(shell.state().rate, MAX_RATE);
+        shell.set_quality(Some(1)).unwrap();
+        assert_eq!(shell.state().active_level, Some(1));
+        shell.toggle().unwrap();
+        assert_eq!(shell.state().status, PlaybackStatus::Paused);
+
+        let statuses = seen
+            .borrow()
+            .iter()
+            .map(|(_, status)| *status)
+            .collect::<Vec<_>>();
+        assert_eq!(statuses.first(), Some(&PlaybackStatus::Loading));
+        assert_eq!(statuses.len(), 6);
+    }
+
+    #[test]
+    fn invalid_commands_are_rejected_before_reaching_the_backend() {
+        let mut shell = PlayerShell::new(Box::new(StubBackend::new()));
+        assert!(shell.play().is_err());
+        assert!(shell.load("  ").is_err());
+        shell.set_rate(1.5).unwrap();
+        shell.load("https://vod.example.com/a.m3u8").unwrap();
+        assert_eq!(shell.state().rate, 1.5);
+        assert!(shell.set_quality(Some(0)).is_err());
+        assert!(shell.seek(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn seeking_is_clamped_to_the_duration() {
+        let mut shell = PlayerShell::new(Box::new(StubBackend::new()));
+        shell.load("https://vod.example.com/a.m3u8").unwrap();
+        shell.handle(PlayerEvent::Ready {
+            duration: Some(120.0),
+            live: false,
+            levels: Vec::new(),
+        });
+        assert!(shell.state().can_seek());
+        shell.seek(500.0).unwrap();
+        assert_eq!(shell.state().position, 120.0);
+        shell.seek(-3.0).unwrap();
+        assert_eq!(shell.state().position, 0.0);
+    }
+
+    #[test]
+    fn events_and_commands_use_tagged_json() {
+        let command = serde_json::to_value(PlayerCommand::SetQuality { level: None }).unwrap();
+        assert_eq!(
+            command,
+            serde_json::json!({"type": "set_quality", "level": null})
+        );
+        let event: PlayerEvent =
+            serde_json::from_str(r#"{"type":"time_update","position":12.5}"#).unwrap();
+        assert_eq!(
+            event,
+            PlayerEvent::TimeUpdate {
+                position: 12.5,
+                duration: None
+            }
+        );
+
+        let mut state = PlayerState::default();
+        state.apply(&PlayerEvent::Error {
+            message: "404".to_string(),
+        });
+        assert_eq!(state.status, PlaybackStatus::Failed);
+        state.apply(&PlayerEvent::Loading);
+        assert_eq!(state.error, None);
+    }
+
+    #[test]
+    fn catch_up_rate_follows_the_drift() {
+        assert_eq!(catch_up_rate(3.2, 3.0), 1.0);
+        assert_eq!(catch_up_rate(5.0, 3.0), 1.2);
+        assert_eq!(catch_up_rate(30.0, 3.0), MAX_CATCH_UP_RATE);
+        assert_eq!(catch_up_rate(1.0, 3.0), MIN_CATCH_UP_RATE);
+        assert_eq!(catch_up_rate(f64::NAN, 3.0), 1.0);
+    }
+
+    #[test]
+    fn low_latency_catches_up_by_rate_and_respects_manual_rates() {
+        let mut shell = PlayerShell::new(Box::new(StubBackend::new()));
+        shell.set_low_latency(true, 3.0).unwrap();
+        shell.load("https://live.example.com/ll.m3u8").unwrap();
+        assert!(shell.state().low_latency.enabled);
+        shell.play().unwrap();
+
+        shell.handle(PlayerEvent::Latency { latency: 8.0 });
+        assert_eq!(shell.state().rate, 1.5);
+        assert_eq!(shell.state().latency, Some(8.0));
+        shell.handle(PlayerEvent::Latency { latency: 3.1 });
+        assert_eq!(shell.state().rate, 1.0);
+
+        shell.set_rate(1.25).unwrap();
+        shell.handle(PlayerEvent::Latency { latency: 8.0 });
+        assert_eq!(shell.state().rate, 1.25);
+
+        shell.set_rate(1.0).unwrap();
+        shell.handle(PlayerEvent::Latency { latency: 8.0 });
+        assert_eq!(shell.state().rate, 1.5);
+        shell.set_low_latency(false, 3.0).unwrap();
+        assert_eq!(shell.state().rate, 1.0);
+        shell.handle(PlayerEvent::Latency { latency: 8.0 });
+        assert_eq!(shell.state().rate, 1.0);
+    }
+}
@@ -0,0 +1,1131 @@
+//! M3U playlists: extended M3U channel lists as published by IPTV providers, and HLS
+//! master and media playlists (RFC 8216).
+//!
+//! [`parse_playlist`] tells the three apart by their tags; the typed parsers can be called
+//! directly when the caller already knows what it fetched. URIs are kept as written, relative
+//! ones included.
+//!
+//! Media playlists understand the low-latency extensions: partial segments, preload hints,
+//! server control and playlist delta updates.
+
+use crate::error::{Result, UmbreonError};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Playlist {
+    Channels(ChannelList),
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+/// An extended M3U channel list.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelList {
+    /// `url-tvg` / `x-tvg-url` of the `#EXTM3U` header.
+    pub epg_url: Option<String>,
+    pub channels: Vec<Channel>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    pub url: String,
+    pub tvg_id: Option<String>,
+    pub tvg_name: Option<String>,
+    pub tvg_logo: Option<String>,
+    /// `group-title`, or the `#EXTGRP` line. Several groups are separated by `;`.
+    pub group: Option<String>,
+    /// The channel's catch-up attributes, falling back to the header's.
+    pub catchup: Option<Catchup>,
+    pub user_agent: Option<String>,
+    pub referrer: Option<String>,
+    /// Every `#EXTINF` attribute as written, including the ones above.
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Channel {
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        group_names(self.group.as_deref())
+    }
+}
+
+/// The names in a `group-title` value; several groups are separated by `;`.
+pub fn group_names(group_title: Option<&str>) -> impl Iterator<Item = &str> {
+    group_title
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|group| !group.is_empty())
+}
+
+/// Catch-up (time-shift) playback: `catchup`, `catchup-source` and `catchup-days`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Catchup {
+    /// `default`, `append`, `shift`, `flussonic`, ... as the provider wrote it.
+    pub kind: String,
+    pub source: Option<String>,
+    pub days: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MasterPlaylist {
+    pub version: Option<u32>,
+    pub independent_segments: bool,
+    pub variants: Vec<Variant>,
+    /// `#EXT-X-I-FRAME-STREAM-INF` entries, used for trick play.
+    pub i_frame_variants: Vec<Variant>,
+    pub renditions: Vec<Rendition>,
+}
+
+impl MasterPlaylist {
+    /// Renditions of `kind` in `group_id`, e.g. the audio tracks a variant refers to.
+    pub fn renditions_in(&self, kind: RenditionKind, group_id: &str) -> Vec<&Rendition> {
+        self.renditions
+            .iter()
+            .filter(|rendition| rendition.kind == kind && rendition.group_id == group_id)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Variant {
+    pub uri: String,
+    pub bandwidth: u64,
+    pub average_bandwidth: Option<u64>,
+    pub resolution: Option<Resolution>,
+    pub frame_rate: Option<f64>,
+    pub codecs: Vec<String>,
+    pub audio: Option<String>,
+    pub video: Option<String>,
+    pub subtitles: Option<String>,
+    /// `None` both when absent and for `CLOSED-CAPTIONS=NONE`.
+    pub closed_captions: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenditionKind {
+    Audio,
+    Video,
+    Subtitles,
+    ClosedCaptions,
+}
+
+impl RenditionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RenditionKind::Audio => "AUDIO",
+            RenditionKind::Video => "VIDEO",
+            RenditionKind::Subtitles => "SUBTITLES",
+            RenditionKind::ClosedCaptions => "CLOSED-CAPTIONS",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "AUDIO" => Some(RenditionKind::Audio),
+            "VIDEO" => Some(RenditionKind::Video),
+            "SUBTITLES" => Some(RenditionKind::Subtitles),
+            "CLOSED-CAPTIONS" => Some(RenditionKind::ClosedCaptions),
+            _ => None,
+        }
+    }
+}
+
+/// An `#EXT-X-MEDIA` alternative rendition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rendition {
+    pub kind: RenditionKind,
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    /// Absent for closed captions and for renditions muxed into the variant stream.
+    pub uri: Option<String>,
+    pub default: bool,
+    pub autoselect: bool,
+    pub forced: bool,
+    pub channels: Option<String>,
+    pub instream_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistType {
+    Event,
+    Vod,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MediaPlaylist {
+    pub version: Option<u32>,
+    pub target_duration: u64,
+    pub media_sequence: u64,
+    pub discontinuity_sequence: u64,
+    pub playlist_type: Option<PlaylistType>,
+    pub independent_segments: bool,
+    /// `#EXT-X-ENDLIST` was present: no more segments will be added.
+    pub ended: bool,
+    pub segments: Vec<Segment>,
+    pub server_control: Option<ServerControl>,
+    /// `PART-TARGET` of `#EXT-X-PART-INF`, in seconds. Present on low-latency playlists.
+    pub part_target: Option<f64>,
+    /// Segments left out of a delta update (`#EXT-X-SKIP`).
+    pub skipped_segments: u64,
+    /// Partial segments published after the last complete segment.
+    pub trailing_parts: Vec<PartialSegment>,
+    pub preload_hint: Option<PreloadHint>,
+}
+
+impl MediaPlaylist {
+    /// Whether the server may still append segments.
+    pub fn is_live(&self) -> bool {
+        !self.ended && self.playlist_type != Some(PlaylistType::Vod)
+    }
+
+    /// Sum of the segment durations in seconds.
+    pub fn duration(&self) -> f64 {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Whether the playlist publishes partial segments (LL-HLS).
+    pub fn is_low_latency(&self) -> bool {
+        self.part_target.is_some()
+    }
+
+    /// Whether the server holds playlist requests until a given segment or part exists.
+    pub fn can_block_reload(&self) -> bool {
+        self.server_control
+            .as_ref()
+            .is_some_and(|control| control.can_block_reload)
+    }
+
+    /// How far from the live edge playback should start, in seconds. Uses the server's
+    /// `PART-HOLD-BACK` or `HOLD-BACK` when given, otherwise the RFC 8216 defaults of three
+    /// part targets or three target durations.
+    pub fn hold_back(&self, low_latency: bool) -> f64 {
+        let control = self.server_control.as_ref();
+        if low_latency && let Some(part_target) = self.part_target {
+            return control
+                .and_then(|control| control.part_hold_back)
+                .unwrap_or(part_target * 3.0);
+        }
+        control
+            .and_then(|control| control.hold_back)
+            .unwrap_or(self.target_duration as f64 * 3.0)
+    }
+}
+
+/// `#EXT-X-SERVER-CONTROL`: what the server supports for live delivery.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerControl {
+    /// Blocking playlist reload (`_HLS_msn`/`_HLS_part` query parameters).
+    pub can_block_reload: bool,
+    /// Delta updates may skip segments older than this many seconds from the end.
+    pub can_skip_until: Option<f64>,
+    pub can_skip_dateranges: bool,
+    pub hold_back: Option<f64>,
+    pub part_hold_back: Option<f64>,
+}
+
+/// `#EXT-X-PART`: a partial segment of a low-latency playlist.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartialSegment {
+    pub uri: String,
+    pub duration: f64,
+    /// Starts with an independent frame, so playback can begin here.
+    pub independent: bool,
+    pub byte_range: Option<ByteRange>,
+    /// The part is unavailable and must not be loaded.
+    pub gap: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreloadHintKind {
+    Part,
+    Map,
+}
+
+/// `#EXT-X-PRELOAD-HINT`: a resource the server will publish next and that can be requested
+/// ahead of time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreloadHint {
+    pub kind: PreloadHintKind,
+    pub uri: String,
+    pub byte_range_start: u64,
+    /// `None` means the range extends to the end of the resource.
+    pub byte_range_length: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Segment {
+    pub uri: String,
+    pub duration: f64,
+    pub title: Option<String>,
+    /// Media sequence number of this segment.
+    pub sequence: u64,
+    /// Preceded by `#EXT-X-DISCONTINUITY`.
+    pub discontinuity: bool,
+    pub byte_range: Option<ByteRange>,
+    pub program_date_time: Option<String>,
+    /// Encryption in effect for this segment; `None` for `METHOD=NONE`.
+    pub key: Option<SegmentKey>,
+    /// URI of the `#EXT-X-MAP` initialization section in effect.
+    pub map: Option<String>,
+    /// The `#EXT-X-PART`s this segment was published as, oldest first.
+    pub parts: Vec<PartialSegment>,
+}
+
+/// A sub-range of the segment resource. Implicit offsets are resolved against the previous
+/// range of the same URI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    pub length: u64,
+    pub offset: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentKey {
+    pub method: String,
+    pub uri: Option<String>,
+    pub iv: Option<String>,
+    pub key_format: Option<String>,
+}
+
+fn error(line: usize, message: impl std::fmt::Display) -> UmbreonError {
+    UmbreonError::parse("M3U", format!("line {line}: {message}"))
+}
+
+/// Non-empty lines with their 1-based line numbers, BOM and surrounding whitespace removed.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn require_header(text: &str) -> Result<()> {
+    match lines(text).next() {
+        Some((_, line)) if line.starts_with("#EXTM3U") => Ok(()),
+        _ => Err(UmbreonError::parse("M3U", "missing #EXTM3U header")),
+    }
+}
+
+fn number<T: std::str::FromStr>(line: usize, what: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| error(line, format!("invalid {what} {value:?}")))
+}
+
+/// Reads one value starting at `rest`: a quoted string, or everything up to a delimiter.
+/// Returns the value and the remaining text.
+fn take_value<'a>(rest: &'a str, delimiters: &[char]) -> (String, &'a str) {
+    if let Some(quoted) = rest.strip_prefix('"') {
+        return match quoted.find('"') {
+            Some(end) => (quoted[..end].to_string(), &quoted[end + 1..]),
+            None => (quoted.to_string(), ""),
+        };
+    }
+    let end = rest.find(delimiters).unwrap_or(rest.len());
+    (rest[..end].trim().to_string(), &rest[end..])
+}
+
+/// An HLS attribute list: `KEY=VALUE` pairs separated by commas, values optionally quoted.
+fn attribute_list(text: &str) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some(eq) = rest.find('=') else {
+            break;
+        };
+        let key = rest[..eq].trim().to_ascii_uppercase();
+        let (value, after) = take_value(rest[eq + 1..].trim_start(), &[',']);
+        attributes.insert(key, value);
+        rest = after.trim_start().trim_start_matches(',').trim_start();
+    }
+    attributes
+}
+
+/// The `key="value"` pairs of an `#EXTINF` or `#EXTM3U` line and the text after the first
+/// unquoted comma (the title).
+fn extinf_attributes(text: &str) -> (BTreeMap<String, String>, Option<String>) {
+    let mut attributes = BTreeMap::new();
+    let mut rest = text.trim_start();
+    loop {
+        if let Some(title) = rest.strip_prefix(',') {
+            let title = title.trim();
+            return (attributes, (!title.is_empty()).then(|| title.to_string()));
+        }
+        let Some(end) = rest.find(['=', ',', ' ', '\t']) else {
+            return (attributes, None);
+        };
+        let key = &rest[..end];
+        rest = &rest[end..];
+        if let Some(value) = rest.strip_prefix('=') {
+            let (value, after) = take_value(value, &[' ', '\t', ',']);
+            attributes.insert(key.to_ascii_lowercase(), value);
+            rest = after;
+        }
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return (attributes, None);
+        }
+    }
+}
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn catchup(attributes: &BTreeMap<String, String>, fallback: Option<&Catchup>) -> Option<Catchup> {
+    let kind = non_empty(
+        attributes
+            .get("catchup")
+            .or_else(|| attributes.get("catchup-type")),
+    );
+    let source = non_empty(attributes.get("catchup-source"));
+    let days = attributes
+        .get("catchup-days")
+        .or_else(|| attributes.get("timeshift"))
+        .and_then(|days| days.trim().parse().ok());
+    if kind.is_none() && source.is_none() && days.is_none() {
+        return fallback.cloned();
+    }
+    let fallback = fallback.cloned().unwrap_or_default();
+    Some(Catchup {
+        kind: kind.unwrap_or(if fallback.kind.is_empty() {
+            "default".to_string()
+        } else {
+            fallback.kind
+        }),
+        source: source.or(fallback.source),
+        days: days.or(fallback.days),
+    })
+}
+
+/// Tells channel lists, master playlists and media playlists apart and parses accordingly.
+pub fn parse_playlist(text: &str) -> Result<Playlist> {
+    let mut is_media = false;
+    for (_, line) in lines(text) {
+        if line.starts_with("#EXT-X-STREAM-INF")
+            || line.starts_with("#EXT-X-I-FRAME-STREAM-INF")
+            || line.starts_with("#EXT-X-MEDIA:")
+        {
+            return parse_master_playlist(text).map(Playlist::Master);
+        }
+        is_media |= line.starts_with("#EXT-X-TARGETDURATION");
+    }
+    if is_media {
+        parse_media_playlist(text).map(Playlist::Media)
+    } else {
+        parse_channel_list(text).map(Playlist::Channels)
+    }
+}
+
+/// An extended M3U channel list. Bare URL lines without `#EXTINF` are kept, named after
+/// their URL. Without an `#EXTM3U` header every entry must look like a URL.
+pub fn parse_channel_list(text: &str) -> Result<ChannelList> {
+    let mut list = ChannelList::default();
+    let mut header_catchup = None;
+    let mut has_header = false;
+    let mut pending: Option<(BTreeMap<String, String>, Option<String>)> = None;
+    let mut group = None;
+    let mut user_agent = None;
+    let mut referrer = None;
+    for (_, line) in lines(text) {
+        if let Some(header) = line.strip_prefix("#EXTM3U") {
+            has_header = true;
+            let (attributes, _) = extinf_attributes(header);
+            list.epg_url = non_empty(
+                attributes
+                    .get("url-tvg")
+                    .or_else(|| attributes.get("x-tvg-url")),
+            );
+            header_catchup = catchup(&attributes, None);
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let info =
+                info.trim_start_matches(|ch: char| ch == '-' || ch == '.' || ch.is_ascii_digit());
+            pending = Some(extinf_attributes(info));
+        } else if let Some(value) = line.strip_prefix("#EXTGRP:") {
+            group = Some(value.trim().to_string());
+        } else if let Some(option) = line.strip_prefix("#EXTVLCOPT:") {
+            if let Some(value) = option.strip_prefix("http-user-agent=") {
+                user_agent = Some(value.trim().to_string());
+            } else if let Some(value) = option.strip_prefix("http-referrer=") {
+                referrer = Some(value.trim().to_string());
+            }
+        } else if !line.starts_with('#') {
+            let (attributes, title) = pending.take().unwrap_or_default();
+            let name = title
+                .or_else(|| non_empty(attributes.get("tvg-name")))
+                .unwrap_or_else(|| line.to_string());
+            list.channels.push(Channel {
+                name,
+                url: line.to_string(),
+                tvg_id: non_empty(attributes.get("tvg-id")),
+                tvg_name: non_empty(attributes.get("tvg-name")),
+                tvg_logo: non_empty(attributes.get("tvg-logo")),
+                group: non_empty(attributes.get("group-title")).or(group.take()),
+                catchup: catchup(&attributes, header_catchup.as_ref()),
+                user_agent: non_empty(attributes.get("http-user-agent")).or(user_agent.take()),
+                referrer: non_empty(attributes.get("http-referrer")).or(referrer.take()),
+                attributes,
+            });
+        }
+    }
+    if !has_header
+        && (list.channels.is_empty()
+            || list
+                .channels
+                .iter()
+                .any(|channel| !channel.url.contains("://")))
+    {
+        return Err(UmbreonError::parse("M3U", "not an M3U playlist"));
+    }
+    Ok(list)
+}
+
+fn resolution(line: usize, value: &str) -> Result<Resolution> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| error(line, format!("invalid RESOLUTION {value:?}")))?;
+    Ok(Resolution {
+        width: number(line, "RESOLUTION", width)?,
+        height: number(line, "RESOLUTION", height)?,
+    })
+}
+
+fn variant(line: usize, attributes: &BTreeMap<String, String>, uri: String) -> Result<Variant> {
+    let bandwidth = attributes
+        .get("BANDWIDTH")
+        .ok_or_else(|| error(line, "stream without BANDWIDTH"))?;
+    Ok(Variant {
+        uri,
+        bandwidth: number(line, "BANDWIDTH", bandwidth)?,
+        average_bandwidth: attributes
+            .get("AVERAGE-BANDWIDTH")
+            .map(|value| number(line, "AVERAGE-BANDWIDTH", value))
+            .transpose()?,
+        resolution: attributes
+            .get("RESOLUTION")
+            .map(|value| resolution(line, value))
+            .transpose()?,
+        frame_rate: attributes
+            .get("FRAME-RATE")
+            .map(|value| number(line, "FRAME-RATE", value))
+            .transpose()?,
+        codecs: attributes
+            .get("CODECS")
+            .map(|codecs| {
+                codecs
+                    .split(',')
+                    .map(|codec| codec.trim().to_string())
+                    .filter(|codec| !codec.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        audio: non_empty(attributes.get("AUDIO")),
+        video: non_empty(attributes.get("VIDEO")),
+        subtitles: non_empty(attributes.get("SUBTITLES")),
+        closed_captions: non_empty(attributes.get("CLOSED-CAPTIONS"))
+            .filter(|value| value != "NONE"),
+    })
+}
+
+fn rendition(line: usize, attributes: &BTreeMap<String, String>) -> Result<Option<Rendition>> {
+    let flag = |name: &str| attributes.get(name).is_some_and(|value| value == "YES");
+    let kind = attributes
+        .get("TYPE")
+        .ok_or_else(|| error(line, "EXT-X-MEDIA without TYPE"))?;
+    let Some(kind) = RenditionKind::parse(kind) else {
+        return Ok(None);
+    };
+    Ok(Some(Rendition {
+        kind,
+        group_id: non_empty(attributes.get("GROUP-ID"))
+            .ok_or_else(|| error(line, "EXT-X-MEDIA without GROUP-ID"))?,
+        name: non_empty(attributes.get("NAME"))
+            .ok_or_else(|| error(line, "EXT-X-MEDIA without NAME"))?,
+        language: non_empty(attributes.get("LANGUAGE")),
+        uri: non_empty(attributes.get("URI")),
+        default: flag("DEFAULT"),
+        autoselect: flag("AUTOSELECT"),
+        forced: flag("FORCED"),
+        channels: non_empty(attributes.get("CHANNELS")),
+        instream_id: non_empty(attributes.get("INSTREAM-ID")),
+    }))
+}
+
+/// An HLS master playlist: variant streams and their alternative renditions.
+pub fn parse_master_playlist(text: &str) -> Result<MasterPlaylist> {
+    require_header(text)?;
+    let mut playlist = MasterPlaylist::default();
+    let mut pending: Option<(usize, BTreeMap<String, String>)> = None;
+    for (line_number, line) in lines(text) {
+        if let Some(value) = line.strip_prefix("#EXT-X-VERSION:") {
+            playlist.version = Some(number(line_number, "EXT-X-VERSION", value)?);
+        } else if line == "#EXT-X-INDEPENDENT-SEGMENTS" {
+            playlist.independent_segments = true;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            pending = Some((line_number, attribute_list(value)));
+        } else if let Some(value) = line.strip_prefix("#EXT-X-I-FRAME-STREAM-INF:") {
+            let attributes = attribute_list(value);
+            let uri = non_empty(attributes.get("URI"))
+                .ok_or_else(|| error(line_number, "I-frame stream without URI"))?;
+            playlist
+                .i_frame_variants
+                .push(variant(line_number, &attributes, uri)?);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA:") {
+            if let Some(rendition) = rendition(line_number, &attribute_list(value))? {
+                playlist.renditions.push(rendition);
+            }
+        } else if !line.starts_with('#') {
+            let (stream_line, attributes) = pending
+                .take()
+                .ok_or_else(|| error(line_number, "URI without EXT-X-STREAM-INF"))?;
+            playlist
+                .variants
+                .push(variant(stream_line, &attributes, line.to_string())?);
+        }
+    }
+    if let Some((line, _)) = pending {
+        return Err(error(line, "EXT-X-STREAM-INF without URI"));
+    }
+    Ok(playlist)
+}
+
+fn byte_range(
+    line: usize,
+    value: &str,
+    previous: Option<(&str, ByteRange)>,
+    uri_hint: &str,
+) -> Result<ByteRange> {
+    let (length, offset) = match value.split_once('@') {
+        Some((length, offset)) => (length, Some(offset)),
+        None => (value, None),
+    };
+    let length = number(line, "EXT-X-BYTERANGE", length)?;
+    let offset = match offset {
+        Some(offset) => number(line, "EXT-X-BYTERANGE", offset)?,
+        None => match previous {
+            Some((uri, range)) if uri == uri_hint => range.offset + range.length,
+            _ => return Err(error(line, "EXT-X-BYTERANGE without offset")),
+        },
+    };
+    Ok(ByteRange { length, offset })
+}
+
+fn optional_number<T: std::str::FromStr>(
+    line: usize,
+    attributes: &BTreeMap<String, String>,
+    name: &str,
+) -> Result<Option<T>> {
+    attributes
+        .get(name)
+        .map(|value| number(line, name, value))
+        .transpose()
+}
+
+fn server_control(line: usize, attributes: &BTreeMap<String, String>) -> Result<ServerControl> {
+    let flag = |name: &str| attributes.get(name).is_some_and(|value| value == "YES");
+    Ok(ServerControl {
+        can_block_reload: flag("CAN-BLOCK-RELOAD"),
+        can_skip_until: optional_number(line, attributes, "CAN-SKIP-UNTIL")?,
+        can_skip_dateranges: flag("CAN-SKIP-DATERANGES"),
+        hold_back: optional_number(line, attributes, "HOLD-BACK")?,
+        part_hold_back: optional_number(line, attributes, "PART-HOLD-BACK")?,
+    })
+}
+
+fn partial_segment(
+    line: usize,
+    attributes: &BTreeMap<String, String>,
+    last_range: &mut Option<(String, ByteRange)>,
+) -> Result<PartialSegment> {
+    let uri =
+        non_empty(attributes.get("URI")).ok_or_else(|| error(line, "EXT-X-PART without URI"))?;
+    let duration = optional_number(line, attributes, "DURATION")?
+        .ok_or_else(|| error(line, "EXT-X-PART without DURATION"))?;
+    let byte_range = match attributes.get("BYTERANGE") {
+        Some(value) => {
+            let previous = last_range
+                .as_ref()
+                .map(|(uri, range)| (uri.as_str(), *range));
+            let range = byte_range(line, value, previous, &uri)?;
+            *last_range = Some((uri.clone(), range));
+            Some(range)
+        }
+        None => None,
+    };
+    let flag = |name: &str| attributes.get(name).is_some_and(|value| value == "YES");
+    Ok(PartialSegment {
+        independent: flag("INDEPENDENT"),
+        gap: flag("GAP"),
+        uri,
+        duration,
+        byte_range,
+    })
+}
+
+fn preload_hint(line: usize, attributes: &BTreeMap<String, String>) -> Result<PreloadHint> {
+    let kind = match attributes.get("TYPE").map(String::as_str) {
+        Some("PART") => PreloadHintKind::Part,
+        Some("MAP") => PreloadHintKind::Map,
+        other => return Err(error(line, format!("unknown preload hint type {other:?}"))),
+    };
+    Ok(PreloadHint {
+        kind,
+        uri: non_empty(attributes.get("URI"))
+            .ok_or_else(|| error(line, "EXT-X-PRELOAD-HINT without URI"))?,
+        byte_range_start: optional_number(line, attributes, "BYTERANGE-START")?.unwrap_or(0),
+        byte_range_length: optional_number(line, attributes, "BYTERANGE-LENGTH")?,
+    })
+}
+
+/// An HLS media playlist: the segment list of one rendition.
+pub fn parse_media_playlist(text: &str) -> Result<MediaPlaylist> {
+    require_header(text)?;
+    let mut playlist = MediaPlaylist::default();
+    let mut target_duration = None;
+    let mut key = None;
+    let mut map = None;
+    let mut pending: Option<Segment> = None;
+    let mut pending_range: Option<(usize, String)> = None;
+    let mut last_range: Option<(String, ByteRange)> = None;
+    let mut discontinuity = false;
+    let mut program_date_time = None;
+    let mut parts = Vec::new();
+    for (line_number, line) in lines(text) {
+        if let Some(value) = line.strip_prefix("#EXT-X-VERSION:") {
+            playlist.version = Some(number(line_number, "EXT-X-VERSION", value)?);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            target_duration = Some(number(line_number, "EXT-X-TARGETDURATION", value)?);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            playlist.media_sequence = number(line_number, "EXT-X-MEDIA-SEQUENCE", value)?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-DISCONTINUITY-SEQUENCE:") {
+            playlist.discontinuity_sequence =
+                number(line_number, "EXT-X-DISCONTINUITY-SEQUENCE", value)?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-PLAYLIST-TYPE:") {
+            playlist.playlist_type = match value.trim() {
+                "VOD" => Some(PlaylistType::Vod),
+                "EVENT" => Some(PlaylistType::Event),
+                other => {
+                    return Err(error(
+                        line_number,
+                        format!("unknown playlist type {other:?}"),
+                    ));
+                }
+            };
+        } else if line == "#EXT-X-INDEPENDENT-SEGMENTS" {
+            playlist.independent_segments = true;
+        } else if line == "#EXT-X-ENDLIST" {
+            playlist.ended = true;
+        } else if line == "#EXT-X-DISCONTINUITY" {
+            discontinuity = true;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+            program_date_time = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("#EXT-X-KEY:") {
+            let attributes = attribute_list(value);
+            let method = non_empty(attributes.get("METHOD"))
+                .ok_or_else(|| error(line_number, "EXT-X-KEY without METHOD"))?;
+            key = (method != "NONE").then(|| SegmentKey {
+                method,
+                uri: non_empty(attributes.get("URI")),
+                iv: non_empty(attributes.get("IV")),
+                key_format: non_empty(attributes.get("KEYFORMAT")),
+            });
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MAP:") {
+            map = non_empty(attribute_list(value).get("URI"));
+        } else if let Some(value) = line.strip_prefix("#EXT-X-SERVER-CONTROL:") {
+            playlist.server_control = Some(server_control(line_number, &attribute_list(value))?);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-PART-INF:") {
+            playlist.part_target =
+                optional_number(line_number, &attribute_list(value), "PART-TARGET")?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-SKIP:") {
+            playlist.skipped_segments =
+                optional_number(line_number, &attribute_list(value), "SKIPPED-SEGMENTS")?
+                    .ok_or_else(|| error(line_number, "EXT-X-SKIP without SKIPPED-SEGMENTS"))?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-PART:") {
+            parts.push(partial_segment(
+                line_number,
+                &attribute_list(value),
+                &mut last_range,
+            )?);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-PRELOAD-HINT:") {
+            playlist.preload_hint = Some(preload_hint(line_number, &attribute_list(value))?);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-BYTERANGE:") {
+            pending_range = Some((line_number, value.trim().to_string()));
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let (duration, title) = value.split_once(',').unwrap_or((value, ""));
+            let title = title.trim();
+            pending = Some(Segment {
+                duration: number(line_number, "EXTINF duration", duration)?,
+                title: (!title.is_empty()).then(|| title.to_string()),
+                ..Segment::default()
+            });
+        } else if !line.starts_with('#') {
+            let mut segment = pending
+                .take()
+                .ok_or_else(|| error(line_number, "segment URI without EXTINF"))?;
+            segment.uri = line.to_string();
+            segment.sequence = playlist.media_sequence
+                + playlist.skipped_segments
+                + playlist.segments.len() as u64;
+            segment.discontinuity = std::mem::take(&mut discontinuity);
+            segment.program_date_time = program_date_time.take();
+            segment.key = key.clone();
+            segment.map = map.clone();
+            segment.parts = std::mem::take(&mut parts);
+            if let Some((range_line, value)) = pending_range.take() {
+                let previous = last_range
+                    .as_ref()
+                    .map(|(uri, range)| (uri.as_str(), *range));
+                let range = byte_range(range_line, &value, previous, line)?;
+                last_range = Some((line.to_string(), range));
+                segment.byte_range = Some(range);
+            }
+            playlist.segments.push(segment);
+        }
+    }
+    playlist.target_duration = target_duration
+        .ok_or_else(|| UmbreonError::parse("M3U", "missing EXT-X-TARGETDURATION"))?;
+    playlist.trailing_parts = parts;
+    Ok(playlist)
+}
+
+/// Channels gathered from several lists, each stream URL kept once.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelLibrary {
+    channels: Vec<Channel>,
+}
+
+impl ChannelLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the channels of `list` whose URL is new and returns how many were added.
+    pub fn add(&mut self, list: ChannelList) -> usize {
+        let mut urls = self
+            .channels
+            .iter()
+            .map(|channel| channel.url.clone())
+            .collect::<HashSet<_>>();
+        let before = self.channels.len();
+        self.channels.extend(
+            list.channels
+                .into_iter()
+                .filter(|channel| urls.insert(channel.url.clone())),
+        );
+        self.channels.len() - before
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    /// Channels per group in order of first appearance; a channel in several groups is
+    /// listed under each. Ungrouped channels come last under `None`.
+    pub fn groups(&self) -> Vec<(Option<&str>, Vec<&Channel>)> {
+        let mut groups: Vec<(Option<&str>, Vec<&Channel>)> = Vec::new();
+        let mut ungrouped = Vec::new();
+        for channel in &self.channels {
+            let mut grouped = false;
+            for name in channel.groups() {
+                grouped = true;
+                match groups.iter_mut().find(|(group, _)| *group == Some(name)) {
+                    Some((_, members)) => members.push(channel),
+                    None => groups.push((Some(name), vec![channel])),
+                }
+            }
+            if !grouped {
+                ungrouped.push(channel);
+            }
+        }
+        if !ungrouped.is_empty() {
+            groups.push((None, ungrouped));
+        }
+        groups
+    }
+
+    pub fn find_by_tvg_id(&self, tvg_id: &str) -> Option<&Channel> {
+        self.channels
+            .iter()
+            .find(|channel| channel.tvg_id.as_deref() == Some(tvg_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPTV: &str = include_str!("../fixtures/playlist/iptv.m3u");
+    const MASTER: &str = include_str!("../fixtures/playlist/master.m3u8");
+    const MEDIA: &str = include_str!("../fixtures/playlist/media.m3u8");
+    const VOD: &str = include_str!("../fixtures/playlist/vod.m3u8");
+    const LOW_LATENCY: &str = include_str!("../fixtures/playlist/low_latency.m3u8");
+
+    #[test]
+    fn iptv_list_reads_tvg_group_and_catchup_attributes() {
+        let list = parse_channel_list(IPTV).unwrap();
+        assert_eq!(
+            list.epg_url.as_deref(),
+            Some("https://epg.example.com/guide.xml.gz")
+        );
+        assert_eq!(list.channels.len(), 6);
+
+        let cctv1 = &list.channels[0];
+        assert_eq!(cctv1.name, "CCTV-1 综合");
+        assert_eq!(cctv1.tvg_id.as_deref(), Some("cctv1.cn"));
+        assert_eq!(cctv1.tvg_name.as_deref(), Some("CCTV-1"));
+        assert_eq!(
+            cctv1.tvg_logo.as_deref(),
+            Some("https://logo.example.com/cctv1.png")
+        );
+        assert_eq!(cctv1.group.as_deref(), Some("央视"));
+        let inherited = cctv1.catchup.as_ref().unwrap();
+        assert_eq!(
+            (inherited.kind.as_str(), inherited.days),
+            ("append", Some(3))
+        );
+
+        let cctv13 = &list.channels[1];
+        assert_eq!(cctv13.name, "CCTV-13 新闻, 直播");
+        let catchup = cctv13.catchup.as_ref().unwrap();
+        assert_eq!(catchup.kind, "shift");
+        assert_eq!(catchup.days, Some(7));
+        assert_eq!(
+            catchup.source.as_deref(),
+            Some("?playseek=${(b)yyyyMMddHHmmss}-${(e)yyyyMMddHHmmss}")
+        );
+
+        let night = &list.channels[2];
+        assert_eq!(night.groups().collect::<Vec<_>>(), vec!["Bilibili", "娱乐"]);
+        assert_eq!(night.user_agent.as_deref(), Some("Mozilla/5.0 Umbreon"));
+        assert_eq!(
+            night.referrer.as_deref(),
+            Some("https://live.bilibili.com/")
+        );
+
+        let radio = &list.channels[3];
+        assert_eq!(radio.name, "Plain radio");
+        assert_eq!(radio.group.as_deref(), Some("Radio"));
+        assert_eq!(radio.user_agent, None);
+
+        let bare = &list.channels[5];
+        assert_eq!(bare.name, "rtmp://rtmp.example.com/live/untitled");
+        assert_eq!(bare.group, None);
+    }
+
+    #[test]
+    fn master_playlist_lists_variants_and_renditions() {
+        let Playlist::Master(master) = parse_playlist(MASTER).unwrap() else {
+            panic!("expected a master playlist");
+        };
+        assert_eq!(master.version, Some(6));
+        assert!(master.independent_segments);
+        assert_eq!(master.variants.len(), 3);
+
+        let top = &master.variants[0];
+        assert_eq!(top.uri, "1080p/index.m3u8");
+        assert_eq!(top.bandwidth, 6_000_000);
+        assert_eq!(top.average_bandwidth, Some(5_400_000));
+        assert_eq!(
+            top.resolution,
+            Some(Resolution {
+                width: 1920,
+                height: 1080
+            })
+        );
+        assert_eq!(top.frame_rate, Some(60.0));
+        assert_eq!(top.codecs, vec!["avc1.640028", "mp4a.40.2"]);
+        assert_eq!(top.closed_captions.as_deref(), Some("cc"));
+        assert_eq!(master.variants[1].closed_captions, None);
+        assert_eq!(
+            master.variants[2].uri,
+            "https://cdn.example.com/live/360p/index.m3u8"
+        );
+
+        assert_eq!(master.i_frame_variants.len(), 1);
+        assert_eq!(master.i_frame_variants[0].uri, "720p/iframes.m3u8");
+
+        let audio = master.renditions_in(RenditionKind::Audio, "aac");
+        assert_eq!(audio.len(), 2);
+        assert_eq!(audio[0].name, "中文");
+        assert!(audio[0].default && audio[0].autoselect);
+        assert_eq!(audio[0].channels.as_deref(), Some("2"));
+        assert!(!audio[1].default);
+        let captions = master.renditions_in(RenditionKind::ClosedCaptions, "cc");
+        assert_eq!(captions[0].instream_id.as_deref(), Some("CC1"));
+        assert_eq!(captions[0].uri, None);
+    }
+
+    #[test]
+    fn media_playlist_tracks_sequence_keys_and_byte_ranges() {
+        let Playlist::Media(media) = parse_playlist(MEDIA).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert_eq!(media.target_duration, 6);
+        assert_eq!(media.discontinuity_sequence, 3);
+        assert!(media.is_live());
+        assert_eq!(media.segments.len(), 4);
+
+        let first = &media.segments[0];
+        assert_eq!(first.sequence, 2680);
+        assert_eq!(first.duration, 6.006);
+        assert_eq!(
+            first.program_date_time.as_deref(),
+            Some("2026-02-05T20:00:00.000+08:00")
+        );
+        let key = first.key.as_ref().unwrap();
+        assert_eq!(key.method, "AES-128");
+        assert_eq!(key.uri.as_deref(), Some("https://keys.example.com/k1"));
+        assert_eq!(media.segments[1].title.as_deref(), Some("第二段"));
+        assert_eq!(media.segments[1].program_date_time, None);
+
+        let ad = &media.segments[2];
+        assert!(ad.discontinuity);
+        assert_eq!(ad.key, None);
+        assert_eq!(
+            ad.byte_range,
+            Some(ByteRange {
+                length: 75232,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            media.segments[3].byte_range,
+            Some(ByteRange {
+                length: 82112,
+                offset: 75232
+            })
+        );
+        assert!(!media.segments[3].discontinuity);
+    }
+
+    #[test]
+    fn vod_playlist_is_finished_and_carries_its_init_section() {
+        let media = parse_media_playlist(VOD).unwrap();
+        assert_eq!(media.playlist_type, Some(PlaylistType::Vod));
+        assert!(media.ended && !media.is_live());
+        assert_eq!(media.duration(), 27.5);
+        assert!(
+            media
+                .segments
+                .iter()
+                .all(|segment| segment.map.as_deref() == Some("init.mp4"))
+        );
+        assert_eq!(media.segments[2].sequence, 2);
+    }
+
+    #[test]
+    fn library_dedupes_urls_and_groups_channels() {
+        let mut library = ChannelLibrary::new();
+        assert_eq!(library.add(parse_channel_list(IPTV).unwrap()), 5);
+        assert_eq!(library.add(parse_channel_list(IPTV).unwrap()), 0);
+
+        let groups = library.groups();
+        let names = groups.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                Some("央视"),
+                Some("Bilibili"),
+                Some("娱乐"),
+                Some("Radio"),
+                None
+            ]
+        );
+        assert_eq!(groups[0].1.len(), 2);
+        assert_eq!(
+            library.find_by_tvg_id("cctv13.cn").unwrap().name,
+            "CCTV-13 新闻, 直播"
+        );
+    }
+
+    #[test]
+    fn low_latency_playlist_reads_parts_hints_and_server_control() {
+        let Playlist::Media(media) = parse_playlist(LOW_LATENCY).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert!(media.is_live());
+        assert!(media.is_low_latency());
+        assert!(media.can_block_reload());
+        let control = media.server_control.as_ref().unwrap();
+        assert_eq!(control.can_skip_until, Some(24.0));
+        assert_eq!(control.hold_back, None);
+        assert_eq!(media.part_target, Some(0.33334));
+        assert_eq!(media.hold_back(true), 1.002);
+        assert_eq!(media.hold_back(false), 12.0);
+
+        assert_eq!(media.skipped_segments, 3);
+        let sequences = media
+            .segments
+            .iter()
+            .map(|segment| segment.sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, vec![269, 270]);
+        assert!(media.segments[0].parts.is_empty());
+        let parts = &media.segments[1].parts;
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].independent && !parts[1].independent);
+        assert!(parts[2].gap);
+        assert_eq!(parts[1].uri, "filePart270.1.mp4");
+
+        let trailing = &media.trailing_parts;
+        assert_eq!(trailing.len(), 2);
+        assert_eq!(
+            trailing[1].byte_range,
+            Some(ByteRange {
+                length: 23000,
+                offset: 20000
+            })
+        );
+        let hint = media.preload_hint.as_ref().unwrap();
+        assert_eq!(hint.kind, PreloadHintKind::Part);
+        assert_eq!(hint.uri, "fileSequence271.mp4");
+        assert_eq!(
+            (hint.byte_range_start, hint.byte_range_length),
+            (43000, None)
+        );
+    }
+
+    #[test]
+    fn malformed_playlists_are_rejected() {
+        assert!(parse_playlist("hello world").is_err());
+        assert!(parse_channel_list("").is_err());
+        assert_eq!(
+            parse_channel_list("https://a.example.com/1.m3u8\n")
+                .unwrap()
+                .channels
+                .len(),
+            1
+        );
+        assert!(parse_master_playlist("#EXT-X-STREAM-INF:BANDWIDTH=1\na.m3u8").is_err());
+        assert!(
+            parse_master_playlist("#EXTM3U\n#EXT-X-STREAM-INF:RESOLUTION=1x1\na.m3u8").is_err()
+        );
+        assert!(parse_master_playlist("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1").is_err());
+        assert!(parse_media_playlist("#EXTM3U\n#EXTINF:4,\na.ts").is_err());
+        assert!(
+            parse_media_playlist("#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXTINF:x,\na.ts").is_err()
+        );
+        assert!(
+            parse_media_playlist(
+                "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-BYTERANGE:10\n#EXTINF:4,\na.ts"
+            )
+            .is_err()
+        );
+        assert!(
+            parse_media_playlist("#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-PART:URI=\"a.mp4\"")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Stream probing: which variant of a master playlist to check for the user's quality and
+//! bandwidth preference, and where a player would start in a media playlist.
+//!
+//! Fetching and timing stay with the caller; everything here works on parsed playlists.
+
+use crate::playlist::{MasterPlaylist, MediaPlaylist, Segment, Variant};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QualityPreference {
+    /// The first listed variant, which is where players start before adapting.
+    #[default]
+    Auto,
+    Highest,
+    Lowest,
+    /// The best variant no taller than this many lines.
+    MaxHeight(u32),
+}
+
+impl QualityPreference {
+    /// Options offered in settings.
+    pub const CHOICES: [QualityPreference; 6] = [
+        QualityPreference::Auto,
+        QualityPreference::Highest,
+        QualityPreference::MaxHeight(1080),
+        QualityPreference::MaxHeight(720),
+        QualityPreference::MaxHeight(480),
+        QualityPreference::Lowest,
+    ];
+
+    /// Stable form for settings storage.
+    pub fn key(self) -> String {
+        match self {
+            QualityPreference::Auto => "auto".to_string(),
+            QualityPreference::Highest => "highest".to_string(),
+            QualityPreference::Lowest => "lowest".to_string(),
+            QualityPreference::MaxHeight(height) => format!("{height}p"),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "auto" => Some(QualityPreference::Auto),
+            "highest" => Some(QualityPreference::Highest),
+            "lowest" => Some(QualityPreference::Lowest),
+            other => other
+                .strip_suffix('p')
+                .and_then(|height| height.parse().ok())
+                .map(QualityPreference::MaxHeight),
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            QualityPreference::Auto => "自动".to_string(),
+            QualityPreference::Highest => "最高画质".to_string(),
+            QualityPreference::Lowest => "最省流量".to_string(),
+            QualityPreference::MaxHeight(height) => format!("不超过 {height}p"),
+        }
+    }
+}
+
+/// Picks the variant to play. Variants above `max_bandwidth` (bits per second) are skipped;
+/// when none fits, the cheapest one is returned so the stream can still be checked.
+pub fn select_variant(
+    master: &MasterPlaylist,
+    preference: QualityPreference,
+    max_bandwidth: Option<u64>,
+) -> Option<&Variant> {
+    let fitting = master
+        .variants
+        .iter()
+        .filter(|variant| max_bandwidth.is_none_or(|cap| variant.bandwidth <= cap))
+        .collect::<Vec<_>>();
+    let lowest = || {
+        master
+            .variants
+            .iter()
+            .min_by_key(|variant| variant.bandwidth)
+    };
+    if fitting.is_empty() {
+        return lowest();
+    }
+    match preference {
+        QualityPreference::Auto => fitting.first().copied(),
+        QualityPreference::Highest => fitting.into_iter().max_by_key(|variant| variant.bandwidth),
+        QualityPreference::Lowest => fitting.into_iter().min_by_key(|variant| variant.bandwidth),
+        QualityPreference::MaxHeight(height) => fitting
+            .iter()
+            .copied()
+            .filter(|variant| {
+                variant
+                    .resolution
+                    .is_none_or(|resolution| resolution.height <= height)
+            })
+            .max_by_key(|variant| variant.bandwidth)
+            .or_else(|| fitting.into_iter().min_by_key(|variant| variant.bandwidth)),
+    }
+}
+
+/// Where playback of a media playlist begins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartPoint<'a> {
+    pub segment: &'a Segment,
+    /// For live playlists, seconds between the start of `segment` and the live edge.
+    pub edge_distance: Option<f64>,
+}
+
+/// VOD starts at the first segment. Live starts at the newest segment that still leaves the
+/// playlist's [hold back](MediaPlaylist::hold_back) before the edge, or at the oldest one
+/// when the window is shorter than that.
+pub fn start_point(media: &MediaPlaylist) -> Option<StartPoint<'_>> {
+    if !media.is_live() {
+        return media.segments.first().map(|segment| StartPoint {
+            segment,
+            edge_distance: None,
+        });
+    }
+    let hold_back = media.hold_back(false);
+    let mut distance = 0.0;
+    let mut start = None;
+    for segment in media.segments.iter().rev() {
+        distance += segment.duration;
+        start = Some(segment);
+        if distance >= hold_back {
+            break;
+        }
+    }
+    start.map(|segment| StartPoint {
+        segment,
+        edge_distance: Some(distance),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::{parse_master_playlist, parse_media_playlist};
+
+    const MASTER: &str = include_str!("../fixtures/playlist/master.m3u8");
+    const MEDIA: &str = include_str!("../fixtures/playlist/media.m3u8");
+    const VOD: &str = include_str!("../fixtures/playlist/vod.m3u8");
+
+    fn pick(preference: QualityPreference, max_bandwidth: Option<u64>) -> String {
+        let master = parse_master_playlist(MASTER).unwrap();
+        select_variant(&master, preference, max_bandwidth)
+            .map(|variant| variant.uri.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn variant_follows_preference_within_bandwidth() {
+        assert_eq!(pick(QualityPreference::Auto, None), "1080p/index.m3u8");
+        assert_eq!(
+            pick(QualityPreference::Auto, Some(4_000_000)),
+            "720p/index.m3u8"
+        );
+        assert_eq!(
+            pick(QualityPreference::Lowest, None),
+            "https://cdn.example.com/live/360p/index.m3u8"
+        );
+        assert_eq!(pick(QualityPreference::Highest, None), "1080p/index.m3u8");
+        assert_eq!(
+            pick(QualityPreference::MaxHeight(720), None),
+            "720p/index.m3u8"
+        );
+        assert_eq!(
+            pick(QualityPreference::MaxHeight(240), None),
+            "https://cdn.example.com/live/360p/index.m3u8"
+        );
+        assert_eq!(
+            pick(QualityPreference::Highest, Some(100_000)),
+            "https://cdn.example.com/live/360p/index.m3u8"
+        );
+    }
+
+    #[test]
+    fn preference_keys_round_trip() {
+        for preference in QualityPreference::CHOICES {
+            assert_eq!(
+                QualityPreference::parse(&preference.key()),
+                Some(preference)
+            );
+        }
+        assert_eq!(QualityPreference::parse("best"), None);
+    }
+
+    #[test]
+    fn live_start_holds_back_three_target_durations() {
+        let media = parse_media_playlist(MEDIA).unwrap();
+        let start = start_point(&media).unwrap();
+        assert_eq!(start.segment.sequence, 2680);
+        let distance = start.edge_distance.unwrap();
+        assert!((distance - 19.478).abs() < 1e-9, "{distance}");
+
+        let vod = parse_media_playlist(VOD).unwrap();
+        let start = start_point(&vod).unwrap();
+        assert_eq!(start.segment.uri, "part0.m4s");
+        assert_eq!(start.edge_distance, None);
+    }
+}
@@ -0,0 +1,212 @@
+//! Encryption for credentials kept on the device.
+//!
+//! Secrets are sealed with XChaCha20-Poly1305 under a 32-byte [`MasterKey`] that either
+//! comes from the platform keystore or is derived from a user passphrase with Argon2id.
+//! Each sealed value is bound to its name, so values cannot be swapped between slots.
+
+use crate::error::{Result, UmbreonError};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use zeroize::Zeroize;
+
+/// Marks a stored value as sealed; anything without it is legacy plaintext.
+pub const SEALED_PREFIX: &str = "enc:v1:";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Sealed under the master key so a wrong passphrase is caught before anything is re-sealed.
+const CHECK_NAME: &str = "umbreon.key_check";
+const CHECK_PLAINTEXT: &str = "umbreon";
+
+#[derive(Clone)]
+pub struct MasterKey([u8; KEY_LEN]);
+
+impl MasterKey {
+    /// A fresh random key, for the device keystore.
+    pub fn generate() -> Self {
+        MasterKey(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Argon2id over `passphrase` with the stored `salt` (see [`new_salt`]).
+    pub fn from_passphrase(passphrase: &str, salt: &str) -> Result<Self> {
+        let salt = STANDARD
+            .decode(salt)
+            .map_err(|err| UmbreonError::parse("key salt", err))?;
+        let mut key = [0u8; KEY_LEN];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|err| UmbreonError::Locked(err.to_string()))?;
+        Ok(MasterKey(key))
+    }
+
+    pub fn from_encoded(encoded: &str) -> Result<Self> {
+        let mut bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|err| UmbreonError::parse("device key", err))?;
+        let key = <[u8; KEY_LEN]>::try_from(bytes.as_slice())
+            .map_err(|_| UmbreonError::parse("device key", "wrong length"));
+        bytes.zeroize();
+        key.map(MasterKey)
+    }
+
+    pub fn encode(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    /// Lowercase hex, the form SQLCipher takes as a raw key (`x'…'`).
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    /// A sealed marker stored next to the salt; [`MasterKey::verify`] checks a key against it.
+    pub fn check_value(&self) -> Result<String> {
+        seal(self, CHECK_NAME, CHECK_PLAINTEXT)
+    }
+
+    pub fn verify(&self, check_value: &str) -> bool {
+        open(self, CHECK_NAME, check_value).is_ok_and(|value| value == CHECK_PLAINTEXT)
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+/// Random salt for [`MasterKey::from_passphrase`], base64 encoded for storage.
+pub fn new_salt() -> String {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    STANDARD.encode(salt)
+}
+
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// Encrypts `plaintext` for the slot `name` as `enc:v1:<base64(nonce || ciphertext)>`.
+pub fn seal(key: &MasterKey, name: &str, plaintext: &str) -> Result<String> {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key.0));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| UmbreonError::Locked(format!("sealing {name} failed")))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(format!("{SEALED_PREFIX}{}", STANDARD.encode(sealed)))
+}
+
+/// Decrypts a value produced by [`seal`] for the same `name`. A wrong key, a value sealed
+/// for another slot, or tampering all fail with [`UmbreonError::Locked`].
+pub fn open(key: &MasterKey, name: &str, sealed: &str) -> Result<String> {
+    let encoded = sealed
+        .strip_prefix(SEALED_PREFIX)
+        .ok_or_else(|| UmbreonError::parse(name, "not a sealed value"))?;
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|err| UmbreonError::parse(name, err))?;
+    if bytes.len() <= NONCE_LEN {
+        return Err(UmbreonError::parse(name, "sealed value is truncated"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key.0));
+    let plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| UmbreonError::Locked(format!("{name} cannot be decrypted")))?;
+    String::from_utf8(plaintext).map_err(|err| UmbreonError::parse(name, err))
+}
+
+/// What may be shown of a secret: the last four characters of long values, nothing of
+/// short ones.
+pub fn redact(secret: &str) -> String {
+    let chars = secret.trim().chars().collect::<Vec<_>>();
+    if chars.is_empty() {
+        return String::new();
+    }
+    if chars.len() < 12 {
+        return "••••".to_string();
+    }
+    let tail = chars[chars.len() - 4..].iter().collect::<String>();
+    format!("••••{tail}")
+}
+
+/// `text` with every occurrence of `secret` replaced by its [`redact`]ed form, for error
+/// messages and logs that might echo a credential back.
+pub fn scrub(text: &str, secret: &str) -> String {
+    let secret = secret.trim();
+    if secret.len() < 4 {
+        return text.to_string();
+    }
+    text.replace(secret, &redact(secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_values_round_trip_and_stay_bound_to_their_slot() {
+        let key = MasterKey::generate();
+        let sealed = seal(&key, "llm_api_key", "sk-test-123").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("sk-test-123"));
+        assert_eq!(open(&key, "llm_api_key", &sealed).unwrap(), "sk-test-123");
+
+        let other = open(&key, "memory_token", &sealed).unwrap_err();
+        assert!(matches!(other, UmbreonError::Locked(_)));
+        let wrong_key = open(&MasterKey::generate(), "llm_api_key", &sealed).unwrap_err();
+        assert!(matches!(wrong_key, UmbreonError::Locked(_)));
+    }
+
+    #[test]
+    fn passphrase_keys_are_reproducible_and_verifiable() {
+        let salt = new_salt();
+        let key = MasterKey::from_passphrase("correct horse", &salt).unwrap();
+        let check = key.check_value().unwrap();
+
+        let again = MasterKey::from_passphrase("correct horse", &salt).unwrap();
+        assert!(again.verify(&check));
+        let wrong = MasterKey::from_passphrase("battery staple", &salt).unwrap();
+        assert!(!wrong.verify(&check));
+
+        let device = MasterKey::from_encoded(&key.encode()).unwrap();
+        assert!(device.verify(&check));
+        assert_eq!(device.to_hex().len(), 64);
+    }
+
+    #[test]
+    fn redaction_hides_all_but_a_short_tail() {
+        assert_eq!(redact("sk-abcdefghijklmnop"), "••••mnop");
+        assert_eq!(redact("short"), "••••");
+        assert_eq!(redact(""), "");
+        assert_eq!(
+            scrub(
+                "Incorrect API key provided: sk-abcdefghijklmnop.",
+                "sk-abcdefghijklmnop"
+            ),
+            "Incorrect API key provided: ••••mnop."
+        );
+    }
+}
@@ -1,4 +1,4 @@
-import { generateText } from "ai";
+import { generateText, streamText } from "ai";
 import { createOpenAI } from "@ai-sdk/openai";
 import {
   withSupermemory,
@@ -16,6 +16,19 @@ type Env = {
 const ACCOUNT_ID = "6fd4697183fdd0026b116cb0b23d4719";
 const GATEWAY_ID = "nace-ai-proxy";
 const MODEL_ID = "gpt-5";
+const SUPERMEMORY_API_BASE = "https://api.supermemory.ai";
+
+// Supermemory metadata values are scalars, so tags are stored comma-joined.
+function documentMetadata(category: unknown, tags: unknown): Record<string, string> {
+  const metadata: Record<string, string> = {};
+  if (category) metadata.category = String(category);
+  if (Array.isArray(tags)) metadata.tags = tags.map((tag) => String(tag)).join(",");
+  return metadata;
+}
+
+function categoryFilter(category: unknown) {
+  return category ? { AND: [{ key: "category", value: String(category) }] } : undefined;
+}
 
 const corsHeaders = {
   "Access-Control-Allow-Origin": "*",
@@ -34,8 +47,9 @@ export default {
         {
           ok: true,
           usage:
-            "POST / { userId, message, mode?, conversationId?, addMemory?, action?, memory?, query? }",
-          actions: "addMemory | searchMemories",
+            "POST / { userId, message, mode?, conversationId?, addMemory?, stream?, action?, memory?, idempotencyKey?, category?, id?, tags?, query?, olderThanDays? }",
+          actions:
+            "addMemory | searchMemories | queryMemory | tagMemory | deleteMemory | purgeMemories",
           mode: "profile | query | full",
           addMemory: "never | always",
         },
@@ -68,10 +82,16 @@ export default {
       addMemory,
       action,
       memory,
+      idempotencyKey,
+      category,
+      id,
+      tags,
+      olderThanDays,
       query,
       limit,
       includeFullDocs,
       toolChoice,
+      stream,
     } = body ?? {};
 
     if (!userId) {
@@ -98,6 +118,41 @@ export default {
         );
       }
 
+      // Replayed outbox writes carry a stable key; using it as the document's customId
+      // makes the retry update the same document instead of adding a duplicate.
+      if (idempotencyKey) {
+        const response = await fetch(`${SUPERMEMORY_API_BASE}/v3/documents`, {
+          method: "POST",
+          headers: {
+            Authorization: `Bearer ${env.SUPERMEMORY_API_KEY}`,
+            "Content-Type": "application/json",
+          },
+          body: JSON.stringify({
+            content: String(memory),
+            containerTags,
+            customId: String(idempotencyKey),
+            metadata: documentMetadata(category, tags),
+          }),
+        });
+
+        if (!response.ok) {
+          const detail = await response.text();
+          return Response.json(
+            { success: false, error: `add failed (${response.status}): ${detail}` },
+            {
+              status: response.status === 429 || response.status >= 500 ? 502 : 400,
+              headers: corsHeaders,
+            }
+          );
+        }
+
+        const document: any = await response.json();
+        return Response.json(
+          { success: true, memory: { id: document.id, status: document.status } },
+          { headers: corsHeaders }
+        );
+      }
+
       const addTool = addMemoryTool(env.SUPERMEMORY_API_KEY, { containerTags });
       const exec = addTool.execute;
       if (!exec) {
@@ -115,6 +170,194 @@ export default {
       return Response.json(result, { headers: corsHeaders });
     }
 
+    if (action === "tagMemory") {
+      if (!id || !Array.isArray(tags)) {
+        return Response.json(
+          { success: false, error: "id and tags are required" },
+          { status: 400, headers: corsHeaders }
+        );
+      }
+
+      const documentUrl = `${SUPERMEMORY_API_BASE}/v3/documents/${encodeURIComponent(String(id))}`;
+      // PATCH replaces the metadata object, so merge into what the document already has
+      // instead of dropping its category and any other keys.
+      const existing = await fetch(documentUrl, {
+        headers: { Authorization: `Bearer ${env.SUPERMEMORY_API_KEY}` },
+      });
+      if (!existing.ok) {
+        const detail = await existing.text();
+        return Response.json(
+          { success: false, error: `tag lookup failed (${existing.status}): ${detail}` },
+          {
+            status: existing.status === 429 || existing.status >= 500 ? 502 : 400,
+            headers: corsHeaders,
+          }
+        );
+      }
+      const document: any = await existing.json();
+      const metadata = {
+        ...(document.metadata ?? {}),
+        ...documentMetadata(category, tags),
+      };
+
+      const response = await fetch(documentUrl, {
+        method: "PATCH",
+        headers: {
+          Authorization: `Bearer ${env.SUPERMEMORY_API_KEY}`,
+          "Content-Type": "application/json",
+        },
+        body: JSON.stringify({ metadata }),
+      });
+
+      if (!response.ok) {
+        const detail = await response.text();
+        return Response.json(
+          { success: false, error: `tag failed (${response.status}): ${detail}` },
+          {
+            status: response.status === 429 || response.status >= 500 ? 502 : 400,
+            headers: corsHeaders,
+          }
+        );
+      }
+
+      return Response.json({ success: true }, { headers: corsHeaders });
+    }
+
+    if (action === "deleteMemory") {
+      if (!id) {
+        return Response.json(
+          { success: false, error: "id is required" },
+          { status: 400, headers: corsHeaders }
+        );
+      }
+
+      const response = await fetch(
+        `${SUPERMEMORY_API_BASE}/v3/documents/${encodeURIComponent(String(id))}`,
+        {
+          method: "DELETE",
+          headers: { Authorization: `Bearer ${env.SUPERMEMORY_API_KEY}` },
+        }
+      );
+
+      if (!response.ok && response.status !== 404) {
+        const detail = await response.text();
+        return Response.json(
+          { success: false, error: `delete failed (${response.status}): ${detail}` },
+          {
+            status: response.status === 429 || response.status >= 500 ? 502 : response.status,
+            headers: corsHeaders,
+          }
+        );
+      }
+
+      return Response.json({ success: true }, { headers: corsHeaders });
+    }
+
+    if (action === "queryMemory") {
+      if (!query) {
+        return Response.json(
+          { success: false, error: "query is required" },
+          { status: 400, headers: corsHeaders }
+        );
+      }
+
+      const response = await fetch(`${SUPERMEMORY_API_BASE}/v3/search`, {
+        method: "POST",
+        headers: {
+          Authorization: `Bearer ${env.SUPERMEMORY_API_KEY}`,
+          "Content-Type": "application/json",
+        },
+        body: JSON.stringify({
+          q: String(query),
+          containerTags,
+          limit: limit ?? 10,
+          filters: categoryFilter(category),
+        }),
+      });
+
+      if (!response.ok) {
+        const detail = await response.text();
+        return Response.json(
+          { success: false, error: `query failed (${response.status}): ${detail}` },
+          {
+            status: response.status === 429 || response.status >= 500 ? 502 : 400,
+            headers: corsHeaders,
+          }
+        );
+      }
+
+      const data: any = await response.json();
+      const results = (data.results ?? []).map((result: any) => ({
+        id: String(result.documentId ?? result.id ?? ""),
+        content:
+          (result.chunks ?? []).map((chunk: any) => chunk.content).join("\n") ||
+          String(result.title ?? ""),
+        score: result.score,
+        category: result.metadata?.category,
+      }));
+
+      return Response.json(
+        { success: true, results, count: results.length },
+        { headers: corsHeaders }
+      );
+    }
+
+    if (action === "purgeMemories") {
+      const days = Number(olderThanDays);
+      if (!Number.isFinite(days) || days <= 0) {
+        return Response.json(
+          { success: false, error: "olderThanDays must be a positive number" },
+          { status: 400, headers: corsHeaders }
+        );
+      }
+
+      const cutoff = Date.now() - days * 24 * 60 * 60 * 1000;
+      const headers = {
+        Authorization: `Bearer ${env.SUPERMEMORY_API_KEY}`,
+        "Content-Type": "application/json",
+      };
+      const stale: string[] = [];
+      for (let page = 1; ; page++) {
+        const response = await fetch(`${SUPERMEMORY_API_BASE}/v3/documents/list`, {
+          method: "POST",
+          headers,
+          body: JSON.stringify({
+            containerTags,
+            filters: categoryFilter(category),
+            limit: 100,
+            page,
+            sort: "createdAt",
+            order: "asc",
+          }),
+        });
+        if (!response.ok) {
+          const detail = await response.text();
+          return Response.json(
+            { success: false, error: `list failed (${response.status}): ${detail}` },
+            { status: 502, headers: corsHeaders }
+          );
+        }
+        const data: any = await response.json();
+        const documents: any[] = data.memories ?? data.documents ?? [];
+        for (const document of documents) {
+          if (Date.parse(document.createdAt) < cutoff) stale.push(String(document.id));
+        }
+        const totalPages = Number(data.pagination?.totalPages ?? page);
+        if (documents.length === 0 || page >= totalPages) break;
+      }
+
+      let deleted = 0;
+      for (const staleId of stale) {
+        const response = await fetch(
+          `${SUPERMEMORY_API_BASE}/v3/documents/${encodeURIComponent(staleId)}`,
+          { method: "DELETE", headers }
+        );
+        if (response.ok || response.status === 404) deleted++;
+      }
+
+      return Response.json({ success: true, deleted }, { headers: corsHeaders });
+    }
+
     if (action === "searchMemories") {
       if (!query) {
         return Response.json(
@@ -185,6 +428,17 @@ export default {
       containerTags,
     });
 
+    if (stream) {
+      const streamed = streamText({
+        model,
+        messages: [{ role: "user", content: String(message) }],
+        tools,
+        toolChoice,
+      });
+
+      return streamed.toTextStreamResponse({ headers: corsHeaders });
+    }
+
     const result = await generateText({
       model,
       messages: [{ role: "user", content: String(message) }],